@@ -1,13 +1,10 @@
 use std::convert::TryInto;
 
 use xim::{AHashMap, Client, ClientError, ClientHandler};
-use xim_parser::{AttributeName, InputStyle, Point};
+use xim_parser::{AttributeName, CaretDirection, CaretStyle, InputStyle, Point};
 
 #[derive(Default)]
 pub struct ExampleHandler {
-    pub im_id: u16,
-    pub ic_id: u16,
-    pub connected: bool,
     pub window: u32,
 }
 
@@ -19,7 +16,6 @@ impl<C: Client> ClientHandler<C> for ExampleHandler {
 
     fn handle_open(&mut self, client: &mut C, input_method_id: u16) -> Result<(), ClientError> {
         log::trace!("Opened");
-        self.im_id = input_method_id;
 
         client.get_im_values(input_method_id, &[AttributeName::QueryInputStyle])
     }
@@ -38,8 +34,8 @@ impl<C: Client> ClientHandler<C> for ExampleHandler {
             )
             .push(AttributeName::ClientWindow, self.window)
             .push(AttributeName::FocusWindow, self.window)
-            .nested_list(AttributeName::PreeditAttributes, |b| {
-                b.push(AttributeName::SpotLocation, Point { x: 0, y: 0 });
+            .preedit_attributes(|b| {
+                b.spot_location(Point { x: 0, y: 0 });
             })
             .build();
         client.create_ic(input_method_id, ic_attributes)
@@ -51,8 +47,6 @@ impl<C: Client> ClientHandler<C> for ExampleHandler {
         input_method_id: u16,
         input_context_id: u16,
     ) -> Result<(), ClientError> {
-        self.connected = true;
-        self.ic_id = input_context_id;
         log::info!("IC created {}, {}", input_method_id, input_context_id);
         Ok(())
     }
@@ -148,4 +142,26 @@ impl<C: Client> ClientHandler<C> for ExampleHandler {
 
         Ok(())
     }
+
+    fn handle_preedit_caret(
+        &mut self,
+        _client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        position: &mut i32,
+        direction: CaretDirection,
+        style: CaretStyle,
+    ) -> Result<(), ClientError> {
+        log::info!(
+            "Preedit caret {}, {}, {}, {:?}, {:?}",
+            input_method_id,
+            input_context_id,
+            position,
+            direction,
+            style
+        );
+
+        // the example doesn't move the caret, reply with the position as is
+        Ok(())
+    }
 }
@@ -37,10 +37,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     log::info!("Start event loop");
 
-    let mut handler = ExampleHandler {
-        window,
-        ..ExampleHandler::default()
-    };
+    let mut handler = ExampleHandler { window };
 
     loop {
         let e = conn.wait_for_event()?;
@@ -53,14 +50,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         } else {
             match e {
                 Event::KeyPress(e) | Event::KeyRelease(e) => {
-                    if handler.connected {
+                    let ic = client.registry().input_contexts().next();
+
+                    if let Some((im_id, ic_id)) =
+                        ic.map(|ic| (ic.input_method_id(), ic.input_context_id()))
+                    {
                         log::trace!("Send: {:?}", e);
-                        client.forward_event(
-                            handler.im_id,
-                            handler.ic_id,
-                            ForwardEventFlag::empty(),
-                            &e,
-                        )?;
+                        if !client.filter_key_event(im_id, ic_id, ForwardEventFlag::empty(), &e)? {
+                            log::trace!("Input context is off: {:?}", e);
+                        }
                     }
                 }
                 _ => {}
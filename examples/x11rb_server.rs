@@ -2,7 +2,7 @@ use x11rb::connection::Connection;
 use xim::{
     x11rb::X11rbServer, Server, ServerError, ServerHandler, UserInputContext, XimConnections,
 };
-use xim_parser::InputStyle;
+use xim_parser::{Extension, InputStyle};
 
 #[derive(Default)]
 struct Handler {}
@@ -64,42 +64,34 @@ impl<S: Server<XEvent = x11rb::protocol::xproto::KeyPressEvent>> ServerHandler<S
         Ok(true)
     }
 
-    fn handle_destroy_ic(
-        &mut self,
-        _server: &mut S,
-        _user_ic: UserInputContext<Self::InputContextData>,
-    ) -> Result<(), ServerError> {
-        Ok(())
-    }
-
-    fn handle_reset_ic(
-        &mut self,
-        _server: &mut S,
-        _user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<String, ServerError> {
-        Ok(String::new())
-    }
-
-    fn handle_set_ic_values(
-        &mut self,
-        _server: &mut S,
-        _user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<(), ServerError> {
-        Ok(())
+    fn extensions(&self) -> Vec<Extension> {
+        vec![
+            Extension {
+                major_opcode: 128,
+                minor_opcode: 50,
+                name: "XIM_EXT_FORWARD_KEYEVENT".into(),
+            },
+            Extension {
+                major_opcode: 128,
+                minor_opcode: 51,
+                name: "XIM_EXT_MOVE".into(),
+            },
+        ]
     }
 
-    fn handle_set_focus(
+    fn handle_spot_moved(
         &mut self,
         _server: &mut S,
-        _user_ic: &mut UserInputContext<Self::InputContextData>,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
     ) -> Result<(), ServerError> {
+        log::info!("Spot moved: {:?}", user_ic.ic.preedit_spot());
         Ok(())
     }
 
-    fn handle_unset_focus(
+    fn handle_destroy_ic(
         &mut self,
         _server: &mut S,
-        _user_ic: &mut UserInputContext<Self::InputContextData>,
+        _user_ic: UserInputContext<Self::InputContextData>,
     ) -> Result<(), ServerError> {
         Ok(())
     }
@@ -0,0 +1,59 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::{xproto::*, Event};
+use xim::{x11rb::X11rbClient, SessionConfig, SessionEvent, XimSession};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    pretty_env_logger::init_custom_env("XIM_RS_LOG");
+
+    let (conn, screen_num) =
+        x11rb::rust_connection::RustConnection::connect(None).expect("Connect X");
+    let screen = &conn.setup().roots[screen_num];
+    let window = conn.generate_id()?;
+    conn.create_window(
+        screen.root_depth,
+        window,
+        screen.root,
+        0,
+        0,
+        800,
+        600,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &CreateWindowAux::default()
+            .background_pixel(screen.black_pixel)
+            .event_mask(EventMask::KEY_PRESS | EventMask::KEY_RELEASE),
+    )?;
+    conn.map_window(window)?;
+    conn.flush()?;
+
+    let client = X11rbClient::init(&conn, screen_num, None)?;
+    let mut session = XimSession::new(client, SessionConfig::new("en_US", window));
+
+    log::info!("Start event loop");
+
+    loop {
+        let e = conn.wait_for_event()?;
+
+        if !session.filter_event(&e)? {
+            if let Event::KeyPress(e) | Event::KeyRelease(e) = e {
+                if !session.forward_key(&e)? {
+                    log::trace!("Input context is off: {:?}", e);
+                }
+            }
+        }
+
+        while let Some(event) = session.next_event() {
+            match event {
+                SessionEvent::Ready => log::info!("Ready with {:?}", session.input_style()),
+                SessionEvent::Commit(text) => log::info!("Commited {}", text),
+                SessionEvent::PreeditUpdate { text, caret } => {
+                    log::info!("Preedit {:?} at {}", text, caret)
+                }
+                SessionEvent::ForwardedKey(e) => log::info!("Forwarded {:?}", e),
+                SessionEvent::Disconnected => log::info!("Disconnected"),
+                _ => {}
+            }
+        }
+    }
+}
@@ -35,7 +35,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let mut handler = ExampleHandler {
             window: window as _,
-            ..ExampleHandler::default()
         };
 
         (xlib.XSelectInput)(display, window, xlib::KeyPressMask | xlib::KeyReleaseMask);
@@ -52,13 +51,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             } else {
                 match e.get_type() {
                     xlib::KeyPress | xlib::KeyRelease => {
-                        if handler.connected {
-                            client.forward_event(
-                                handler.im_id,
-                                handler.ic_id,
+                        let ic = client.registry().input_contexts().next();
+
+                        if let Some((im_id, ic_id)) =
+                            ic.map(|ic| (ic.input_method_id(), ic.input_context_id()))
+                        {
+                            if !client.filter_key_event(
+                                im_id,
+                                ic_id,
                                 ForwardEventFlag::empty(),
                                 &e.key,
-                            )?;
+                            )? {
+                                log::trace!("Input context is off: {:?}", e.key);
+                            }
                         }
                     }
                     _ => {}
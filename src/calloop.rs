@@ -0,0 +1,185 @@
+//! [`calloop`](calloop-library) event source for [`X11rbClient`].
+//!
+//! [`X11rbSource`] owns a client with its handler and watches the X connection, and the socket
+//! of the TCP transport when the server uses it. Whenever they're readable it drains the
+//! events, passes them to the client and hands the ones unrelated to XIM to the callback, so
+//! the client runs in the same event loop as the rest of the application.
+//!
+//! ```ignore
+//! let (conn, screen_num) = x11rb::connect(None)?;
+//! let client = X11rbClient::init(Arc::new(conn), screen_num, None)?;
+//! let source = X11rbSource::new(client, MyHandler::default())?;
+//!
+//! event_loop
+//!     .handle()
+//!     .insert_source(source, |event, client, state| {
+//!         // `event` is not related to XIM, forward key events with `client`
+//!     })?;
+//! ```
+//!
+//! [calloop-library]: https://crates.io/crates/calloop
+
+use calloop::generic::Generic;
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use std::os::unix::io::{AsFd, AsRawFd, OwnedFd, RawFd};
+use x11rb::protocol::Event;
+
+use crate::client::{ClientError, ClientHandler};
+use crate::x11rb::{ConnectionFd, HasConnection, X11rbClient};
+
+/// Event source which drives an [`X11rbClient`], see [module documentation](self).
+///
+/// Events which are not related to XIM are passed to the callback along with the client.
+pub struct X11rbSource<C: HasConnection, H> {
+    client: X11rbClient<C>,
+    handler: H,
+    conn: Generic<OwnedFd>,
+    /// Raw fd of the TCP stream of the client and the source polling its duplicate
+    tcp: Option<(RawFd, Generic<OwnedFd>)>,
+}
+
+impl<C, H> X11rbSource<C, H>
+where
+    C: HasConnection,
+    C::Connection: ConnectionFd,
+    H: ClientHandler<X11rbClient<C>>,
+{
+    pub fn new(client: X11rbClient<C>, handler: H) -> Result<Self, ClientError> {
+        let fd = client.conn().connection_fd().try_clone_to_owned()?;
+
+        Ok(Self {
+            client,
+            handler,
+            conn: Generic::new(fd, Interest::READ, Mode::Level),
+            tcp: None,
+        })
+    }
+
+    pub fn client(&self) -> &X11rbClient<C> {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut X11rbClient<C> {
+        &mut self.client
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Drain every event the connection has, including ones queued while waiting replies
+    fn dispatch<F>(&mut self, callback: &mut F) -> Result<(), ClientError>
+    where
+        F: FnMut(Event, &mut X11rbClient<C>),
+    {
+        while let Some(e) = self.client.poll_filter_pending(&mut self.handler)? {
+            callback(e, &mut self.client);
+        }
+
+        Ok(())
+    }
+
+    fn tcp_changed(&self) -> bool {
+        self.client.tcp_stream().map(AsRawFd::as_raw_fd) != self.tcp.as_ref().map(|(fd, _)| *fd)
+    }
+
+    /// Follow the TCP transport of the client, which comes and goes with the server
+    fn register_tcp(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        if self.tcp_changed() {
+            if let Some((_, mut tcp)) = self.tcp.take() {
+                tcp.unregister(poll)?;
+            }
+
+            if let Some(stream) = self.client.tcp_stream() {
+                let fd = stream.as_fd().try_clone_to_owned()?;
+                self.tcp = Some((
+                    stream.as_raw_fd(),
+                    Generic::new(fd, Interest::READ, Mode::Level),
+                ));
+            } else {
+                return Ok(());
+            }
+        } else if let Some((_, tcp)) = self.tcp.as_mut() {
+            return tcp.reregister(poll, token_factory);
+        }
+
+        match self.tcp.as_mut() {
+            Some((_, tcp)) => tcp.register(poll, token_factory),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<C, H> EventSource for X11rbSource<C, H>
+where
+    C: HasConnection,
+    C::Connection: ConnectionFd,
+    H: ClientHandler<X11rbClient<C>>,
+{
+    type Event = Event;
+    type Metadata = X11rbClient<C>;
+    type Ret = ();
+    type Error = ClientError;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, ClientError>
+    where
+        F: FnMut(Event, &mut X11rbClient<C>),
+    {
+        // both are level triggered and drained below, only acknowledge the readiness here
+        self.conn
+            .process_events(readiness, token, |_, _| Ok(PostAction::Continue))?;
+
+        if let Some((_, tcp)) = self.tcp.as_mut() {
+            tcp.process_events(readiness, token, |_, _| Ok(PostAction::Continue))?;
+        }
+
+        self.dispatch(&mut callback)?;
+
+        if self.tcp_changed() {
+            Ok(PostAction::Reregister)
+        } else {
+            Ok(PostAction::Continue)
+        }
+    }
+
+    fn register(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.conn.register(poll, token_factory)?;
+        self.register_tcp(poll, token_factory)
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.conn.reregister(poll, token_factory)?;
+        self.register_tcp(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.conn.unregister(poll)?;
+
+        if let Some((_, mut tcp)) = self.tcp.take() {
+            tcp.unregister(poll)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,17 +1,66 @@
 mod attribute_builder;
+#[cfg(feature = "std")]
+mod blocking;
+mod dyn_handler;
+pub mod geometry;
+mod ic_values;
+mod interceptor;
+mod keysym;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod pending;
+mod preedit;
+mod quirks;
+mod registry;
+#[cfg(feature = "std")]
+mod reply;
+#[cfg(feature = "std")]
+mod session;
+mod state;
+mod transport_info;
+mod trigger;
 
 pub use self::attribute_builder::AttributeBuilder;
+#[cfg(feature = "std")]
+pub use self::blocking::BlockingClient;
+pub use self::dyn_handler::{DynClient, DynHandler};
+pub use self::ic_values::IcValues;
+pub use self::interceptor::RequestInterceptor;
+pub use self::keysym::keysym_to_char;
+#[cfg(feature = "metrics")]
+pub use self::metrics::{Metrics, ReplyStats};
+pub use self::pending::{PendingRequest, PendingRequests, ReplyKind, RequestId};
+pub use self::preedit::{PreeditRun, PreeditState};
+pub use self::quirks::Quirks;
+pub use self::registry::{ImRegistry, InputContextInfo, InputMethodInfo};
+#[cfg(feature = "std")]
+pub use self::session::{SessionConfig, SessionEvent, XimSession};
+pub use self::state::ClientState;
+pub use self::transport_info::TransportInfo;
+pub use self::trigger::TriggerKeys;
+use crate::encoding::Encoding;
 use crate::AHashMap;
 use xim_parser::{
     Attr, Attribute, AttributeName, CaretDirection, CaretStyle, CommitData, Extension, Feedback,
-    ForwardEventFlag, PreeditDrawStatus, Request,
+    ForwardEventFlag, Point, PreeditDrawStatus, Request, StrConvText, StrConversionOperation,
+    TriggerNotifyFlag,
 };
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt;
 
+const EXT_MOVE: &str = "XIM_EXT_MOVE";
+const EXT_FORWARD_KEYEVENT: &str = "XIM_EXT_FORWARD_KEYEVENT";
+
+/// Bit of `response_type` set for events sent by `SendEvent` instead of the X server
+const SEND_EVENT_MASK: u8 = 0x80;
+
+/// `KeyPressMask | KeyReleaseMask`, events forwarded after the input context is triggered on
+const TRIGGER_EVENT_MASK: u32 = 0b11;
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum ClientError {
@@ -20,6 +69,8 @@ pub enum ClientError {
     UnsupportedTransport,
     InvalidReply,
     NoXimServer,
+    Timeout,
+    AuthFailed,
     #[cfg(feature = "std")]
     Other(alloc::boxed::Box<dyn std::error::Error + Send + Sync>),
 }
@@ -30,6 +81,13 @@ impl From<xim_parser::ReadError> for ClientError {
     }
 }
 
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ClientError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
 impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -40,6 +98,8 @@ impl fmt::Display for ClientError {
             ClientError::UnsupportedTransport => write!(f, "Server Transport is not supported"),
             ClientError::InvalidReply => write!(f, "Invalid reply from server"),
             ClientError::NoXimServer => write!(f, "Can't connect xim server"),
+            ClientError::Timeout => write!(f, "Timeout while waiting reply"),
+            ClientError::AuthFailed => write!(f, "Server rejected authentication"),
             #[cfg(feature = "std")]
             ClientError::Other(e) => write!(f, "Other error: {}", e),
         }
@@ -49,36 +109,169 @@ impl fmt::Display for ClientError {
 #[cfg(feature = "std")]
 impl std::error::Error for ClientError {}
 
+/// Register `req` to client state then send it
+pub(crate) fn send_req<C: ClientCore>(client: &mut C, mut req: Request) -> Result<(), ClientError> {
+    if let Some(interceptor) = client.state_mut().interceptor_mut() {
+        if !interceptor.on_send(&mut req) {
+            log::debug!("{} is dropped by interceptor", req.name());
+            return Ok(());
+        }
+    }
+
+    let values = match req {
+        Request::CreateIc {
+            ref ic_attributes, ..
+        }
+        | Request::SetIcValues {
+            ref ic_attributes, ..
+        } => Some(IcValues::decode(client.ic_attributes(), ic_attributes)),
+        _ => None,
+    };
+    client.state_mut().request_sent(&req, values);
+    client.send_req(req)
+}
+
+/// Start the handshake once the transport is connected
+pub(crate) fn send_connect<C: ClientCore>(client: &mut C) -> Result<(), ClientError> {
+    send_req(
+        client,
+        Request::Connect {
+            client_major_protocol_version: 1,
+            client_minor_protocol_version: 0,
+            endian: xim_parser::Endian::Native,
+            client_auth_protocol_names: Vec::new(),
+        },
+    )
+}
+
 pub fn handle_request<C: ClientCore>(
     client: &mut C,
     handler: &mut impl ClientHandler<C>,
-    req: Request,
+    mut req: Request,
 ) -> Result<(), ClientError> {
-    if log::log_enabled!(log::Level::Trace) {
-        log::trace!("<-: {:?}", req);
-    } else {
-        log::debug!("<-: {}", req.name());
+    crate::trace::request_received(&req);
+
+    if let Some(interceptor) = client.state_mut().interceptor_mut() {
+        if !interceptor.on_receive(&mut req) {
+            log::debug!("{} is dropped by interceptor", req.name());
+            return Ok(());
+        }
+    }
+
+    if !client.state_mut().reply_received(&req) {
+        if client.state().quirks().ignore_unexpected_replies {
+            log::debug!("Ignore unexpected {}", req.name());
+            return Ok(());
+        }
+
+        log::warn!("Unexpected {}, no request is waiting it", req.name());
+    }
+
+    // the span of the request lasts until its reply is handled
+    #[cfg(feature = "tracing")]
+    let _span = client
+        .state()
+        .pending_requests()
+        .current()
+        .map(|current| current.span().clone().entered());
+
+    let ret = dispatch_request(client, handler, req);
+    client.state_mut().reply_handled();
+    ret
+}
+
+/// Outcome of an X event processed by a backend
+pub(crate) enum Filtered {
+    /// The event is not related to XIM
+    Ignored,
+    /// The event is consumed by the transport
+    Consumed,
+    /// A request is received from the server
+    Request(Request),
+    /// The server is gone, the state of the client is already reset
+    ServerDisconnected,
+}
+
+/// Call the handler for what a backend found in an event, return whether the event is consumed
+pub(crate) fn dispatch_filtered<C: ClientCore>(
+    client: &mut C,
+    handler: &mut impl ClientHandler<C>,
+    filtered: Filtered,
+) -> Result<bool, ClientError> {
+    match filtered {
+        Filtered::Ignored => Ok(false),
+        Filtered::Consumed => Ok(true),
+        Filtered::Request(req) => {
+            handle_request(client, handler, req)?;
+            Ok(true)
+        }
+        Filtered::ServerDisconnected => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("server disconnected");
+            handler.handle_server_disconnected(client)?;
+            Ok(true)
+        }
     }
+}
 
+fn dispatch_request<C: ClientCore>(
+    client: &mut C,
+    handler: &mut impl ClientHandler<C>,
+    req: Request,
+) -> Result<(), ClientError> {
     match req {
         Request::ConnectReply {
             server_major_protocol_version: _,
             server_minor_protocol_version: _,
-        } => handler.handle_connect(client),
+        } => {
+            #[cfg(feature = "tracing")]
+            tracing::info!("connected");
+            handler.handle_connect(client)
+        }
+        Request::AuthRequired {
+            auth_protocol_index,
+            auth_data,
+        } => {
+            let reply = handler.handle_auth_required(client, auth_protocol_index, &auth_data)?;
+            send_auth_reply(client, reply)
+        }
+        Request::AuthNext { auth_data } => {
+            let reply = handler.handle_auth_next(client, &auth_data)?;
+            send_auth_reply(client, reply)
+        }
+        Request::AuthNg {} => Err(ClientError::AuthFailed),
         Request::OpenReply {
             input_method_id,
             im_attrs,
-            ic_attrs,
+            mut ic_attrs,
         } => {
+            #[cfg(feature = "tracing")]
+            tracing::info!(input_method_id, "opened");
             log::debug!("im_attrs: {:#?}", im_attrs);
             log::debug!("ic_attrs: {:#?}", ic_attrs);
+            if client.state().quirks().share_attribute_ids {
+                for attr in im_attrs.iter() {
+                    if ic_attrs.iter().all(|ic_attr| ic_attr.name != attr.name) {
+                        ic_attrs.push(attr.clone());
+                    }
+                }
+            }
+            client.state_mut().set_attribute_types(&im_attrs, &ic_attrs);
             client.set_attrs(im_attrs, ic_attrs);
             // Require for uim
-            client.send_req(Request::EncodingNegotiation {
-                encodings: vec!["COMPOUND_TEXT".into()],
-                encoding_infos: vec![],
-                input_method_id,
-            })
+            send_req(
+                client,
+                Request::EncodingNegotiation {
+                    encodings: client
+                        .state()
+                        .encodings()
+                        .iter()
+                        .map(|encoding| encoding.name().into())
+                        .collect(),
+                    encoding_infos: vec![],
+                    input_method_id,
+                },
+            )
         }
         Request::EncodingNegotiationReply {
             input_method_id,
@@ -106,26 +299,82 @@ pub fn handle_request<C: ClientCore>(
                 })
                 .collect(),
         ),
+        Request::SetImValuesReply { input_method_id } => {
+            handler.handle_set_im_values(client, input_method_id)
+        }
         Request::SetIcValuesReply {
             input_method_id,
             input_context_id,
         } => handler.handle_set_ic_values(client, input_method_id, input_context_id),
+        Request::GetIcValuesReply {
+            input_method_id,
+            input_context_id,
+            ic_attributes,
+        } => {
+            let values = IcValues::decode(client.ic_attributes(), &ic_attributes);
+            handler.handle_get_ic_values(client, input_method_id, input_context_id, values)
+        }
         Request::CreateIcReply {
             input_method_id,
             input_context_id,
-        } => handler.handle_create_ic(client, input_method_id, input_context_id),
+        } => {
+            #[cfg(feature = "tracing")]
+            tracing::info!(input_method_id, input_context_id, "ic created");
+            handler.handle_create_ic(client, input_method_id, input_context_id)
+        }
+        Request::DestroyIcReply {
+            input_method_id,
+            input_context_id,
+        } => handler.handle_destroy_ic(client, input_method_id, input_context_id),
         Request::SetEventMask {
             input_method_id,
             input_context_id,
             forward_event_mask,
             synchronous_event_mask,
-        } => handler.handle_set_event_mask(
-            client,
+        }
+        | Request::ExtSetEventMask {
             input_method_id,
             input_context_id,
             forward_event_mask,
             synchronous_event_mask,
-        ),
+            ..
+        } => {
+            let changed = client.state_mut().set_event_mask(
+                input_method_id,
+                input_context_id,
+                forward_event_mask,
+                synchronous_event_mask,
+            );
+
+            handler.handle_set_event_mask(
+                client,
+                input_method_id,
+                input_context_id,
+                forward_event_mask,
+                synchronous_event_mask,
+            )?;
+
+            if changed {
+                notify_active(client, handler, input_method_id, input_context_id)?;
+            }
+
+            Ok(())
+        }
+        Request::ResetIcReply {
+            input_method_id,
+            input_context_id,
+            preedit_string,
+        } => {
+            let preedit_string = encoding_of(client, input_method_id)
+                .decode(&preedit_string)
+                .ok_or(ClientError::InvalidReply)?;
+            handler.handle_reset_ic_reply(
+                client,
+                input_method_id,
+                input_context_id,
+                &preedit_string,
+            )
+        }
         Request::CloseReply { input_method_id } => handler.handle_close(client, input_method_id),
         Request::DisconnectReply {} => {
             handler.handle_disconnect();
@@ -138,77 +387,218 @@ pub fn handle_request<C: ClientCore>(
             input_context_id,
             flag,
             ..
+        } => dispatch_forward_event(
+            client,
+            handler,
+            input_method_id,
+            input_context_id,
+            flag,
+            &xev,
+        ),
+        Request::ExtForwardKeyEvent {
+            input_method_id,
+            input_context_id,
+            flag,
+            sequence_number,
+            xev_type,
+            keycode,
+            state,
+            time,
+            window,
         } => {
-            handler.handle_forward_event(
+            let negotiated = client
+                .registry()
+                .input_method(input_method_id)
+                .and_then(|im| im.extension(EXT_FORWARD_KEYEVENT))
+                .is_some();
+
+            if !negotiated {
+                log::warn!("{} is not negotiated", EXT_FORWARD_KEYEVENT);
+                return Ok(());
+            }
+
+            // the server re-injects the event, mark it as synthesized like `SendEvent`
+            let xev = xim_parser::XEvent {
+                response_type: xev_type | SEND_EVENT_MASK,
+                detail: keycode,
+                sequence: sequence_number,
+                time,
+                root: 0,
+                event: window,
+                child: 0,
+                root_x: 0,
+                root_y: 0,
+                event_x: 0,
+                event_y: 0,
+                state,
+                same_screen: true,
+            };
+
+            dispatch_forward_event(
                 client,
+                handler,
                 input_method_id,
                 input_context_id,
                 flag,
-                client.deserialize_event(&xev),
-            )?;
-
-            if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
-                client.send_req(Request::SyncReply {
-                    input_method_id,
-                    input_context_id,
-                })?;
-            }
-
-            Ok(())
+                &xev,
+            )
         }
         Request::Commit {
             input_method_id,
             input_context_id,
             data,
-        } => match data {
-            CommitData::Keysym { keysym: _, .. } => {
-                log::warn!("Keysym commit is not supported");
-                Ok(())
-            }
-            CommitData::Chars {
-                commited,
-                syncronous,
-            } => {
-                handler.handle_commit(
-                    client,
-                    input_method_id,
-                    input_context_id,
-                    &xim_ctext::compound_text_to_utf8(&commited).expect("Encoding Error"),
-                )?;
+        } => {
+            let syncronous = match data {
+                CommitData::Keysym { keysym, syncronous } => {
+                    match keysym_to_char(keysym) {
+                        Some(c) => handler.handle_commit(
+                            client,
+                            input_method_id,
+                            input_context_id,
+                            c.encode_utf8(&mut [0; 4]),
+                        )?,
+                        None => handler.handle_commit_keysym(
+                            client,
+                            input_method_id,
+                            input_context_id,
+                            keysym,
+                        )?,
+                    }
 
-                if syncronous {
-                    client.send_req(Request::SyncReply {
+                    syncronous
+                }
+                CommitData::Chars {
+                    commited,
+                    syncronous,
+                } => {
+                    handler.handle_commit(
+                        client,
                         input_method_id,
                         input_context_id,
-                    })?;
+                        &encoding_of(client, input_method_id)
+                            .decode(&commited)
+                            .ok_or(ClientError::InvalidReply)?,
+                    )?;
+
+                    syncronous
                 }
+                CommitData::Both {
+                    keysym,
+                    commited,
+                    syncronous,
+                } => {
+                    handler.handle_commit_both(
+                        client,
+                        input_method_id,
+                        input_context_id,
+                        keysym,
+                        &encoding_of(client, input_method_id)
+                            .decode(&commited)
+                            .ok_or(ClientError::InvalidReply)?,
+                    )?;
 
-                Ok(())
-            }
-            CommitData::Both { .. } => {
-                log::warn!("Both commit data is not supported");
-                Ok(())
+                    syncronous
+                }
+            };
+
+            if syncronous {
+                send_req(
+                    client,
+                    Request::SyncReply {
+                        input_method_id,
+                        input_context_id,
+                    },
+                )?;
             }
-        },
+
+            Ok(())
+        }
         Request::Sync {
             input_method_id,
             input_context_id,
-        } => client.send_req(Request::SyncReply {
+        } => send_req(
+            client,
+            Request::SyncReply {
+                input_method_id,
+                input_context_id,
+            },
+        ),
+        Request::SyncReply {
             input_method_id,
             input_context_id,
-        }),
-        Request::SyncReply { .. } => {
-            // Nothing to do
+        } => flush_forward_events(client, input_method_id, input_context_id),
+        Request::RegisterTriggerKeys {
+            on_keys, off_keys, ..
+        } => {
+            // input_method_id is not used, trigger keys are for the whole connection
+            client
+                .state_mut()
+                .trigger_keys_mut()
+                .register(on_keys, off_keys);
             Ok(())
         }
+        Request::TriggerNotifyReply {
+            input_method_id,
+            input_context_id,
+        } => notify_active(client, handler, input_method_id, input_context_id),
+        Request::StrConversion {
+            input_method_id,
+            input_context_id,
+            position,
+            direction,
+            operation,
+            factor,
+            ..
+        } => {
+            let text = handler.handle_str_conversion(
+                client,
+                input_method_id,
+                input_context_id,
+                position,
+                direction,
+                operation,
+                factor,
+            )?;
+
+            send_req(
+                client,
+                Request::StrConversionReply {
+                    input_method_id,
+                    input_context_id,
+                    feedback: 0,
+                    text: StrConvText {
+                        string: encoding_of(client, input_method_id).encode(&text),
+                        feedbacks: vec![0; text.chars().count()],
+                    },
+                },
+            )
+        }
         Request::PreeditStart {
             input_method_id,
             input_context_id,
-        } => handler.handle_preedit_start(client, input_method_id, input_context_id),
+        } => {
+            handler.handle_preedit_start(client, input_method_id, input_context_id)?;
+            update_preedit(
+                client,
+                handler,
+                input_method_id,
+                input_context_id,
+                PreeditState::clear,
+            )
+        }
         Request::PreeditDone {
             input_method_id,
             input_context_id,
-        } => handler.handle_preedit_done(client, input_method_id, input_context_id),
+        } => {
+            handler.handle_preedit_done(client, input_method_id, input_context_id)?;
+            update_preedit(
+                client,
+                handler,
+                input_method_id,
+                input_context_id,
+                PreeditState::clear,
+            )
+        }
         Request::PreeditDraw {
             input_method_id,
             input_context_id,
@@ -219,7 +609,10 @@ pub fn handle_request<C: ClientCore>(
             status,
             feedbacks,
         } => {
-            let preedit_string = xim_ctext::compound_text_to_utf8(&preedit_string).unwrap();
+            let preedit_string = encoding_of(client, input_method_id)
+                .decode(&preedit_string)
+                .ok_or(ClientError::InvalidReply)?;
+            let tracked = feedbacks.clone();
             handler.handle_preedit_draw(
                 client,
                 input_method_id,
@@ -230,6 +623,22 @@ pub fn handle_request<C: ClientCore>(
                 status,
                 &preedit_string,
                 feedbacks,
+            )?;
+            update_preedit(
+                client,
+                handler,
+                input_method_id,
+                input_context_id,
+                |preedit| {
+                    preedit.apply_draw(
+                        caret,
+                        chg_first,
+                        chg_length,
+                        status,
+                        &preedit_string,
+                        &tracked,
+                    )
+                },
             )
         }
         Request::PreeditCaret {
@@ -250,11 +659,54 @@ pub fn handle_request<C: ClientCore>(
             )?;
 
             // Send the reply.
-            client.send_req(Request::PreeditCaretReply {
-                input_method_id,
-                input_context_id,
-                position,
-            })
+            send_req(
+                client,
+                Request::PreeditCaretReply {
+                    input_method_id,
+                    input_context_id,
+                    position,
+                },
+            )?;
+
+            if direction == CaretDirection::AbsolutePosition {
+                update_preedit(
+                    client,
+                    handler,
+                    input_method_id,
+                    input_context_id,
+                    |preedit| preedit.set_caret(position),
+                )
+            } else {
+                Ok(())
+            }
+        }
+        Request::Geometry {
+            input_method_id,
+            input_context_id,
+        } => handler.handle_geometry(client, input_method_id, input_context_id),
+        Request::ExtensionMessage {
+            major_opcode,
+            minor_opcode,
+            data,
+        } => {
+            let ext = client
+                .registry()
+                .input_methods()
+                .flat_map(|im| im.extensions())
+                .find(|ext| ext.major_opcode == major_opcode && ext.minor_opcode == minor_opcode)
+                .cloned();
+
+            match ext {
+                Some(ext) => handler.handle_extension_message(client, &ext, &data),
+                None => {
+                    log::warn!(
+                        "Unknown extension message ({}, {})",
+                        major_opcode,
+                        minor_opcode
+                    );
+                    Ok(())
+                }
+            }
         }
         _ => {
             log::warn!("Unknown request {:?}", req);
@@ -263,6 +715,154 @@ pub fn handle_request<C: ClientCore>(
     }
 }
 
+/// Apply `f` to the tracked preedit of the input context and pass it to the handler, nothing
+/// is done unless preedit tracking is on
+fn update_preedit<C: ClientCore>(
+    client: &mut C,
+    handler: &mut impl ClientHandler<C>,
+    input_method_id: u16,
+    input_context_id: u16,
+    f: impl FnOnce(&mut PreeditState),
+) -> Result<(), ClientError> {
+    if !client.state().preedit_tracking() {
+        return Ok(());
+    }
+
+    let preedit = match client
+        .state_mut()
+        .registry_mut()
+        .input_context_mut(input_method_id, input_context_id)
+    {
+        Some(ic) => {
+            let preedit = ic.preedit_mut();
+            f(preedit);
+            preedit.clone()
+        }
+        None => return Ok(()),
+    };
+
+    handler.handle_preedit_changed(client, input_method_id, input_context_id, &preedit)
+}
+
+/// Pass an event forwarded by the server to the handler then reply `SyncReply` if it's asked
+fn dispatch_forward_event<C: ClientCore>(
+    client: &mut C,
+    handler: &mut impl ClientHandler<C>,
+    input_method_id: u16,
+    input_context_id: u16,
+    flag: ForwardEventFlag,
+    xev: &xim_parser::XEvent,
+) -> Result<(), ClientError> {
+    handler.handle_forward_event(
+        client,
+        input_method_id,
+        input_context_id,
+        flag,
+        client.deserialize_event(xev),
+    )?;
+
+    if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
+        send_req(
+            client,
+            Request::SyncReply {
+                input_method_id,
+                input_context_id,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Answer the authentication of the server, `None` gives it up with `AuthNg`
+fn send_auth_reply<C: ClientCore>(
+    client: &mut C,
+    auth_data: Option<Vec<u8>>,
+) -> Result<(), ClientError> {
+    match auth_data {
+        Some(auth_data) => send_req(client, Request::AuthReply { auth_data }),
+        None => send_req(client, Request::AuthNg {}),
+    }
+}
+
+/// Send `ForwardEvent`, or queue it while the input context waits `SyncReply`
+fn send_forward_event<C: ClientCore>(
+    client: &mut C,
+    input_method_id: u16,
+    input_context_id: u16,
+    flag: ForwardEventFlag,
+    xev: xim_parser::XEvent,
+) -> Result<(), ClientError> {
+    if let Some(ic) = client
+        .state_mut()
+        .registry_mut()
+        .input_context_mut(input_method_id, input_context_id)
+    {
+        if ic.is_waiting_sync() {
+            ic.queue_event(flag, xev);
+            return Ok(());
+        }
+    }
+
+    send_req(
+        client,
+        Request::ForwardEvent {
+            input_method_id,
+            input_context_id,
+            flag,
+            serial_number: xev.sequence,
+            xev,
+        },
+    )
+}
+
+/// Send events queued until `SyncReply`, stop again at a synchronous event
+fn flush_forward_events<C: ClientCore>(
+    client: &mut C,
+    input_method_id: u16,
+    input_context_id: u16,
+) -> Result<(), ClientError> {
+    loop {
+        let next = client
+            .state_mut()
+            .registry_mut()
+            .input_context_mut(input_method_id, input_context_id)
+            .and_then(|ic| ic.pop_queued_event());
+
+        match next {
+            Some((flag, xev)) => {
+                send_forward_event(client, input_method_id, input_context_id, flag, xev)?
+            }
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Encoding negotiated for the input method
+fn encoding_of<C: ClientCore>(client: &C, input_method_id: u16) -> Encoding {
+    client
+        .registry()
+        .input_method(input_method_id)
+        .map_or_else(Encoding::default, |im| im.encoding())
+}
+
+fn notify_active<C: ClientCore>(
+    client: &mut C,
+    handler: &mut impl ClientHandler<C>,
+    input_method_id: u16,
+    input_context_id: u16,
+) -> Result<(), ClientError> {
+    let active = match client
+        .registry()
+        .input_context(input_method_id, input_context_id)
+    {
+        Some(ic) => ic.is_active(),
+        None => return Ok(()),
+    };
+
+    handler.handle_active_changed(client, input_method_id, input_context_id, active)
+}
+
 pub trait ClientCore {
     type XEvent;
 
@@ -270,17 +870,63 @@ pub trait ClientCore {
     fn ic_attributes(&self) -> &AHashMap<AttributeName, u16>;
     fn im_attributes(&self) -> &AHashMap<AttributeName, u16>;
     fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent;
+    /// Keysym of the key event, used to check trigger keys
+    fn lookup_keysym(&mut self, xev: &Self::XEvent) -> Result<u32, ClientError>;
     fn deserialize_event(&self, xev: &xim_parser::XEvent) -> Self::XEvent;
     fn send_req(&mut self, req: Request) -> Result<(), ClientError>;
+    /// Flush requests buffered in the X connection
+    fn flush(&mut self) -> Result<(), ClientError>;
+    fn state(&self) -> &ClientState;
+    fn state_mut(&mut self) -> &mut ClientState;
 }
 
 pub trait Client {
     type XEvent;
 
-    fn build_ic_attributes(&self) -> AttributeBuilder;
-    fn build_im_attributes(&self) -> AttributeBuilder;
+    fn build_ic_attributes(&self) -> AttributeBuilder<'_>;
+    fn build_im_attributes(&self) -> AttributeBuilder<'_>;
+
+    /// Requests waiting for replies
+    fn pending_requests(&self) -> &PendingRequests;
+    fn pending_requests_mut(&mut self) -> &mut PendingRequests;
+    /// Opened input methods and created input contexts
+    fn registry(&self) -> &ImRegistry;
+    /// Use it to attach user data to input contexts
+    fn registry_mut(&mut self) -> &mut ImRegistry;
+    /// Trigger keys registered by the server
+    fn trigger_keys(&self) -> &TriggerKeys;
+    /// Transport parameters of the server, `None` until it's connected
+    fn transport(&self) -> Option<&TransportInfo>;
+    /// Set encodings offered to the server in order of preference, it's applied to input
+    /// methods opened after. `COMPOUND_TEXT` is used when the server supports none of them.
+    fn set_encodings(&mut self, encodings: Vec<Encoding>);
+    /// Workarounds applied for the server
+    fn quirks(&self) -> Quirks;
+    fn set_quirks(&mut self, quirks: Quirks);
+    /// Keep the preedit of each input context from `PreeditDraw` and pass it to
+    /// [`ClientHandler::handle_preedit_changed`], see [`InputContextInfo::preedit`]
+    fn set_preedit_tracking(&mut self, tracking: bool);
+    /// Snapshot of requests exchanged with the server and latencies of their replies
+    #[cfg(feature = "metrics")]
+    fn metrics(&self) -> Metrics;
+    #[cfg(feature = "metrics")]
+    fn reset_metrics(&mut self);
+    /// Install hooks called for every request sent or received, `None` removes it
+    fn set_interceptor(&mut self, interceptor: Option<Box<dyn RequestInterceptor>>);
+    /// Drop requests not replied in [`PendingRequests::timeout`], return
+    /// [`ClientError::Timeout`] when any request is dropped.
+    ///
+    /// Call it periodically, for example at [`PendingRequests::next_deadline`], to not wait
+    /// forever for a server which stopped responding.
+    #[cfg(feature = "std")]
+    fn poll_timeouts(&mut self) -> Result<(), ClientError>;
 
     fn disconnect(&mut self) -> Result<(), ClientError>;
+    /// Destroy every input context, close every input method then disconnect from the server.
+    ///
+    /// It doesn't wait the replies, use `BlockingClient::shutdown_blocking` to wait them and
+    /// destroy the client window.
+    fn shutdown(&mut self) -> Result<(), ClientError>;
     fn open(&mut self, locale: &str) -> Result<(), ClientError>;
     fn close(&mut self, input_method_id: u16) -> Result<(), ClientError>;
     fn quert_extension(
@@ -288,17 +934,39 @@ pub trait Client {
         input_method_id: u16,
         extensions: &[&str],
     ) -> Result<(), ClientError>;
+    /// Send `data` with the opcodes of extension `name` which the server replied to
+    /// [`Client::quert_extension`], return `false` when the extension is not negotiated.
+    ///
+    /// `data` is the body of the message after the header, it's padded to 4 bytes.
+    fn send_extension_request(
+        &mut self,
+        input_method_id: u16,
+        name: &str,
+        data: Vec<u8>,
+    ) -> Result<bool, ClientError>;
     fn get_im_values(
         &mut self,
         input_method_id: u16,
         names: &[AttributeName],
     ) -> Result<(), ClientError>;
+    /// Set IM attributes, use [`Client::build_im_attributes`] to build `im_attributes`
+    fn set_im_values(
+        &mut self,
+        input_method_id: u16,
+        im_attributes: Vec<Attribute>,
+    ) -> Result<(), ClientError>;
     fn set_ic_values(
         &mut self,
         input_method_id: u16,
         input_context_id: u16,
         ic_attributes: Vec<Attribute>,
     ) -> Result<(), ClientError>;
+    fn get_ic_values(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        names: &[AttributeName],
+    ) -> Result<(), ClientError>;
     fn create_ic(
         &mut self,
         input_method_id: u16,
@@ -316,6 +984,52 @@ pub trait Client {
         flag: ForwardEventFlag,
         xev: &Self::XEvent,
     ) -> Result<(), ClientError>;
+    /// Forward several events and flush the connection once, instead of once per event.
+    ///
+    /// Useful to pass events piled up by fast typing or key repeat. Every event is still sent
+    /// as its own `ForwardEvent` since servers read a message per transfer.
+    fn forward_events(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xevs: &[Self::XEvent],
+    ) -> Result<(), ClientError>;
+    /// Forward a core event other than key events, e.g. `ButtonPress` for candidate windows.
+    ///
+    /// `xev` is serialized by `serialize_button_event` or `serialize_motion_event` of the
+    /// backend module.
+    /// It's forwarded only when the server asked the type with `SetEventMask`, return `false`
+    /// otherwise.
+    fn forward_core_event(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: &xim_parser::XEvent,
+    ) -> Result<bool, ClientError>;
+    /// Pass a key event to the input context.
+    ///
+    /// When the server registered trigger keys, `TriggerNotify` is sent for the on/off keys and
+    /// other events are forwarded only while the input context is on. Return `false` when the
+    /// event is not passed to the server so the application should handle it by itself.
+    fn filter_key_event(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: &Self::XEvent,
+    ) -> Result<bool, ClientError>;
+    /// Move the spot location of over-the-spot input context, `XIM_EXT_MOVE` is used when
+    /// the server replied it to [`Client::quert_extension`]
+    fn update_spot(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        spot_location: Point,
+    ) -> Result<(), ClientError>;
+    /// Reset input context, the server replies with the preedit string it had
+    fn reset_ic(&mut self, input_method_id: u16, input_context_id: u16) -> Result<(), ClientError>;
     fn set_focus(&mut self, input_method_id: u16, input_context_id: u16)
         -> Result<(), ClientError>;
     fn unset_focus(
@@ -331,18 +1045,109 @@ where
 {
     type XEvent = C::XEvent;
 
-    fn build_ic_attributes(&self) -> AttributeBuilder {
-        AttributeBuilder::new(self.ic_attributes())
+    fn build_ic_attributes(&self) -> AttributeBuilder<'_> {
+        AttributeBuilder::new(self.ic_attributes(), self.state().ic_attribute_types())
+    }
+
+    fn build_im_attributes(&self) -> AttributeBuilder<'_> {
+        AttributeBuilder::new(self.im_attributes(), self.state().im_attribute_types())
     }
 
-    fn build_im_attributes(&self) -> AttributeBuilder {
-        AttributeBuilder::new(self.im_attributes())
+    #[inline]
+    fn pending_requests(&self) -> &PendingRequests {
+        self.state().pending_requests()
+    }
+
+    #[inline]
+    fn pending_requests_mut(&mut self) -> &mut PendingRequests {
+        self.state_mut().pending_requests_mut()
+    }
+
+    #[inline]
+    fn registry(&self) -> &ImRegistry {
+        self.state().registry()
+    }
+
+    #[inline]
+    fn registry_mut(&mut self) -> &mut ImRegistry {
+        self.state_mut().registry_mut()
+    }
+
+    #[inline]
+    fn trigger_keys(&self) -> &TriggerKeys {
+        self.state().trigger_keys()
+    }
+
+    fn transport(&self) -> Option<&TransportInfo> {
+        self.state().transport()
+    }
+
+    fn set_encodings(&mut self, encodings: Vec<Encoding>) {
+        self.state_mut().set_encodings(encodings);
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.state().quirks()
+    }
+
+    fn set_quirks(&mut self, quirks: Quirks) {
+        self.state_mut().set_quirks(quirks);
+    }
+
+    fn set_preedit_tracking(&mut self, tracking: bool) {
+        self.state_mut().set_preedit_tracking(tracking);
+    }
+
+    #[cfg(feature = "metrics")]
+    fn metrics(&self) -> Metrics {
+        self.state().metrics().clone()
+    }
+
+    #[cfg(feature = "metrics")]
+    fn reset_metrics(&mut self) {
+        *self.state_mut().metrics_mut() = Metrics::new();
+    }
+
+    fn set_interceptor(&mut self, interceptor: Option<Box<dyn RequestInterceptor>>) {
+        self.state_mut().set_interceptor(interceptor);
+    }
+
+    #[cfg(feature = "std")]
+    fn poll_timeouts(&mut self) -> Result<(), ClientError> {
+        let expired = self
+            .pending_requests_mut()
+            .remove_expired(std::time::Instant::now());
+
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        for req in expired.iter() {
+            log::warn!("{:?} is not replied in time", req);
+            self.state_mut().registry_mut().request_failed(req.id());
+            #[cfg(feature = "metrics")]
+            self.state_mut().metrics_mut().request_expired(req);
+        }
+
+        // don't hold events forever for a lost SyncReply
+        for req in expired.iter() {
+            if let (ReplyKind::Sync, Some(input_method_id), Some(input_context_id)) =
+                (req.reply(), req.input_method_id(), req.input_context_id())
+            {
+                flush_forward_events(self, input_method_id, input_context_id)?;
+            }
+        }
+
+        Err(ClientError::Timeout)
     }
 
     fn open(&mut self, locale: &str) -> Result<(), ClientError> {
-        self.send_req(Request::Open {
-            locale: locale.into(),
-        })
+        send_req(
+            self,
+            Request::Open {
+                locale: locale.into(),
+            },
+        )
     }
 
     fn quert_extension(
@@ -350,10 +1155,40 @@ where
         input_method_id: u16,
         extensions: &[&str],
     ) -> Result<(), ClientError> {
-        self.send_req(Request::QueryExtension {
-            input_method_id,
-            extensions: extensions.iter().map(|&e| e.into()).collect(),
-        })
+        send_req(
+            self,
+            Request::QueryExtension {
+                input_method_id,
+                extensions: extensions.iter().map(|&e| e.into()).collect(),
+            },
+        )
+    }
+
+    fn send_extension_request(
+        &mut self,
+        input_method_id: u16,
+        name: &str,
+        data: Vec<u8>,
+    ) -> Result<bool, ClientError> {
+        let (major_opcode, minor_opcode) = match self
+            .registry()
+            .input_method(input_method_id)
+            .and_then(|im| im.extension(name))
+        {
+            Some(ext) => (ext.major_opcode, ext.minor_opcode),
+            None => return Ok(false),
+        };
+
+        send_req(
+            self,
+            Request::ExtensionMessage {
+                major_opcode,
+                minor_opcode,
+                data,
+            },
+        )?;
+
+        Ok(true)
     }
 
     fn get_im_values(
@@ -361,13 +1196,30 @@ where
         input_method_id: u16,
         names: &[AttributeName],
     ) -> Result<(), ClientError> {
-        self.send_req(Request::GetImValues {
-            input_method_id,
-            im_attributes: names
-                .iter()
-                .filter_map(|name| self.im_attributes().get(name).copied())
-                .collect(),
-        })
+        send_req(
+            self,
+            Request::GetImValues {
+                input_method_id,
+                im_attributes: names
+                    .iter()
+                    .filter_map(|name| self.im_attributes().get(name).copied())
+                    .collect(),
+            },
+        )
+    }
+
+    fn set_im_values(
+        &mut self,
+        input_method_id: u16,
+        im_attributes: Vec<Attribute>,
+    ) -> Result<(), ClientError> {
+        send_req(
+            self,
+            Request::SetImValues {
+                input_method_id,
+                attributes: im_attributes,
+            },
+        )
     }
 
     fn set_ic_values(
@@ -376,11 +1228,33 @@ where
         input_context_id: u16,
         ic_attributes: Vec<Attribute>,
     ) -> Result<(), ClientError> {
-        self.send_req(Request::SetIcValues {
-            input_method_id,
-            input_context_id,
-            ic_attributes,
-        })
+        send_req(
+            self,
+            Request::SetIcValues {
+                input_method_id,
+                input_context_id,
+                ic_attributes,
+            },
+        )
+    }
+
+    fn get_ic_values(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        names: &[AttributeName],
+    ) -> Result<(), ClientError> {
+        send_req(
+            self,
+            Request::GetIcValues {
+                input_method_id,
+                input_context_id,
+                ic_attributes: names
+                    .iter()
+                    .filter_map(|name| self.ic_attributes().get(name).copied())
+                    .collect(),
+            },
+        )
     }
 
     fn create_ic(
@@ -388,10 +1262,13 @@ where
         input_method_id: u16,
         ic_attributes: Vec<Attribute>,
     ) -> Result<(), ClientError> {
-        self.send_req(Request::CreateIc {
-            input_method_id,
-            ic_attributes,
-        })
+        send_req(
+            self,
+            Request::CreateIc {
+                input_method_id,
+                ic_attributes,
+            },
+        )
     }
 
     fn forward_event(
@@ -402,21 +1279,82 @@ where
         xev: &Self::XEvent,
     ) -> Result<(), ClientError> {
         let ev = self.serialize_event(xev);
-        self.send_req(Request::ForwardEvent {
-            input_method_id,
-            input_context_id,
-            flag,
-            serial_number: ev.sequence,
-            xev: ev,
-        })
+        send_forward_event(self, input_method_id, input_context_id, flag, ev)
+    }
+
+    fn forward_events(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xevs: &[Self::XEvent],
+    ) -> Result<(), ClientError> {
+        self.state_mut().set_batching(true);
+        let ret = xevs
+            .iter()
+            .try_for_each(|xev| self.forward_event(input_method_id, input_context_id, flag, xev));
+        self.state_mut().set_batching(false);
+        ret?;
+
+        self.flush()
+    }
+
+    fn forward_core_event(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: &xim_parser::XEvent,
+    ) -> Result<bool, ClientError> {
+        let forwards = self
+            .registry()
+            .input_context(input_method_id, input_context_id)
+            .map_or(false, |ic| ic.forwards(xev.response_type));
+
+        if !forwards {
+            return Ok(false);
+        }
+
+        send_forward_event(self, input_method_id, input_context_id, flag, xev.clone())?;
+
+        Ok(true)
     }
 
     fn disconnect(&mut self) -> Result<(), ClientError> {
-        self.send_req(Request::Disconnect {})
+        send_req(self, Request::Disconnect {})
+    }
+
+    fn shutdown(&mut self) -> Result<(), ClientError> {
+        // nothing to clean up before XIM_XCONNECT
+        if self.transport().is_none() {
+            return Ok(());
+        }
+
+        let input_contexts: Vec<(u16, u16)> = self
+            .registry()
+            .input_contexts()
+            .map(|ic| (ic.input_method_id(), ic.input_context_id()))
+            .collect();
+        let input_methods: Vec<u16> = self
+            .registry()
+            .input_methods()
+            .map(|im| im.input_method_id())
+            .collect();
+
+        self.state_mut().set_batching(true);
+        let ret = input_contexts
+            .into_iter()
+            .try_for_each(|(im, ic)| self.destroy_ic(im, ic))
+            .and_then(|_| input_methods.into_iter().try_for_each(|im| self.close(im)))
+            .and_then(|_| self.disconnect());
+        self.state_mut().set_batching(false);
+        ret?;
+
+        self.flush()
     }
 
     fn close(&mut self, input_method_id: u16) -> Result<(), ClientError> {
-        self.send_req(Request::Close { input_method_id })
+        send_req(self, Request::Close { input_method_id })
     }
 
     fn destroy_ic(
@@ -424,10 +1362,128 @@ where
         input_method_id: u16,
         input_context_id: u16,
     ) -> Result<(), ClientError> {
-        self.send_req(Request::DestroyIc {
-            input_method_id,
-            input_context_id,
-        })
+        send_req(
+            self,
+            Request::DestroyIc {
+                input_method_id,
+                input_context_id,
+            },
+        )
+    }
+
+    fn filter_key_event(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: &Self::XEvent,
+    ) -> Result<bool, ClientError> {
+        let ev = self.serialize_event(xev);
+        let ic = self
+            .registry()
+            .input_context(input_method_id, input_context_id);
+        let active = ic.map_or(true, |ic| ic.is_active());
+        let wait_event_mask = self.state().quirks().wait_event_mask;
+        let forwards = ic.map_or(true, |ic| {
+            ic.forwards(ev.response_type) && !(wait_event_mask && ic.forward_event_mask().is_none())
+        });
+
+        // trigger keys are checked on KeyPress
+        if !self.trigger_keys().is_empty() && ev.response_type & 0x7f == 2 {
+            let keysym = self.lookup_keysym(xev)?;
+
+            if let Some((trigger, index)) =
+                self.trigger_keys()
+                    .check(active, keysym, u32::from(ev.state))
+            {
+                let on = trigger == TriggerNotifyFlag::OnKeyList;
+                if let Some(ic) = self
+                    .state_mut()
+                    .registry_mut()
+                    .input_context_mut(input_method_id, input_context_id)
+                {
+                    ic.set_active(on);
+                    // until the server sets its own masks
+                    ic.set_event_mask(TRIGGER_EVENT_MASK, 0);
+                }
+
+                send_req(
+                    self,
+                    Request::TriggerNotify {
+                        input_method_id,
+                        input_context_id,
+                        flag: trigger,
+                        index,
+                        event_mask: TRIGGER_EVENT_MASK,
+                    },
+                )?;
+
+                return Ok(true);
+            }
+        }
+
+        if forwards {
+            self.forward_event(input_method_id, input_context_id, flag, xev)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn update_spot(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        spot_location: Point,
+    ) -> Result<(), ClientError> {
+        let ext_move = self
+            .registry()
+            .input_method(input_method_id)
+            .and_then(|im| im.extension(EXT_MOVE))
+            .map_or(false, |ext| {
+                // opcodes of the parser are fixed
+                (ext.major_opcode, ext.minor_opcode) == (128, 51)
+            });
+
+        if ext_move {
+            // ExtMove has no reply, update the registry by ourselves
+            if let Some(ic) = self
+                .state_mut()
+                .registry_mut()
+                .input_context_mut(input_method_id, input_context_id)
+            {
+                ic.set_spot_location(spot_location.clone());
+            }
+
+            return send_req(
+                self,
+                Request::ExtMove {
+                    input_method_id,
+                    input_context_id,
+                    x: spot_location.x,
+                    y: spot_location.y,
+                },
+            );
+        }
+
+        let ic_attributes = self
+            .build_ic_attributes()
+            .preedit_attributes(|b| {
+                b.spot_location(spot_location);
+            })
+            .build();
+
+        self.set_ic_values(input_method_id, input_context_id, ic_attributes)
+    }
+
+    fn reset_ic(&mut self, input_method_id: u16, input_context_id: u16) -> Result<(), ClientError> {
+        send_req(
+            self,
+            Request::ResetIc {
+                input_method_id,
+                input_context_id,
+            },
+        )
     }
 
     fn set_focus(
@@ -435,29 +1491,64 @@ where
         input_method_id: u16,
         input_context_id: u16,
     ) -> Result<(), ClientError> {
-        self.send_req(Request::SetIcFocus {
-            input_method_id,
-            input_context_id,
-        })
+        send_req(
+            self,
+            Request::SetIcFocus {
+                input_method_id,
+                input_context_id,
+            },
+        )
     }
     fn unset_focus(
         &mut self,
         input_method_id: u16,
         input_context_id: u16,
     ) -> Result<(), ClientError> {
-        self.send_req(Request::UnsetIcFocus {
-            input_method_id,
-            input_context_id,
-        })
+        send_req(
+            self,
+            Request::UnsetIcFocus {
+                input_method_id,
+                input_context_id,
+            },
+        )
     }
 }
 
+/// Callbacks of replies and requests sent by the server.
+///
+/// Every method has a default implementation which ignores the message, replies which the
+/// protocol requires (e.g. `SyncReply`, `PreeditCaretReply`) are sent by the client after the
+/// callback returns. Implementors only need to override what they're interested in.
 #[allow(unused_variables)]
-pub trait ClientHandler<C: Client> {
+pub trait ClientHandler<C: Client + ?Sized> {
     fn handle_connect(&mut self, client: &mut C) -> Result<(), ClientError> {
         Ok(())
     }
     fn handle_disconnect(&mut self) {}
+    /// Called when the server requires authentication to connect, return the data for the
+    /// `auth_protocol_index`th protocol or `None` to refuse it.
+    ///
+    /// The default refuses every authentication.
+    fn handle_auth_required(
+        &mut self,
+        client: &mut C,
+        auth_protocol_index: u8,
+        auth_data: &[u8],
+    ) -> Result<Option<Vec<u8>>, ClientError> {
+        Ok(None)
+    }
+    /// Called when the server needs more data to authenticate, return `None` to give it up
+    fn handle_auth_next(
+        &mut self,
+        client: &mut C,
+        auth_data: &[u8],
+    ) -> Result<Option<Vec<u8>>, ClientError> {
+        Ok(None)
+    }
+    /// Called when the server window is destroyed, every input method and input context are gone
+    fn handle_server_disconnected(&mut self, client: &mut C) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_open(&mut self, client: &mut C, input_method_id: u16) -> Result<(), ClientError> {
         Ok(())
     }
@@ -471,6 +1562,16 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// Called when the server sends a message of a negotiated extension which has no format in
+    /// [`Request`]
+    fn handle_extension_message(
+        &mut self,
+        client: &mut C,
+        extension: &Extension,
+        data: &[u8],
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_get_im_values(
         &mut self,
         client: &mut C,
@@ -479,6 +1580,13 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    fn handle_set_im_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_set_ic_values(
         &mut self,
         client: &mut C,
@@ -487,6 +1595,25 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    fn handle_get_ic_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        values: IcValues,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
+    /// Called when the server wants to renegotiate the geometry of the input context, see
+    /// [`geometry`](crate::geometry)
+    fn handle_geometry(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_create_ic(
         &mut self,
         client: &mut C,
@@ -503,6 +1630,16 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// `preedit_string` is the preedit string of the input context before reset
+    fn handle_reset_ic_reply(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        preedit_string: &str,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_commit(
         &mut self,
         client: &mut C,
@@ -512,6 +1649,32 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// Called when both keysym and text are committed, deliver `text` to
+    /// [`ClientHandler::handle_commit`] by default
+    fn handle_commit_both(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        keysym: u32,
+        text: &str,
+    ) -> Result<(), ClientError> {
+        self.handle_commit(client, input_method_id, input_context_id, text)
+    }
+    /// Called when committed keysym doesn't map to text
+    fn handle_commit_keysym(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        keysym: u32,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
+    /// Called when the server sends back a key event.
+    ///
+    /// Events of `XIM_EXT_FORWARD_KEYEVENT` are synthesized by the server, their type has the
+    /// `SendEvent` bit (`0x80`) set like events sent by `SendEvent`.
     fn handle_forward_event(
         &mut self,
         client: &mut C,
@@ -532,6 +1695,17 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// Called when the input context is turned on or off with the dynamic event flow, key events
+    /// are not forwarded to the server while it's off
+    fn handle_active_changed(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        active: bool,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
     fn handle_preedit_start(
         &mut self,
         client: &mut C,
@@ -554,6 +1728,27 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// Called when the server asks the text around the caret, used for reconversion.
+    ///
+    /// Return `factor` units of text in `direction` from `position`. With
+    /// [`StrConversionOperation::Substitution`], the returned text should also be deleted
+    /// since the server will commit its replacement.
+    fn handle_str_conversion(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        position: u16,
+        direction: CaretDirection,
+        operation: StrConversionOperation,
+        factor: u16,
+    ) -> Result<String, ClientError> {
+        Ok(String::new())
+    }
+    /// Called when the server moves the caret of the preedit string.
+    ///
+    /// Set `position` to the caret position the application actually used, it's sent back to
+    /// the server with `PreeditCaretReply`.
     fn handle_preedit_caret(
         &mut self,
         client: &mut C,
@@ -573,4 +1768,15 @@ pub trait ClientHandler<C: Client> {
     ) -> Result<(), ClientError> {
         Ok(())
     }
+    /// Called with the whole preedit after it's changed by `PreeditStart`, `PreeditDraw`,
+    /// `PreeditCaret` or `PreeditDone` when [`Client::set_preedit_tracking`] is on
+    fn handle_preedit_changed(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        preedit: &PreeditState,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
 }
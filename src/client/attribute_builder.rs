@@ -1,45 +1,144 @@
 use crate::AHashMap;
 use alloc::vec::Vec;
-use xim_parser::{Attribute, AttributeName, XimWrite};
+use xim_parser::{AttrType, Attribute, AttributeName, FontSet, Point, Rectangle, XimWrite};
+
+/// Size of the value of `ty`, `None` when it has variable length
+fn value_size(ty: AttrType) -> Option<usize> {
+    match ty {
+        AttrType::Separator => Some(0),
+        AttrType::Byte => Some(1),
+        AttrType::Word => Some(2),
+        AttrType::Long
+        | AttrType::Window
+        | AttrType::XPoint
+        | AttrType::PreeditState
+        | AttrType::ResetState => Some(4),
+        AttrType::XRectangle => Some(8),
+        AttrType::Char
+        | AttrType::Style
+        | AttrType::XFontSet
+        | AttrType::HotkeyTriggers
+        | AttrType::StringConversion
+        | AttrType::NestedList => None,
+    }
+}
+
+/// Return `false` when `value` doesn't fit the type of `name` reported by the server
+fn check_value(
+    types: &AHashMap<AttributeName, AttrType>,
+    name: AttributeName,
+    value: &[u8],
+) -> bool {
+    let ty = match types.get(&name) {
+        Some(ty) => *ty,
+        None => return true,
+    };
+
+    match value_size(ty) {
+        Some(size) if size != value.len() => {
+            debug_assert_eq!(
+                size,
+                value.len(),
+                "value of {:?} doesn't match its type {:?}",
+                name,
+                ty
+            );
+            log::error!(
+                "Skip {:?}, its value has {} bytes but {:?} needs {}",
+                name,
+                value.len(),
+                ty,
+                size
+            );
+            false
+        }
+        _ => true,
+    }
+}
 
 pub struct NestedListBuilder<'a> {
     id_map: &'a AHashMap<AttributeName, u16>,
+    types: &'a AHashMap<AttributeName, AttrType>,
     out: &'a mut Vec<u8>,
 }
 
 impl<'a> NestedListBuilder<'a> {
     pub fn push<V: XimWrite>(self, name: AttributeName, value: V) -> Self {
         if let Some(id) = self.id_map.get(&name).copied() {
-            let attr = Attribute {
-                id,
-                value: xim_parser::write_to_vec(value),
-            };
-            xim_parser::write_extend_vec(attr, self.out);
+            let value = xim_parser::write_to_vec(value);
+            if check_value(self.types, name, &value) {
+                xim_parser::write_extend_vec(Attribute { id, value }, self.out);
+            }
         }
 
         self
     }
+
+    pub fn area(self, area: Rectangle) -> Self {
+        self.push(AttributeName::Area, area)
+    }
+
+    pub fn area_needed(self, area_needed: Rectangle) -> Self {
+        self.push(AttributeName::AreaNeeded, area_needed)
+    }
+
+    pub fn spot_location(self, spot_location: Point) -> Self {
+        self.push(AttributeName::SpotLocation, spot_location)
+    }
+
+    /// Foreground pixel
+    pub fn foreground(self, pixel: u32) -> Self {
+        self.push(AttributeName::Foreground, pixel)
+    }
+
+    /// Background pixel
+    pub fn background(self, pixel: u32) -> Self {
+        self.push(AttributeName::Background, pixel)
+    }
+
+    /// Comma separated list of base font names
+    pub fn font_set(self, base_font_names: &str) -> Self {
+        self.push(
+            AttributeName::FontSet,
+            FontSet {
+                name: base_font_names.into(),
+            },
+        )
+    }
+
+    pub fn line_space(self, line_space: u32) -> Self {
+        self.push(AttributeName::LineSpace, line_space)
+    }
 }
 
+/// Builds attributes with the ids assigned by the server.
+///
+/// Attributes the server doesn't support are skipped. Values which don't fit the type reported
+/// by the server are skipped too, it panics in debug build since it's a bug of the caller.
 pub struct AttributeBuilder<'a> {
     id_map: &'a AHashMap<AttributeName, u16>,
+    types: &'a AHashMap<AttributeName, AttrType>,
     out: Vec<Attribute>,
 }
 
 impl<'a> AttributeBuilder<'a> {
-    pub(crate) fn new(id_map: &'a AHashMap<AttributeName, u16>) -> Self {
+    pub(crate) fn new(
+        id_map: &'a AHashMap<AttributeName, u16>,
+        types: &'a AHashMap<AttributeName, AttrType>,
+    ) -> Self {
         Self {
             id_map,
+            types,
             out: Vec::new(),
         }
     }
 
     pub fn push<V: XimWrite>(mut self, name: AttributeName, value: V) -> Self {
         if let Some(id) = self.id_map.get(&name).copied() {
-            self.out.push(Attribute {
-                id,
-                value: xim_parser::write_to_vec(value),
-            });
+            let value = xim_parser::write_to_vec(value);
+            if check_value(self.types, name, &value) {
+                self.out.push(Attribute { id, value });
+            }
         }
 
         self
@@ -50,6 +149,7 @@ impl<'a> AttributeBuilder<'a> {
             let mut value = Vec::new();
             f(NestedListBuilder {
                 id_map: self.id_map,
+                types: self.types,
                 out: &mut value,
             });
             self.out.push(Attribute { id, value });
@@ -58,6 +158,16 @@ impl<'a> AttributeBuilder<'a> {
         self
     }
 
+    /// Push `PreeditAttributes` built by `f`
+    pub fn preedit_attributes(self, f: impl FnOnce(NestedListBuilder)) -> Self {
+        self.nested_list(AttributeName::PreeditAttributes, f)
+    }
+
+    /// Push `StatusAttributes` built by `f`
+    pub fn status_attributes(self, f: impl FnOnce(NestedListBuilder)) -> Self {
+        self.nested_list(AttributeName::StatusAttributes, f)
+    }
+
     pub fn build(self) -> Vec<Attribute> {
         self.out
     }
@@ -0,0 +1,192 @@
+use crate::AHashMap;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use std::time::{Duration, Instant};
+use xim_parser::{Attribute, AttributeName};
+
+use super::reply::{Reply, ReplyHook};
+use super::{Client, ClientError, ClientHandler};
+
+/// Blocking request/reply API for backends which can wait events by themselves.
+///
+/// Every `*_blocking` method sends a request then pumps X events until the matching reply
+/// arrives or `timeout` is elapsed. XIM messages are dispatched to `handler` as usual, other
+/// events are queued into [`BlockingClient::pending_events`] so they are not lost.
+pub trait BlockingClient: Client + Sized {
+    type Event;
+
+    /// Wait the next X event until `deadline`, return `None` on timeout or when
+    /// [`BlockingClient::dispatch_transport`] has something to read
+    fn wait_event(&mut self, deadline: Instant) -> Result<Option<Self::Event>, ClientError>;
+
+    /// Dispatch an event to XIM, return `false` when it's not consumed
+    fn dispatch_event<H: ClientHandler<Self>>(
+        &mut self,
+        e: &Self::Event,
+        handler: &mut H,
+    ) -> Result<bool, ClientError>;
+
+    /// Dispatch requests which arrived on a transport other than X events, return `false` when
+    /// nothing is read
+    fn dispatch_transport<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<bool, ClientError> {
+        let _ = handler;
+        Ok(false)
+    }
+
+    /// Events which are not consumed by XIM while waiting replies
+    fn pending_events(&mut self) -> &mut VecDeque<Self::Event>;
+
+    /// Destroy the window created to talk with the server, the client can't be used after it
+    fn destroy_window(&mut self) -> Result<(), ClientError>;
+
+    /// [`Client::shutdown`] and wait until the server replies `Disconnect`.
+    ///
+    /// The client window is destroyed even when the server doesn't reply in `timeout`.
+    fn shutdown_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        timeout: Duration,
+    ) -> Result<(), ClientError> {
+        let connected = self.transport().is_some();
+        let mut ret = self.shutdown();
+
+        if connected && ret.is_ok() {
+            ret = wait_reply(self, handler, timeout, |reply| match *reply {
+                Reply::Disconnect => Some(()),
+                _ => None,
+            });
+        }
+
+        self.destroy_window()?;
+        ret
+    }
+
+    /// Wait until the server accepts the connection
+    fn connect_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        timeout: Duration,
+    ) -> Result<(), ClientError> {
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::Connect => Some(()),
+            _ => None,
+        })
+    }
+
+    /// Open input method and return `input_method_id`
+    fn open_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        locale: &str,
+        timeout: Duration,
+    ) -> Result<u16, ClientError> {
+        self.open(locale)?;
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::Open(input_method_id) => Some(input_method_id),
+            _ => None,
+        })
+    }
+
+    fn close_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        input_method_id: u16,
+        timeout: Duration,
+    ) -> Result<(), ClientError> {
+        self.close(input_method_id)?;
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::Close(id) if id == input_method_id => Some(()),
+            _ => None,
+        })
+    }
+
+    fn get_im_values_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        input_method_id: u16,
+        names: &[AttributeName],
+        timeout: Duration,
+    ) -> Result<AHashMap<AttributeName, Vec<u8>>, ClientError> {
+        self.get_im_values(input_method_id, names)?;
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::GetImValues(id, attributes) if id == input_method_id => Some(attributes.clone()),
+            _ => None,
+        })
+    }
+
+    /// Create input context and return `input_context_id`
+    fn create_ic_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        input_method_id: u16,
+        ic_attributes: Vec<Attribute>,
+        timeout: Duration,
+    ) -> Result<u16, ClientError> {
+        self.create_ic(input_method_id, ic_attributes)?;
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::CreateIc(im, ic) if im == input_method_id => Some(ic),
+            _ => None,
+        })
+    }
+
+    fn set_ic_values_blocking<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+        input_method_id: u16,
+        input_context_id: u16,
+        ic_attributes: Vec<Attribute>,
+        timeout: Duration,
+    ) -> Result<(), ClientError> {
+        self.set_ic_values(input_method_id, input_context_id, ic_attributes)?;
+        wait_reply(self, handler, timeout, |reply| match *reply {
+            Reply::SetIcValues(im, ic) if im == input_method_id && ic == input_context_id => {
+                Some(())
+            }
+            _ => None,
+        })
+    }
+}
+
+fn wait_reply<C, H, T>(
+    client: &mut C,
+    handler: &mut H,
+    timeout: Duration,
+    check: impl Fn(&Reply) -> Option<T>,
+) -> Result<T, ClientError>
+where
+    C: BlockingClient,
+    H: ClientHandler<C>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let mut out = None;
+        let mut hook = ReplyHook {
+            handler: &mut *handler,
+            hook: |reply: &Reply| {
+                if out.is_none() {
+                    out = check(reply);
+                }
+            },
+        };
+
+        if let Some(e) = client.wait_event(deadline)? {
+            if !client.dispatch_event(&e, &mut hook)? {
+                client.pending_events().push_back(e);
+            }
+        }
+
+        client.dispatch_transport(&mut hook)?;
+
+        if let Some(out) = out {
+            return Ok(out);
+        }
+
+        if Instant::now() >= deadline {
+            return Err(ClientError::Timeout);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use crate::AHashMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use xim_parser::{
+    AttributeName, CaretDirection, CaretStyle, Extension, Feedback, ForwardEventFlag,
+    PreeditDrawStatus, StrConversionOperation,
+};
+
+use super::{Client, ClientError, ClientHandler, IcValues, PreeditState};
+
+/// Client as a trait object, use it instead of writing `dyn Client` in handler methods so the
+/// lifetime of the trait object isn't elided to the lifetime of the reference
+pub type DynClient<E> = dyn Client<XEvent = E>;
+
+/// Pass callbacks of a backend to a handler of [`DynClient`].
+///
+/// Handlers implemented for `DynClient<E>` don't depend on the backend, so they can be stored
+/// behind a trait object together with the client.
+///
+/// ```ignore
+/// let mut handler: Box<dyn ClientHandler<DynClient<KeyPressEvent>>> = ..;
+/// client.filter_event(&e, &mut DynHandler(&mut *handler))?;
+/// ```
+pub struct DynHandler<'h, E: 'static>(pub &'h mut dyn ClientHandler<DynClient<E>>);
+
+impl<'h, C, E> ClientHandler<C> for DynHandler<'h, E>
+where
+    C: Client<XEvent = E> + 'static,
+{
+    fn handle_connect(&mut self, client: &mut C) -> Result<(), ClientError> {
+        self.0.handle_connect(client)
+    }
+
+    fn handle_disconnect(&mut self) {
+        self.0.handle_disconnect();
+    }
+
+    fn handle_auth_required(
+        &mut self,
+        client: &mut C,
+        auth_protocol_index: u8,
+        auth_data: &[u8],
+    ) -> Result<Option<Vec<u8>>, ClientError> {
+        self.0
+            .handle_auth_required(client, auth_protocol_index, auth_data)
+    }
+
+    fn handle_auth_next(
+        &mut self,
+        client: &mut C,
+        auth_data: &[u8],
+    ) -> Result<Option<Vec<u8>>, ClientError> {
+        self.0.handle_auth_next(client, auth_data)
+    }
+
+    fn handle_server_disconnected(&mut self, client: &mut C) -> Result<(), ClientError> {
+        self.0.handle_server_disconnected(client)
+    }
+
+    fn handle_open(&mut self, client: &mut C, input_method_id: u16) -> Result<(), ClientError> {
+        self.0.handle_open(client, input_method_id)
+    }
+
+    fn handle_close(&mut self, client: &mut C, input_method_id: u16) -> Result<(), ClientError> {
+        self.0.handle_close(client, input_method_id)
+    }
+
+    fn handle_query_extension(
+        &mut self,
+        client: &mut C,
+        extensions: &[Extension],
+    ) -> Result<(), ClientError> {
+        self.0.handle_query_extension(client, extensions)
+    }
+
+    fn handle_extension_message(
+        &mut self,
+        client: &mut C,
+        extension: &Extension,
+        data: &[u8],
+    ) -> Result<(), ClientError> {
+        self.0.handle_extension_message(client, extension, data)
+    }
+
+    fn handle_get_im_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        attributes: AHashMap<AttributeName, Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_get_im_values(client, input_method_id, attributes)
+    }
+
+    fn handle_set_im_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0.handle_set_im_values(client, input_method_id)
+    }
+
+    fn handle_set_ic_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_set_ic_values(client, input_method_id, input_context_id)
+    }
+
+    fn handle_get_ic_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        values: IcValues,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_get_ic_values(client, input_method_id, input_context_id, values)
+    }
+
+    fn handle_geometry(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_geometry(client, input_method_id, input_context_id)
+    }
+
+    fn handle_create_ic(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_create_ic(client, input_method_id, input_context_id)
+    }
+
+    fn handle_destroy_ic(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_destroy_ic(client, input_method_id, input_context_id)
+    }
+
+    fn handle_reset_ic_reply(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        preedit_string: &str,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_reset_ic_reply(client, input_method_id, input_context_id, preedit_string)
+    }
+
+    fn handle_commit(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        text: &str,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_commit(client, input_method_id, input_context_id, text)
+    }
+
+    fn handle_commit_both(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        keysym: u32,
+        text: &str,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_commit_both(client, input_method_id, input_context_id, keysym, text)
+    }
+
+    fn handle_commit_keysym(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        keysym: u32,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_commit_keysym(client, input_method_id, input_context_id, keysym)
+    }
+
+    fn handle_forward_event(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: E,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_forward_event(client, input_method_id, input_context_id, flag, xev)
+    }
+
+    fn handle_set_event_mask(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        forward_event_mask: u32,
+        synchronous_event_mask: u32,
+    ) -> Result<(), ClientError> {
+        self.0.handle_set_event_mask(
+            client,
+            input_method_id,
+            input_context_id,
+            forward_event_mask,
+            synchronous_event_mask,
+        )
+    }
+
+    fn handle_active_changed(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        active: bool,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_active_changed(client, input_method_id, input_context_id, active)
+    }
+
+    fn handle_preedit_start(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_preedit_start(client, input_method_id, input_context_id)
+    }
+
+    fn handle_preedit_draw(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        caret: i32,
+        chg_first: i32,
+        chg_len: i32,
+        status: PreeditDrawStatus,
+        preedit_string: &str,
+        feedbacks: Vec<Feedback>,
+    ) -> Result<(), ClientError> {
+        self.0.handle_preedit_draw(
+            client,
+            input_method_id,
+            input_context_id,
+            caret,
+            chg_first,
+            chg_len,
+            status,
+            preedit_string,
+            feedbacks,
+        )
+    }
+
+    fn handle_str_conversion(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        position: u16,
+        direction: CaretDirection,
+        operation: StrConversionOperation,
+        factor: u16,
+    ) -> Result<String, ClientError> {
+        self.0.handle_str_conversion(
+            client,
+            input_method_id,
+            input_context_id,
+            position,
+            direction,
+            operation,
+            factor,
+        )
+    }
+
+    fn handle_preedit_caret(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        position: &mut i32,
+        direction: CaretDirection,
+        style: CaretStyle,
+    ) -> Result<(), ClientError> {
+        self.0.handle_preedit_caret(
+            client,
+            input_method_id,
+            input_context_id,
+            position,
+            direction,
+            style,
+        )
+    }
+
+    fn handle_preedit_done(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_preedit_done(client, input_method_id, input_context_id)
+    }
+
+    fn handle_preedit_changed(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        preedit: &PreeditState,
+    ) -> Result<(), ClientError> {
+        self.0
+            .handle_preedit_changed(client, input_method_id, input_context_id, preedit)
+    }
+}
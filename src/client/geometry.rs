@@ -0,0 +1,204 @@
+//! Geometry management of the preedit and status areas.
+//!
+//! With the off-the-spot (`PREEDIT_AREA`, `STATUS_AREA`) and over-the-spot (`PREEDIT_POSITION`)
+//! styles the client decides where the server draws, and the server tells how much space it
+//! needs by `AreaNeeded`. [`Geometry`] drives this negotiation for an input context:
+//!
+//! 1. Call [`Geometry::query`] after the input context is created, and from
+//!    [`ClientHandler::handle_geometry`](crate::ClientHandler::handle_geometry) when the server
+//!    asks for it.
+//! 2. Pass the reply from
+//!    [`ClientHandler::handle_get_ic_values`](crate::ClientHandler::handle_get_ic_values) to
+//!    [`Geometry::handle_ic_values`], which sets the areas laid out by [`layout`].
+//! 3. Call [`Geometry::set_window`] when the window is resized.
+
+use xim_parser::{AttributeName, InputStyle, Rectangle};
+
+use super::{Client, ClientError, IcValues};
+
+/// Areas assigned to the server
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Areas {
+    pub preedit: Option<Rectangle>,
+    pub status: Option<Rectangle>,
+}
+
+/// Lay out the areas of `style` in `window`.
+///
+/// The status area is placed at the bottom-left corner with the size the server needs, the
+/// off-the-spot preedit area takes the rest of the bottom line. The over-the-spot preedit area
+/// is the whole window since the server draws around the spot location.
+pub fn layout(
+    style: InputStyle,
+    window: &Rectangle,
+    preedit_needed: Option<&Rectangle>,
+    status_needed: Option<&Rectangle>,
+) -> Areas {
+    let bottom = |height: u16| window.y.saturating_add((window.height - height) as i16);
+
+    let status = if style.contains(InputStyle::STATUS_AREA) {
+        let (width, height) = status_needed.map_or((0, 0), |area| (area.width, area.height));
+        let height = height.min(window.height);
+        Some(Rectangle {
+            x: window.x,
+            y: bottom(height),
+            width: width.min(window.width),
+            height,
+        })
+    } else {
+        None
+    };
+
+    let preedit = if style.contains(InputStyle::PREEDIT_AREA) {
+        let status_width = status.as_ref().map_or(0, |area| area.width);
+        let height = preedit_needed
+            .map(|area| area.height)
+            .or_else(|| status.as_ref().map(|area| area.height))
+            .unwrap_or(0)
+            .min(window.height);
+        Some(Rectangle {
+            x: window.x.saturating_add(status_width as i16),
+            y: bottom(height),
+            width: window.width - status_width,
+            height,
+        })
+    } else if style.contains(InputStyle::PREEDIT_POSITION) {
+        Some(window.clone())
+    } else {
+        None
+    };
+
+    Areas { preedit, status }
+}
+
+/// Keeps the areas of an input context fitted to its window
+#[derive(Clone, Debug)]
+pub struct Geometry {
+    input_method_id: u16,
+    input_context_id: u16,
+    style: InputStyle,
+    window: Rectangle,
+    preedit_needed: Option<Rectangle>,
+    status_needed: Option<Rectangle>,
+    areas: Areas,
+}
+
+impl Geometry {
+    /// `window` is the rectangle of the focus window where the areas are placed
+    pub fn new(
+        input_method_id: u16,
+        input_context_id: u16,
+        style: InputStyle,
+        window: Rectangle,
+    ) -> Self {
+        Self {
+            input_method_id,
+            input_context_id,
+            style,
+            window,
+            preedit_needed: None,
+            status_needed: None,
+            areas: Areas::default(),
+        }
+    }
+
+    pub fn input_method_id(&self) -> u16 {
+        self.input_method_id
+    }
+
+    pub fn input_context_id(&self) -> u16 {
+        self.input_context_id
+    }
+
+    pub fn window(&self) -> &Rectangle {
+        &self.window
+    }
+
+    /// Areas which are set to the server
+    pub fn areas(&self) -> &Areas {
+        &self.areas
+    }
+
+    /// Ask the server the areas it needs
+    pub fn query<C: Client + ?Sized>(&self, client: &mut C) -> Result<(), ClientError> {
+        client.get_ic_values(
+            self.input_method_id,
+            self.input_context_id,
+            &[
+                AttributeName::PreeditAttributes,
+                AttributeName::AreaNeeded,
+                AttributeName::SeparatorofNestedList,
+                AttributeName::StatusAttributes,
+                AttributeName::AreaNeeded,
+                AttributeName::SeparatorofNestedList,
+            ],
+        )
+    }
+
+    /// Apply `AreaNeeded` of `values` when they're replied for this input context, return
+    /// `true` when new areas are set
+    pub fn handle_ic_values<C: Client + ?Sized>(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+        values: &IcValues,
+    ) -> Result<bool, ClientError> {
+        if input_method_id != self.input_method_id || input_context_id != self.input_context_id {
+            return Ok(false);
+        }
+
+        if values.preedit_area_needed.is_some() {
+            self.preedit_needed = values.preedit_area_needed.clone();
+        }
+
+        if values.status_area_needed.is_some() {
+            self.status_needed = values.status_area_needed.clone();
+        }
+
+        self.update(client)
+    }
+
+    /// Move the areas into resized `window`, return `true` when new areas are set
+    pub fn set_window<C: Client + ?Sized>(
+        &mut self,
+        client: &mut C,
+        window: Rectangle,
+    ) -> Result<bool, ClientError> {
+        self.window = window;
+        self.update(client)
+    }
+
+    fn update<C: Client + ?Sized>(&mut self, client: &mut C) -> Result<bool, ClientError> {
+        let areas = layout(
+            self.style,
+            &self.window,
+            self.preedit_needed.as_ref(),
+            self.status_needed.as_ref(),
+        );
+
+        if areas == self.areas {
+            return Ok(false);
+        }
+
+        let mut builder = client.build_ic_attributes();
+
+        if let Some(area) = areas.preedit.clone() {
+            builder = builder.preedit_attributes(|b| {
+                b.area(area);
+            });
+        }
+
+        if let Some(area) = areas.status.clone() {
+            builder = builder.status_attributes(|b| {
+                b.area(area);
+            });
+        }
+
+        let ic_attributes = builder.build();
+        client.set_ic_values(self.input_method_id, self.input_context_id, ic_attributes)?;
+        self.areas = areas;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::AHashMap;
+use xim_parser::{Attribute, AttributeName, InputStyle, Point, Rectangle, XimWrite};
+
+/// Typed values of IC attributes
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct IcValues {
+    pub input_style: Option<InputStyle>,
+    pub client_window: Option<u32>,
+    pub focus_window: Option<u32>,
+    pub filter_events: Option<u32>,
+    pub spot_location: Option<Point>,
+    pub preedit_area: Option<Rectangle>,
+    pub preedit_area_needed: Option<Rectangle>,
+    pub status_area: Option<Rectangle>,
+    pub status_area_needed: Option<Rectangle>,
+}
+
+impl IcValues {
+    /// Decode `attributes` with the attribute ids `ic_attributes` which the server gave
+    pub fn decode(ic_attributes: &AHashMap<AttributeName, u16>, attributes: &[Attribute]) -> Self {
+        let name_of = |id: u16| {
+            ic_attributes
+                .iter()
+                .find(|(_, v)| **v == id)
+                .map(|(name, _)| *name)
+        };
+
+        let mut values = Self::default();
+
+        for attr in attributes {
+            match name_of(attr.id) {
+                Some(AttributeName::InputStyle) => {
+                    values.input_style = xim_parser::read(&attr.value).ok();
+                }
+                Some(AttributeName::ClientWindow) => {
+                    values.client_window = xim_parser::read(&attr.value).ok();
+                }
+                Some(AttributeName::FocusWindow) => {
+                    values.focus_window = xim_parser::read(&attr.value).ok();
+                }
+                Some(AttributeName::FilterEvents) => {
+                    values.filter_events = xim_parser::read(&attr.value).ok();
+                }
+                Some(AttributeName::PreeditAttributes) => {
+                    let mut b = &attr.value[..];
+                    while let Ok(attr) = xim_parser::read::<Attribute>(b) {
+                        b = &b[attr.size()..];
+                        match name_of(attr.id) {
+                            Some(AttributeName::SpotLocation) => {
+                                values.spot_location = xim_parser::read(&attr.value).ok();
+                            }
+                            Some(AttributeName::Area) => {
+                                values.preedit_area = xim_parser::read(&attr.value).ok();
+                            }
+                            Some(AttributeName::AreaNeeded) => {
+                                values.preedit_area_needed = xim_parser::read(&attr.value).ok();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Some(AttributeName::StatusAttributes) => {
+                    let mut b = &attr.value[..];
+                    while let Ok(attr) = xim_parser::read::<Attribute>(b) {
+                        b = &b[attr.size()..];
+                        match name_of(attr.id) {
+                            Some(AttributeName::Area) => {
+                                values.status_area = xim_parser::read(&attr.value).ok();
+                            }
+                            Some(AttributeName::AreaNeeded) => {
+                                values.status_area_needed = xim_parser::read(&attr.value).ok();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                name => {
+                    log::debug!("Ignore IC attribute {:?}", name);
+                }
+            }
+        }
+
+        values
+    }
+}
@@ -0,0 +1,18 @@
+use xim_parser::Request;
+
+/// Hooks called for every request exchanged with the server.
+///
+/// Requests can be inspected or rewritten in place, return `false` to drop it. Dropped requests
+/// are neither sent to the server nor passed to the handler, and are not tracked as pending.
+#[allow(unused_variables)]
+pub trait RequestInterceptor {
+    /// Called before `req` is sent to the server
+    fn on_send(&mut self, req: &mut Request) -> bool {
+        true
+    }
+
+    /// Called before `req` from the server is handled
+    fn on_receive(&mut self, req: &mut Request) -> bool {
+        true
+    }
+}
@@ -0,0 +1,49 @@
+/// Map a keysym to the character it types.
+///
+/// Only printable Latin-1 keysyms and Unicode keysyms(`0x0100xxxx`) are mapped, `None` is
+/// returned for others.
+pub fn keysym_to_char(keysym: u32) -> Option<char> {
+    match keysym {
+        0x20..=0x7e | 0xa0..=0xff => char::from_u32(keysym),
+        0x0100_0100..=0x0110_ffff => char::from_u32(keysym - 0x0100_0000),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::keysym_to_char;
+
+    #[test]
+    fn latin1() {
+        assert_eq!(keysym_to_char(0x20), Some(' '));
+        assert_eq!(keysym_to_char(0x61), Some('a'));
+        assert_eq!(keysym_to_char(0x7e), Some('~'));
+        assert_eq!(keysym_to_char(0xa0), Some('\u{a0}'));
+        assert_eq!(keysym_to_char(0xe9), Some('é'));
+        assert_eq!(keysym_to_char(0xff), Some('ÿ'));
+        // control characters and DEL have no keysym of their own
+        assert_eq!(keysym_to_char(0x1f), None);
+        assert_eq!(keysym_to_char(0x7f), None);
+        assert_eq!(keysym_to_char(0x9f), None);
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(keysym_to_char(0x0100_0100), Some('\u{100}'));
+        assert_eq!(keysym_to_char(0x0100_d55c), Some('한'));
+        assert_eq!(keysym_to_char(0x0101_f600), Some('😀'));
+        assert_eq!(keysym_to_char(0x0110_ffff), Some('\u{10ffff}'));
+        // surrogates are not characters
+        assert_eq!(keysym_to_char(0x0100_d800), None);
+        assert_eq!(keysym_to_char(0x0111_0000), None);
+    }
+
+    #[test]
+    fn others() {
+        // BackSpace, Return and dead_acute
+        assert_eq!(keysym_to_char(0xff08), None);
+        assert_eq!(keysym_to_char(0xff0d), None);
+        assert_eq!(keysym_to_char(0xfe51), None);
+    }
+}
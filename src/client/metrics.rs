@@ -0,0 +1,98 @@
+use crate::{AHashMap, RequestCounts};
+use core::convert::TryFrom;
+use std::time::{Duration, Instant};
+use xim_parser::Request;
+
+use super::{PendingRequest, ReplyKind};
+
+/// Replies of a kind of request
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReplyStats {
+    /// Requests replied, including errors
+    pub replied: u64,
+    /// Requests failed with `Error`
+    pub errors: u64,
+    /// Requests not replied in the timeout of [`PendingRequests`](crate::PendingRequests)
+    pub timeouts: u64,
+    /// Sum of latencies of `replied` requests
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl ReplyStats {
+    /// `None` when nothing is replied yet
+    pub fn average_latency(&self) -> Option<Duration> {
+        let replied = u32::try_from(self.replied).ok().filter(|n| *n > 0)?;
+        Some(self.total_latency / replied)
+    }
+
+    /// Ratio of errors and timeouts to every finished request, `0.0` when nothing is finished
+    pub fn error_rate(&self) -> f64 {
+        let finished = self.replied + self.timeouts;
+
+        if finished == 0 {
+            0.0
+        } else {
+            (self.errors + self.timeouts) as f64 / finished as f64
+        }
+    }
+
+    fn record(&mut self, sent_at: Instant, now: Instant, error: bool) {
+        let latency = now.saturating_duration_since(sent_at);
+        self.replied += 1;
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+
+        if error {
+            self.errors += 1;
+        }
+    }
+}
+
+/// Snapshot of messages the client exchanged with the server, see
+/// [`Client::metrics`](crate::Client::metrics)
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    requests: RequestCounts,
+    replies: AHashMap<ReplyKind, ReplyStats>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests sent to and received from the server
+    pub fn requests(&self) -> &RequestCounts {
+        &self.requests
+    }
+
+    pub fn replies(&self, kind: ReplyKind) -> Option<&ReplyStats> {
+        self.replies.get(&kind)
+    }
+
+    pub fn reply_stats(&self) -> impl Iterator<Item = (ReplyKind, &ReplyStats)> + '_ {
+        self.replies.iter().map(|(kind, stats)| (*kind, stats))
+    }
+
+    pub(crate) fn request_sent(&mut self, req: &Request) {
+        self.requests.request_sent(req);
+    }
+
+    pub(crate) fn request_received(&mut self, req: &Request) {
+        self.requests.request_received(req);
+    }
+
+    /// `pending` is replied by `req`
+    pub(crate) fn reply_received(&mut self, pending: &PendingRequest, req: &Request) {
+        self.replies.entry(pending.reply()).or_default().record(
+            pending.sent_at(),
+            Instant::now(),
+            matches!(req, Request::Error { .. }),
+        );
+    }
+
+    pub(crate) fn request_expired(&mut self, pending: &PendingRequest) {
+        self.replies.entry(pending.reply()).or_default().timeouts += 1;
+    }
+}
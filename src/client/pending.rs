@@ -0,0 +1,434 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+use xim_parser::{ErrorFlag, ForwardEventFlag, Request};
+
+/// Reply that a request is waiting for
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ReplyKind {
+    Connect,
+    Disconnect,
+    Open,
+    Close,
+    EncodingNegotiation,
+    QueryExtension,
+    SetImValues,
+    GetImValues,
+    CreateIc,
+    DestroyIc,
+    SetIcValues,
+    GetIcValues,
+    ResetIc,
+    TriggerNotify,
+    Sync,
+}
+
+impl ReplyKind {
+    /// Reply kind and ids which `req` expects
+    fn of_request(req: &Request) -> Option<(Self, Option<u16>, Option<u16>)> {
+        let ret = match *req {
+            Request::Connect { .. } => (Self::Connect, None, None),
+            Request::Disconnect {} => (Self::Disconnect, None, None),
+            Request::Open { .. } => (Self::Open, None, None),
+            Request::Close { input_method_id } => (Self::Close, Some(input_method_id), None),
+            Request::EncodingNegotiation {
+                input_method_id, ..
+            } => (Self::EncodingNegotiation, Some(input_method_id), None),
+            Request::QueryExtension {
+                input_method_id, ..
+            } => (Self::QueryExtension, Some(input_method_id), None),
+            Request::SetImValues {
+                input_method_id, ..
+            } => (Self::SetImValues, Some(input_method_id), None),
+            Request::GetImValues {
+                input_method_id, ..
+            } => (Self::GetImValues, Some(input_method_id), None),
+            Request::CreateIc {
+                input_method_id, ..
+            } => (Self::CreateIc, Some(input_method_id), None),
+            Request::DestroyIc {
+                input_method_id,
+                input_context_id,
+            } => (
+                Self::DestroyIc,
+                Some(input_method_id),
+                Some(input_context_id),
+            ),
+            Request::SetIcValues {
+                input_method_id,
+                input_context_id,
+                ..
+            } => (
+                Self::SetIcValues,
+                Some(input_method_id),
+                Some(input_context_id),
+            ),
+            Request::GetIcValues {
+                input_method_id,
+                input_context_id,
+                ..
+            } => (
+                Self::GetIcValues,
+                Some(input_method_id),
+                Some(input_context_id),
+            ),
+            Request::ResetIc {
+                input_method_id,
+                input_context_id,
+            } => (Self::ResetIc, Some(input_method_id), Some(input_context_id)),
+            Request::TriggerNotify {
+                input_method_id,
+                input_context_id,
+                ..
+            } => (
+                Self::TriggerNotify,
+                Some(input_method_id),
+                Some(input_context_id),
+            ),
+            Request::Sync {
+                input_method_id,
+                input_context_id,
+            } => (Self::Sync, Some(input_method_id), Some(input_context_id)),
+            // the server replies SyncReply after processing a synchronous event
+            Request::ForwardEvent {
+                input_method_id,
+                input_context_id,
+                flag,
+                ..
+            } if flag.contains(ForwardEventFlag::SYNCHRONOUS) => {
+                (Self::Sync, Some(input_method_id), Some(input_context_id))
+            }
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+
+    /// Reply kind and ids of reply `req`
+    fn of_reply(req: &Request) -> Option<(Self, u16, Option<u16>)> {
+        let ret = match *req {
+            Request::ConnectReply { .. } | Request::AuthNg {} => (Self::Connect, 0, None),
+            Request::DisconnectReply {} => (Self::Disconnect, 0, None),
+            Request::OpenReply {
+                input_method_id, ..
+            } => (Self::Open, input_method_id, None),
+            Request::CloseReply { input_method_id } => (Self::Close, input_method_id, None),
+            Request::EncodingNegotiationReply {
+                input_method_id, ..
+            } => (Self::EncodingNegotiation, input_method_id, None),
+            Request::QueryExtensionReply {
+                input_method_id, ..
+            } => (Self::QueryExtension, input_method_id, None),
+            Request::SetImValuesReply { input_method_id } => {
+                (Self::SetImValues, input_method_id, None)
+            }
+            Request::GetImValuesReply {
+                input_method_id, ..
+            } => (Self::GetImValues, input_method_id, None),
+            Request::CreateIcReply {
+                input_method_id,
+                input_context_id,
+            } => (Self::CreateIc, input_method_id, Some(input_context_id)),
+            Request::DestroyIcReply {
+                input_method_id,
+                input_context_id,
+            } => (Self::DestroyIc, input_method_id, Some(input_context_id)),
+            Request::SetIcValuesReply {
+                input_method_id,
+                input_context_id,
+            } => (Self::SetIcValues, input_method_id, Some(input_context_id)),
+            Request::GetIcValuesReply {
+                input_method_id,
+                input_context_id,
+                ..
+            } => (Self::GetIcValues, input_method_id, Some(input_context_id)),
+            Request::ResetIcReply {
+                input_method_id,
+                input_context_id,
+                ..
+            } => (Self::ResetIc, input_method_id, Some(input_context_id)),
+            Request::TriggerNotifyReply {
+                input_method_id,
+                input_context_id,
+            } => (Self::TriggerNotify, input_method_id, Some(input_context_id)),
+            Request::SyncReply {
+                input_method_id,
+                input_context_id,
+            } => (Self::Sync, input_method_id, Some(input_context_id)),
+            _ => return None,
+        };
+
+        Some(ret)
+    }
+}
+
+/// Identifier of a sent request, increases for every request waiting a reply
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RequestId(pub u32);
+
+/// A request waiting for its reply
+pub struct PendingRequest {
+    id: RequestId,
+    reply: ReplyKind,
+    input_method_id: Option<u16>,
+    input_context_id: Option<u16>,
+    context: Option<Box<dyn Any>>,
+    #[cfg(feature = "std")]
+    sent_at: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl PendingRequest {
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    pub fn reply(&self) -> ReplyKind {
+        self.reply
+    }
+
+    pub fn input_method_id(&self) -> Option<u16> {
+        self.input_method_id
+    }
+
+    pub fn input_context_id(&self) -> Option<u16> {
+        self.input_context_id
+    }
+
+    pub fn context(&self) -> Option<&dyn Any> {
+        self.context.as_deref()
+    }
+
+    pub fn take_context(&mut self) -> Option<Box<dyn Any>> {
+        self.context.take()
+    }
+
+    /// Time when the request is sent
+    #[cfg(feature = "std")]
+    pub fn sent_at(&self) -> Instant {
+        self.sent_at
+    }
+
+    /// Span which lasts from sending the request to handling its reply
+    #[cfg(feature = "tracing")]
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    fn matches(
+        &self,
+        reply: ReplyKind,
+        input_method_id: u16,
+        input_context_id: Option<u16>,
+    ) -> bool {
+        // ids not known when request is sent(e.g. Open, CreateIc) match any id
+        self.reply == reply
+            && self
+                .input_method_id
+                .map_or(true, |id| id == input_method_id)
+            && match (self.input_context_id, input_context_id) {
+                (Some(expected), Some(id)) => expected == id,
+                _ => true,
+            }
+    }
+}
+
+impl fmt::Debug for PendingRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingRequest")
+            .field("id", &self.id)
+            .field("reply", &self.reply)
+            .field("input_method_id", &self.input_method_id)
+            .field("input_context_id", &self.input_context_id)
+            .field("context", &self.context.is_some())
+            .finish()
+    }
+}
+
+/// Table of requests waiting for replies.
+///
+/// Requests are registered when sent and matched with replies when they arrive, so unexpected
+/// or duplicated replies can be detected. The matched request is available from
+/// [`PendingRequests::current`] while its reply is handled, which lets handlers get a context
+/// attached with [`PendingRequests::attach`] instead of tracking it by themselves.
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    requests: VecDeque<PendingRequest>,
+    current: Option<PendingRequest>,
+    next_id: u32,
+    #[cfg(feature = "std")]
+    timeout: Option<Duration>,
+}
+
+impl PendingRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `req` if it expects a reply
+    pub fn push(&mut self, req: &Request) -> Option<RequestId> {
+        let (reply, input_method_id, input_context_id) = ReplyKind::of_request(req)?;
+        let id = RequestId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        self.requests.push_back(PendingRequest {
+            id,
+            reply,
+            input_method_id,
+            input_context_id,
+            context: None,
+            #[cfg(feature = "std")]
+            sent_at: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "xim_request",
+                id = id.0,
+                reply = ?reply,
+                input_method_id,
+                input_context_id,
+            ),
+        });
+
+        Some(id)
+    }
+
+    /// Attach `context` to request `id`, return `false` when it's already replied
+    pub fn attach(&mut self, id: RequestId, context: Box<dyn Any>) -> bool {
+        match self.requests.iter_mut().find(|req| req.id == id) {
+            Some(req) => {
+                req.context = Some(context);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Id of the last request waiting a reply
+    pub fn last_id(&self) -> Option<RequestId> {
+        self.requests.back().map(|req| req.id)
+    }
+
+    /// Request matched with the reply being handled
+    pub fn current(&self) -> Option<&PendingRequest> {
+        self.current.as_ref()
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut PendingRequest> {
+        self.current.as_mut()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PendingRequest> {
+        self.requests.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// How long a request waits its reply, requests never expire when it's `None` (default)
+    #[cfg(feature = "std")]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Time when the oldest request expires, useful to schedule
+    /// [`Client::poll_timeouts`](crate::Client::poll_timeouts)
+    #[cfg(feature = "std")]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let timeout = self.timeout?;
+        self.requests.iter().map(|req| req.sent_at + timeout).min()
+    }
+
+    /// Remove requests which are not replied until `now`
+    #[cfg(feature = "std")]
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<PendingRequest> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Vec::new(),
+        };
+
+        let mut expired = Vec::new();
+        let mut i = 0;
+
+        while i < self.requests.len() {
+            if now.saturating_duration_since(self.requests[i].sent_at) >= timeout {
+                expired.extend(self.requests.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+
+        expired
+    }
+
+    /// Match `req` with the oldest request waiting it, return `false` when `req` is a reply but
+    /// nothing waits it
+    pub(crate) fn start_reply(&mut self, req: &Request) -> bool {
+        self.current = None;
+
+        let (reply, input_method_id, input_context_id) = match ReplyKind::of_reply(req) {
+            Some(key) => key,
+            None => return true,
+        };
+
+        match self
+            .requests
+            .iter()
+            .position(|req| req.matches(reply, input_method_id, input_context_id))
+        {
+            Some(pos) => {
+                self.current = self.requests.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the oldest request failed with error
+    pub(crate) fn start_error(
+        &mut self,
+        flag: ErrorFlag,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) {
+        self.current = None;
+
+        let pos = self.requests.iter().position(|req| {
+            (!flag.contains(ErrorFlag::INPUT_METHOD_ID_VALID)
+                || req.input_method_id.map_or(true, |id| id == input_method_id))
+                && (!flag.contains(ErrorFlag::INPUT_CONTEXT_ID_VALID)
+                    || req
+                        .input_context_id
+                        .map_or(true, |id| id == input_context_id))
+        });
+
+        if let Some(pos) = pos {
+            self.current = self.requests.remove(pos);
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.current = None;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.requests.clear();
+        self.current = None;
+    }
+}
@@ -0,0 +1,235 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ops::Range;
+use xim_parser::{Feedback, PreeditDrawStatus};
+
+/// Characters of the preedit string sharing the same feedback
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PreeditRun {
+    /// Char range of the run in [`PreeditState::text`]
+    pub range: Range<usize>,
+    /// `None` for characters drawn without feedback
+    pub feedback: Option<Feedback>,
+}
+
+/// Current preedit of an input context built from `PreeditDraw` deltas.
+///
+/// The client keeps it for each input context when
+/// [`Client::set_preedit_tracking`](crate::Client::set_preedit_tracking) is on and passes it to
+/// [`ClientHandler::handle_preedit_changed`](crate::ClientHandler::handle_preedit_changed).
+/// Positions are in chars like the protocol.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PreeditState {
+    chars: Vec<char>,
+    feedbacks: Vec<Option<Feedback>>,
+    caret: usize,
+}
+
+impl PreeditState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Length in chars
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Char index of the caret
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Feedback of each char
+    pub fn feedbacks(&self) -> &[Option<Feedback>] {
+        &self.feedbacks
+    }
+
+    /// Split the text into runs of the same feedback
+    pub fn runs(&self) -> Vec<PreeditRun> {
+        let mut runs: Vec<PreeditRun> = Vec::new();
+
+        for (i, feedback) in self.feedbacks.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.feedback == *feedback => run.range.end = i + 1,
+                _ => runs.push(PreeditRun {
+                    range: i..i + 1,
+                    feedback: *feedback,
+                }),
+            }
+        }
+
+        runs
+    }
+
+    /// Apply a `PreeditDraw`.
+    ///
+    /// `chg_length` chars from `chg_first` are replaced with `text`, with `NO_STRING` only the
+    /// feedbacks of them are updated when any is given, otherwise they're deleted.
+    pub fn apply_draw(
+        &mut self,
+        caret: i32,
+        chg_first: i32,
+        chg_length: i32,
+        status: PreeditDrawStatus,
+        text: &str,
+        feedbacks: &[Feedback],
+    ) {
+        let len = self.chars.len();
+        let start = usize::try_from(chg_first).unwrap_or(0).min(len);
+        let end = start
+            .saturating_add(usize::try_from(chg_length).unwrap_or(0))
+            .min(len);
+
+        if status.contains(PreeditDrawStatus::NO_STRING) && !feedbacks.is_empty() {
+            for (slot, feedback) in self.feedbacks[start..end].iter_mut().zip(feedbacks) {
+                *slot = Some(*feedback);
+            }
+        } else {
+            let chars: Vec<char> = if status.contains(PreeditDrawStatus::NO_STRING) {
+                Vec::new()
+            } else {
+                text.chars().collect()
+            };
+            let new_feedbacks: Vec<Option<Feedback>> = (0..chars.len())
+                .map(|i| feedbacks.get(i).copied())
+                .collect();
+
+            self.chars.splice(start..end, chars);
+            self.feedbacks.splice(start..end, new_feedbacks);
+        }
+
+        self.set_caret(caret);
+    }
+
+    /// Move the caret, it's clamped into the text
+    pub fn set_caret(&mut self, caret: i32) {
+        self.caret = usize::try_from(caret).unwrap_or(0).min(self.chars.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.chars.clear();
+        self.feedbacks.clear();
+        self.caret = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreeditState;
+    use xim_parser::{Feedback, PreeditDrawStatus};
+
+    fn preedit(text: &str, caret: i32) -> PreeditState {
+        let mut preedit = PreeditState::new();
+        preedit.apply_draw(
+            caret,
+            0,
+            0,
+            PreeditDrawStatus::empty(),
+            text,
+            &[Feedback::Underline; 8][..text.chars().count()],
+        );
+        preedit
+    }
+
+    #[test]
+    fn replace() {
+        let mut preedit = preedit("abc", 3);
+        preedit.apply_draw(
+            2,
+            1,
+            1,
+            PreeditDrawStatus::empty(),
+            "한",
+            &[Feedback::Reverse],
+        );
+
+        assert_eq!(preedit.text(), "a한c");
+        assert_eq!(preedit.caret(), 2);
+        assert_eq!(
+            preedit.feedbacks(),
+            [
+                Some(Feedback::Underline),
+                Some(Feedback::Reverse),
+                Some(Feedback::Underline)
+            ]
+        );
+    }
+
+    #[test]
+    fn no_string_deletes() {
+        let mut preedit = preedit("abc", 3);
+        preedit.apply_draw(1, 1, 2, PreeditDrawStatus::NO_STRING, "", &[]);
+
+        assert_eq!(preedit.text(), "a");
+        assert_eq!(preedit.feedbacks(), [Some(Feedback::Underline)]);
+        assert_eq!(preedit.caret(), 1);
+    }
+
+    #[test]
+    fn feedback_only() {
+        let mut preedit = preedit("abc", 3);
+        preedit.apply_draw(
+            0,
+            1,
+            2,
+            PreeditDrawStatus::NO_STRING,
+            "",
+            &[Feedback::Reverse, Feedback::Highlight],
+        );
+
+        assert_eq!(preedit.text(), "abc");
+        assert_eq!(preedit.caret(), 0);
+        assert_eq!(
+            preedit.feedbacks(),
+            [
+                Some(Feedback::Underline),
+                Some(Feedback::Reverse),
+                Some(Feedback::Highlight)
+            ]
+        );
+    }
+
+    #[test]
+    fn without_feedback() {
+        let mut preedit = preedit("ab", 2);
+        preedit.apply_draw(3, 2, 0, PreeditDrawStatus::NO_FEEDBACK, "c", &[]);
+
+        assert_eq!(preedit.text(), "abc");
+        assert_eq!(preedit.feedbacks()[2], None);
+        assert_eq!(preedit.runs().len(), 2);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut preedit = preedit("abc", 3);
+        preedit.apply_draw(100, 10, 5, PreeditDrawStatus::empty(), "d", &[]);
+
+        assert_eq!(preedit.text(), "abcd");
+        assert_eq!(preedit.caret(), 4);
+
+        preedit.apply_draw(-1, -3, 100, PreeditDrawStatus::NO_STRING, "", &[]);
+
+        assert!(preedit.is_empty());
+        assert_eq!(preedit.caret(), 0);
+
+        preedit.apply_draw(
+            0,
+            0,
+            1,
+            PreeditDrawStatus::NO_STRING,
+            "",
+            &[Feedback::Reverse],
+        );
+        assert!(preedit.is_empty());
+    }
+}
@@ -0,0 +1,47 @@
+/// Workarounds for known misbehaviors of XIM servers.
+///
+/// Backends detect them from the server name with [`Quirks::detect`], use
+/// [`Client::set_quirks`](crate::Client::set_quirks) to override it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct Quirks {
+    /// Drop replies which no request is waiting instead of passing them to the handler.
+    ///
+    /// fcitx4 sends empty replies for requests it already replied.
+    pub ignore_unexpected_replies: bool,
+    /// Skip `_XIM_PROTOCOL` messages whose property is empty instead of failing.
+    ///
+    /// fcitx4 sometimes points to a property which has no value.
+    pub ignore_empty_messages: bool,
+    /// Look up IC attributes in the IM attribute list too.
+    ///
+    /// ibus lists some IC attributes only with the IM attributes of `OpenReply`.
+    pub share_attribute_ids: bool,
+    /// Don't forward key events to an input context until the server sets its event mask.
+    ///
+    /// scim replies `CreateIc` before `SetEventMask` and loses events forwarded in between.
+    pub wait_event_mask: bool,
+}
+
+impl Quirks {
+    /// No workaround is applied
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Workarounds for the server registered as `@server=server_name`
+    pub fn detect(server_name: &str) -> Self {
+        let mut quirks = Self::none();
+
+        if server_name.eq_ignore_ascii_case("fcitx") {
+            quirks.ignore_unexpected_replies = true;
+            quirks.ignore_empty_messages = true;
+        } else if server_name.eq_ignore_ascii_case("ibus") {
+            quirks.share_attribute_ids = true;
+        } else if server_name.eq_ignore_ascii_case("scim") {
+            quirks.wait_event_mask = true;
+        }
+
+        quirks
+    }
+}
@@ -0,0 +1,453 @@
+use crate::AHashMap;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use xim_parser::{Extension, ForwardEventFlag, InputStyle, Point, Request, XEvent};
+
+use super::{Encoding, IcValues, PreeditState, RequestId};
+
+/// `KeyPressMask | KeyReleaseMask`
+const KEY_EVENT_MASK: u32 = 0b11;
+
+/// X event mask which selects the core event of type `response_type`
+fn event_mask_of(response_type: u8) -> Option<u32> {
+    match response_type & 0x7f {
+        // KeyPress, KeyRelease, ButtonPress and ButtonRelease are 2 to 5, their masks are
+        // 1 << 0 to 1 << 3
+        ty @ 2..=5 => Some(1 << (ty - 2)),
+        // MotionNotify, PointerMotionMask
+        6 => Some(1 << 6),
+        _ => None,
+    }
+}
+
+/// Input context created by the client
+#[derive(Clone, Debug)]
+pub struct InputContextInfo {
+    input_method_id: u16,
+    input_context_id: u16,
+    input_style: InputStyle,
+    client_window: Option<u32>,
+    focus_window: Option<u32>,
+    spot_location: Option<Point>,
+    forward_event_mask: Option<u32>,
+    synchronous_event_mask: u32,
+    active: bool,
+    waiting_sync: bool,
+    queued_events: VecDeque<(ForwardEventFlag, XEvent)>,
+    preedit: Option<PreeditState>,
+}
+
+impl InputContextInfo {
+    fn new(input_method_id: u16, input_context_id: u16) -> Self {
+        Self {
+            input_method_id,
+            input_context_id,
+            input_style: InputStyle::empty(),
+            client_window: None,
+            focus_window: None,
+            spot_location: None,
+            forward_event_mask: None,
+            synchronous_event_mask: 0,
+            active: true,
+            waiting_sync: false,
+            queued_events: VecDeque::new(),
+            preedit: None,
+        }
+    }
+
+    fn apply(&mut self, values: IcValues) {
+        if let Some(style) = values.input_style {
+            self.input_style = style;
+        }
+        if values.client_window.is_some() {
+            self.client_window = values.client_window;
+        }
+        if values.focus_window.is_some() {
+            self.focus_window = values.focus_window;
+        }
+        if values.spot_location.is_some() {
+            self.spot_location = values.spot_location;
+        }
+    }
+
+    pub fn input_method_id(&self) -> u16 {
+        self.input_method_id
+    }
+
+    pub fn input_context_id(&self) -> u16 {
+        self.input_context_id
+    }
+
+    pub fn input_style(&self) -> InputStyle {
+        self.input_style
+    }
+
+    pub fn client_window(&self) -> Option<u32> {
+        self.client_window
+    }
+
+    pub fn focus_window(&self) -> Option<u32> {
+        self.focus_window
+    }
+
+    pub fn spot_location(&self) -> Option<Point> {
+        self.spot_location.clone()
+    }
+
+    /// Events which the server wants to be forwarded, `None` until the server sets it
+    pub fn forward_event_mask(&self) -> Option<u32> {
+        self.forward_event_mask
+    }
+
+    pub fn synchronous_event_mask(&self) -> u32 {
+        self.synchronous_event_mask
+    }
+
+    /// Return `true` when key events are forwarded to the server.
+    ///
+    /// This is always `true` with the static event flow, with the dynamic event flow it's
+    /// turned on and off by trigger keys and `SetEventMask`.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Return `true` while a synchronous `ForwardEvent` waits its `SyncReply`, events forwarded
+    /// meanwhile are queued to keep their order
+    pub fn is_waiting_sync(&self) -> bool {
+        self.waiting_sync
+    }
+
+    /// Number of events queued until `SyncReply` arrives
+    pub fn queued_events(&self) -> usize {
+        self.queued_events.len()
+    }
+
+    /// Current preedit, `None` unless preedit tracking is on and the server drew it
+    pub fn preedit(&self) -> Option<&PreeditState> {
+        self.preedit.as_ref()
+    }
+
+    pub(crate) fn preedit_mut(&mut self) -> &mut PreeditState {
+        self.preedit.get_or_insert_with(PreeditState::new)
+    }
+
+    pub(crate) fn queue_event(&mut self, flag: ForwardEventFlag, xev: XEvent) {
+        self.queued_events.push_back((flag, xev));
+    }
+
+    pub(crate) fn pop_queued_event(&mut self) -> Option<(ForwardEventFlag, XEvent)> {
+        if self.waiting_sync {
+            None
+        } else {
+            self.queued_events.pop_front()
+        }
+    }
+
+    pub(crate) fn set_spot_location(&mut self, spot_location: Point) {
+        self.spot_location = Some(spot_location);
+    }
+
+    /// Return `true` when the event of X event type `response_type` should be forwarded.
+    ///
+    /// Key events are forwarded until the server sets the mask, other events only when the
+    /// server asks them.
+    pub(crate) fn forwards(&self, response_type: u8) -> bool {
+        if !self.active {
+            return false;
+        }
+
+        let event_mask = match event_mask_of(response_type) {
+            Some(event_mask) => event_mask,
+            None => return true,
+        };
+
+        match self.forward_event_mask {
+            Some(mask) => mask & event_mask != 0,
+            None => event_mask & KEY_EVENT_MASK != 0,
+        }
+    }
+
+    pub(crate) fn set_event_mask(&mut self, forward_event_mask: u32, synchronous_event_mask: u32) {
+        self.forward_event_mask = Some(forward_event_mask);
+        self.synchronous_event_mask = synchronous_event_mask;
+    }
+
+    pub(crate) fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+}
+
+/// Input method opened by the client
+#[derive(Clone, Debug)]
+pub struct InputMethodInfo {
+    input_method_id: u16,
+    locale: String,
+    encoding: Encoding,
+    extensions: Vec<Extension>,
+    input_contexts: AHashMap<u16, InputContextInfo>,
+}
+
+impl InputMethodInfo {
+    pub fn input_method_id(&self) -> u16 {
+        self.input_method_id
+    }
+
+    pub fn locale(&self) -> &str {
+        self.locale.as_str()
+    }
+
+    /// Encoding negotiated with the server, used for committed and preedit strings
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Extensions which the server replied to `QueryExtension`
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    pub fn extension(&self, name: &str) -> Option<&Extension> {
+        self.extensions.iter().find(|ext| ext.name == name)
+    }
+
+    pub fn input_context(&self, input_context_id: u16) -> Option<&InputContextInfo> {
+        self.input_contexts.get(&input_context_id)
+    }
+
+    pub fn input_contexts(&self) -> impl Iterator<Item = &InputContextInfo> {
+        self.input_contexts.values()
+    }
+}
+
+enum Sent {
+    Open(String),
+    IcValues(IcValues),
+    Sync(u16, u16),
+}
+
+/// Input methods and input contexts of the client, updated when their replies arrive.
+///
+/// Users can attach their own data to an input context with [`ImRegistry::set_ic_data`], it's
+/// dropped with the input context.
+#[derive(Default)]
+pub struct ImRegistry {
+    input_methods: AHashMap<u16, InputMethodInfo>,
+    sent: AHashMap<RequestId, Sent>,
+    ic_data: AHashMap<(u16, u16), Box<dyn Any>>,
+}
+
+impl ImRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input_method(&self, input_method_id: u16) -> Option<&InputMethodInfo> {
+        self.input_methods.get(&input_method_id)
+    }
+
+    pub fn input_methods(&self) -> impl Iterator<Item = &InputMethodInfo> {
+        self.input_methods.values()
+    }
+
+    /// Input contexts of every input method
+    pub fn input_contexts(&self) -> impl Iterator<Item = &InputContextInfo> {
+        self.input_methods
+            .values()
+            .flat_map(|im| im.input_contexts.values())
+    }
+
+    pub fn input_context(
+        &self,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Option<&InputContextInfo> {
+        self.input_method(input_method_id)?
+            .input_context(input_context_id)
+    }
+
+    /// User data of the input context, `None` when it's not set or not a `T`
+    pub fn ic_data<T: Any>(&self, input_method_id: u16, input_context_id: u16) -> Option<&T> {
+        self.ic_data
+            .get(&(input_method_id, input_context_id))?
+            .downcast_ref()
+    }
+
+    pub fn ic_data_mut<T: Any>(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Option<&mut T> {
+        self.ic_data
+            .get_mut(&(input_method_id, input_context_id))?
+            .downcast_mut()
+    }
+
+    /// Attach `data` to the input context, return `false` when it doesn't exist
+    pub fn set_ic_data<T: Any>(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        data: T,
+    ) -> bool {
+        if self
+            .input_context(input_method_id, input_context_id)
+            .is_none()
+        {
+            return false;
+        }
+
+        self.ic_data
+            .insert((input_method_id, input_context_id), Box::new(data));
+        true
+    }
+
+    pub fn take_ic_data(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Option<Box<dyn Any>> {
+        self.ic_data.remove(&(input_method_id, input_context_id))
+    }
+
+    pub(crate) fn input_context_mut(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Option<&mut InputContextInfo> {
+        self.input_methods
+            .get_mut(&input_method_id)?
+            .input_contexts
+            .get_mut(&input_context_id)
+    }
+
+    pub(crate) fn set_encoding(&mut self, input_method_id: u16, encoding: Encoding) {
+        if let Some(im) = self.input_methods.get_mut(&input_method_id) {
+            im.encoding = encoding;
+        }
+    }
+
+    pub(crate) fn request_sent(&mut self, id: RequestId, req: &Request, values: Option<IcValues>) {
+        let sent = match (req, values) {
+            (Request::Open { locale }, _) => Sent::Open(locale.clone()),
+            (
+                Request::ForwardEvent {
+                    input_method_id,
+                    input_context_id,
+                    ..
+                },
+                _,
+            ) => {
+                self.set_waiting_sync(*input_method_id, *input_context_id, true);
+                Sent::Sync(*input_method_id, *input_context_id)
+            }
+            (_, Some(values)) => Sent::IcValues(values),
+            _ => return,
+        };
+
+        self.sent.insert(id, sent);
+    }
+
+    /// Forget request `id` which failed or expired
+    pub(crate) fn request_failed(&mut self, id: RequestId) {
+        if let Some(Sent::Sync(input_method_id, input_context_id)) = self.sent.remove(&id) {
+            self.set_waiting_sync(input_method_id, input_context_id, false);
+        }
+    }
+
+    fn set_waiting_sync(&mut self, input_method_id: u16, input_context_id: u16, waiting: bool) {
+        if let Some(ic) = self.input_context_mut(input_method_id, input_context_id) {
+            ic.waiting_sync = waiting;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.input_methods.clear();
+        self.sent.clear();
+        self.ic_data.clear();
+    }
+
+    pub(crate) fn reply_received(&mut self, id: RequestId, req: &Request) {
+        let sent = self.sent.remove(&id);
+
+        match *req {
+            Request::OpenReply {
+                input_method_id, ..
+            } => {
+                let locale = match sent {
+                    Some(Sent::Open(locale)) => locale,
+                    _ => String::new(),
+                };
+                self.input_methods.insert(
+                    input_method_id,
+                    InputMethodInfo {
+                        input_method_id,
+                        locale,
+                        encoding: Encoding::default(),
+                        extensions: Vec::new(),
+                        input_contexts: AHashMap::with_hasher(Default::default()),
+                    },
+                );
+            }
+            Request::QueryExtensionReply {
+                input_method_id,
+                ref extensions,
+            } => {
+                if let Some(im) = self.input_methods.get_mut(&input_method_id) {
+                    im.extensions = extensions.clone();
+                }
+            }
+            Request::CloseReply { input_method_id } => {
+                self.input_methods.remove(&input_method_id);
+                self.ic_data.retain(|(im, _), _| *im != input_method_id);
+            }
+            Request::CreateIcReply {
+                input_method_id,
+                input_context_id,
+            } => {
+                if let Some(im) = self.input_methods.get_mut(&input_method_id) {
+                    let mut ic = InputContextInfo::new(input_method_id, input_context_id);
+                    if let Some(Sent::IcValues(values)) = sent {
+                        ic.apply(values);
+                    }
+                    im.input_contexts.insert(input_context_id, ic);
+                }
+            }
+            Request::SetIcValuesReply {
+                input_method_id,
+                input_context_id,
+            } => {
+                if let (Some(ic), Some(Sent::IcValues(values))) = (
+                    self.input_methods
+                        .get_mut(&input_method_id)
+                        .and_then(|im| im.input_contexts.get_mut(&input_context_id)),
+                    sent,
+                ) {
+                    ic.apply(values);
+                }
+            }
+            Request::DestroyIcReply {
+                input_method_id,
+                input_context_id,
+            } => {
+                if let Some(im) = self.input_methods.get_mut(&input_method_id) {
+                    im.input_contexts.remove(&input_context_id);
+                }
+                self.ic_data.remove(&(input_method_id, input_context_id));
+            }
+            Request::SyncReply {
+                input_method_id,
+                input_context_id,
+            } => {
+                self.set_waiting_sync(input_method_id, input_context_id, false);
+            }
+            Request::DisconnectReply {} => {
+                self.input_methods.clear();
+                self.ic_data.clear();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use xim_parser::{
+    AttributeName, Feedback, ForwardEventFlag, InputStyle, InputStyleList, Point, PreeditDrawStatus,
+};
+
+use super::{BlockingClient, Client, ClientError, ClientHandler, PreeditState};
+use crate::AHashMap;
+
+/// What [`XimSession`] sets up
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SessionConfig {
+    /// Locale of the input method
+    pub locale: String,
+    /// Client and focus window of the input context
+    pub window: u32,
+    /// Input styles in order of preference, the first one the server supports is used
+    pub styles: Vec<InputStyle>,
+}
+
+impl SessionConfig {
+    /// Prefer on-the-spot, then over-the-spot, then root window style
+    pub fn new(locale: &str, window: u32) -> Self {
+        Self {
+            locale: locale.into(),
+            window,
+            styles: vec![
+                InputStyle::PREEDIT_CALLBACKS | InputStyle::STATUS_NOTHING,
+                InputStyle::PREEDIT_POSITION | InputStyle::STATUS_NOTHING,
+                InputStyle::PREEDIT_NOTHING | InputStyle::STATUS_NOTHING,
+            ],
+        }
+    }
+}
+
+/// Events of [`XimSession`] for the application
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SessionEvent<E> {
+    /// The input context is created, key events are passed to the server from now
+    Ready,
+    /// Text is committed
+    Commit(String),
+    /// The preedit text is changed, `caret` is a char index into `text`
+    PreeditUpdate { text: String, caret: usize },
+    /// The server doesn't handle the key event, the application should process it
+    ForwardedKey(E),
+    /// The server is gone, the session is set up again when it comes back
+    Disconnected,
+}
+
+/// Connection to the server with one input context, which takes care of the handshake for the
+/// common case.
+///
+/// It connects to the server, opens an input method, picks an input style from
+/// [`SessionConfig::styles`] and creates an input context for [`SessionConfig::window`]. The
+/// application only passes events to it and reads [`SessionEvent`]s back.
+///
+/// ```ignore
+/// let client = X11rbClient::init(conn, screen_num, None)?;
+/// let mut session = XimSession::new(client, SessionConfig::new("en_US", window));
+///
+/// loop {
+///     let e = conn.wait_for_event()?;
+///
+///     if !session.filter_event(&e)? {
+///         if let Event::KeyPress(e) | Event::KeyRelease(e) = e {
+///             session.forward_key(&e)?;
+///         }
+///     }
+///
+///     while let Some(event) = session.next_event() {
+///         // draw commits and preedit
+///     }
+/// }
+/// ```
+pub struct XimSession<C: Client> {
+    client: C,
+    handler: SessionHandler<C::XEvent>,
+}
+
+impl<C: BlockingClient> XimSession<C> {
+    /// Create it before any event is passed to `client` so the handshake is not missed
+    pub fn new(client: C, config: SessionConfig) -> Self {
+        Self {
+            client,
+            handler: SessionHandler {
+                config,
+                input_method_id: None,
+                input_context_id: None,
+                style: InputStyle::empty(),
+                preedit: PreeditState::new(),
+                events: VecDeque::new(),
+            },
+        }
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut C {
+        &mut self.client
+    }
+
+    /// Input style picked for the input context, empty until it's created
+    pub fn input_style(&self) -> InputStyle {
+        self.handler.style
+    }
+
+    /// The input context is created
+    pub fn is_ready(&self) -> bool {
+        self.handler.input_context_id.is_some()
+    }
+
+    /// Filter an event of the backend, return `false` when it's not related to XIM
+    pub fn filter_event(&mut self, e: &C::Event) -> Result<bool, ClientError> {
+        self.client.dispatch_event(e, &mut self.handler)
+    }
+
+    /// Pass a key event to the server, return `false` when the application should process it
+    /// by itself
+    pub fn forward_key(&mut self, xev: &C::XEvent) -> Result<bool, ClientError> {
+        match self.handler.ids() {
+            Some((input_method_id, input_context_id)) => self.client.filter_key_event(
+                input_method_id,
+                input_context_id,
+                ForwardEventFlag::empty(),
+                xev,
+            ),
+            None => Ok(false),
+        }
+    }
+
+    /// Move the spot location for over-the-spot style, it's ignored for other styles
+    pub fn set_spot(&mut self, x: i16, y: i16) -> Result<(), ClientError> {
+        if !self.handler.style.contains(InputStyle::PREEDIT_POSITION) {
+            return Ok(());
+        }
+
+        match self.handler.ids() {
+            Some((input_method_id, input_context_id)) => {
+                self.client
+                    .update_spot(input_method_id, input_context_id, Point { x, y })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Take the next event
+    pub fn next_event(&mut self) -> Option<SessionEvent<C::XEvent>> {
+        self.handler.events.pop_front()
+    }
+}
+
+struct SessionHandler<E> {
+    config: SessionConfig,
+    input_method_id: Option<u16>,
+    input_context_id: Option<u16>,
+    style: InputStyle,
+    preedit: PreeditState,
+    events: VecDeque<SessionEvent<E>>,
+}
+
+impl<E> SessionHandler<E> {
+    fn ids(&self) -> Option<(u16, u16)> {
+        Some((self.input_method_id?, self.input_context_id?))
+    }
+
+    /// Pick the first style of the config which the server supports
+    fn pick_style(&self, supported: &[InputStyle]) -> Option<InputStyle> {
+        self.config
+            .styles
+            .iter()
+            .find(|style| supported.contains(style))
+            .copied()
+    }
+
+    fn update_preedit(&mut self) {
+        self.events.push_back(SessionEvent::PreeditUpdate {
+            text: self.preedit.text(),
+            caret: self.preedit.caret(),
+        });
+    }
+}
+
+impl<C: Client> ClientHandler<C> for SessionHandler<C::XEvent> {
+    fn handle_connect(&mut self, client: &mut C) -> Result<(), ClientError> {
+        client.open(&self.config.locale)
+    }
+
+    fn handle_server_disconnected(&mut self, _client: &mut C) -> Result<(), ClientError> {
+        self.input_method_id = None;
+        self.input_context_id = None;
+        self.style = InputStyle::empty();
+        self.preedit.clear();
+        self.events.push_back(SessionEvent::Disconnected);
+        Ok(())
+    }
+
+    fn handle_open(&mut self, client: &mut C, input_method_id: u16) -> Result<(), ClientError> {
+        self.input_method_id = Some(input_method_id);
+        client.get_im_values(input_method_id, &[AttributeName::QueryInputStyle])
+    }
+
+    fn handle_get_im_values(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        attributes: AHashMap<AttributeName, Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        if self.input_method_id != Some(input_method_id) || self.input_context_id.is_some() {
+            return Ok(());
+        }
+
+        let supported = match attributes.get(&AttributeName::QueryInputStyle) {
+            Some(value) => xim_parser::read::<InputStyleList>(value)?.styles,
+            None => Vec::new(),
+        };
+
+        let style = self
+            .pick_style(&supported)
+            .ok_or(ClientError::InvalidReply)?;
+        self.style = style;
+
+        let mut builder = client
+            .build_ic_attributes()
+            .push(AttributeName::InputStyle, style)
+            .push(AttributeName::ClientWindow, self.config.window)
+            .push(AttributeName::FocusWindow, self.config.window);
+
+        if style.contains(InputStyle::PREEDIT_POSITION) {
+            builder = builder.preedit_attributes(|b| {
+                b.spot_location(Point { x: 0, y: 0 });
+            });
+        }
+
+        let ic_attributes = builder.build();
+        client.create_ic(input_method_id, ic_attributes)
+    }
+
+    fn handle_create_ic(
+        &mut self,
+        client: &mut C,
+        input_method_id: u16,
+        input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        self.input_context_id = Some(input_context_id);
+        self.events.push_back(SessionEvent::Ready);
+        client.set_focus(input_method_id, input_context_id)
+    }
+
+    fn handle_commit(
+        &mut self,
+        _client: &mut C,
+        _input_method_id: u16,
+        _input_context_id: u16,
+        text: &str,
+    ) -> Result<(), ClientError> {
+        self.events.push_back(SessionEvent::Commit(text.into()));
+        Ok(())
+    }
+
+    fn handle_forward_event(
+        &mut self,
+        _client: &mut C,
+        _input_method_id: u16,
+        _input_context_id: u16,
+        _flag: ForwardEventFlag,
+        xev: C::XEvent,
+    ) -> Result<(), ClientError> {
+        self.events.push_back(SessionEvent::ForwardedKey(xev));
+        Ok(())
+    }
+
+    fn handle_preedit_draw(
+        &mut self,
+        _client: &mut C,
+        _input_method_id: u16,
+        _input_context_id: u16,
+        caret: i32,
+        chg_first: i32,
+        chg_len: i32,
+        status: PreeditDrawStatus,
+        preedit_string: &str,
+        feedbacks: Vec<Feedback>,
+    ) -> Result<(), ClientError> {
+        self.preedit.apply_draw(
+            caret,
+            chg_first,
+            chg_len,
+            status,
+            preedit_string,
+            &feedbacks,
+        );
+        self.update_preedit();
+
+        Ok(())
+    }
+
+    fn handle_preedit_done(
+        &mut self,
+        _client: &mut C,
+        _input_method_id: u16,
+        _input_context_id: u16,
+    ) -> Result<(), ClientError> {
+        if !self.preedit.is_empty() {
+            self.preedit.clear();
+            self.update_preedit();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+#[cfg(feature = "metrics")]
+use super::Metrics;
+use super::{
+    Encoding, IcValues, ImRegistry, PendingRequests, Quirks, RequestInterceptor, TransportInfo,
+    TriggerKeys,
+};
+use crate::AHashMap;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use xim_parser::{Attr, AttrType, AttributeName, Request};
+
+/// State of client which is shared by every backend
+#[derive(Default)]
+pub struct ClientState {
+    pending_requests: PendingRequests,
+    registry: ImRegistry,
+    trigger_keys: TriggerKeys,
+    encodings: Vec<Encoding>,
+    quirks: Quirks,
+    preedit_tracking: bool,
+    interceptor: Option<Box<dyn RequestInterceptor>>,
+    im_attribute_types: AHashMap<AttributeName, AttrType>,
+    ic_attribute_types: AHashMap<AttributeName, AttrType>,
+    batching: bool,
+    transport: Option<TransportInfo>,
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
+}
+
+impl ClientState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn pending_requests(&self) -> &PendingRequests {
+        &self.pending_requests
+    }
+
+    #[inline]
+    pub fn pending_requests_mut(&mut self) -> &mut PendingRequests {
+        &mut self.pending_requests
+    }
+
+    #[inline]
+    pub fn registry(&self) -> &ImRegistry {
+        &self.registry
+    }
+
+    #[inline]
+    pub fn trigger_keys(&self) -> &TriggerKeys {
+        &self.trigger_keys
+    }
+
+    /// Encodings offered to the server in order of preference
+    pub fn encodings(&self) -> &[Encoding] {
+        if self.encodings.is_empty() {
+            &[Encoding::CompoundText]
+        } else {
+            &self.encodings
+        }
+    }
+
+    pub fn set_encodings(&mut self, encodings: Vec<Encoding>) {
+        self.encodings = encodings;
+    }
+
+    #[inline]
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Return `true` when preedits of input contexts are tracked
+    #[inline]
+    pub fn preedit_tracking(&self) -> bool {
+        self.preedit_tracking
+    }
+
+    pub fn set_preedit_tracking(&mut self, tracking: bool) {
+        self.preedit_tracking = tracking;
+    }
+
+    pub fn set_interceptor(&mut self, interceptor: Option<Box<dyn RequestInterceptor>>) {
+        self.interceptor = interceptor;
+    }
+
+    #[inline]
+    pub(crate) fn interceptor_mut(&mut self) -> Option<&mut (dyn RequestInterceptor + 'static)> {
+        self.interceptor.as_deref_mut()
+    }
+
+    /// Types of IM attributes reported by the server
+    #[inline]
+    pub fn im_attribute_types(&self) -> &AHashMap<AttributeName, AttrType> {
+        &self.im_attribute_types
+    }
+
+    /// Types of IC attributes reported by the server
+    #[inline]
+    pub fn ic_attribute_types(&self) -> &AHashMap<AttributeName, AttrType> {
+        &self.ic_attribute_types
+    }
+
+    pub(crate) fn set_attribute_types(&mut self, im_attrs: &[Attr], ic_attrs: &[Attr]) {
+        self.im_attribute_types = im_attrs.iter().map(|attr| (attr.name, attr.ty)).collect();
+        self.ic_attribute_types = ic_attrs.iter().map(|attr| (attr.name, attr.ty)).collect();
+    }
+
+    /// Transport parameters of the server, `None` until it's connected
+    #[inline]
+    pub fn transport(&self) -> Option<&TransportInfo> {
+        self.transport.as_ref()
+    }
+
+    pub(crate) fn set_transport(&mut self, transport: TransportInfo) {
+        self.transport = Some(transport);
+    }
+
+    /// Messages exchanged with the server, they're kept across reconnections
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub(crate) fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    /// Return `true` while requests are sent without flushing the connection
+    #[inline]
+    pub fn is_batching(&self) -> bool {
+        self.batching
+    }
+
+    pub(crate) fn set_batching(&mut self, batching: bool) {
+        self.batching = batching;
+    }
+
+    #[inline]
+    pub(crate) fn trigger_keys_mut(&mut self) -> &mut TriggerKeys {
+        &mut self.trigger_keys
+    }
+
+    #[inline]
+    pub(crate) fn registry_mut(&mut self) -> &mut ImRegistry {
+        &mut self.registry
+    }
+
+    /// Update event masks set by the server, return `true` when the input context is turned
+    /// on or off by them
+    pub(crate) fn set_event_mask(
+        &mut self,
+        input_method_id: u16,
+        input_context_id: u16,
+        forward_event_mask: u32,
+        synchronous_event_mask: u32,
+    ) -> bool {
+        let dynamic = !self.trigger_keys.is_empty();
+
+        match self
+            .registry
+            .input_context_mut(input_method_id, input_context_id)
+        {
+            Some(ic) => {
+                ic.set_event_mask(forward_event_mask, synchronous_event_mask);
+
+                // with the dynamic event flow, the server stops forwarding by empty mask
+                let active = !dynamic || forward_event_mask != 0;
+                let changed = ic.is_active() != active;
+                ic.set_active(active);
+                changed
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn request_sent(&mut self, req: &Request, values: Option<IcValues>) {
+        #[cfg(feature = "metrics")]
+        self.metrics.request_sent(req);
+
+        if let Some(id) = self.pending_requests.push(req) {
+            self.registry.request_sent(id, req, values);
+        }
+    }
+
+    /// Return `false` when nothing is waiting the reply
+    pub(crate) fn reply_received(&mut self, req: &Request) -> bool {
+        #[cfg(feature = "metrics")]
+        self.metrics.request_received(req);
+
+        if let Request::Error {
+            flag,
+            input_method_id,
+            input_context_id,
+            ..
+        } = *req
+        {
+            self.pending_requests
+                .start_error(flag, input_method_id, input_context_id);
+            if let Some(current) = self.pending_requests.current() {
+                self.registry.request_failed(current.id());
+                #[cfg(feature = "metrics")]
+                self.metrics.reply_received(current, req);
+            }
+            return true;
+        }
+
+        if !self.pending_requests.start_reply(req) {
+            return false;
+        }
+
+        if let Some(current) = self.pending_requests.current() {
+            self.registry.reply_received(current.id(), req);
+            #[cfg(feature = "metrics")]
+            self.metrics.reply_received(current, req);
+        }
+
+        if let Request::EncodingNegotiationReply {
+            input_method_id,
+            category,
+            index,
+        } = *req
+        {
+            // category 0 is index of the name list, -1 means nothing is matched
+            let encoding = match (category, usize::try_from(index)) {
+                (0, Ok(index)) => self.encodings().get(index).copied().unwrap_or_default(),
+                _ => Encoding::default(),
+            };
+            log::info!("Negotiated encoding {}", encoding.name());
+            self.registry.set_encoding(input_method_id, encoding);
+        }
+
+        if let Request::CreateIcReply {
+            input_method_id,
+            input_context_id,
+        } = *req
+        {
+            // with the dynamic event flow, nothing is forwarded until a trigger key is hit
+            if !self.trigger_keys.is_empty() {
+                if let Some(ic) = self
+                    .registry
+                    .input_context_mut(input_method_id, input_context_id)
+                {
+                    ic.set_active(false);
+                }
+            }
+        }
+
+        true
+    }
+
+    pub(crate) fn reply_handled(&mut self) {
+        self.pending_requests.finish();
+    }
+
+    /// Forget every request and input method, used when the server is gone
+    pub(crate) fn reset(&mut self) {
+        self.pending_requests.clear();
+        self.registry.clear();
+        self.trigger_keys.clear();
+        self.im_attribute_types.clear();
+        self.ic_attribute_types.clear();
+        self.transport = None;
+    }
+}
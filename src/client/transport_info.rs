@@ -0,0 +1,60 @@
+use alloc::string::String;
+
+/// Transport parameters negotiated with the server by `XIM_XCONNECT`, or the address of the TCP
+/// transport
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TransportInfo {
+    server_name: String,
+    tcp_address: Option<String>,
+    major_version: u32,
+    minor_version: u32,
+    transport_max: usize,
+}
+
+impl TransportInfo {
+    pub(crate) fn new(
+        server_name: String,
+        major_version: u32,
+        minor_version: u32,
+        transport_max: usize,
+    ) -> Self {
+        Self {
+            server_name,
+            tcp_address: None,
+            major_version,
+            minor_version,
+            transport_max,
+        }
+    }
+
+    /// Transport connected to `host:port` by TCP, it has no `XIM_XCONNECT` parameters
+    pub(crate) fn tcp(server_name: String, address: String) -> Self {
+        Self {
+            tcp_address: Some(address),
+            ..Self::new(server_name, 0, 0, 0)
+        }
+    }
+
+    /// Name of the server registered as `@server=name`
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// `host:port` of the server when it's connected by TCP
+    pub fn tcp_address(&self) -> Option<&str> {
+        self.tcp_address.as_deref()
+    }
+
+    pub fn major_version(&self) -> u32 {
+        self.major_version
+    }
+
+    pub fn minor_version(&self) -> u32 {
+        self.minor_version
+    }
+
+    /// Messages shorter than this are sent by `ClientMessage`s, longer ones by a property
+    pub fn transport_max(&self) -> usize {
+        self.transport_max
+    }
+}
@@ -0,0 +1,59 @@
+use alloc::vec::Vec;
+use xim_parser::{TriggerKey, TriggerNotifyFlag};
+
+/// Trigger keys registered by the server which uses the dynamic event flow model.
+///
+/// Key events are not forwarded to the server until the user hits one of the on keys, and are
+/// forwarded again until one of the off keys is hit.
+#[derive(Clone, Debug, Default)]
+pub struct TriggerKeys {
+    on_keys: Vec<TriggerKey>,
+    off_keys: Vec<TriggerKey>,
+}
+
+impl TriggerKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_keys(&self) -> &[TriggerKey] {
+        &self.on_keys
+    }
+
+    pub fn off_keys(&self) -> &[TriggerKey] {
+        &self.off_keys
+    }
+
+    /// Return `true` when the server doesn't register any trigger key (static event flow)
+    pub fn is_empty(&self) -> bool {
+        self.on_keys.is_empty() && self.off_keys.is_empty()
+    }
+
+    pub(crate) fn register(&mut self, on_keys: Vec<TriggerKey>, off_keys: Vec<TriggerKey>) {
+        self.on_keys = on_keys;
+        self.off_keys = off_keys;
+    }
+
+    /// Find the off key when the input context is `active`, otherwise the on key matches
+    /// `keysym` and `state`
+    pub(crate) fn check(
+        &self,
+        active: bool,
+        keysym: u32,
+        state: u32,
+    ) -> Option<(TriggerNotifyFlag, u32)> {
+        let (flag, keys) = if active {
+            (TriggerNotifyFlag::OffKeyList, &self.off_keys)
+        } else {
+            (TriggerNotifyFlag::OnKeyList, &self.on_keys)
+        };
+
+        keys.iter()
+            .position(|key| key.keysym == keysym && state & key.modifier_mask == key.modifier)
+            .map(|index| (flag, index as u32))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,53 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Encoding of texts which can be negotiated by `EncodingNegotiation`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Encoding {
+    CompoundText,
+    Utf8,
+}
+
+impl Encoding {
+    /// Name used in `EncodingNegotiation`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::CompoundText => "COMPOUND_TEXT",
+            Self::Utf8 => "UTF-8",
+        }
+    }
+
+    /// Encoding named `name` in `EncodingNegotiation`
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.starts_with("COMPOUND_TEXT") {
+            Some(Self::CompoundText)
+        } else if name.eq_ignore_ascii_case("UTF-8") || name.eq_ignore_ascii_case("UTF8") {
+            Some(Self::Utf8)
+        } else {
+            None
+        }
+    }
+
+    /// `None` when `bytes` is not valid
+    pub fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Self::CompoundText => xim_ctext::compound_text_to_utf8(bytes).ok(),
+            Self::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+        }
+    }
+
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Self::CompoundText => xim_ctext::utf8_to_compound_text(text),
+            Self::Utf8 => text.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl Default for Encoding {
+    /// `COMPOUND_TEXT` is used when the negotiation fails
+    fn default() -> Self {
+        Self::CompoundText
+    }
+}
@@ -15,14 +15,29 @@
 //! - A wrapper around [`x11rb`](x11rb-library), the X rust bindings. See the [`x11rb`] module
 //!   for more information (requires the `x11rb-client` or `x11rb-server` feature).
 //! - A wrapper around [`x11-dl`](x11dl-library), the standard X11 library. See the [`xlib`]
-//!   module for more information (requires the `xlib-client` feature).
+//!   module for more information (requires the `xlib-client` or `xlib-server` feature).
+//! - Spans of requests and events of the client state, and of connections and requests of the
+//!   server, emitted through [`tracing`](tracing-library) (requires the `tracing` feature).
+//! - Counters of requests and reply latencies of the client, see [`Client::metrics`], and of
+//!   clients and requests of the server (requires the `metrics` feature).
+//! - An adapter producing winit style IME events for a window of
+//!   [`raw-window-handle`](raw-window-handle-library). See the [`winit`] module (requires the
+//!   `winit` feature with the `x11rb-client` or `xlib-client` feature).
+//! - An event source for [`calloop`](calloop-library) driving an x11rb client. See the
+//!   [`calloop`](mod@calloop) module (requires the `calloop` feature).
 //!
 //! [x11rb-library]: https://crates.io/crates/x11rb
 //! [x11dl-library]: https://crates.io/crates/x11-dl
+//! [tracing-library]: https://crates.io/crates/tracing
+//! [raw-window-handle-library]: https://crates.io/crates/raw-window-handle
+//! [calloop-library]: https://crates.io/crates/calloop
 
 #![no_std]
 #![allow(clippy::uninlined_format_args, clippy::too_many_arguments)]
-#![cfg_attr(not(feature = "xlib-client"), forbid(unsafe_code))]
+#![cfg_attr(
+    not(any(feature = "xlib-client", feature = "xlib-server")),
+    forbid(unsafe_code)
+)]
 #![forbid(future_incompatible)]
 
 extern crate alloc;
@@ -35,20 +50,58 @@ mod client;
 #[cfg(feature = "server")]
 mod server;
 
+#[cfg(any(feature = "client", feature = "server"))]
+mod encoding;
+#[cfg(all(any(feature = "client", feature = "server"), feature = "metrics"))]
+mod request_counts;
+#[cfg(any(feature = "client", feature = "server"))]
+mod trace;
+#[cfg(any(feature = "client", feature = "server"))]
+mod transport;
+
+#[cfg(feature = "calloop")]
+pub mod calloop;
+#[cfg(all(
+    feature = "winit",
+    any(feature = "x11rb-client", feature = "xlib-client")
+))]
+pub mod winit;
 #[cfg(any(feature = "x11rb-server", feature = "x11rb-client"))]
 pub mod x11rb;
-#[cfg(feature = "xlib-client")]
+#[cfg(any(feature = "x11rb-client", feature = "xlib-client"))]
+mod xim_client;
+#[cfg(any(feature = "xlib-client", feature = "xlib-server"))]
 pub mod xlib;
 
 #[cfg(feature = "client")]
-pub use crate::client::{Client, ClientError, ClientHandler};
+pub use crate::client::geometry;
+#[cfg(feature = "client")]
+pub use crate::client::{
+    keysym_to_char, Client, ClientError, ClientHandler, DynClient, DynHandler, IcValues,
+    ImRegistry, InputContextInfo, InputMethodInfo, PendingRequest, PendingRequests, PreeditRun,
+    PreeditState, Quirks, ReplyKind, RequestId, RequestInterceptor, TransportInfo, TriggerKeys,
+};
+#[cfg(all(feature = "client", feature = "std"))]
+pub use crate::client::{BlockingClient, SessionConfig, SessionEvent, XimSession};
+#[cfg(all(feature = "client", feature = "metrics"))]
+pub use crate::client::{Metrics, ReplyStats};
+#[cfg(any(feature = "client", feature = "server"))]
+pub use crate::encoding::Encoding;
+#[cfg(all(any(feature = "client", feature = "server"), feature = "metrics"))]
+pub use crate::request_counts::RequestCounts;
+#[cfg(all(feature = "server", feature = "metrics"))]
+pub use crate::server::ServerStats;
+#[cfg(any(feature = "x11rb-client", feature = "xlib-client"))]
+pub use crate::xim_client::XimClient;
 
 #[cfg(feature = "server")]
 pub const ALL_LOCALES: &str = include_str!("./all_locales.txt");
 
 #[cfg(feature = "server")]
 pub use crate::server::{
-    InputContext, InputMethod, Server, ServerCore, ServerError, ServerHandler, UserInputContext,
+    AttributeChange, AttributeChanges, AttributeList, AttributeStore, AuthStep, ConnectionLimits,
+    DynServer, DynServerHandler, DynServerHandlerObject, FeedbackRun, InputContext, InputMethod,
+    LocaleSet, Server, ServerCore, ServerError, ServerHandler, UserInputContext, WireTap,
     XimConnection, XimConnections,
 };
 pub type AHashMap<K, V> = hashbrown::HashMap<K, V, ahash::RandomState>;
@@ -61,6 +114,7 @@ struct Atoms<Atom> {
     TRANSPORT: Atom,
     XIM_XCONNECT: Atom,
     XIM_PROTOCOL: Atom,
+    XIM_MOREDATA: Atom,
 }
 
 impl<Atom> Atoms<Atom> {
@@ -75,6 +129,7 @@ impl<Atom> Atoms<Atom> {
             TRANSPORT: f("TRANSPORT")?,
             XIM_XCONNECT: f("_XIM_XCONNECT")?,
             XIM_PROTOCOL: f("_XIM_PROTOCOL")?,
+            XIM_MOREDATA: f("_XIM_MOREDATA")?,
         })
     }
 
@@ -89,6 +144,7 @@ impl<Atom> Atoms<Atom> {
             TRANSPORT: f("TRANSPORT\0")?,
             XIM_XCONNECT: f("_XIM_XCONNECT\0")?,
             XIM_PROTOCOL: f("_XIM_PROTOCOL\0")?,
+            XIM_MOREDATA: f("_XIM_MOREDATA\0")?,
         })
     }
 }
@@ -0,0 +1,45 @@
+use crate::AHashMap;
+use xim_parser::Request;
+
+/// Numbers of requests exchanged with the peer by their names, part of
+/// [`Metrics`](crate::Metrics) of the client and [`ServerStats`](crate::ServerStats) of the
+/// server
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RequestCounts {
+    sent: AHashMap<&'static str, u64>,
+    received: AHashMap<&'static str, u64>,
+}
+
+impl RequestCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of requests named `name` sent to the peer
+    pub fn sent(&self, name: &str) -> u64 {
+        self.sent.get(name).copied().unwrap_or(0)
+    }
+
+    /// Number of requests named `name` received from the peer
+    pub fn received(&self, name: &str) -> u64 {
+        self.received.get(name).copied().unwrap_or(0)
+    }
+
+    /// Names of sent requests with their numbers
+    pub fn sent_counts(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.sent.iter().map(|(name, count)| (*name, *count))
+    }
+
+    /// Names of received requests with their numbers
+    pub fn received_counts(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.received.iter().map(|(name, count)| (*name, *count))
+    }
+
+    pub(crate) fn request_sent(&mut self, req: &Request) {
+        *self.sent.entry(req.name()).or_default() += 1;
+    }
+
+    pub(crate) fn request_received(&mut self, req: &Request) {
+        *self.received.entry(req.name()).or_default() += 1;
+    }
+}
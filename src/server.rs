@@ -1,18 +1,35 @@
+mod attribute_store;
 mod connection;
+mod dyn_handler;
+mod locale_set;
+#[cfg(feature = "metrics")]
+mod stats;
+#[cfg(test)]
+mod tests;
+mod wire_tap;
 
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt;
 use core::num::NonZeroU16;
+use core::ops::Range;
 
 use xim_parser::{
-    CommitData, ErrorCode, ErrorFlag, Feedback, InputStyle, PreeditDrawStatus, Request,
+    attrs, Attr, AttributeName, CaretDirection, CaretStyle, CommitData, ErrorCode, ErrorFlag,
+    Extension, Feedback, ForwardEventFlag, InputStyle, PreeditDrawStatus, PreeditStateFlag,
+    Request, StatusContent, StatusTextContent, StrConversionOperation, TriggerKey,
 };
 
+pub use self::attribute_store::{AttributeChange, AttributeChanges, AttributeList, AttributeStore};
 pub use self::connection::{
-    InputContext, InputMethod, UserInputContext, XimConnection, XimConnections,
+    ConnectionLimits, InputContext, InputMethod, UserInputContext, XimConnection, XimConnections,
 };
+pub use self::dyn_handler::{DynServer, DynServerHandler, DynServerHandlerObject};
+pub use self::locale_set::LocaleSet;
+#[cfg(feature = "metrics")]
+pub use self::stats::ServerStats;
+pub use self::wire_tap::WireTap;
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -51,7 +68,32 @@ impl fmt::Display for ServerError {
 #[cfg(feature = "std")]
 impl std::error::Error for ServerError {}
 
-pub trait ServerHandler<S: Server> {
+/// Next step of the authentication, see [`ServerHandler::handle_auth_reply`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuthStep {
+    /// Send `ConnectReply`
+    Accept,
+    /// Send `AuthNext` with the data
+    Next(Vec<u8>),
+    /// Send `AuthNg` and close the connection
+    Reject,
+}
+
+/// Feedback of the characters of a preedit in `range`, see [`Server::preedit_draw_styled`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeedbackRun {
+    /// Range of characters, not bytes
+    pub range: Range<usize>,
+    pub feedback: Feedback,
+}
+
+impl FeedbackRun {
+    pub fn new(range: Range<usize>, feedback: Feedback) -> Self {
+        Self { range, feedback }
+    }
+}
+
+pub trait ServerHandler<S: Server + ?Sized> {
     type InputStyleArray: AsRef<[InputStyle]>;
     type InputContextData;
 
@@ -64,8 +106,77 @@ pub trait ServerHandler<S: Server> {
     fn input_styles(&self) -> Self::InputStyleArray;
     fn filter_events(&self) -> u32;
 
+    /// Input style used for the style `requested` by `CreateIc`, `BadStyle` is sent when it
+    /// returns `None`.
+    ///
+    /// By default only the styles of [`ServerHandler::input_styles`] are accepted, return
+    /// another one of them to fall back instead.
+    fn resolve_input_style(&self, requested: InputStyle) -> Option<InputStyle> {
+        if self.input_styles().as_ref().contains(&requested) {
+            Some(requested)
+        } else {
+            None
+        }
+    }
+
+    /// Whether an input method of `locale` requested by `Open` can be created,
+    /// `LocaleNotSupported` is sent instead of `OpenReply` when it returns `false`
+    #[allow(unused_variables)]
+    fn accept_locale(&self, locale: &str) -> bool {
+        true
+    }
+
+    /// IM attributes advertised by `OpenReply`, the ones other than `QueryInputStyle` are
+    /// answered by [`ServerHandler::handle_get_im_value`]
+    fn im_attributes(&self) -> Vec<Attr> {
+        vec![attrs::QUERY_INPUT_STYLE]
+    }
+
+    /// Encoded value of the IM attribute `name` asked by `GetImValues`, `BadName` is sent when
+    /// it returns `None`. `QueryInputStyle` is answered by [`ServerHandler::input_styles`].
+    #[allow(unused_variables)]
+    fn handle_get_im_value(&self, name: AttributeName) -> Option<Vec<u8>> {
+        None
+    }
+
     fn handle_connect(&mut self, server: &mut S) -> Result<(), ServerError>;
 
+    /// Called for `Connect` with the authentication protocols the client offered, return the
+    /// index of the one to use with its initial data to send `AuthRequired`.
+    ///
+    /// The default accepts every client without authentication.
+    #[allow(unused_variables)]
+    fn auth_required(
+        &mut self,
+        server: &mut S,
+        auth_protocol_names: &[String],
+    ) -> Result<Option<(u8, Vec<u8>)>, ServerError> {
+        Ok(None)
+    }
+
+    /// Called when the client answers `AuthRequired` or `AuthNext`
+    #[allow(unused_variables)]
+    fn handle_auth_reply(
+        &mut self,
+        server: &mut S,
+        auth_data: &[u8],
+    ) -> Result<AuthStep, ServerError> {
+        Ok(AuthStep::Reject)
+    }
+
+    /// Called with the attributes of `CreateIc` before the input context is created, they can
+    /// be changed here.
+    ///
+    /// Return [`ServerError::XimError`] to reject it, the code and detail are sent to the client.
+    #[allow(unused_variables)]
+    fn before_create_ic(
+        &mut self,
+        server: &mut S,
+        attributes: &mut AttributeStore,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
     fn handle_create_ic(
         &mut self,
         server: &mut S,
@@ -77,29 +188,48 @@ pub trait ServerHandler<S: Server> {
         server: &mut S,
         user_ic: UserInputContext<Self::InputContextData>,
     ) -> Result<(), ServerError>;
+
+    /// Return the preedit string of the input context, which is cleared by the client.
+    ///
+    /// The default returns an empty string.
+    #[allow(unused_variables)]
     fn handle_reset_ic(
         &mut self,
         server: &mut S,
         user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<String, ServerError>;
+    ) -> Result<String, ServerError> {
+        Ok(String::new())
+    }
 
+    #[allow(unused_variables)]
     fn handle_set_focus(
         &mut self,
         server: &mut S,
         user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<(), ServerError>;
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
 
+    #[allow(unused_variables)]
     fn handle_unset_focus(
         &mut self,
         server: &mut S,
         user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<(), ServerError>;
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
 
+    /// Called after attributes of `SetIcValues` are stored to the input context, `changes` are
+    /// the attributes whose value differs from the stored one
+    #[allow(unused_variables)]
     fn handle_set_ic_values(
         &mut self,
         server: &mut S,
         user_ic: &mut UserInputContext<Self::InputContextData>,
-    ) -> Result<(), ServerError>;
+        changes: &AttributeChanges,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
 
     /// return `false` when event back to client
     /// if return `true` it consumed and don't back to client
@@ -109,6 +239,116 @@ pub trait ServerHandler<S: Server> {
         user_ic: &mut UserInputContext<Self::InputContextData>,
         xev: &S::XEvent,
     ) -> Result<bool, ServerError>;
+
+    /// Called after IM attributes of `SetImValues` are stored to `input_method`
+    #[allow(unused_variables)]
+    fn handle_set_im_values(
+        &mut self,
+        server: &mut S,
+        input_method: &mut InputMethod<Self::InputContextData>,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Called when the client moves the spot location by `XIM_EXT_MOVE`, the new location is
+    /// already set to the input context
+    #[allow(unused_variables)]
+    fn handle_spot_moved(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Called when the client replies `PreeditCaret` with the new caret `position`
+    #[allow(unused_variables)]
+    fn handle_preedit_caret(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
+        position: i32,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Keys which turn the input method on, with the ones of [`ServerHandler::trigger_off_keys`]
+    /// they're registered after `Open`. The client then forwards key events only while it's on.
+    ///
+    /// Nothing is registered when both are empty, the client forwards every key event then.
+    fn trigger_on_keys(&self) -> Vec<TriggerKey> {
+        Vec::new()
+    }
+
+    /// Keys which turn the input method off
+    fn trigger_off_keys(&self) -> Vec<TriggerKey> {
+        Vec::new()
+    }
+
+    /// Called when a trigger key is pressed, `index` is the position of the key in
+    /// [`ServerHandler::trigger_on_keys`] when `active` or [`ServerHandler::trigger_off_keys`]
+    /// otherwise
+    #[allow(unused_variables)]
+    fn handle_trigger(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
+        active: bool,
+        index: u32,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Called when the client replies `SyncReply` for [`Server::commit_sync`] or
+    /// [`Server::sync`], before the events queued until then are handled
+    #[allow(unused_variables)]
+    fn handle_sync_done(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Called when the client replies `StrConversion` with the text around its caret
+    #[allow(unused_variables)]
+    fn handle_str_conversion_reply(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<Self::InputContextData>,
+        text: &str,
+        feedbacks: &[u32],
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    /// Called for a request this crate doesn't handle, return `true` when it's handled
+    #[allow(unused_variables)]
+    fn handle_unknown_request(
+        &mut self,
+        server: &mut S,
+        connection: &mut XimConnection<Self::InputContextData>,
+        req: &Request,
+    ) -> Result<bool, ServerError> {
+        Ok(false)
+    }
+
+    /// Extensions with opcodes assigned by the server, `QueryExtension` is replied with the ones
+    /// the client asks for
+    fn extensions(&self) -> Vec<Extension> {
+        Vec::new()
+    }
+
+    /// Called for a message of an extension negotiated by the client
+    #[allow(unused_variables)]
+    fn handle_extension_message(
+        &mut self,
+        server: &mut S,
+        extension: &Extension,
+        data: &[u8],
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
 }
 
 pub trait Server {
@@ -124,7 +364,65 @@ pub trait Server {
     ) -> Result<(), ServerError>;
 
     fn preedit_draw(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError>;
+    /// Draw `s` with `feedbacks` and the caret at `caret` characters. Characters not covered by
+    /// any run are underlined like [`Server::preedit_draw`], later runs override earlier ones.
+    fn preedit_draw_styled(
+        &mut self,
+        ic: &mut InputContext,
+        s: &str,
+        feedbacks: &[FeedbackRun],
+        caret: usize,
+    ) -> Result<(), ServerError>;
     fn commit(&mut self, ic: &InputContext, s: &str) -> Result<(), ServerError>;
+    /// Commit `keysym` instead of a text, e.g. the result of a dead key
+    fn commit_keysym(&mut self, ic: &InputContext, keysym: u32) -> Result<(), ServerError>;
+    /// Commit `keysym` and `s` together
+    fn commit_both(&mut self, ic: &InputContext, keysym: u32, s: &str) -> Result<(), ServerError>;
+
+    /// Ask the client `factor` units of text in `direction` from `position`, it's replied to
+    /// [`ServerHandler::handle_str_conversion_reply`]. With
+    /// [`StrConversionOperation::Substitution`] the client also deletes the text.
+    fn request_string_conversion(
+        &mut self,
+        ic: &InputContext,
+        position: u16,
+        direction: CaretDirection,
+        operation: StrConversionOperation,
+        factor: u16,
+    ) -> Result<(), ServerError>;
+
+    /// Start drawing the status, it's done by [`Server::status_draw`] when it's not started yet
+    fn status_start(&mut self, ic: &mut InputContext) -> Result<(), ServerError>;
+    /// Draw `s` in the status area
+    fn status_draw(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError>;
+    /// Draw `pixmap` in the status area
+    fn status_draw_bitmap(&mut self, ic: &mut InputContext, pixmap: u32)
+        -> Result<(), ServerError>;
+    /// Finish drawing the status, nothing is sent when it's not started
+    fn status_done(&mut self, ic: &mut InputContext) -> Result<(), ServerError>;
+
+    /// Move the caret in the preedit, the client replies the new position with
+    /// [`ServerHandler::handle_preedit_caret`]
+    fn preedit_caret(
+        &mut self,
+        ic: &InputContext,
+        position: i32,
+        direction: CaretDirection,
+        style: CaretStyle,
+    ) -> Result<(), ServerError>;
+
+    /// Put the caret after `index` characters of the preedit without sending its text again,
+    /// nothing is sent when the preedit is not started. Unlike [`Server::preedit_caret`] the
+    /// client doesn't reply.
+    fn set_preedit_caret(&mut self, ic: &InputContext, index: usize) -> Result<(), ServerError>;
+
+    /// Enable or disable the preedit of `ic`, the client is notified so the application can show
+    /// or hide its composition UI. The state is stored in `PreeditAttributes` for `GetIcValues`.
+    fn set_preedit_state(
+        &mut self,
+        ic: &mut InputContext,
+        enabled: bool,
+    ) -> Result<(), ServerError>;
 
     fn set_event_mask(
         &mut self,
@@ -132,6 +430,44 @@ pub trait Server {
         forward_event_mask: u32,
         synchronous_event_mask: u32,
     ) -> Result<(), ServerError>;
+
+    /// Commit `s` synchronously, [`ServerHandler::handle_sync_done`] is called when the client
+    /// replies `SyncReply` and events it forwards are queued until then
+    fn commit_sync(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError>;
+
+    /// Send `Sync` to the client, events it forwards are queued until `SyncReply`
+    fn sync(&mut self, ic: &mut InputContext) -> Result<(), ServerError>;
+
+    /// Let the client forward events of `forward_event_mask`, which is usually
+    /// [`ServerHandler::filter_events`]. It's sent by `XIM_EXT_SET_EVENT_MASK` when the client
+    /// negotiated it, `SetEventMask` otherwise.
+    fn activate_ic(
+        &mut self,
+        ic: &mut InputContext,
+        forward_event_mask: u32,
+    ) -> Result<(), ServerError>;
+
+    /// Stop the client forwarding events, they're handled by the client until
+    /// [`Server::activate_ic`]
+    fn deactivate_ic(&mut self, ic: &mut InputContext) -> Result<(), ServerError>;
+
+    /// Send a synthesized key event which the client processes as typed. It's sent by
+    /// `XIM_EXT_FORWARD_KEYEVENT` when the client negotiated it, `ForwardEvent` otherwise.
+    fn ext_forward_key_event(
+        &mut self,
+        ic: &InputContext,
+        xev: &Self::XEvent,
+    ) -> Result<(), ServerError>;
+
+    /// Forward `xev` to the client, usually a modified copy of the event passed to
+    /// [`ServerHandler::handle_forward_event`]. With [`ForwardEventFlag::SYNCHRONOUS`] events
+    /// forwarded by the client are queued until it replies `SyncReply`.
+    fn forward_event(
+        &mut self,
+        ic: &mut InputContext,
+        xev: &Self::XEvent,
+        flag: ForwardEventFlag,
+    ) -> Result<(), ServerError>;
 }
 
 impl<S: ServerCore> Server for S {
@@ -174,9 +510,29 @@ impl<S: ServerCore> Server for S {
     }
 
     fn preedit_draw(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError> {
-        let preedit_length = s.chars().count();
+        self.preedit_draw_styled(ic, s, &[], s.chars().count())
+    }
+
+    fn preedit_draw_styled(
+        &mut self,
+        ic: &mut InputContext,
+        s: &str,
+        feedbacks: &[FeedbackRun],
+        caret: usize,
+    ) -> Result<(), ServerError> {
+        let mut preedit: Vec<(char, Feedback)> =
+            s.chars().map(|c| (c, Feedback::Underline)).collect();
+
+        for run in feedbacks {
+            let end = run.range.end.min(preedit.len());
+            let start = run.range.start.min(end);
+
+            for (_, feedback) in &mut preedit[start..end] {
+                *feedback = run.feedback;
+            }
+        }
 
-        if preedit_length == 0 {
+        if preedit.is_empty() {
             if ic.preedit_started {
                 self.send_req(
                     ic.client_win(),
@@ -184,8 +540,8 @@ impl<S: ServerCore> Server for S {
                         input_method_id: ic.input_method_id().get(),
                         input_context_id: ic.input_context_id().get(),
                         chg_first: 0,
-                        chg_length: ic.prev_preedit_length as _,
-                        caret: preedit_length as _,
+                        chg_length: ic.prev_preedit.len() as _,
+                        caret: 0,
                         preedit_string: Vec::new(),
                         feedbacks: Vec::new(),
                         status: PreeditDrawStatus::NO_FEEDBACK | PreeditDrawStatus::NO_STRING,
@@ -199,7 +555,7 @@ impl<S: ServerCore> Server for S {
                     },
                 )?;
                 ic.preedit_started = false;
-                ic.prev_preedit_length = 0;
+                ic.prev_preedit.clear();
             }
         } else {
             if !ic.preedit_started {
@@ -213,21 +569,46 @@ impl<S: ServerCore> Server for S {
                 ic.preedit_started = true;
             }
 
+            // only send the region which is changed from the previous draw
+            let prev = &ic.prev_preedit;
+            let prefix = prev
+                .iter()
+                .zip(preedit.iter())
+                .take_while(|(l, r)| l == r)
+                .count();
+            let suffix = prev[prefix..]
+                .iter()
+                .rev()
+                .zip(preedit[prefix..].iter().rev())
+                .take_while(|(l, r)| l == r)
+                .count();
+            let chg_length = prev.len() - prefix - suffix;
+            let changed = &preedit[prefix..preedit.len() - suffix];
+            let (preedit_string, status) = if changed.is_empty() {
+                (
+                    Vec::new(),
+                    PreeditDrawStatus::NO_FEEDBACK | PreeditDrawStatus::NO_STRING,
+                )
+            } else {
+                let text: String = changed.iter().map(|(c, _)| *c).collect();
+                (ic.encoding().encode(&text), PreeditDrawStatus::empty())
+            };
+
             self.send_req(
                 ic.client_win(),
                 Request::PreeditDraw {
                     input_method_id: ic.input_method_id().get(),
                     input_context_id: ic.input_context_id().get(),
-                    chg_first: 0,
-                    chg_length: ic.prev_preedit_length as _,
-                    caret: preedit_length as _,
-                    preedit_string: xim_ctext::utf8_to_compound_text(s),
-                    feedbacks: vec![Feedback::Underline; preedit_length],
-                    status: PreeditDrawStatus::empty(),
+                    chg_first: prefix as _,
+                    chg_length: chg_length as _,
+                    caret: caret.min(preedit.len()) as _,
+                    preedit_string,
+                    feedbacks: changed.iter().map(|(_, f)| *f).collect(),
+                    status,
                 },
             )?;
 
-            ic.prev_preedit_length = preedit_length;
+            ic.prev_preedit = preedit;
         }
 
         Ok(())
@@ -240,13 +621,195 @@ impl<S: ServerCore> Server for S {
                 input_method_id: ic.input_method_id().get(),
                 input_context_id: ic.input_context_id().get(),
                 data: CommitData::Chars {
-                    commited: xim_ctext::utf8_to_compound_text(s),
+                    commited: ic.encoding().encode(s),
+                    syncronous: false,
+                },
+            },
+        )
+    }
+
+    fn commit_keysym(&mut self, ic: &InputContext, keysym: u32) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::Commit {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                data: CommitData::Keysym {
+                    keysym,
+                    syncronous: false,
+                },
+            },
+        )
+    }
+
+    fn commit_both(&mut self, ic: &InputContext, keysym: u32, s: &str) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::Commit {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                data: CommitData::Both {
+                    keysym,
+                    commited: ic.encoding().encode(s),
                     syncronous: false,
                 },
             },
         )
     }
 
+    fn request_string_conversion(
+        &mut self,
+        ic: &InputContext,
+        position: u16,
+        direction: CaretDirection,
+        operation: StrConversionOperation,
+        factor: u16,
+    ) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::StrConversion {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                position,
+                direction,
+                operation,
+                factor,
+                conversion_type: 0,
+            },
+        )
+    }
+
+    fn status_start(&mut self, ic: &mut InputContext) -> Result<(), ServerError> {
+        if !ic.status_started {
+            self.send_req(
+                ic.client_win(),
+                Request::StatusStart {
+                    input_method_id: ic.input_method_id().get(),
+                    input_context_id: ic.input_context_id().get(),
+                },
+            )?;
+            ic.status_started = true;
+        }
+
+        Ok(())
+    }
+
+    fn status_draw(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError> {
+        let status = if s.is_empty() {
+            PreeditDrawStatus::NO_FEEDBACK | PreeditDrawStatus::NO_STRING
+        } else {
+            PreeditDrawStatus::NO_FEEDBACK
+        };
+
+        self.status_start(ic)?;
+        self.send_req(
+            ic.client_win(),
+            Request::StatusDraw {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                content: StatusContent::Text(StatusTextContent {
+                    status,
+                    status_string: ic.encoding().encode(s),
+                    feedbacks: Vec::new(),
+                }),
+            },
+        )
+    }
+
+    fn status_draw_bitmap(
+        &mut self,
+        ic: &mut InputContext,
+        pixmap: u32,
+    ) -> Result<(), ServerError> {
+        self.status_start(ic)?;
+        self.send_req(
+            ic.client_win(),
+            Request::StatusDraw {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                content: StatusContent::Pixmap(pixmap),
+            },
+        )
+    }
+
+    fn status_done(&mut self, ic: &mut InputContext) -> Result<(), ServerError> {
+        if ic.status_started {
+            self.send_req(
+                ic.client_win(),
+                Request::StatusDone {
+                    input_method_id: ic.input_method_id().get(),
+                    input_context_id: ic.input_context_id().get(),
+                },
+            )?;
+            ic.status_started = false;
+        }
+
+        Ok(())
+    }
+
+    fn set_preedit_caret(&mut self, ic: &InputContext, index: usize) -> Result<(), ServerError> {
+        if !ic.preedit_started {
+            return Ok(());
+        }
+
+        self.send_req(
+            ic.client_win(),
+            Request::PreeditDraw {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                chg_first: 0,
+                chg_length: 0,
+                caret: index.min(ic.prev_preedit.len()) as _,
+                preedit_string: Vec::new(),
+                feedbacks: Vec::new(),
+                status: PreeditDrawStatus::NO_FEEDBACK | PreeditDrawStatus::NO_STRING,
+            },
+        )
+    }
+
+    fn set_preedit_state(
+        &mut self,
+        ic: &mut InputContext,
+        enabled: bool,
+    ) -> Result<(), ServerError> {
+        let state = if enabled {
+            PreeditStateFlag::ENABLE
+        } else {
+            PreeditStateFlag::DISABLE
+        };
+
+        ic.attributes_mut()
+            .set_preedit(AttributeName::PreeditState, state);
+
+        self.send_req(
+            ic.client_win(),
+            Request::PreeditState {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                state,
+            },
+        )
+    }
+
+    fn preedit_caret(
+        &mut self,
+        ic: &InputContext,
+        position: i32,
+        direction: CaretDirection,
+        style: CaretStyle,
+    ) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::PreeditCaret {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                position,
+                direction,
+                style,
+            },
+        )
+    }
+
     fn set_event_mask(
         &mut self,
         ic: &InputContext,
@@ -263,11 +826,147 @@ impl<S: ServerCore> Server for S {
             },
         )
     }
+
+    fn commit_sync(&mut self, ic: &mut InputContext, s: &str) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::Commit {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                data: CommitData::Chars {
+                    commited: ic.encoding().encode(s),
+                    syncronous: true,
+                },
+            },
+        )?;
+        ic.waiting_sync = true;
+        Ok(())
+    }
+
+    fn sync(&mut self, ic: &mut InputContext) -> Result<(), ServerError> {
+        self.send_req(
+            ic.client_win(),
+            Request::Sync {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+            },
+        )?;
+        ic.waiting_sync = true;
+        Ok(())
+    }
+
+    fn activate_ic(
+        &mut self,
+        ic: &mut InputContext,
+        forward_event_mask: u32,
+    ) -> Result<(), ServerError> {
+        send_event_mask(self, ic, forward_event_mask)?;
+        ic.active = true;
+        Ok(())
+    }
+
+    fn deactivate_ic(&mut self, ic: &mut InputContext) -> Result<(), ServerError> {
+        send_event_mask(self, ic, 0)?;
+        ic.active = false;
+        Ok(())
+    }
+
+    fn ext_forward_key_event(
+        &mut self,
+        ic: &InputContext,
+        xev: &Self::XEvent,
+    ) -> Result<(), ServerError> {
+        let xev = self.serialize_event(xev);
+        let input_method_id = ic.input_method_id().get();
+        let input_context_id = ic.input_context_id().get();
+
+        let req = if ic.ext_forward_keyevent {
+            Request::ExtForwardKeyEvent {
+                input_method_id,
+                input_context_id,
+                flag: ForwardEventFlag::empty(),
+                sequence_number: xev.sequence,
+                // without the bit of `SendEvent`
+                xev_type: xev.response_type & 0x7f,
+                keycode: xev.detail,
+                state: xev.state,
+                time: xev.time,
+                window: xev.event,
+            }
+        } else {
+            Request::ForwardEvent {
+                input_method_id,
+                input_context_id,
+                serial_number: 0,
+                flag: ForwardEventFlag::empty(),
+                xev,
+            }
+        };
+
+        self.send_req(ic.client_win(), req)
+    }
+
+    fn forward_event(
+        &mut self,
+        ic: &mut InputContext,
+        xev: &Self::XEvent,
+        flag: ForwardEventFlag,
+    ) -> Result<(), ServerError> {
+        let xev = self.serialize_event(xev);
+
+        self.send_req(
+            ic.client_win(),
+            Request::ForwardEvent {
+                input_method_id: ic.input_method_id().get(),
+                input_context_id: ic.input_context_id().get(),
+                serial_number: 0,
+                flag,
+                xev,
+            },
+        )?;
+
+        if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
+            ic.waiting_sync = true;
+        }
+
+        Ok(())
+    }
+}
+
+fn send_event_mask<S: ServerCore>(
+    server: &mut S,
+    ic: &InputContext,
+    mask: u32,
+) -> Result<(), ServerError> {
+    let input_method_id = ic.input_method_id().get();
+    let input_context_id = ic.input_context_id().get();
+
+    let req = if ic.ext_set_event_mask {
+        Request::ExtSetEventMask {
+            input_method_id,
+            input_context_id,
+            filter_event_mask: mask,
+            intercept_event_mask: 0,
+            select_event_mask: mask,
+            forward_event_mask: mask,
+            synchronous_event_mask: 0,
+        }
+    } else {
+        Request::SetEventMask {
+            input_method_id,
+            input_context_id,
+            forward_event_mask: mask,
+            synchronous_event_mask: 0,
+        }
+    };
+
+    server.send_req(ic.client_win(), req)
 }
 
 pub trait ServerCore {
     type XEvent;
 
+    fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent;
     fn deserialize_event(&self, ev: &xim_parser::XEvent) -> Self::XEvent;
     fn send_req(&mut self, client_win: u32, req: Request) -> Result<(), ServerError>;
 }
@@ -0,0 +1,375 @@
+use crate::AHashMap;
+use alloc::vec::Vec;
+use core::num::NonZeroU32;
+use xim_parser::{
+    attrs, Attribute, AttributeName, Endian, InputStyle, Point, PreeditStateFlag, Rectangle,
+    XimRead, XimWrite,
+};
+
+/// Attributes of an input context set by `CreateIc` and `SetIcValues`.
+///
+/// Values are kept as they're encoded by the client, and the ones this crate understands are
+/// checked to be decodable before they're stored. Attributes in `PreeditAttributes` and
+/// `StatusAttributes` are kept apart from top level ones. Values are read and written in the
+/// byte order of the client.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeStore {
+    values: AHashMap<AttributeName, Vec<u8>>,
+    preedit: AHashMap<AttributeName, Vec<u8>>,
+    status: AHashMap<AttributeName, Vec<u8>>,
+    endian: Endian,
+}
+
+/// Return `false` when `value` can't be decoded as the type of `name`
+fn is_valid(name: AttributeName, value: &[u8], endian: Endian) -> bool {
+    fn check<T: XimRead>(value: &[u8], endian: Endian) -> bool {
+        xim_parser::read_with_endian::<T>(value, endian).is_ok()
+    }
+
+    match name {
+        AttributeName::InputStyle => check::<InputStyle>(value, endian),
+        AttributeName::ClientWindow
+        | AttributeName::FocusWindow
+        | AttributeName::Foreground
+        | AttributeName::Background
+        | AttributeName::LineSpace
+        | AttributeName::FilterEvents => check::<u32>(value, endian),
+        AttributeName::SpotLocation => check::<Point>(value, endian),
+        AttributeName::Area | AttributeName::AreaNeeded => check::<Rectangle>(value, endian),
+        AttributeName::PreeditState => check::<PreeditStateFlag>(value, endian),
+        _ => true,
+    }
+}
+
+/// Attributes in the nested list `value`
+fn read_nested_list(value: &[u8], endian: Endian) -> impl Iterator<Item = Attribute> + '_ {
+    let mut b = value;
+
+    core::iter::from_fn(move || {
+        if b.is_empty() {
+            return None;
+        }
+
+        match xim_parser::read_with_endian::<Attribute>(b, endian) {
+            Ok(attr) => {
+                b = &b[attr.size().min(b.len())..];
+                Some(attr)
+            }
+            Err(_) => {
+                log::warn!("Invalid nested list");
+                None
+            }
+        }
+    })
+}
+
+/// Store `value` unless it's invalid, the change is returned when it differs from the old value
+fn insert(
+    map: &mut AHashMap<AttributeName, Vec<u8>>,
+    list: AttributeList,
+    name: AttributeName,
+    value: Vec<u8>,
+    endian: Endian,
+) -> Option<AttributeChange> {
+    if !is_valid(name, &value, endian) {
+        log::warn!("Ignore invalid value of {:?}: {:?}", name, value);
+        return None;
+    }
+
+    if map.get(&name) == Some(&value) {
+        return None;
+    }
+
+    let new = value.clone();
+    let old = map.insert(name, value);
+
+    Some(AttributeChange {
+        list,
+        name,
+        old,
+        new,
+        endian,
+    })
+}
+
+/// List of an attribute in [`AttributeStore`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum AttributeList {
+    TopLevel,
+    /// `PreeditAttributes`
+    Preedit,
+    /// `StatusAttributes`
+    Status,
+}
+
+/// Value of an attribute changed by `SetIcValues`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttributeChange {
+    list: AttributeList,
+    name: AttributeName,
+    old: Option<Vec<u8>>,
+    new: Vec<u8>,
+    endian: Endian,
+}
+
+impl AttributeChange {
+    pub fn list(&self) -> AttributeList {
+        self.list
+    }
+
+    pub fn name(&self) -> AttributeName {
+        self.name
+    }
+
+    /// Encoded value before the change, `None` when it wasn't set
+    pub fn old_value(&self) -> Option<&[u8]> {
+        self.old.as_deref()
+    }
+
+    /// Encoded value after the change
+    pub fn new_value(&self) -> &[u8] {
+        &self.new
+    }
+
+    /// Decode the old value as `T`
+    pub fn old_as<T: XimRead>(&self) -> Option<T> {
+        xim_parser::read_with_endian(self.old_value()?, self.endian).ok()
+    }
+
+    /// Decode the new value as `T`
+    pub fn new_as<T: XimRead>(&self) -> Option<T> {
+        xim_parser::read_with_endian(self.new_value(), self.endian).ok()
+    }
+}
+
+/// Attributes changed by `SetIcValues`, values the same as the stored ones are not included.
+///
+/// ```ignore
+/// if let Some(spot) = changes.preedit(AttributeName::SpotLocation) {
+///     let spot: Point = spot.new_as().unwrap();
+///     move_candidate_window(spot);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AttributeChanges {
+    changes: Vec<AttributeChange>,
+}
+
+impl AttributeChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AttributeChange> + '_ {
+        self.changes.iter()
+    }
+
+    /// Change of a top level attribute
+    pub fn get(&self, name: AttributeName) -> Option<&AttributeChange> {
+        self.find(AttributeList::TopLevel, name)
+    }
+
+    /// Change of an attribute in `PreeditAttributes`
+    pub fn preedit(&self, name: AttributeName) -> Option<&AttributeChange> {
+        self.find(AttributeList::Preedit, name)
+    }
+
+    /// Change of an attribute in `StatusAttributes`
+    pub fn status(&self, name: AttributeName) -> Option<&AttributeChange> {
+        self.find(AttributeList::Status, name)
+    }
+
+    /// Last change of `name` in `list`, a request may set an attribute more than once
+    fn find(&self, list: AttributeList, name: AttributeName) -> Option<&AttributeChange> {
+        self.changes
+            .iter()
+            .rev()
+            .find(|change| change.list == list && change.name == name)
+    }
+}
+
+impl AttributeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store for a client of the byte order `endian`
+    pub fn with_endian(endian: Endian) -> Self {
+        Self {
+            endian,
+            ..Self::default()
+        }
+    }
+
+    /// Byte order of the encoded values
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Encoded value of a top level attribute
+    pub fn get(&self, name: AttributeName) -> Option<&[u8]> {
+        self.values.get(&name).map(Vec::as_slice)
+    }
+
+    /// Encoded value of an attribute in `PreeditAttributes`
+    pub fn preedit(&self, name: AttributeName) -> Option<&[u8]> {
+        self.preedit.get(&name).map(Vec::as_slice)
+    }
+
+    /// Encoded value of an attribute in `StatusAttributes`
+    pub fn status(&self, name: AttributeName) -> Option<&[u8]> {
+        self.status.get(&name).map(Vec::as_slice)
+    }
+
+    /// Decode a top level attribute as `T`
+    pub fn get_as<T: XimRead>(&self, name: AttributeName) -> Option<T> {
+        xim_parser::read_with_endian(self.get(name)?, self.endian).ok()
+    }
+
+    /// Decode an attribute in `PreeditAttributes` as `T`
+    pub fn preedit_as<T: XimRead>(&self, name: AttributeName) -> Option<T> {
+        xim_parser::read_with_endian(self.preedit(name)?, self.endian).ok()
+    }
+
+    /// Decode an attribute in `StatusAttributes` as `T`
+    pub fn status_as<T: XimRead>(&self, name: AttributeName) -> Option<T> {
+        xim_parser::read_with_endian(self.status(name)?, self.endian).ok()
+    }
+
+    pub fn input_style(&self) -> InputStyle {
+        self.get_as(AttributeName::InputStyle)
+            .unwrap_or_else(InputStyle::empty)
+    }
+
+    pub fn client_window(&self) -> Option<NonZeroU32> {
+        self.get_as(AttributeName::ClientWindow)
+            .and_then(NonZeroU32::new)
+    }
+
+    pub fn focus_window(&self) -> Option<NonZeroU32> {
+        self.get_as(AttributeName::FocusWindow)
+            .and_then(NonZeroU32::new)
+    }
+
+    pub fn spot_location(&self) -> Option<Point> {
+        self.preedit_as(AttributeName::SpotLocation)
+    }
+
+    pub fn preedit_area(&self) -> Option<Rectangle> {
+        self.preedit_as(AttributeName::Area)
+    }
+
+    pub fn status_area(&self) -> Option<Rectangle> {
+        self.status_as(AttributeName::Area)
+    }
+
+    /// Whether the preedit is enabled, set by the client or [`Server::set_preedit_state`]
+    ///
+    /// [`Server::set_preedit_state`]: crate::Server::set_preedit_state
+    pub fn preedit_state(&self) -> Option<PreeditStateFlag> {
+        self.preedit_as(AttributeName::PreeditState)
+    }
+
+    pub fn set<V: XimWrite>(&mut self, name: AttributeName, value: V) {
+        let value = xim_parser::write_to_vec_with_endian(value, self.endian);
+        self.values.insert(name, value);
+    }
+
+    pub fn set_preedit<V: XimWrite>(&mut self, name: AttributeName, value: V) {
+        let value = xim_parser::write_to_vec_with_endian(value, self.endian);
+        self.preedit.insert(name, value);
+    }
+
+    pub fn set_status<V: XimWrite>(&mut self, name: AttributeName, value: V) {
+        let value = xim_parser::write_to_vec_with_endian(value, self.endian);
+        self.status.insert(name, value);
+    }
+
+    /// Store attributes of `CreateIc` or `SetIcValues` and return the changed ones
+    pub(crate) fn apply(&mut self, attributes: Vec<Attribute>) -> AttributeChanges {
+        let mut changes = AttributeChanges::new();
+
+        for attr in attributes {
+            let name = match attrs::get_name(attr.id) {
+                Some(name) => name,
+                None => {
+                    log::warn!("Unknown attr id: {}", attr.id);
+                    continue;
+                }
+            };
+
+            let (list, nested) = match name {
+                AttributeName::PreeditAttributes => (AttributeList::Preedit, &mut self.preedit),
+                AttributeName::StatusAttributes => (AttributeList::Status, &mut self.status),
+                name => {
+                    changes.changes.extend(insert(
+                        &mut self.values,
+                        AttributeList::TopLevel,
+                        name,
+                        attr.value,
+                        self.endian,
+                    ));
+                    continue;
+                }
+            };
+
+            for attr in read_nested_list(&attr.value, self.endian) {
+                match attrs::get_name(attr.id) {
+                    Some(name) => {
+                        changes
+                            .changes
+                            .extend(insert(nested, list, name, attr.value, self.endian))
+                    }
+                    None => log::warn!("Unknown nested attr id: {}", attr.id),
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Encode a top level attribute for `GetIcValues`
+    pub(crate) fn encode(&self, name: AttributeName) -> Option<Attribute> {
+        Some(Attribute {
+            id: attrs::get_id(name),
+            value: self.values.get(&name)?.clone(),
+        })
+    }
+
+    /// Encode `names` in `PreeditAttributes` or `StatusAttributes` for `GetIcValues`
+    pub(crate) fn encode_nested(
+        &self,
+        list: AttributeName,
+        names: impl Iterator<Item = AttributeName>,
+    ) -> Attribute {
+        let values = if list == AttributeName::PreeditAttributes {
+            &self.preedit
+        } else {
+            &self.status
+        };
+        let mut value = Vec::new();
+
+        for name in names {
+            match values.get(&name) {
+                Some(v) => xim_parser::write_extend_vec_with_endian(
+                    Attribute {
+                        id: attrs::get_id(name),
+                        value: v.clone(),
+                    },
+                    &mut value,
+                    self.endian,
+                ),
+                None => log::warn!("{:?} of {:?} is not set", name, list),
+            }
+        }
+
+        Attribute {
+            id: attrs::get_id(list),
+            value,
+        }
+    }
+}
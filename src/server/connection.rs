@@ -1,29 +1,45 @@
 mod im_vec;
 
 use crate::AHashMap;
+use alloc::collections::VecDeque;
+use alloc::format;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::num::{NonZeroU16, NonZeroU32};
 use xim_parser::{
-    attrs, Attribute, AttributeName, ErrorCode, ForwardEventFlag, InputStyle, InputStyleList,
-    Point, Request, XimWrite,
+    attrs, Attribute, AttributeName, Endian, ErrorCode, Extension, Feedback, ForwardEventFlag,
+    InputStyle, InputStyleList, Point, Request, TriggerNotifyFlag,
 };
 
 use self::im_vec::ImVec;
-use crate::server::{Server, ServerCore, ServerError, ServerHandler};
+use crate::encoding::Encoding;
+use crate::server::{
+    AttributeStore, AuthStep, Server, ServerCore, ServerError, ServerHandler, WireTap,
+};
+use crate::transport::MessageBuffer;
+
+const EXT_FORWARD_KEYEVENT: &str = "XIM_EXT_FORWARD_KEYEVENT";
+const EXT_SET_EVENT_MASK: &str = "XIM_EXT_SET_EVENT_MASK";
 
 pub struct InputContext {
     client_win: u32,
-    app_win: Option<NonZeroU32>,
-    app_focus_win: Option<NonZeroU32>,
     input_method_id: NonZeroU16,
     input_context_id: NonZeroU16,
-    input_style: InputStyle,
-    preedit_spot: Point,
     pub(super) preedit_started: bool,
-    pub(super) prev_preedit_length: usize,
+    pub(super) status_started: bool,
+    /// Characters and feedbacks of the preedit the client is drawing
+    pub(super) prev_preedit: Vec<(char, Feedback)>,
+    pub(super) ext_forward_keyevent: bool,
+    pub(super) ext_set_event_mask: bool,
+    pub(super) active: bool,
+    pub(super) waiting_sync: bool,
+    /// Events forwarded while waiting `SyncReply`
+    queued_events: VecDeque<(ForwardEventFlag, xim_parser::XEvent)>,
+    attributes: AttributeStore,
     locale: String,
+    encoding: Encoding,
+    client_version: (u16, u16),
 }
 
 impl InputContext {
@@ -35,15 +51,20 @@ impl InputContext {
     ) -> Self {
         Self {
             client_win,
-            app_win: None,
-            app_focus_win: None,
             input_method_id,
             input_context_id,
-            input_style: InputStyle::empty(),
-            preedit_spot: Point { x: 0, y: 0 },
             preedit_started: false,
-            prev_preedit_length: 0,
+            status_started: false,
+            prev_preedit: Vec::new(),
+            ext_forward_keyevent: false,
+            ext_set_event_mask: false,
+            active: true,
+            waiting_sync: false,
+            queued_events: VecDeque::new(),
+            attributes: AttributeStore::new(),
             locale,
+            encoding: Encoding::default(),
+            client_version: (1, 0),
         }
     }
 
@@ -52,15 +73,17 @@ impl InputContext {
     }
 
     pub fn app_win(&self) -> Option<NonZeroU32> {
-        self.app_win
+        self.attributes.client_window()
     }
 
     pub fn app_focus_win(&self) -> Option<NonZeroU32> {
-        self.app_focus_win
+        self.attributes.focus_window()
     }
 
     pub fn preedit_spot(&self) -> Point {
-        self.preedit_spot.clone()
+        self.attributes
+            .spot_location()
+            .unwrap_or(Point { x: 0, y: 0 })
     }
 
     pub fn input_method_id(&self) -> NonZeroU16 {
@@ -72,12 +95,49 @@ impl InputContext {
     }
 
     pub fn input_style(&self) -> InputStyle {
-        self.input_style
+        self.attributes.input_style()
+    }
+
+    /// Whether the client forwards key events, it's always `true` unless trigger keys are
+    /// registered
+    pub fn is_active(&self) -> bool {
+        self.active
     }
 
     pub fn locale(&self) -> &str {
         self.locale.as_str()
     }
+
+    /// `(major, minor)` protocol version of the client which created the input context
+    pub fn client_protocol_version(&self) -> (u16, u16) {
+        self.client_version
+    }
+
+    /// Encoding of strings sent to the client, it's negotiated by the input method
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Whether `SyncReply` of [`Server::sync`], [`Server::commit_sync`] or a synchronous
+    /// [`Server::forward_event`] is not received yet, events forwarded by the client are queued
+    /// until then
+    pub fn is_waiting_sync(&self) -> bool {
+        self.waiting_sync
+    }
+
+    /// Number of events forwarded while waiting `SyncReply`
+    pub fn queued_events(&self) -> usize {
+        self.queued_events.len()
+    }
+
+    /// Every attribute set by the client
+    pub fn attributes(&self) -> &AttributeStore {
+        &self.attributes
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut AttributeStore {
+        &mut self.attributes
+    }
 }
 
 pub struct UserInputContext<T> {
@@ -91,62 +151,14 @@ impl<T> UserInputContext<T> {
     }
 }
 
-fn set_ic_attrs(ic: &mut InputContext, ic_attributes: Vec<Attribute>) {
-    for attr in ic_attributes {
-        let name = if let Some(name) = attrs::get_name(attr.id) {
-            name
-        } else {
-            log::warn!("Unknown attr id: {}", attr.id);
-            continue;
-        };
-
-        match name {
-            AttributeName::InputStyle => {
-                if let Ok(style) = xim_parser::read(&attr.value) {
-                    log::debug!("Style: {:?}", style);
-                    ic.input_style = style;
-                }
-            }
-            AttributeName::ClientWindow => {
-                ic.app_win = xim_parser::read(&attr.value).ok().and_then(NonZeroU32::new);
-            }
-            AttributeName::FocusWindow => {
-                ic.app_focus_win = xim_parser::read(&attr.value).ok().and_then(NonZeroU32::new);
-            }
-            AttributeName::PreeditAttributes => {
-                let mut b = &attr.value[..];
-                while !b.is_empty() {
-                    match xim_parser::read::<Attribute>(b) {
-                        Ok(attr) => {
-                            b = &b[attr.size()..];
-                            match attrs::get_name(attr.id) {
-                                Some(AttributeName::SpotLocation) => {
-                                    if let Ok(spot) = xim_parser::read(&attr.value) {
-                                        log::debug!("Spot: {:?}", spot);
-                                        ic.preedit_spot = spot;
-                                    }
-                                }
-                                name => {
-                                    log::warn!("Ignore unhandled preedit attr: {:?}", name);
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            break;
-                        }
-                    }
-                }
-            }
-            name => {
-                log::warn!("Ignore unhandled attr: {:?}", name);
-            }
-        }
-    }
-}
-
 pub struct InputMethod<T> {
     pub(crate) locale: String,
     pub(crate) input_contexts: ImVec<UserInputContext<T>>,
+    pub(crate) extensions: Vec<Extension>,
+    pub(crate) attributes: AHashMap<AttributeName, Vec<u8>>,
+    /// Trigger keys are registered, input contexts start inactive
+    pub(crate) dynamic_event_flow: bool,
+    pub(crate) encoding: Encoding,
 }
 
 impl<T> InputMethod<T> {
@@ -154,6 +166,10 @@ impl<T> InputMethod<T> {
         Self {
             locale,
             input_contexts: ImVec::new(),
+            extensions: Vec::new(),
+            attributes: AHashMap::with_hasher(Default::default()),
+            dynamic_event_flow: false,
+            encoding: Encoding::default(),
         }
     }
 
@@ -161,6 +177,28 @@ impl<T> InputMethod<T> {
         self.locale.clone()
     }
 
+    /// IM attributes set by `SetImValues`
+    pub fn attributes(&self) -> &AHashMap<AttributeName, Vec<u8>> {
+        &self.attributes
+    }
+
+    /// Encoding negotiated by `EncodingNegotiation`, `COMPOUND_TEXT` until then
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Extensions negotiated by `QueryExtension`
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Whether `name` is negotiated with the opcodes of the parser
+    fn has_extension(&self, name: &str, minor_opcode: u8) -> bool {
+        self.extensions.iter().any(|ext| {
+            ext.name == name && (ext.major_opcode, ext.minor_opcode) == (128, minor_opcode)
+        })
+    }
+
     pub fn new_ic(&mut self, ic: UserInputContext<T>) -> (NonZeroU16, &mut UserInputContext<T>) {
         self.input_contexts.new_item(ic)
     }
@@ -185,6 +223,19 @@ pub struct XimConnection<T> {
     pub(crate) client_win: u32,
     pub(crate) disconnected: bool,
     pub(crate) input_methods: ImVec<InputMethod<T>>,
+    pub(crate) message_buffer: MessageBuffer,
+    pub(crate) client_version: (u16, u16),
+    endian: Endian,
+    /// `AuthRequired` is sent and `ConnectReply` is not yet
+    authenticating: bool,
+    pub(crate) limits: ConnectionLimits,
+    /// `(input_method_id, input_context_id)` of the input context which has focus
+    focused: Option<(u16, u16)>,
+    /// `SetIcFocus` is received since [`XimConnections::update_focus`]
+    focus_gained: bool,
+    /// Parent of the spans of requests from the client
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl<T> XimConnection<T> {
@@ -193,7 +244,98 @@ impl<T> XimConnection<T> {
             client_win,
             disconnected: false,
             input_methods: ImVec::new(),
+            message_buffer: MessageBuffer::new(),
+            client_version: (1, 0),
+            endian: Endian::Native,
+            authenticating: false,
+            limits: ConnectionLimits::default(),
+            focused: None,
+            focus_gained: false,
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("xim_connection", client_win),
+        }
+    }
+
+    pub fn client_win(&self) -> u32 {
+        self.client_win
+    }
+
+    /// Input context of the last `SetIcFocus` unless it's unfocused or destroyed
+    pub fn focused_ic_mut(&mut self) -> Option<&mut UserInputContext<T>> {
+        let (input_method_id, input_context_id) = self.focused?;
+        self.get_input_method(input_method_id)
+            .ok()?
+            .get_input_context(input_context_id)
+            .ok()
+    }
+
+    pub fn input_methods(&self) -> impl Iterator<Item = &InputMethod<T>> + '_ {
+        self.input_methods.values()
+    }
+
+    pub fn input_methods_mut(&mut self) -> impl Iterator<Item = &mut InputMethod<T>> + '_ {
+        self.input_methods.values_mut()
+    }
+
+    /// Input contexts of every input method
+    pub fn iter_ics(&self) -> impl Iterator<Item = &UserInputContext<T>> + '_ {
+        self.input_methods()
+            .flat_map(|im| im.input_contexts.values())
+    }
+
+    /// Input contexts of every input method
+    pub fn iter_ics_mut(&mut self) -> impl Iterator<Item = &mut UserInputContext<T>> + '_ {
+        self.input_methods_mut()
+            .flat_map(|im| im.input_contexts.values_mut())
+    }
+
+    /// Byte order of the client declared by `Connect`, requests are read and written with it
+    pub fn client_endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Read a request with the byte order of the client, `data` is of the final `_XIM_PROTOCOL`
+    /// message
+    pub(crate) fn read_request(
+        &mut self,
+        data: &[u8],
+        tap: Option<&mut (dyn WireTap + '_)>,
+    ) -> Result<Request, ServerError> {
+        if let Some(tap) = tap {
+            if self.message_buffer.is_empty() {
+                tap.on_receive(self.client_win, data);
+            } else {
+                let mut message = self.message_buffer.buffered().to_vec();
+                message.extend_from_slice(data);
+                tap.on_receive(self.client_win, &message);
+            }
         }
+
+        // `Connect` starts with its opcode and the endian is the first byte of the body, it may
+        // be split into `_XIM_MOREDATA` messages
+        let header = if self.message_buffer.is_empty() {
+            data
+        } else {
+            self.message_buffer.buffered()
+        };
+        if header.first() == Some(&1) {
+            if let Some(endian) = header.get(4).copied().and_then(Endian::from_u8) {
+                self.endian = endian;
+            }
+        }
+
+        Ok(self.message_buffer.read(data, self.endian)?)
+    }
+
+    /// `(major, minor)` protocol version of the client sent by `Connect`, `(1, 0)` until then
+    pub fn client_protocol_version(&self) -> (u16, u16) {
+        self.client_version
+    }
+
+    /// Extensions are defined by protocol version 1.x, clients of an older or unknown major
+    /// version don't get any, including `XIM_EXT_FORWARD_KEYEVENT` and `XIM_EXT_SET_EVENT_MASK`
+    fn supports_extensions(&self) -> bool {
+        self.client_version.0 == 1
     }
 
     pub fn disconnect<S: ServerCore + Server, H: ServerHandler<S, InputContextData = T>>(
@@ -201,6 +343,9 @@ impl<T> XimConnection<T> {
         server: &mut S,
         handler: &mut H,
     ) -> Result<(), ServerError> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
         for (_id, im) in self.input_methods.drain() {
             for (_id, ic) in im.input_contexts {
                 handler.handle_destroy_ic(server, ic)?;
@@ -208,16 +353,123 @@ impl<T> XimConnection<T> {
         }
 
         self.disconnected = true;
+        self.focused = None;
 
         Ok(())
     }
 
+    /// Destroy an input context without a request of the client, e.g. when its engine failed.
+    ///
+    /// The client is told with an error of `code` since the protocol has no message for it, then
+    /// [`ServerHandler::handle_destroy_ic`] is called.
+    pub fn destroy_ic<S: ServerCore + Server, H: ServerHandler<S, InputContextData = T>>(
+        &mut self,
+        server: &mut S,
+        handler: &mut H,
+        input_method_id: NonZeroU16,
+        input_context_id: NonZeroU16,
+        code: ErrorCode,
+        detail: String,
+    ) -> Result<(), ServerError> {
+        if self.focused == Some((input_method_id.get(), input_context_id.get())) {
+            self.focused = None;
+        }
+
+        let user_ic = self
+            .get_input_method(input_method_id.get())?
+            .remove_input_context(input_context_id.get())?;
+
+        if user_ic.ic.preedit_started {
+            server.send_req(
+                self.client_win,
+                Request::PreeditDone {
+                    input_method_id: input_method_id.get(),
+                    input_context_id: input_context_id.get(),
+                },
+            )?;
+        }
+
+        server.error(
+            self.client_win,
+            code,
+            detail,
+            Some(input_method_id),
+            Some(input_context_id),
+        )?;
+
+        handler.handle_destroy_ic(server, user_ic)
+    }
+
+    fn accept_connect<S: ServerCore, H: ServerHandler<S, InputContextData = T>>(
+        &mut self,
+        server: &mut S,
+        handler: &mut H,
+    ) -> Result<(), ServerError> {
+        self.authenticating = false;
+        server.send_req(
+            self.client_win,
+            Request::ConnectReply {
+                server_major_protocol_version: 1,
+                server_minor_protocol_version: 0,
+            },
+        )?;
+        handler.handle_connect(server)
+    }
+
     fn get_input_method(&mut self, id: u16) -> Result<&mut InputMethod<T>, ServerError> {
         self.input_methods
             .get_item(id)
             .ok_or(ServerError::ClientNotExists)
     }
 
+    /// Pass a key event of the client to the handler, send it back when it's not consumed
+    fn forward_event<S: ServerCore, H: ServerHandler<S, InputContextData = T>>(
+        &mut self,
+        server: &mut S,
+        handler: &mut H,
+        input_method_id: u16,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        xev: xim_parser::XEvent,
+    ) -> Result<(), ServerError> {
+        let input_context = self
+            .get_input_method(input_method_id)?
+            .get_input_context(input_context_id)?;
+
+        if input_context.ic.waiting_sync {
+            input_context.ic.queued_events.push_back((flag, xev));
+            return Ok(());
+        }
+
+        let ev = server.deserialize_event(&xev);
+        let consumed = handler.handle_forward_event(server, input_context, &ev)?;
+
+        if !consumed {
+            server.send_req(
+                self.client_win,
+                Request::ForwardEvent {
+                    input_method_id,
+                    input_context_id,
+                    serial_number: 0,
+                    flag: ForwardEventFlag::empty(),
+                    xev,
+                },
+            )?;
+        }
+
+        if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
+            server.send_req(
+                self.client_win,
+                Request::SyncReply {
+                    input_method_id,
+                    input_context_id,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn remove_input_method(&mut self, id: u16) -> Result<InputMethod<T>, ServerError> {
         self.input_methods
             .remove_item(id)
@@ -230,12 +482,57 @@ impl<T> XimConnection<T> {
         req: Request,
         handler: &mut H,
     ) -> Result<(), ServerError> {
-        if log::log_enabled!(log::Level::Trace) {
-            log::trace!("<-: {:?}", req);
-        } else {
-            log::debug!("<-: {}", req.name());
+        // replies and errors sent while handling it are in the span of the request
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            parent: &self.span,
+            "xim_request",
+            request = req.name(),
+            input_method_id = req.input_method_id(),
+            input_context_id = req.input_context_id(),
+            error = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let input_method_id = req.input_method_id().and_then(NonZeroU16::new);
+        let input_context_id = req.input_context_id().and_then(NonZeroU16::new);
+        let ret = self.dispatch_request(server, req, handler);
+
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &ret {
+            span.record("error", tracing::field::display(e));
         }
 
+        match ret {
+            // ids are sent by the client so it's the client's fault, keep serving the others
+            Err(ServerError::ClientNotExists) => {
+                log::warn!(
+                    "Unknown input method {:?} or input context {:?} of {}",
+                    input_method_id,
+                    input_context_id,
+                    self.client_win
+                );
+                server.error(
+                    self.client_win,
+                    ErrorCode::BadSomething,
+                    "Unknown input method or input context".into(),
+                    input_method_id,
+                    input_context_id,
+                )
+            }
+            ret => ret,
+        }
+    }
+
+    fn dispatch_request<S: ServerCore, H: ServerHandler<S, InputContextData = T>>(
+        &mut self,
+        server: &mut S,
+        req: Request,
+        handler: &mut H,
+    ) -> Result<(), ServerError> {
+        crate::trace::request_received(&req);
+
         match req {
             Request::Error {
                 code,
@@ -249,15 +546,58 @@ impl<T> XimConnection<T> {
                 log::error!("XIM ERROR! code: {:?}, detail: {}", code, detail);
             }
 
-            Request::Connect { .. } => {
-                server.send_req(
-                    self.client_win,
-                    Request::ConnectReply {
-                        server_major_protocol_version: 1,
-                        server_minor_protocol_version: 0,
-                    },
-                )?;
-                handler.handle_connect(server)?;
+            Request::Connect {
+                client_major_protocol_version,
+                client_minor_protocol_version,
+                client_auth_protocol_names,
+                ..
+            } => {
+                self.client_version =
+                    (client_major_protocol_version, client_minor_protocol_version);
+                log::debug!("Client protocol version: {:?}", self.client_version);
+
+                // the connection is accepted without authentication unless the handler asks one
+                match handler.auth_required(server, &client_auth_protocol_names)? {
+                    Some((auth_protocol_index, auth_data)) => {
+                        self.authenticating = true;
+                        server.send_req(
+                            self.client_win,
+                            Request::AuthRequired {
+                                auth_protocol_index,
+                                auth_data,
+                            },
+                        )?;
+                    }
+                    None => self.accept_connect(server, handler)?,
+                }
+            }
+
+            Request::AuthReply { auth_data } => {
+                if !self.authenticating {
+                    log::warn!("AuthReply without AuthRequired");
+                    server.send_req(self.client_win, Request::AuthNg {})?;
+                    return Ok(());
+                }
+
+                match handler.handle_auth_reply(server, &auth_data)? {
+                    AuthStep::Accept => self.accept_connect(server, handler)?,
+                    AuthStep::Next(auth_data) => {
+                        server.send_req(self.client_win, Request::AuthNext { auth_data })?;
+                    }
+                    AuthStep::Reject => {
+                        server.send_req(self.client_win, Request::AuthNg {})?;
+                        self.authenticating = false;
+                        self.disconnected = true;
+                    }
+                }
+            }
+
+            Request::AuthNg {} => {
+                // the client gave up the authentication
+                if self.authenticating {
+                    self.authenticating = false;
+                    self.disconnected = true;
+                }
             }
 
             Request::Disconnect {} => {
@@ -266,13 +606,50 @@ impl<T> XimConnection<T> {
             }
 
             Request::Open { locale } => {
-                let (input_method_id, _im) = self.input_methods.new_item(InputMethod::new(locale));
+                if self.input_methods.len() >= self.limits.max_input_methods {
+                    log::warn!("Too many input methods for {}", self.client_win);
+                    return server.error(
+                        self.client_win,
+                        ErrorCode::BadAlloc,
+                        "Too many input methods".into(),
+                        None,
+                        None,
+                    );
+                }
+
+                if !handler.accept_locale(&locale) {
+                    log::warn!("Unsupported locale {}", locale);
+                    return server.error(
+                        self.client_win,
+                        ErrorCode::LocaleNotSupported,
+                        format!("Locale {} is not supported", locale),
+                        None,
+                        None,
+                    );
+                }
+
+                let (input_method_id, im) = self.input_methods.new_item(InputMethod::new(locale));
+                let on_keys = handler.trigger_on_keys();
+                let off_keys = handler.trigger_off_keys();
+
+                // must be sent before `OpenReply` for the dynamic event flow
+                if !on_keys.is_empty() || !off_keys.is_empty() {
+                    im.dynamic_event_flow = true;
+                    server.send_req(
+                        self.client_win,
+                        Request::RegisterTriggerKeys {
+                            input_method_id: input_method_id.get(),
+                            on_keys,
+                            off_keys,
+                        },
+                    )?;
+                }
 
                 server.send_req(
                     self.client_win,
                     Request::OpenReply {
                         input_method_id: input_method_id.get(),
-                        im_attrs: vec![attrs::QUERY_INPUT_STYLE],
+                        im_attrs: handler.im_attributes(),
                         ic_attrs: vec![
                             attrs::INPUT_STYLE,
                             attrs::CLIENTWIN,
@@ -290,6 +667,7 @@ impl<T> XimConnection<T> {
                             attrs::BACKGROUND_PIXMAP,
                             attrs::SPOT_LOCATION,
                             attrs::LINE_SPACE,
+                            attrs::PREEDIT_STATE,
                             attrs::SEPARATOR_OF_NESTED_LIST,
                         ],
                     },
@@ -301,15 +679,64 @@ impl<T> XimConnection<T> {
                 ic_attributes,
             } => {
                 let client_win = self.client_win;
+                let client_version = self.client_version;
+                let endian = self.endian;
+                let max_input_contexts = self.limits.max_input_contexts;
                 let im = self.get_input_method(input_method_id)?;
+
+                if im.input_contexts.len() >= max_input_contexts {
+                    log::warn!("Too many input contexts for {}", client_win);
+                    return server.error(
+                        client_win,
+                        ErrorCode::BadAlloc,
+                        "Too many input contexts".into(),
+                        NonZeroU16::new(input_method_id),
+                        None,
+                    );
+                }
                 let mut ic = InputContext::new(
                     client_win,
                     NonZeroU16::new(input_method_id).unwrap(),
                     NonZeroU16::new(1).unwrap(),
                     im.clone_locale(),
                 );
-                set_ic_attrs(&mut ic, ic_attributes);
-                let input_style = ic.input_style;
+                ic.attributes = AttributeStore::with_endian(endian);
+                ic.attributes.apply(ic_attributes);
+                ic.encoding = im.encoding;
+                ic.client_version = client_version;
+                ic.ext_forward_keyevent = im.has_extension(EXT_FORWARD_KEYEVENT, 50);
+                ic.ext_set_event_mask = im.has_extension(EXT_SET_EVENT_MASK, 48);
+                ic.active = !im.dynamic_event_flow;
+
+                match handler.before_create_ic(server, &mut ic.attributes) {
+                    Ok(()) => {}
+                    Err(ServerError::XimError(code, detail)) => {
+                        log::warn!("CreateIc is rejected: {:?} {}", code, detail);
+                        return server.error(
+                            client_win,
+                            code,
+                            detail,
+                            NonZeroU16::new(input_method_id),
+                            None,
+                        );
+                    }
+                    Err(e) => return Err(e),
+                }
+
+                let input_style = match handler.resolve_input_style(ic.input_style()) {
+                    Some(style) => style,
+                    None => {
+                        log::warn!("Unsupported input style: {:?}", ic.input_style());
+                        return server.error(
+                            client_win,
+                            ErrorCode::BadStyle,
+                            "Unsupported input style".into(),
+                            NonZeroU16::new(input_method_id),
+                            None,
+                        );
+                    }
+                };
+                ic.attributes.set(AttributeName::InputStyle, input_style);
                 let ic = UserInputContext::new(ic, handler.new_ic_data(server, input_style)?);
                 let (input_context_id, ic) = im.new_ic(ic);
                 ic.ic.input_context_id = input_context_id;
@@ -329,6 +756,10 @@ impl<T> XimConnection<T> {
                 input_context_id,
                 input_method_id,
             } => {
+                if self.focused == Some((input_method_id, input_context_id)) {
+                    self.focused = None;
+                }
+
                 handler.handle_destroy_ic(
                     server,
                     self.get_input_method(input_method_id)?
@@ -344,6 +775,10 @@ impl<T> XimConnection<T> {
             }
 
             Request::Close { input_method_id } => {
+                if matches!(self.focused, Some((im, _)) if im == input_method_id) {
+                    self.focused = None;
+                }
+
                 for (_id, ic) in self.remove_input_method(input_method_id)?.input_contexts {
                     handler.handle_destroy_ic(server, ic)?;
                 }
@@ -352,14 +787,33 @@ impl<T> XimConnection<T> {
             }
 
             Request::QueryExtension {
-                input_method_id, ..
+                input_method_id,
+                extensions,
             } => {
-                // Extension not supported now
+                // an empty list asks every supported extension
+                let supported = if self.supports_extensions() {
+                    handler.extensions()
+                } else {
+                    Vec::new()
+                };
+                let extensions: Vec<Extension> = if extensions.is_empty() {
+                    supported
+                } else {
+                    supported
+                        .into_iter()
+                        .filter(|ext| extensions.contains(&ext.name))
+                        .collect()
+                };
+
+                log::debug!("Extensions: {:?}", extensions);
+
+                self.get_input_method(input_method_id)?.extensions = extensions.clone();
+
                 server.send_req(
                     self.client_win,
                     Request::QueryExtensionReply {
                         input_method_id,
-                        extensions: Vec::new(),
+                        extensions,
                     },
                 )?;
             }
@@ -370,11 +824,14 @@ impl<T> XimConnection<T> {
             } => {
                 log::debug!("Encodings: {:?}", encodings);
 
+                // the client lists encodings in order of its preference
                 match encodings
                     .iter()
-                    .position(|e| e.starts_with("COMPOUND_TEXT"))
+                    .enumerate()
+                    .find_map(|(pos, e)| Some((pos, Encoding::from_name(e)?)))
                 {
-                    Some(pos) => {
+                    Some((pos, encoding)) => {
+                        self.get_input_method(input_method_id)?.encoding = encoding;
                         server.send_req(
                             self.client_win,
                             Request::EncodingNegotiationReply {
@@ -404,15 +861,50 @@ impl<T> XimConnection<T> {
                     .get_input_method(input_method_id)?
                     .get_input_context(input_context_id)?;
                 let ret = handler.handle_reset_ic(server, ic)?;
+
+                // the client drops its preedit, the next draw starts from an empty one
+                if ic.ic.preedit_started {
+                    server.send_req(
+                        ic.ic.client_win(),
+                        Request::PreeditDone {
+                            input_method_id,
+                            input_context_id,
+                        },
+                    )?;
+                    ic.ic.preedit_started = false;
+                }
+                ic.ic.prev_preedit.clear();
+
                 server.send_req(
                     ic.ic.client_win(),
                     Request::ResetIcReply {
                         input_method_id,
                         input_context_id,
-                        preedit_string: xim_ctext::utf8_to_compound_text(&ret),
+                        preedit_string: ic.ic.encoding().encode(&ret),
                     },
                 )?;
             }
+            Request::SetImValues {
+                input_method_id,
+                attributes,
+            } => {
+                let im = self.get_input_method(input_method_id)?;
+
+                for attr in attributes {
+                    match attrs::get_name(attr.id) {
+                        Some(name) => {
+                            im.attributes.insert(name, attr.value);
+                        }
+                        None => log::warn!("Unknown attr id: {}", attr.id),
+                    }
+                }
+
+                handler.handle_set_im_values(server, im)?;
+                server.send_req(
+                    self.client_win,
+                    Request::SetImValuesReply { input_method_id },
+                )?;
+            }
             Request::GetImValues {
                 input_method_id,
                 im_attributes,
@@ -424,20 +916,29 @@ impl<T> XimConnection<T> {
                         AttributeName::QueryInputStyle => {
                             out.push(Attribute {
                                 id: attrs::get_id(name),
-                                value: xim_parser::write_to_vec(InputStyleList {
-                                    styles: handler.input_styles().as_ref().to_vec(),
-                                }),
+                                value: xim_parser::write_to_vec_with_endian(
+                                    InputStyleList {
+                                        styles: handler.input_styles().as_ref().to_vec(),
+                                    },
+                                    self.endian,
+                                ),
                             });
                         }
-                        _ => {
-                            return server.error(
-                                self.client_win,
-                                ErrorCode::BadName,
-                                "Unknown im attribute name".into(),
-                                NonZeroU16::new(input_method_id),
-                                None,
-                            );
-                        }
+                        name => match handler.handle_get_im_value(name) {
+                            Some(value) => out.push(Attribute {
+                                id: attrs::get_id(name),
+                                value,
+                            }),
+                            None => {
+                                return server.error(
+                                    self.client_win,
+                                    ErrorCode::BadName,
+                                    "Unknown im attribute name".into(),
+                                    NonZeroU16::new(input_method_id),
+                                    None,
+                                );
+                            }
+                        },
                     }
                 }
 
@@ -460,28 +961,22 @@ impl<T> XimConnection<T> {
                     .get_input_context(input_context_id)?
                     .ic;
                 let mut out = Vec::with_capacity(ic_attributes.len());
+                let mut names = ic_attributes.into_iter().filter_map(attrs::get_name);
 
-                for name in ic_attributes.into_iter().filter_map(attrs::get_name) {
+                while let Some(name) = names.next() {
                     match name {
-                        AttributeName::InputStyle => out.push(Attribute {
-                            id: attrs::get_id(name),
-                            value: xim_parser::write_to_vec(ic.input_style()),
-                        }),
-                        AttributeName::ClientWindow => out.push(Attribute {
-                            id: attrs::get_id(name),
-                            value: xim_parser::write_to_vec(
-                                ic.app_win().map_or(0, NonZeroU32::get),
-                            ),
-                        }),
-                        AttributeName::FocusWindow => out.push(Attribute {
-                            id: attrs::get_id(name),
-                            value: xim_parser::write_to_vec(
-                                ic.app_focus_win().map_or(0, NonZeroU32::get),
-                            ),
-                        }),
+                        AttributeName::PreeditAttributes | AttributeName::StatusAttributes => {
+                            let nested = names
+                                .by_ref()
+                                .take_while(|name| *name != AttributeName::SeparatorofNestedList);
+                            out.push(ic.attributes.encode_nested(name, nested));
+                        }
                         AttributeName::FilterEvents => out.push(Attribute {
                             id: attrs::get_id(name),
-                            value: xim_parser::write_to_vec(handler.filter_events()),
+                            value: xim_parser::write_to_vec_with_endian(
+                                handler.filter_events(),
+                                ic.attributes.endian(),
+                            ),
                         }),
                         AttributeName::QueryInputStyle => {
                             return server.error(
@@ -492,9 +987,10 @@ impl<T> XimConnection<T> {
                                 None,
                             );
                         }
-                        name => {
-                            log::warn!("Unimplemented attribute {:?}", name);
-                        }
+                        name => match ic.attributes.encode(name) {
+                            Some(attr) => out.push(attr),
+                            None => log::warn!("{:?} is not set", name),
+                        },
                     }
                 }
 
@@ -517,7 +1013,7 @@ impl<T> XimConnection<T> {
                     .get_input_method(input_method_id)?
                     .get_input_context(input_context_id)?;
 
-                set_ic_attrs(&mut ic.ic, ic_attributes);
+                let changes = ic.ic.attributes.apply(ic_attributes);
 
                 server.send_req(
                     ic.ic.client_win(),
@@ -527,7 +1023,7 @@ impl<T> XimConnection<T> {
                     },
                 )?;
 
-                handler.handle_set_ic_values(server, ic)?;
+                handler.handle_set_ic_values(server, ic, &changes)?;
             }
 
             Request::SetIcFocus {
@@ -538,6 +1034,8 @@ impl<T> XimConnection<T> {
                     .get_input_method(input_method_id)?
                     .get_input_context(input_context_id)?;
                 handler.handle_set_focus(server, ic)?;
+                self.focused = Some((input_method_id, input_context_id));
+                self.focus_gained = true;
             }
 
             Request::UnsetIcFocus {
@@ -548,46 +1046,121 @@ impl<T> XimConnection<T> {
                     .get_input_method(input_method_id)?
                     .get_input_context(input_context_id)?;
                 handler.handle_unset_focus(server, ic)?;
+
+                if self.focused == Some((input_method_id, input_context_id)) {
+                    self.focused = None;
+                }
             }
 
             // Ignore start reply
             Request::PreeditStartReply { .. } => {}
 
-            Request::ForwardEvent {
+            Request::TriggerNotify {
                 input_method_id,
                 input_context_id,
-                serial_number: _,
                 flag,
-                xev,
+                index,
+                ..
             } => {
-                let ev = server.deserialize_event(&xev);
-                let input_context = self
+                let active = flag == TriggerNotifyFlag::OnKeyList;
+                let ic = self
                     .get_input_method(input_method_id)?
                     .get_input_context(input_context_id)?;
-                let consumed = handler.handle_forward_event(server, input_context, &ev)?;
+                handler.handle_trigger(server, ic, active, index)?;
 
-                if !consumed {
-                    server.send_req(
-                        self.client_win,
-                        Request::ForwardEvent {
-                            input_method_id,
-                            input_context_id,
-                            serial_number: 0,
-                            flag: ForwardEventFlag::empty(),
-                            xev,
-                        },
-                    )?;
+                if active {
+                    server.activate_ic(&mut ic.ic, handler.filter_events())?;
+                } else {
+                    server.deactivate_ic(&mut ic.ic)?;
                 }
+                server.send_req(
+                    self.client_win,
+                    Request::TriggerNotifyReply {
+                        input_method_id,
+                        input_context_id,
+                    },
+                )?;
+            }
 
-                if flag.contains(ForwardEventFlag::SYNCHRONOUS) {
-                    server.send_req(
-                        self.client_win,
-                        Request::SyncReply {
-                            input_method_id,
-                            input_context_id,
-                        },
-                    )?;
-                }
+            Request::StrConversionReply {
+                input_method_id,
+                input_context_id,
+                text,
+                ..
+            } => {
+                let ic = self
+                    .get_input_method(input_method_id)?
+                    .get_input_context(input_context_id)?;
+                let string = ic
+                    .ic
+                    .encoding()
+                    .decode(&text.string)
+                    .ok_or(ServerError::InvalidReply)?;
+                handler.handle_str_conversion_reply(server, ic, &string, &text.feedbacks)?;
+            }
+
+            Request::PreeditCaretReply {
+                input_method_id,
+                input_context_id,
+                position,
+            } => {
+                let ic = self
+                    .get_input_method(input_method_id)?
+                    .get_input_context(input_context_id)?;
+                handler.handle_preedit_caret(server, ic, position)?;
+            }
+
+            Request::ForwardEvent {
+                input_method_id,
+                input_context_id,
+                serial_number: _,
+                flag,
+                xev,
+            } => {
+                self.forward_event(
+                    server,
+                    handler,
+                    input_method_id,
+                    input_context_id,
+                    flag,
+                    xev,
+                )?;
+            }
+
+            Request::ExtForwardKeyEvent {
+                input_method_id,
+                input_context_id,
+                flag,
+                sequence_number,
+                xev_type,
+                keycode,
+                state,
+                time,
+                window,
+            } => {
+                let xev = xim_parser::XEvent {
+                    response_type: xev_type,
+                    detail: keycode,
+                    sequence: sequence_number,
+                    time,
+                    root: 0,
+                    event: window,
+                    child: 0,
+                    root_x: 0,
+                    root_y: 0,
+                    event_x: 0,
+                    event_y: 0,
+                    state,
+                    same_screen: true,
+                };
+                self.forward_event(
+                    server,
+                    handler,
+                    input_method_id,
+                    input_context_id,
+                    flag,
+                    xev,
+                )?;
             }
 
             Request::Sync {
@@ -603,10 +1176,89 @@ impl<T> XimConnection<T> {
                 )?;
             }
 
-            Request::SyncReply { .. } => {}
+            Request::SyncReply {
+                input_method_id,
+                input_context_id,
+            } => {
+                let ic = self
+                    .get_input_method(input_method_id)?
+                    .get_input_context(input_context_id)?;
+
+                if ic.ic.waiting_sync {
+                    ic.ic.waiting_sync = false;
+                    handler.handle_sync_done(server, ic)?;
+                }
 
-            _ => {
-                log::warn!("Unknown request: {:?}", req);
+                // the handler may wait another `SyncReply` with one of them
+                loop {
+                    let ic = &mut self
+                        .get_input_method(input_method_id)?
+                        .get_input_context(input_context_id)?
+                        .ic;
+
+                    if ic.waiting_sync {
+                        break;
+                    }
+
+                    let (flag, xev) = match ic.queued_events.pop_front() {
+                        Some(e) => e,
+                        None => break,
+                    };
+
+                    self.forward_event(
+                        server,
+                        handler,
+                        input_method_id,
+                        input_context_id,
+                        flag,
+                        xev,
+                    )?;
+                }
+            }
+
+            Request::ExtMove {
+                input_method_id,
+                input_context_id,
+                x,
+                y,
+            } => {
+                let ic = self
+                    .get_input_method(input_method_id)?
+                    .get_input_context(input_context_id)?;
+                ic.ic
+                    .attributes
+                    .set_preedit(AttributeName::SpotLocation, Point { x, y });
+                handler.handle_spot_moved(server, ic)?;
+            }
+
+            Request::ExtensionMessage {
+                major_opcode,
+                minor_opcode,
+                data,
+            } => {
+                let ext = self
+                    .input_methods
+                    .values()
+                    .flat_map(|im| im.extensions.iter())
+                    .find(|ext| {
+                        ext.major_opcode == major_opcode && ext.minor_opcode == minor_opcode
+                    })
+                    .cloned();
+
+                match ext {
+                    Some(ext) => handler.handle_extension_message(server, &ext, &data)?,
+                    None => log::warn!(
+                        "Message of extension {}/{} which is not negotiated",
+                        major_opcode,
+                        minor_opcode
+                    ),
+                }
+            }
+
+            req => {
+                if !handler.handle_unknown_request(server, self, &req)? {
+                    log::warn!("Unknown request: {:?}", req);
+                }
             }
         }
 
@@ -614,8 +1266,29 @@ impl<T> XimConnection<T> {
     }
 }
 
+/// Limits of resources a client can create, `BadAlloc` is sent when they're exceeded
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConnectionLimits {
+    /// Input methods opened by a connection
+    pub max_input_methods: usize,
+    /// Input contexts created in an input method
+    pub max_input_contexts: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_input_methods: 64,
+            max_input_contexts: 1024,
+        }
+    }
+}
+
 pub struct XimConnections<T> {
     pub(crate) connections: AHashMap<u32, XimConnection<T>>,
+    limits: ConnectionLimits,
+    /// Communication window of the connection which got focus last
+    focused: Option<u32>,
 }
 
 impl<T> Default for XimConnections<T> {
@@ -628,12 +1301,98 @@ impl<T> XimConnections<T> {
     pub fn new() -> Self {
         Self {
             connections: AHashMap::with_hasher(Default::default()),
+            limits: ConnectionLimits::default(),
+            focused: None,
         }
     }
 
-    pub fn new_connection(&mut self, com_win: u32, client_win: u32) {
+    pub fn limits(&self) -> ConnectionLimits {
+        self.limits
+    }
+
+    /// Limits of connections made after this
+    pub fn set_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = limits;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &XimConnection<T>> + '_ {
+        self.connections.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut XimConnection<T>> + '_ {
+        self.connections.values_mut()
+    }
+
+    /// Every input context of every connection
+    pub fn iter_ics(&self) -> impl Iterator<Item = &UserInputContext<T>> + '_ {
+        self.iter().flat_map(XimConnection::iter_ics)
+    }
+
+    /// Every input context of every connection
+    pub fn iter_ics_mut(&mut self) -> impl Iterator<Item = &mut UserInputContext<T>> + '_ {
+        self.iter_mut().flat_map(XimConnection::iter_ics_mut)
+    }
+
+    /// Input context which got focus last among every connection, `None` when it lost focus
+    pub fn focused_ic_mut(&mut self) -> Option<&mut UserInputContext<T>> {
+        self.connections.get_mut(&self.focused?)?.focused_ic_mut()
+    }
+
+    /// Follow focus changes of the connection of `com_win` after it handled a request
+    pub(crate) fn update_focus(&mut self, com_win: u32) {
+        if let Some(connection) = self.connections.get_mut(&com_win) {
+            if connection.focus_gained {
+                connection.focus_gained = false;
+                self.focused = Some(com_win);
+            }
+        }
+    }
+
+    /// First input context matching `pred`
+    pub fn find_ic(
+        &mut self,
+        mut pred: impl FnMut(&UserInputContext<T>) -> bool,
+    ) -> Option<&mut UserInputContext<T>> {
+        self.iter_ics_mut().find(|ic| pred(ic))
+    }
+
+    /// Input context `input_context_id` of input method `input_method_id` of the connection of
+    /// `com_win`
+    pub fn get_ic_mut(
+        &mut self,
+        com_win: u32,
+        input_method_id: NonZeroU16,
+        input_context_id: NonZeroU16,
+    ) -> Option<&mut UserInputContext<T>> {
         self.connections
-            .insert(com_win, XimConnection::new(client_win));
+            .get_mut(&com_win)?
+            .get_input_method(input_method_id.get())
+            .ok()?
+            .get_input_context(input_context_id.get())
+            .ok()
+    }
+
+    /// Input context with the ids of the client of `client_win`, for code which only kept
+    /// [`InputContext::client_win`] and the ids of an input context, e.g. engine callbacks.
+    ///
+    /// Ids are only unique in a connection so the client window is needed to tell them apart.
+    pub fn find_ic_by_ids(
+        &mut self,
+        client_win: u32,
+        input_method_id: NonZeroU16,
+        input_context_id: NonZeroU16,
+    ) -> Option<&mut UserInputContext<T>> {
+        self.find_ic(|user_ic| {
+            user_ic.ic.client_win() == client_win
+                && user_ic.ic.input_method_id() == input_method_id
+                && user_ic.ic.input_context_id() == input_context_id
+        })
+    }
+
+    pub fn new_connection(&mut self, com_win: u32, client_win: u32) {
+        let mut connection = XimConnection::new(client_win);
+        connection.limits = self.limits;
+        self.connections.insert(com_win, connection);
     }
 
     pub fn get_connection(&mut self, com_win: u32) -> Option<&mut XimConnection<T>> {
@@ -17,12 +17,22 @@ impl<T> ImVec<T> {
 
     fn next(&mut self) -> NonZeroU16 {
         let ret = self.next;
-        self.next = NonZeroU16::new(self.next.get() + 1).unwrap();
+        self.next = NonZeroU16::new(self.next.get().wrapping_add(1))
+            .unwrap_or_else(|| NonZeroU16::new(1).unwrap());
         ret
     }
 
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
     pub fn new_item(&mut self, data: T) -> (NonZeroU16, &mut T) {
-        let idx = self.next();
+        let mut idx = self.next();
+
+        // ids are reused after wrapping around, skip the ones still alive
+        while self.inner.len() < usize::from(u16::MAX) && self.inner.contains_key(&idx) {
+            idx = self.next();
+        }
 
         let val = match self.inner.entry(idx) {
             Entry::Occupied(mut o) => {
@@ -44,6 +54,14 @@ impl<T> ImVec<T> {
         self.inner.get_mut(&NonZeroU16::new(idx)?)
     }
 
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.inner.values()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.inner.values_mut()
+    }
+
     pub fn drain(&mut self) -> impl Iterator<Item = (NonZeroU16, T)> + '_ {
         self.inner.drain()
     }
@@ -0,0 +1,237 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use xim_parser::{Attr, AttributeName, Extension, InputStyle, Request, TriggerKey};
+
+use super::{
+    AttributeChanges, AttributeStore, AuthStep, InputMethod, Server, ServerError, ServerHandler,
+    UserInputContext, XimConnection,
+};
+
+/// Server as a trait object, use it instead of writing `dyn Server` in handler methods so the
+/// lifetime of the trait object isn't elided to the lifetime of the reference
+pub type DynServer<E> = dyn Server<XEvent = E>;
+
+/// Pass callbacks of a backend to a handler of [`DynServer`].
+///
+/// Handlers implemented for `DynServer<E>` with `InputStyleArray = Vec<InputStyle>` don't depend
+/// on the backend, so engines can be chosen at runtime behind a trait object.
+///
+/// ```ignore
+/// let mut handler: Box<DynServerHandlerObject<KeyPressEvent, ()>> = if use_hangul {
+///     Box::new(HangulHandler::new())
+/// } else {
+///     Box::new(PinyinHandler::new())
+/// };
+/// server.filter_event(&e, &mut connections, &mut DynServerHandler(&mut *handler))?;
+/// ```
+pub struct DynServerHandler<'h, E: 'static, T>(pub &'h mut DynServerHandlerObject<E, T>);
+
+/// Handler of [`DynServer`] as a trait object
+pub type DynServerHandlerObject<E, T> =
+    dyn ServerHandler<DynServer<E>, InputStyleArray = Vec<InputStyle>, InputContextData = T>;
+
+impl<'h, S, E, T> ServerHandler<S> for DynServerHandler<'h, E, T>
+where
+    S: Server<XEvent = E> + 'static,
+{
+    type InputStyleArray = Vec<InputStyle>;
+    type InputContextData = T;
+
+    fn new_ic_data(&mut self, server: &mut S, input_style: InputStyle) -> Result<T, ServerError> {
+        self.0.new_ic_data(server, input_style)
+    }
+
+    fn input_styles(&self) -> Vec<InputStyle> {
+        self.0.input_styles()
+    }
+
+    fn filter_events(&self) -> u32 {
+        self.0.filter_events()
+    }
+
+    fn resolve_input_style(&self, requested: InputStyle) -> Option<InputStyle> {
+        self.0.resolve_input_style(requested)
+    }
+
+    fn accept_locale(&self, locale: &str) -> bool {
+        self.0.accept_locale(locale)
+    }
+
+    fn im_attributes(&self) -> Vec<Attr> {
+        self.0.im_attributes()
+    }
+
+    fn handle_get_im_value(&self, name: AttributeName) -> Option<Vec<u8>> {
+        self.0.handle_get_im_value(name)
+    }
+
+    fn handle_connect(&mut self, server: &mut S) -> Result<(), ServerError> {
+        self.0.handle_connect(server)
+    }
+
+    fn auth_required(
+        &mut self,
+        server: &mut S,
+        auth_protocol_names: &[String],
+    ) -> Result<Option<(u8, Vec<u8>)>, ServerError> {
+        self.0.auth_required(server, auth_protocol_names)
+    }
+
+    fn handle_auth_reply(
+        &mut self,
+        server: &mut S,
+        auth_data: &[u8],
+    ) -> Result<AuthStep, ServerError> {
+        self.0.handle_auth_reply(server, auth_data)
+    }
+
+    fn before_create_ic(
+        &mut self,
+        server: &mut S,
+        attributes: &mut AttributeStore,
+    ) -> Result<(), ServerError> {
+        self.0.before_create_ic(server, attributes)
+    }
+
+    fn handle_create_ic(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_create_ic(server, user_ic)
+    }
+
+    fn handle_destroy_ic(
+        &mut self,
+        server: &mut S,
+        user_ic: UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_destroy_ic(server, user_ic)
+    }
+
+    fn handle_reset_ic(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<String, ServerError> {
+        self.0.handle_reset_ic(server, user_ic)
+    }
+
+    fn handle_set_focus(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_set_focus(server, user_ic)
+    }
+
+    fn handle_unset_focus(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_unset_focus(server, user_ic)
+    }
+
+    fn handle_set_ic_values(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+        changes: &AttributeChanges,
+    ) -> Result<(), ServerError> {
+        self.0.handle_set_ic_values(server, user_ic, changes)
+    }
+
+    fn handle_forward_event(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+        xev: &E,
+    ) -> Result<bool, ServerError> {
+        self.0.handle_forward_event(server, user_ic, xev)
+    }
+
+    fn handle_set_im_values(
+        &mut self,
+        server: &mut S,
+        input_method: &mut InputMethod<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_set_im_values(server, input_method)
+    }
+
+    fn handle_spot_moved(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_spot_moved(server, user_ic)
+    }
+
+    fn handle_preedit_caret(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+        position: i32,
+    ) -> Result<(), ServerError> {
+        self.0.handle_preedit_caret(server, user_ic, position)
+    }
+
+    fn trigger_on_keys(&self) -> Vec<TriggerKey> {
+        self.0.trigger_on_keys()
+    }
+
+    fn trigger_off_keys(&self) -> Vec<TriggerKey> {
+        self.0.trigger_off_keys()
+    }
+
+    fn handle_trigger(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+        active: bool,
+        index: u32,
+    ) -> Result<(), ServerError> {
+        self.0.handle_trigger(server, user_ic, active, index)
+    }
+
+    fn handle_sync_done(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+    ) -> Result<(), ServerError> {
+        self.0.handle_sync_done(server, user_ic)
+    }
+
+    fn handle_str_conversion_reply(
+        &mut self,
+        server: &mut S,
+        user_ic: &mut UserInputContext<T>,
+        text: &str,
+        feedbacks: &[u32],
+    ) -> Result<(), ServerError> {
+        self.0
+            .handle_str_conversion_reply(server, user_ic, text, feedbacks)
+    }
+
+    fn handle_unknown_request(
+        &mut self,
+        server: &mut S,
+        connection: &mut XimConnection<T>,
+        req: &Request,
+    ) -> Result<bool, ServerError> {
+        self.0.handle_unknown_request(server, connection, req)
+    }
+
+    fn extensions(&self) -> Vec<Extension> {
+        self.0.extensions()
+    }
+
+    fn handle_extension_message(
+        &mut self,
+        server: &mut S,
+        extension: &Extension,
+        data: &[u8],
+    ) -> Result<(), ServerError> {
+        self.0.handle_extension_message(server, extension, data)
+    }
+}
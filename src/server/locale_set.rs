@@ -0,0 +1,117 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Entry {
+    Locale(String),
+    Glob(String),
+}
+
+/// Locales supported by a server.
+///
+/// It's rendered as the `@locale=` list by [`fmt::Display`] and answers
+/// [`ServerHandler::accept_locale`](crate::ServerHandler::accept_locale) by
+/// [`LocaleSet::contains`].
+///
+/// ```
+/// # use xim::LocaleSet;
+/// let locales = LocaleSet::new().add("ko").add_glob("ja_JP*");
+///
+/// assert!(locales.contains("ko_KR.UTF-8"));
+/// assert!(locales.contains("ja_JP.eucJP"));
+/// assert!(!locales.contains("ja"));
+/// assert_eq!(locales.to_string(), "ko,ja");
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LocaleSet {
+    entries: Vec<Entry>,
+}
+
+impl LocaleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every locale of [`ALL_LOCALES`](crate::ALL_LOCALES)
+    pub fn all() -> Self {
+        crate::ALL_LOCALES
+            .split(',')
+            .fold(Self::new(), |set, locale| set.add(locale))
+    }
+
+    /// Add `locale`, it also contains locales with the territory or codeset like clients
+    /// compare them, e.g. `ko` contains `ko_KR.UTF-8`
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, locale: &str) -> Self {
+        self.entries.push(Entry::Locale(locale.into()));
+        self
+    }
+
+    /// Add locales matching `pattern` where `*` matches any characters and `?` matches one.
+    ///
+    /// Clients only compare the language of their locale with the advertised list, so it's
+    /// advertised by the language before the first `_`, `.`, `@` or wildcard.
+    pub fn add_glob(mut self, pattern: &str) -> Self {
+        self.entries.push(Entry::Glob(pattern.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether `locale` requested by `Open` is supported
+    pub fn contains(&self, locale: &str) -> bool {
+        self.entries.iter().any(|entry| match entry {
+            Entry::Locale(l) => match locale.strip_prefix(l.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with(&['_', '.', '@'][..]),
+                None => false,
+            },
+            Entry::Glob(pattern) => glob_match(pattern.as_bytes(), locale.as_bytes()),
+        })
+    }
+
+    fn advertised(&self) -> impl Iterator<Item = &str> + '_ {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::Locale(l) => l.as_str(),
+                Entry::Glob(pattern) => pattern
+                    .split(&['_', '.', '@', '*', '?'][..])
+                    .next()
+                    .unwrap_or_default(),
+            })
+            .filter(|l| !l.is_empty())
+    }
+}
+
+impl fmt::Display for LocaleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut written: Vec<&str> = Vec::new();
+
+        for locale in self.advertised() {
+            if written.contains(&locale) {
+                continue;
+            }
+
+            if !written.is_empty() {
+                f.write_str(",")?;
+            }
+
+            f.write_str(locale)?;
+            written.push(locale);
+        }
+
+        Ok(())
+    }
+}
+
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| glob_match(rest, &s[i..])),
+        Some((b'?', rest)) => !s.is_empty() && glob_match(rest, &s[1..]),
+        Some((c, rest)) => s.first() == Some(c) && glob_match(rest, &s[1..]),
+    }
+}
@@ -0,0 +1,60 @@
+use crate::RequestCounts;
+use xim_parser::Request;
+
+use super::XimConnections;
+
+/// Snapshot of clients and messages of a server, see
+/// [`X11rbServer::stats`](crate::x11rb::X11rbServer::stats)
+#[derive(Clone, Debug, Default)]
+pub struct ServerStats {
+    /// Connected clients
+    pub connections: usize,
+    /// Open input methods of every client
+    pub input_methods: usize,
+    /// Input contexts of every client
+    pub input_contexts: usize,
+    requests: RequestCounts,
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests sent to and received from every client
+    pub fn requests(&self) -> &RequestCounts {
+        &self.requests
+    }
+
+    /// Number of `Commit` sent to clients
+    pub fn commits(&self) -> u64 {
+        self.requests.sent("Commit")
+    }
+
+    /// Number of `Error` sent to clients
+    pub fn errors(&self) -> u64 {
+        self.requests.sent("Error")
+    }
+
+    pub(crate) fn request_sent(&mut self, req: &Request) {
+        self.requests.request_sent(req);
+    }
+
+    pub(crate) fn request_received(&mut self, req: &Request) {
+        self.requests.request_received(req);
+    }
+
+    /// Copy of the counters with the clients of `connections`
+    pub(crate) fn snapshot<T>(&self, connections: &XimConnections<T>) -> Self {
+        let mut stats = self.clone();
+
+        stats.connections = connections.iter().count();
+        stats.input_methods = connections
+            .iter()
+            .map(|connection| connection.input_methods().count())
+            .sum();
+        stats.input_contexts = connections.iter_ics().count();
+
+        stats
+    }
+}
@@ -0,0 +1,696 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::num::NonZeroU16;
+use xim_parser::{
+    attrs, Attribute, AttributeName, Endian, ErrorCode, Extension, Feedback, ForwardEventFlag,
+    InputStyle, Point, PreeditDrawStatus, PreeditStateFlag, Request,
+};
+
+use super::{
+    ConnectionLimits, FeedbackRun, InputContext, Server, ServerCore, ServerError, ServerHandler,
+    UserInputContext, XimConnection,
+};
+
+const CLIENT_WIN: u32 = 1;
+
+/// Server which keeps the requests it sends
+#[derive(Default)]
+struct TestServer {
+    sent: Vec<Request>,
+}
+
+impl ServerCore for TestServer {
+    type XEvent = xim_parser::XEvent;
+
+    fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
+        xev.clone()
+    }
+
+    fn deserialize_event(&self, ev: &xim_parser::XEvent) -> Self::XEvent {
+        ev.clone()
+    }
+
+    fn send_req(&mut self, _client_win: u32, req: Request) -> Result<(), ServerError> {
+        self.sent.push(req);
+        Ok(())
+    }
+}
+
+impl TestServer {
+    fn take(&mut self) -> Vec<Request> {
+        core::mem::take(&mut self.sent)
+    }
+}
+
+/// Handler which keeps the preedit it's asked to draw until `ResetIc`
+#[derive(Default)]
+struct TestHandler {
+    preedit: String,
+    /// Calls of the handler in order
+    calls: Vec<Call>,
+    /// Keycode of a forwarded event which is answered with `commit_sync`
+    commit_on: Option<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Call {
+    SyncDone,
+    /// Keycode of a forwarded event
+    ForwardEvent(u8),
+}
+
+impl ServerHandler<TestServer> for TestHandler {
+    type InputStyleArray = [InputStyle; 1];
+    type InputContextData = ();
+
+    fn new_ic_data(
+        &mut self,
+        _server: &mut TestServer,
+        _input_style: InputStyle,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    fn input_styles(&self) -> Self::InputStyleArray {
+        [InputStyle::PREEDIT_CALLBACKS | InputStyle::STATUS_NOTHING]
+    }
+
+    fn filter_events(&self) -> u32 {
+        1
+    }
+
+    fn extensions(&self) -> Vec<Extension> {
+        vec![Extension {
+            major_opcode: 128,
+            minor_opcode: 0,
+            name: "XIM_EXT_MOVE".into(),
+        }]
+    }
+
+    fn handle_connect(&mut self, _server: &mut TestServer) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    fn handle_create_ic(
+        &mut self,
+        server: &mut TestServer,
+        user_ic: &mut UserInputContext<()>,
+    ) -> Result<(), ServerError> {
+        server.preedit_draw(&mut user_ic.ic, &self.preedit)
+    }
+
+    fn handle_destroy_ic(
+        &mut self,
+        _server: &mut TestServer,
+        _user_ic: UserInputContext<()>,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    fn handle_reset_ic(
+        &mut self,
+        _server: &mut TestServer,
+        _user_ic: &mut UserInputContext<()>,
+    ) -> Result<String, ServerError> {
+        Ok(core::mem::take(&mut self.preedit))
+    }
+
+    fn handle_forward_event(
+        &mut self,
+        server: &mut TestServer,
+        user_ic: &mut UserInputContext<()>,
+        xev: &xim_parser::XEvent,
+    ) -> Result<bool, ServerError> {
+        self.calls.push(Call::ForwardEvent(xev.detail));
+
+        if self.commit_on == Some(xev.detail) {
+            server.commit_sync(&mut user_ic.ic, "x")?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn handle_sync_done(
+        &mut self,
+        _server: &mut TestServer,
+        _user_ic: &mut UserInputContext<()>,
+    ) -> Result<(), ServerError> {
+        self.calls.push(Call::SyncDone);
+        Ok(())
+    }
+}
+
+fn new_ic() -> InputContext {
+    let id = NonZeroU16::new(1).unwrap();
+    InputContext::new(CLIENT_WIN, id, id, "en_US".into())
+}
+
+/// `(chg_first, chg_length, caret, text, feedbacks, status)` of the last `PreeditDraw`
+fn last_draw(
+    server: &mut TestServer,
+) -> (i32, i32, i32, Vec<u8>, Vec<Feedback>, PreeditDrawStatus) {
+    let draw = server.take().into_iter().rev().find_map(|req| match req {
+        Request::PreeditDraw {
+            chg_first,
+            chg_length,
+            caret,
+            preedit_string,
+            feedbacks,
+            status,
+            ..
+        } => Some((
+            chg_first,
+            chg_length,
+            caret,
+            preedit_string,
+            feedbacks,
+            status,
+        )),
+        _ => None,
+    });
+
+    draw.expect("PreeditDraw is sent")
+}
+
+fn draw_from(server: &mut TestServer, prev: &str) -> InputContext {
+    let mut ic = new_ic();
+    server.preedit_draw(&mut ic, prev).unwrap();
+    server.take();
+    ic
+}
+
+#[test]
+fn preedit_insert() {
+    let mut server = TestServer::default();
+    let mut ic = draw_from(&mut server, "ab");
+
+    server.preedit_draw(&mut ic, "axb").unwrap();
+
+    assert_eq!(
+        last_draw(&mut server),
+        (
+            1,
+            0,
+            3,
+            ic.encoding().encode("x"),
+            vec![Feedback::Underline],
+            PreeditDrawStatus::empty()
+        )
+    );
+}
+
+#[test]
+fn preedit_delete() {
+    let mut server = TestServer::default();
+    let mut ic = draw_from(&mut server, "abc");
+
+    server.preedit_draw(&mut ic, "ac").unwrap();
+
+    assert_eq!(
+        last_draw(&mut server),
+        (
+            1,
+            1,
+            2,
+            Vec::new(),
+            Vec::new(),
+            PreeditDrawStatus::NO_STRING | PreeditDrawStatus::NO_FEEDBACK
+        )
+    );
+}
+
+#[test]
+fn preedit_replace_middle() {
+    let mut server = TestServer::default();
+    let mut ic = draw_from(&mut server, "abcd");
+
+    server.preedit_draw(&mut ic, "aXYZd").unwrap();
+
+    assert_eq!(
+        last_draw(&mut server),
+        (
+            1,
+            2,
+            5,
+            ic.encoding().encode("XYZ"),
+            vec![Feedback::Underline; 3],
+            PreeditDrawStatus::empty()
+        )
+    );
+}
+
+#[test]
+fn preedit_feedback_only() {
+    let mut server = TestServer::default();
+    let mut ic = draw_from(&mut server, "abc");
+
+    server
+        .preedit_draw_styled(
+            &mut ic,
+            "abc",
+            &[FeedbackRun::new(1..2, Feedback::Reverse)],
+            2,
+        )
+        .unwrap();
+
+    assert_eq!(
+        last_draw(&mut server),
+        (
+            1,
+            1,
+            2,
+            ic.encoding().encode("b"),
+            vec![Feedback::Reverse],
+            PreeditDrawStatus::empty()
+        )
+    );
+}
+
+#[test]
+fn preedit_clear() {
+    let mut server = TestServer::default();
+    let mut ic = draw_from(&mut server, "ab");
+
+    server.preedit_draw(&mut ic, "").unwrap();
+    let sent = server.take();
+
+    assert!(matches!(
+        sent[..],
+        [
+            Request::PreeditDraw {
+                chg_first: 0,
+                chg_length: 2,
+                ..
+            },
+            Request::PreeditDone { .. }
+        ]
+    ));
+
+    // drawn from scratch after it's done
+    server.preedit_draw(&mut ic, "c").unwrap();
+    let sent = server.take();
+
+    assert!(matches!(
+        sent[..],
+        [
+            Request::PreeditStart { .. },
+            Request::PreeditDraw {
+                chg_first: 0,
+                chg_length: 0,
+                ..
+            }
+        ]
+    ));
+}
+
+#[test]
+fn reset_ic_drops_preedit() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler {
+        preedit: "ab".into(),
+        ..TestHandler::default()
+    };
+    let mut connection = XimConnection::new(CLIENT_WIN);
+
+    for req in [
+        Request::Connect {
+            endian: xim_parser::Endian::Native,
+            client_major_protocol_version: 1,
+            client_minor_protocol_version: 0,
+            client_auth_protocol_names: Vec::new(),
+        },
+        Request::Open {
+            locale: "en_US".into(),
+        },
+        Request::CreateIc {
+            input_method_id: 1,
+            ic_attributes: vec![Attribute {
+                id: attrs::get_id(AttributeName::InputStyle),
+                value: xim_parser::write_to_vec(handler.input_styles()[0]),
+            }],
+        },
+        Request::ResetIc {
+            input_method_id: 1,
+            input_context_id: 1,
+        },
+    ] {
+        connection
+            .handle_request(&mut server, req, &mut handler)
+            .unwrap();
+    }
+
+    let sent = server.take();
+    let reset = sent
+        .iter()
+        .position(|req| matches!(req, Request::ResetIcReply { .. }))
+        .unwrap();
+    assert!(matches!(sent[reset - 1], Request::PreeditDone { .. }));
+
+    let ic = &mut connection.iter_ics_mut().next().unwrap().ic;
+    server.preedit_draw(ic, "c").unwrap();
+    let sent = server.take();
+
+    assert!(matches!(
+        sent[..],
+        [
+            Request::PreeditStart { .. },
+            Request::PreeditDraw {
+                chg_first: 0,
+                chg_length: 0,
+                ..
+            }
+        ]
+    ));
+}
+
+#[test]
+fn big_endian_attributes() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler::default();
+    let mut connection = XimConnection::new(CLIENT_WIN);
+    let endian = Endian::Big;
+    let input_style = handler.input_styles()[0];
+    let spot = Point { x: 10, y: 20 };
+
+    for (i, req) in vec![
+        Request::Connect {
+            endian,
+            client_major_protocol_version: 1,
+            client_minor_protocol_version: 0,
+            client_auth_protocol_names: Vec::new(),
+        },
+        Request::Open {
+            locale: "en_US".into(),
+        },
+        Request::CreateIc {
+            input_method_id: 1,
+            ic_attributes: vec![
+                Attribute {
+                    id: attrs::get_id(AttributeName::InputStyle),
+                    value: xim_parser::write_to_vec_with_endian(input_style, endian),
+                },
+                Attribute {
+                    id: attrs::get_id(AttributeName::PreeditAttributes),
+                    value: xim_parser::write_to_vec_with_endian(
+                        Attribute {
+                            id: attrs::get_id(AttributeName::SpotLocation),
+                            value: xim_parser::write_to_vec_with_endian(spot.clone(), endian),
+                        },
+                        endian,
+                    ),
+                },
+            ],
+        },
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let data = xim_parser::write_to_vec_with_endian(&req, endian);
+        let data = if i == 0 {
+            // `Connect` is split into `_XIM_MOREDATA` messages
+            connection.message_buffer.push(&data[..8]).unwrap();
+            &data[8..]
+        } else {
+            &data[..]
+        };
+        let req = connection.read_request(data, None).unwrap();
+        connection
+            .handle_request(&mut server, req, &mut handler)
+            .unwrap();
+    }
+
+    assert_eq!(connection.client_endian(), endian);
+
+    let ic = &mut connection.iter_ics_mut().next().unwrap().ic;
+    assert_eq!(ic.input_style(), input_style);
+    assert_eq!(ic.preedit_spot(), spot);
+
+    // values set by the server are written in the byte order of the client too
+    server.set_preedit_state(ic, false).unwrap();
+    assert_eq!(
+        ic.attributes().preedit(AttributeName::PreeditState),
+        Some(&xim_parser::write_to_vec_with_endian(PreeditStateFlag::DISABLE, endian)[..])
+    );
+}
+
+/// Extensions replied to `QueryExtension` of a client of `version`
+fn query_extension(version: (u16, u16)) -> Vec<Extension> {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler::default();
+    let mut connection = XimConnection::new(CLIENT_WIN);
+
+    for req in [
+        Request::Connect {
+            endian: Endian::Native,
+            client_major_protocol_version: version.0,
+            client_minor_protocol_version: version.1,
+            client_auth_protocol_names: Vec::new(),
+        },
+        Request::Open {
+            locale: "en_US".into(),
+        },
+        Request::QueryExtension {
+            input_method_id: 1,
+            extensions: Vec::new(),
+        },
+    ] {
+        connection
+            .handle_request(&mut server, req, &mut handler)
+            .unwrap();
+    }
+
+    assert_eq!(connection.client_protocol_version(), version);
+
+    server
+        .take()
+        .into_iter()
+        .find_map(|req| match req {
+            Request::QueryExtensionReply { extensions, .. } => Some(extensions),
+            _ => None,
+        })
+        .expect("QueryExtensionReply is sent")
+}
+
+#[test]
+fn extensions_by_protocol_version() {
+    assert_eq!(query_extension((1, 0)), handler_extensions());
+    assert_eq!(query_extension((1, 1)), handler_extensions());
+    assert_eq!(query_extension((0, 9)), Vec::new());
+    assert_eq!(query_extension((2, 0)), Vec::new());
+}
+
+fn handler_extensions() -> Vec<Extension> {
+    TestHandler::default().extensions()
+}
+
+fn connect() -> Request {
+    Request::Connect {
+        endian: Endian::Native,
+        client_major_protocol_version: 1,
+        client_minor_protocol_version: 0,
+        client_auth_protocol_names: Vec::new(),
+    }
+}
+
+fn open() -> Request {
+    Request::Open {
+        locale: "en_US".into(),
+    }
+}
+
+fn create_ic() -> Request {
+    Request::CreateIc {
+        input_method_id: 1,
+        ic_attributes: vec![Attribute {
+            id: attrs::get_id(AttributeName::InputStyle),
+            value: xim_parser::write_to_vec(TestHandler::default().input_styles()[0]),
+        }],
+    }
+}
+
+/// Key press of `keycode` forwarded by the client
+fn forward_key(keycode: u8) -> Request {
+    Request::ForwardEvent {
+        input_method_id: 1,
+        input_context_id: 1,
+        flag: ForwardEventFlag::SYNCHRONOUS | ForwardEventFlag::REQUEST_FILTERING,
+        serial_number: 0,
+        xev: xim_parser::XEvent {
+            response_type: 2,
+            detail: keycode,
+            sequence: 0,
+            time: 0,
+            root: 0,
+            event: 0,
+            child: 0,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state: 0,
+            same_screen: true,
+        },
+    }
+}
+
+fn sync_reply() -> Request {
+    Request::SyncReply {
+        input_method_id: 1,
+        input_context_id: 1,
+    }
+}
+
+fn handle(
+    connection: &mut XimConnection<()>,
+    server: &mut TestServer,
+    handler: &mut TestHandler,
+    reqs: impl IntoIterator<Item = Request>,
+) {
+    for req in reqs {
+        connection.handle_request(server, req, handler).unwrap();
+    }
+}
+
+/// Connection with an input context which is waiting `SyncReply` of `commit_sync`
+fn waiting_sync(server: &mut TestServer, handler: &mut TestHandler) -> XimConnection<()> {
+    let mut connection = XimConnection::new(CLIENT_WIN);
+    handle(
+        &mut connection,
+        server,
+        handler,
+        [connect(), open(), create_ic()],
+    );
+
+    let ic = &mut connection.iter_ics_mut().next().unwrap().ic;
+    server.commit_sync(ic, "a").unwrap();
+    server.take();
+
+    connection
+}
+
+fn first_ic(connection: &mut XimConnection<()>) -> &InputContext {
+    &connection.iter_ics_mut().next().unwrap().ic
+}
+
+#[test]
+fn forward_event_queued_while_waiting_sync() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler::default();
+    let mut connection = waiting_sync(&mut server, &mut handler);
+
+    handle(
+        &mut connection,
+        &mut server,
+        &mut handler,
+        [forward_key(10), forward_key(11)],
+    );
+
+    assert_eq!(handler.calls, []);
+    assert!(server.take().is_empty());
+    assert_eq!(first_ic(&mut connection).queued_events(), 2);
+
+    handle(&mut connection, &mut server, &mut handler, [sync_reply()]);
+
+    assert_eq!(
+        handler.calls,
+        [
+            Call::SyncDone,
+            Call::ForwardEvent(10),
+            Call::ForwardEvent(11)
+        ]
+    );
+    let ic = first_ic(&mut connection);
+    assert!(!ic.is_waiting_sync());
+    assert_eq!(ic.queued_events(), 0);
+}
+
+#[test]
+fn commit_sync_in_replay_stops_drain() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler {
+        commit_on: Some(10),
+        ..TestHandler::default()
+    };
+    let mut connection = waiting_sync(&mut server, &mut handler);
+
+    handle(
+        &mut connection,
+        &mut server,
+        &mut handler,
+        [forward_key(10), forward_key(11), sync_reply()],
+    );
+
+    assert_eq!(handler.calls, [Call::SyncDone, Call::ForwardEvent(10)]);
+    let ic = first_ic(&mut connection);
+    assert!(ic.is_waiting_sync());
+    assert_eq!(ic.queued_events(), 1);
+
+    handle(&mut connection, &mut server, &mut handler, [sync_reply()]);
+
+    assert_eq!(
+        handler.calls,
+        [
+            Call::SyncDone,
+            Call::ForwardEvent(10),
+            Call::SyncDone,
+            Call::ForwardEvent(11)
+        ]
+    );
+    assert_eq!(first_ic(&mut connection).queued_events(), 0);
+}
+
+#[test]
+fn auth_reply_without_auth_required() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler::default();
+    let mut connection = XimConnection::new(CLIENT_WIN);
+
+    handle(&mut connection, &mut server, &mut handler, [connect()]);
+    assert!(matches!(server.take()[..], [Request::ConnectReply { .. }]));
+
+    handle(
+        &mut connection,
+        &mut server,
+        &mut handler,
+        [Request::AuthReply {
+            auth_data: vec![1, 2, 3],
+        }],
+    );
+    assert_eq!(server.take(), [Request::AuthNg {}]);
+}
+
+/// Code of the last error sent to the client
+fn last_error(server: &mut TestServer) -> Option<ErrorCode> {
+    server.take().into_iter().rev().find_map(|req| match req {
+        Request::Error { code, .. } => Some(code),
+        _ => None,
+    })
+}
+
+#[test]
+fn bad_alloc_at_limits() {
+    let mut server = TestServer::default();
+    let mut handler = TestHandler::default();
+    let mut connection = XimConnection::new(CLIENT_WIN);
+    connection.limits = ConnectionLimits {
+        max_input_methods: 1,
+        max_input_contexts: 1,
+    };
+
+    handle(
+        &mut connection,
+        &mut server,
+        &mut handler,
+        [connect(), open(), create_ic()],
+    );
+    assert_eq!(last_error(&mut server), None);
+
+    handle(&mut connection, &mut server, &mut handler, [open()]);
+    assert_eq!(last_error(&mut server), Some(ErrorCode::BadAlloc));
+
+    handle(&mut connection, &mut server, &mut handler, [create_ic()]);
+    assert_eq!(last_error(&mut server), Some(ErrorCode::BadAlloc));
+    assert_eq!(connection.iter_ics_mut().count(), 1);
+}
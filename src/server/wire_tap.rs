@@ -0,0 +1,14 @@
+/// Observer of the raw messages exchanged with clients, e.g. to capture the traffic for a bug
+/// report.
+///
+/// Messages are passed as they're on the wire, after joining `_XIM_MOREDATA` parts of incoming
+/// ones and in the byte order of the client. Outgoing ones are passed before they're split into
+/// ClientMessages, without the padding of the last one, whichever backend sends them.
+#[allow(unused_variables)]
+pub trait WireTap {
+    /// Called with a message from `client_win` before it's parsed
+    fn on_receive(&mut self, client_win: u32, data: &[u8]) {}
+
+    /// Called with a message to `client_win` after it's serialized
+    fn on_send(&mut self, client_win: u32, data: &[u8]) {}
+}
@@ -0,0 +1,47 @@
+//! Logging of XIM messages, emitted as `tracing` events when the `tracing` feature is enabled
+
+use xim_parser::Request;
+
+/// `req` is written to the peer
+#[inline]
+pub(crate) fn request_sent(req: &Request) {
+    #[cfg(feature = "tracing")]
+    {
+        if tracing::enabled!(tracing::Level::TRACE) {
+            tracing::trace!(request = req.name(), "->: {:?}", req);
+        } else {
+            tracing::debug!(request = req.name(), "->");
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        if log::log_enabled!(log::Level::Trace) {
+            log::trace!("->: {:?}", req);
+        } else {
+            log::debug!("->: {}", req.name());
+        }
+    }
+}
+
+/// `req` is read from the peer
+#[inline]
+pub(crate) fn request_received(req: &Request) {
+    #[cfg(feature = "tracing")]
+    {
+        if tracing::enabled!(tracing::Level::TRACE) {
+            tracing::trace!(request = req.name(), "<-: {:?}", req);
+        } else {
+            tracing::debug!(request = req.name(), "<-");
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        if log::log_enabled!(log::Level::Trace) {
+            log::trace!("<-: {:?}", req);
+        } else {
+            log::debug!("<-: {}", req.name());
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Helpers for the X transport shared by every backend.
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use xim_parser::{Endian, ReadError, Request};
+
+#[cfg(all(feature = "client", feature = "std"))]
+use crate::client::{ClientError, Quirks};
+
+/// Size of data that fits in one ClientMessage
+pub(crate) const CM_DATA_SIZE: usize = 20;
+
+/// Size of the largest XIM message, its length is counted in 4 bytes by a `u16` after the
+/// 4 bytes header
+pub(crate) const MAX_MESSAGE_SIZE: usize = 4 + u16::MAX as usize * 4;
+
+/// Pad `buf` to a multiple of [`CM_DATA_SIZE`] and split it into ClientMessage data.
+///
+/// The flag is `true` for the last message which must be sent as `_XIM_PROTOCOL`, every message
+/// before it must be sent as `_XIM_MOREDATA`.
+pub(crate) fn split_client_messages(
+    buf: &mut Vec<u8>,
+) -> impl Iterator<Item = ([u8; CM_DATA_SIZE], bool)> + '_ {
+    let last = (buf.len().max(1) - 1) / CM_DATA_SIZE;
+    buf.resize((last + 1) * CM_DATA_SIZE, 0);
+
+    buf.chunks(CM_DATA_SIZE)
+        .enumerate()
+        .map(move |(i, chunk)| (chunk.try_into().unwrap(), i == last))
+}
+
+/// Concatenates `_XIM_MOREDATA` messages until the final `_XIM_PROTOCOL` message arrives.
+#[derive(Debug, Default)]
+pub(crate) struct MessageBuffer {
+    buf: Vec<u8>,
+}
+
+impl MessageBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append data of `_XIM_MOREDATA` message.
+    ///
+    /// The buffered data is dropped with an error when it grows over [`MAX_MESSAGE_SIZE`], so a
+    /// peer which never sends the final `_XIM_PROTOCOL` message can't grow it without bound.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), ReadError> {
+        let len = self.buf.len() + data.len();
+
+        if len > MAX_MESSAGE_SIZE {
+            self.buf = Vec::new();
+            return Err(ReadError::InvalidData(
+                "MessageLength",
+                format!("{} bytes of _XIM_MOREDATA", len),
+            ));
+        }
+
+        self.buf.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// No `_XIM_MOREDATA` message is buffered
+    #[cfg(feature = "server")]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Data of `_XIM_MOREDATA` messages buffered so far
+    #[cfg(feature = "server")]
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Read request with data of the final `_XIM_PROTOCOL` message
+    pub fn read(&mut self, data: &[u8], endian: Endian) -> Result<Request, ReadError> {
+        if self.buf.is_empty() {
+            return xim_parser::read_with_endian(data, endian);
+        }
+
+        self.buf.extend_from_slice(data);
+        let req = xim_parser::read_with_endian(&self.buf, endian);
+        self.buf.clear();
+        req
+    }
+}
+
+/// Part of a property read by `GetProperty`
+#[cfg(all(feature = "client", feature = "std"))]
+pub(crate) struct PropertyChunk {
+    /// Format of the property, `0` when it doesn't exist
+    pub format: u8,
+    pub data: Vec<u8>,
+    /// Bytes left after `data`
+    pub bytes_after: u32,
+}
+
+/// Read the request in the property of a format 32 `_XIM_PROTOCOL` message.
+///
+/// `fetch(offset, length)` gets and deletes the property like `GetProperty`, both are in 4 byte
+/// units. The property is read again while it's shorter than the declared `length`, then
+/// checked against it. Return `None` for an empty property when
+/// [`Quirks::ignore_empty_messages`] is set.
+#[cfg(all(feature = "client", feature = "std"))]
+pub(crate) fn read_property_request(
+    length: u32,
+    quirks: Quirks,
+    mut fetch: impl FnMut(u32, u32) -> Result<PropertyChunk, ClientError>,
+) -> Result<Option<Request>, ClientError> {
+    let length = length as usize;
+    let mut data = Vec::with_capacity(length);
+
+    loop {
+        let remaining = length.saturating_sub(data.len());
+        let chunk = fetch((data.len() / 4) as u32, ((remaining + 3) / 4) as u32)?;
+
+        if chunk.format != 8 && !chunk.data.is_empty() {
+            log::warn!("Property of format {} is not a XIM message", chunk.format);
+            return Err(ClientError::InvalidReply);
+        }
+
+        let partial = !chunk.data.is_empty() && chunk.bytes_after != 0;
+        data.extend_from_slice(&chunk.data);
+
+        if !partial || data.len() >= length {
+            if chunk.bytes_after != 0 {
+                log::warn!("{} bytes are left in the property", chunk.bytes_after);
+            }
+            break;
+        }
+    }
+
+    if data.is_empty() && quirks.ignore_empty_messages {
+        log::debug!("Skip empty property");
+        return Ok(None);
+    }
+
+    if data.len() < length {
+        log::warn!(
+            "Property has {} bytes but {} bytes are declared",
+            data.len(),
+            length
+        );
+        return Err(ClientError::InvalidReply);
+    }
+
+    data.truncate(length);
+
+    Ok(Some(xim_parser::read(&data)?))
+}
+
+/// Transport picked from the `TRANSPORT` selection of the server
+#[cfg(all(feature = "client", feature = "std"))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Transport<'a> {
+    X,
+    /// `host:port` of the server socket
+    Tcp(&'a str),
+}
+
+/// Pick a supported transport from `@transport=X/,tcp/host:port,...`, `X/` is preferred
+#[cfg(all(feature = "client", feature = "std"))]
+pub(crate) fn select_transport(value: &[u8]) -> Option<Transport<'_>> {
+    let value = core::str::from_utf8(value.strip_prefix(b"@transport=")?).ok()?;
+    let mut tcp = None;
+
+    for transport in value.trim_end_matches('\0').split(',') {
+        let transport = transport.trim();
+
+        if transport == "X/" {
+            return Some(Transport::X);
+        } else if let Some(address) = transport.strip_prefix("tcp/") {
+            tcp = tcp.or(Some(Transport::Tcp(address)));
+        }
+    }
+
+    tcp
+}
+
+/// XIM messages written directly to a socket instead of X events.
+#[cfg(all(feature = "client", feature = "std"))]
+pub(crate) struct TcpTransport {
+    address: alloc::string::String,
+    stream: std::net::TcpStream,
+    buf: Vec<u8>,
+    closed: bool,
+}
+
+#[cfg(all(feature = "client", feature = "std"))]
+impl TcpTransport {
+    pub fn connect(address: &str) -> std::io::Result<Self> {
+        let stream = std::net::TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            address: address.into(),
+            stream,
+            buf: Vec::with_capacity(1024),
+            closed: false,
+        })
+    }
+
+    /// `host:port` advertised by the server
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn stream(&self) -> &std::net::TcpStream {
+        &self.stream
+    }
+
+    pub fn send(&mut self, req: &Request) -> std::io::Result<()> {
+        use std::io::Write;
+        use xim_parser::XimWrite;
+
+        crate::trace::request_sent(req);
+        let mut buf = alloc::vec![0; req.size()];
+        xim_parser::write(req, &mut buf);
+        self.stream.write_all(&buf)
+    }
+
+    /// Read bytes arrived without blocking, return `true` when a request or EOF is ready
+    pub fn fill(&mut self) -> std::io::Result<bool> {
+        use std::io::{ErrorKind, Read};
+
+        let mut chunk = [0; 1024];
+        self.stream.set_nonblocking(true)?;
+
+        let ret = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.closed = true;
+                    break Ok(());
+                }
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.stream.set_nonblocking(false)?;
+        ret?;
+
+        Ok(self.closed || self.message_len().is_some())
+    }
+
+    /// Take the next buffered request
+    pub fn read(&mut self) -> Result<Option<Request>, ReadError> {
+        let len = match self.message_len() {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        let req = xim_parser::read(&self.buf[..len]);
+        self.buf.drain(..len);
+        req.map(Some)
+    }
+
+    /// The server closed the socket and every buffered request is read
+    pub fn is_closed(&self) -> bool {
+        self.closed && self.message_len().is_none()
+    }
+
+    /// Length of the first message when it's fully buffered, the server writes in the byte
+    /// order of the client
+    fn message_len(&self) -> Option<usize> {
+        let header = self.buf.get(..4)?;
+        let len = 4 + u16::from_ne_bytes([header[2], header[3]]) as usize * 4;
+
+        if self.buf.len() >= len {
+            Some(len)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageBuffer, CM_DATA_SIZE, MAX_MESSAGE_SIZE};
+    use xim_parser::{Endian, ReadError, Request};
+
+    #[test]
+    fn message_buffer_cap() {
+        let mut buffer = MessageBuffer::new();
+        let chunk = [0; CM_DATA_SIZE];
+
+        for _ in 0..MAX_MESSAGE_SIZE / CM_DATA_SIZE {
+            buffer.push(&chunk).unwrap();
+        }
+
+        assert!(matches!(
+            buffer.push(&chunk),
+            Err(ReadError::InvalidData("MessageLength", _))
+        ));
+        assert!(buffer.buf.is_empty());
+
+        // next message is read from scratch
+        let req = Request::Disconnect {};
+        let data = xim_parser::write_to_vec(&req);
+        assert_eq!(buffer.read(&data, Endian::Native).unwrap(), req);
+    }
+}
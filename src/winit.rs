@@ -0,0 +1,145 @@
+//! Adapter which turns XIM into winit style IME events.
+//!
+//! winit has no XIM support of its own on some setups, [`ImeAdapter`] drives a [`XimSession`]
+//! for a window given as [`RawWindowHandle`] and reports what happened as [`Ime`] events,
+//! which map one to one to `winit::event::Ime`.
+//!
+//! ```ignore
+//! let window = window.window_handle()?.as_raw();
+//! let client = X11rbClient::init(conn, screen_num, None)?;
+//! let mut ime = ImeAdapter::new(client, "en_US", window).expect("X11 window");
+//!
+//! // for every X event
+//! if !ime.filter_event(&e)? {
+//!     if let Event::KeyPress(e) | Event::KeyRelease(e) = e {
+//!         ime.forward_key(&e)?;
+//!     }
+//! }
+//!
+//! while let Some(event) = ime.next_event() {
+//!     // handle it like `WindowEvent::Ime`
+//! }
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::convert::TryFrom;
+use raw_window_handle::RawWindowHandle;
+
+use crate::client::{BlockingClient, ClientError, SessionConfig, SessionEvent, XimSession};
+
+/// IME events in the shape of `winit::event::Ime`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Ime {
+    /// The input context is ready
+    Enabled,
+    /// The preedit text is changed, the cursor is a byte range of the text. An empty text with
+    /// no cursor clears the preedit.
+    Preedit(String, Option<(usize, usize)>),
+    /// Text is committed, the preedit is cleared before it
+    Commit(String),
+    /// The input context is gone with the server
+    Disabled,
+}
+
+/// X11 window id of `handle`, `None` for other platforms
+pub fn x11_window(handle: &RawWindowHandle) -> Option<u32> {
+    match handle {
+        RawWindowHandle::Xlib(handle) => u32::try_from(handle.window).ok(),
+        RawWindowHandle::Xcb(handle) => Some(handle.window.get()),
+        _ => None,
+    }
+}
+
+/// Maps XIM callbacks of a window to [`Ime`] events, see [module documentation](self)
+pub struct ImeAdapter<C: BlockingClient> {
+    session: XimSession<C>,
+    preedit: bool,
+    events: VecDeque<Ime>,
+    forwarded_keys: VecDeque<C::XEvent>,
+}
+
+impl<C: BlockingClient> ImeAdapter<C> {
+    /// Set up an input context for `window`, return `None` when it's not an X11 window
+    pub fn new(client: C, locale: &str, window: RawWindowHandle) -> Option<Self> {
+        let window = x11_window(&window)?;
+
+        Some(Self {
+            session: XimSession::new(client, SessionConfig::new(locale, window)),
+            preedit: false,
+            events: VecDeque::new(),
+            forwarded_keys: VecDeque::new(),
+        })
+    }
+
+    pub fn session(&self) -> &XimSession<C> {
+        &self.session
+    }
+
+    pub fn session_mut(&mut self) -> &mut XimSession<C> {
+        &mut self.session
+    }
+
+    /// Filter an event of the backend, return `false` when it's not related to XIM
+    pub fn filter_event(&mut self, e: &C::Event) -> Result<bool, ClientError> {
+        let consumed = self.session.filter_event(e)?;
+        self.collect();
+        Ok(consumed)
+    }
+
+    /// Pass a key event to the server, return `false` when the application should process it
+    /// by itself
+    pub fn forward_key(&mut self, xev: &C::XEvent) -> Result<bool, ClientError> {
+        self.session.forward_key(xev)
+    }
+
+    /// Place the candidate window near the cursor, like `Window::set_ime_cursor_area`
+    pub fn set_cursor_area(&mut self, x: i16, y: i16) -> Result<(), ClientError> {
+        self.session.set_spot(x, y)
+    }
+
+    /// Take the next IME event
+    pub fn next_event(&mut self) -> Option<Ime> {
+        self.events.pop_front()
+    }
+
+    /// Take the next key event the server passed back, the application should process it like
+    /// a key event of the window
+    pub fn next_forwarded_key(&mut self) -> Option<C::XEvent> {
+        self.forwarded_keys.pop_front()
+    }
+
+    fn clear_preedit(&mut self) {
+        if self.preedit {
+            self.preedit = false;
+            self.events.push_back(Ime::Preedit(String::new(), None));
+        }
+    }
+
+    fn collect(&mut self) {
+        while let Some(event) = self.session.next_event() {
+            match event {
+                SessionEvent::Ready => self.events.push_back(Ime::Enabled),
+                SessionEvent::Commit(text) => {
+                    self.clear_preedit();
+                    self.events.push_back(Ime::Commit(text));
+                }
+                SessionEvent::PreeditUpdate { text, .. } if text.is_empty() => self.clear_preedit(),
+                SessionEvent::PreeditUpdate { text, caret } => {
+                    let cursor = text
+                        .char_indices()
+                        .nth(caret)
+                        .map_or(text.len(), |(pos, _)| pos);
+                    self.preedit = true;
+                    self.events
+                        .push_back(Ime::Preedit(text, Some((cursor, cursor))));
+                }
+                SessionEvent::ForwardedKey(xev) => self.forwarded_keys.push_back(xev),
+                SessionEvent::Disconnected => {
+                    self.clear_preedit();
+                    self.events.push_back(Ime::Disabled);
+                }
+            }
+        }
+    }
+}
@@ -5,24 +5,47 @@
 //!
 //! [`x11rb`]: https://crates.io/crates/x11rb
 
+#[cfg(feature = "x11rb-server")]
+use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
-use std::{convert::TryInto, rc::Rc, sync::Arc};
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::{rc::Rc, sync::Arc};
 use x11rb::protocol::xproto::EventMask;
 
 #[cfg(feature = "x11rb-client")]
 use crate::client::{
-    handle_request as client_handle_request, ClientCore, ClientError, ClientHandler,
+    dispatch_filtered, send_connect, BlockingClient, ClientCore, ClientError, ClientHandler,
+    ClientState, Filtered, Quirks, TransportInfo,
 };
+#[cfg(all(feature = "x11rb-server", feature = "metrics"))]
+use crate::server::ServerStats;
 #[cfg(feature = "x11rb-server")]
-use crate::server::{ServerCore, ServerError, ServerHandler, XimConnection, XimConnections};
-#[cfg(feature = "x11rb-client")]
+use crate::server::{
+    ServerCore, ServerError, ServerHandler, WireTap, XimConnection, XimConnections,
+};
 use crate::AHashMap;
 #[cfg(feature = "x11rb-client")]
+use alloc::collections::VecDeque;
+#[cfg(feature = "x11rb-client")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "x11rb-client")]
 use xim_parser::{Attr, AttributeName};
 
-use crate::Atoms;
+#[cfg(feature = "x11rb-client")]
+use crate::transport::{
+    read_property_request, select_transport, MessageBuffer, PropertyChunk, TcpTransport, Transport,
+};
+use crate::{transport::split_client_messages, Atoms};
+#[cfg(feature = "x11rb-client")]
+use core::convert::TryFrom;
+#[cfg(feature = "x11rb-client")]
+use rustix::event::{PollFd, PollFlags, Timespec};
+#[cfg(feature = "x11rb-client")]
+use std::net::TcpStream;
+#[cfg(feature = "x11rb-client")]
+use std::os::unix::io::{AsRawFd, RawFd};
 
 #[cfg(feature = "x11rb-xcb")]
 use x11rb::xcb_ffi::XCBConnection;
@@ -33,18 +56,19 @@ use x11rb::{
     errors::{ConnectError, ConnectionError, ParseError, ReplyError, ReplyOrIdError},
     protocol::{
         xproto::{
-            Atom, AtomEnum, ClientMessageEvent, ConnectionExt, KeyPressEvent, PropMode, Screen,
+            Atom, AtomEnum, ButtonPressEvent, ChangeWindowAttributesAux, ClientMessageEvent,
+            ConnectionExt, KeyButMask, KeyPressEvent, MotionNotifyEvent, PropMode, Screen,
             SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass, CLIENT_MESSAGE_EVENT,
             SELECTION_NOTIFY_EVENT,
         },
         Event,
     },
-    rust_connection::RustConnection,
+    rust_connection::{RustConnection, Stream},
     wrapper::ConnectionExt as _,
     COPY_DEPTH_FROM_PARENT, CURRENT_TIME,
 };
 
-use xim_parser::{Request, XimWrite};
+use xim_parser::{Endian, Request, XimWrite};
 
 macro_rules! convert_error {
     ($($ty:ty,)+) => {
@@ -99,6 +123,26 @@ impl HasConnection for RustConnection {
     }
 }
 
+/// Connection which has a file descriptor to poll
+pub trait ConnectionFd {
+    fn connection_fd(&self) -> BorrowedFd<'_>;
+}
+
+#[cfg(feature = "x11rb-xcb")]
+impl ConnectionFd for XCBConnection {
+    #[inline(always)]
+    fn connection_fd(&self) -> BorrowedFd<'_> {
+        self.as_fd()
+    }
+}
+
+impl<S: Stream + AsFd> ConnectionFd for RustConnection<S> {
+    #[inline(always)]
+    fn connection_fd(&self) -> BorrowedFd<'_> {
+        self.stream().as_fd()
+    }
+}
+
 #[cfg(feature = "x11rb-client")]
 impl<C: HasConnection> HasConnection for X11rbClient<C> {
     type Connection = C::Connection;
@@ -119,7 +163,7 @@ impl<C: HasConnection> HasConnection for X11rbServer<C> {
     }
 }
 
-impl<'x, C: HasConnection> HasConnection for &'x C {
+impl<C: HasConnection> HasConnection for &C {
     type Connection = C::Connection;
 
     #[inline(always)]
@@ -151,9 +195,18 @@ pub struct X11rbServer<C: HasConnection> {
     has_conn: C,
     locale_data: String,
     im_win: Window,
+    root: Window,
+    server_names: Vec<Atom>,
+    /// Whether `server_names` are still in `XIM_SERVERS` by us
+    registered: bool,
     atoms: Atoms<Atom>,
     buf: Vec<u8>,
     sequence: u16,
+    /// Byte order of clients which differ from ours, keyed by their windows
+    client_endians: AHashMap<u32, Endian>,
+    #[cfg(feature = "metrics")]
+    stats: ServerStats,
+    wire_tap: Option<Box<dyn WireTap>>,
 }
 
 #[cfg(feature = "x11rb-server")]
@@ -164,7 +217,18 @@ impl<C: HasConnection> X11rbServer<C> {
         im_name: &str,
         locales: &str,
     ) -> Result<Self, ServerError> {
-        let im_name = format!("@server={}", im_name);
+        Self::init_with_names(has_conn, screen_num, &[im_name], locales)
+    }
+
+    /// Register the server under every name of `im_names`, e.g. the name of another input
+    /// method which some applications hardcode in `XMODIFIERS`. Clients of every name are
+    /// handled by the same window and [`XimConnections`].
+    pub fn init_with_names(
+        has_conn: C,
+        screen_num: usize,
+        im_names: &[&str],
+        locales: &str,
+    ) -> Result<Self, ServerError> {
         let conn = has_conn.conn();
         let screen = &conn.setup().roots[screen_num];
         let im_win = conn.generate_id()?;
@@ -200,29 +264,41 @@ impl<C: HasConnection> X11rbServer<C> {
             return Err(ServerError::InvalidReply);
         }
 
-        let server_name = conn.intern_atom(false, im_name.as_bytes())?.reply()?.atom;
+        let prev_servers: Vec<Atom> = if reply.type_ != x11rb::NONE {
+            reply.value32().ok_or(ServerError::InvalidReply)?.collect()
+        } else {
+            Vec::new()
+        };
 
-        let mut found = false;
+        let mut server_names = Vec::with_capacity(im_names.len());
+        let mut new_servers = Vec::new();
 
-        if reply.type_ != x11rb::NONE {
-            for prop in reply.value32().ok_or(ServerError::InvalidReply)? {
-                if prop == server_name {
-                    log::info!("Found previous XIM_SERVER it will overrided");
-                    found = true;
-                }
+        for im_name in im_names {
+            let im_name = format!("@server={}", im_name);
+            let server_name = conn.intern_atom(false, im_name.as_bytes())?.reply()?.atom;
+
+            if server_names.contains(&server_name) {
+                continue;
+            }
+
+            if prev_servers.contains(&server_name) {
+                log::info!("Found previous XIM_SERVER it will overrided");
+            } else {
+                new_servers.push(server_name);
             }
-        }
 
-        // override owner
-        conn.set_selection_owner(im_win, server_name, x11rb::CURRENT_TIME)?;
+            // override owner
+            conn.set_selection_owner(im_win, server_name, x11rb::CURRENT_TIME)?;
+            server_names.push(server_name);
+        }
 
-        if !found {
+        if !new_servers.is_empty() {
             conn.change_property32(
                 PropMode::PREPEND,
                 screen.root,
                 atoms.XIM_SERVERS,
                 AtomEnum::ATOM,
-                &[server_name],
+                &new_servers,
             )?;
         }
 
@@ -230,16 +306,94 @@ impl<C: HasConnection> X11rbServer<C> {
 
         log::info!("Start server win: {}", im_win);
 
+        let root = screen.root;
+
         Ok(Self {
             has_conn,
             locale_data: format!("@locale={}", locales),
             im_win,
+            root,
+            server_names,
+            registered: true,
             atoms,
             buf: Vec::with_capacity(1024),
             sequence: 0,
+            client_endians: AHashMap::with_hasher(Default::default()),
+            #[cfg(feature = "metrics")]
+            stats: ServerStats::new(),
+            wire_tap: None,
         })
     }
 
+    /// Pass every raw message exchanged with clients to `tap`, `None` removes it
+    pub fn set_wire_tap(&mut self, tap: Option<Box<dyn WireTap>>) {
+        self.wire_tap = tap;
+    }
+
+    /// Clients of `connections` and requests exchanged with them
+    #[cfg(feature = "metrics")]
+    pub fn stats<T>(&self, connections: &XimConnections<T>) -> ServerStats {
+        self.stats.snapshot(connections)
+    }
+
+    /// Remove the server from `XIM_SERVERS` of the root window and give up the selection, so
+    /// clients don't try to connect to it anymore. It's done on drop too.
+    ///
+    /// Names of which another server took the selection are not removed.
+    pub fn unregister(&mut self) -> Result<(), ServerError> {
+        if !self.registered {
+            return Ok(());
+        }
+
+        self.registered = false;
+
+        let conn = self.has_conn.conn();
+        let mut owned = Vec::with_capacity(self.server_names.len());
+
+        for &server_name in self.server_names.iter() {
+            let owner = conn.get_selection_owner(server_name)?.reply()?.owner;
+
+            if owner != self.im_win {
+                log::info!("XIM_SERVER is owned by another server");
+                continue;
+            }
+
+            conn.set_selection_owner(x11rb::NONE, server_name, x11rb::CURRENT_TIME)?;
+            owned.push(server_name);
+        }
+
+        if owned.is_empty() {
+            return Ok(());
+        }
+
+        let reply = conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms.XIM_SERVERS,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+
+        if let Some(servers) = reply.value32() {
+            let servers: Vec<Atom> = servers.filter(|s| !owned.contains(s)).collect();
+
+            conn.change_property32(
+                PropMode::REPLACE,
+                self.root,
+                self.atoms.XIM_SERVERS,
+                AtomEnum::ATOM,
+                &servers,
+            )?;
+        }
+
+        conn.flush()?;
+
+        Ok(())
+    }
+
     pub fn filter_event<T>(
         &mut self,
         e: &Event,
@@ -275,6 +429,11 @@ impl<C: HasConnection> X11rbServer<C> {
                     )?;
                     let client_win = msg.data.as_data32()[0];
                     log::info!("XConnected with {}", client_win);
+                    // clean up the connection when the client dies without `Disconnect`
+                    self.conn().change_window_attributes(
+                        client_win,
+                        &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+                    )?;
                     self.conn().send_event(
                         false,
                         client_win,
@@ -290,11 +449,20 @@ impl<C: HasConnection> X11rbServer<C> {
                     )?;
                     self.conn().flush()?;
                     connections.new_connection(com_win, client_win);
+                } else if msg.type_ == self.atoms.XIM_MOREDATA {
+                    if let Some(connection) = connections.get_connection(msg.window) {
+                        connection.message_buffer.push(&msg.data.as_data8())?;
+                    } else {
+                        log::warn!("Unknown connection");
+                    }
                 } else if msg.type_ == self.atoms.XIM_PROTOCOL {
                     if let Some(connection) = connections.get_connection(msg.window) {
                         self.handle_xim_protocol(msg, connection, handler)?;
                         if connection.disconnected {
+                            self.client_endians.remove(&connection.client_win);
                             connections.remove_connection(msg.window);
+                        } else {
+                            connections.update_focus(msg.window);
                         }
                     } else {
                         log::warn!("Unknown connection");
@@ -303,6 +471,29 @@ impl<C: HasConnection> X11rbServer<C> {
 
                 Ok(true)
             }
+            Event::DestroyNotify(e) => {
+                let com_win = connections
+                    .connections
+                    .iter()
+                    .find(|(_, connection)| connection.client_win == e.window)
+                    .map(|(com_win, _)| *com_win);
+
+                match com_win {
+                    Some(com_win) => {
+                        log::info!("Client window {} is destroyed", e.window);
+                        self.client_endians.remove(&e.window);
+
+                        if let Some(mut connection) = connections.remove_connection(com_win) {
+                            connection.disconnect(self, handler)?;
+                        }
+
+                        self.conn().destroy_window(com_win)?;
+                        self.conn().flush()?;
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            }
             _ => Ok(false),
         }
     }
@@ -313,18 +504,31 @@ impl<C: HasConnection> X11rbServer<C> {
         connection: &mut XimConnection<T>,
         handler: &mut impl ServerHandler<Self, InputContextData = T>,
     ) -> Result<(), ServerError> {
-        if msg.format == 32 {
+        let req = if msg.format == 32 {
             let [length, atom, ..] = msg.data.as_data32();
             let data = self
                 .conn()
                 .get_property(true, msg.window, atom, AtomEnum::ANY, 0, length)?
                 .reply()?
                 .value;
-            let req = xim_parser::read(&data)?;
-            connection.handle_request(self, req, handler)
+            connection.read_request(&data, self.wire_tap.as_deref_mut())?
+        } else {
+            connection.read_request(&msg.data.as_data8(), self.wire_tap.as_deref_mut())?
+        };
+
+        #[cfg(feature = "metrics")]
+        self.stats.request_received(&req);
+        self.update_client_endian(connection);
+        connection.handle_request(self, req, handler)
+    }
+
+    /// Replies are written in the byte order `Connect` declared
+    fn update_client_endian<T>(&mut self, connection: &XimConnection<T>) {
+        if connection.client_endian() == Endian::Native {
+            self.client_endians.remove(&connection.client_win);
         } else {
-            let req = xim_parser::read(&msg.data.as_data8())?;
-            connection.handle_request(self, req, handler)
+            self.client_endians
+                .insert(connection.client_win, connection.client_endian());
         }
     }
 
@@ -358,20 +562,51 @@ impl<C: HasConnection> X11rbServer<C> {
     }
 }
 
+#[cfg(feature = "x11rb-server")]
+impl<C: HasConnection> Drop for X11rbServer<C> {
+    fn drop(&mut self) {
+        if let Err(e) = self.unregister() {
+            log::warn!("Can't unregister XIM server: {}", e);
+        }
+    }
+}
+
 #[cfg(feature = "x11rb-server")]
 impl<C: HasConnection> ServerCore for X11rbServer<C> {
     type XEvent = KeyPressEvent;
 
     fn send_req(&mut self, client_win: u32, req: Request) -> Result<(), ServerError> {
-        send_req_impl(
+        #[cfg(feature = "metrics")]
+        self.stats.request_sent(&req);
+        let endian = self
+            .client_endians
+            .get(&client_win)
+            .copied()
+            .unwrap_or(Endian::Native);
+
+        send_req_impl::<_, ServerError>(
             &self.has_conn,
             &self.atoms,
             client_win,
             &mut self.buf,
             &mut self.sequence,
             20,
+            true,
+            endian,
             &req,
-        )
+        )?;
+
+        if let Some(tap) = self.wire_tap.as_mut() {
+            // drop the padding of ClientMessages
+            tap.on_send(client_win, &self.buf[..req.size()]);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
+        serialize_event_impl(xev)
     }
 
     #[inline]
@@ -383,6 +618,9 @@ impl<C: HasConnection> ServerCore for X11rbServer<C> {
 #[cfg(feature = "x11rb-client")]
 pub struct X11rbClient<C: HasConnection> {
     has_conn: C,
+    root: Window,
+    im_name: String,
+    wait_server: bool,
     server_owner_window: Window,
     im_window: Window,
     server_atom: Atom,
@@ -393,6 +631,19 @@ pub struct X11rbClient<C: HasConnection> {
     ic_attributes: AHashMap<AttributeName, u16>,
     sequence: u16,
     buf: Vec<u8>,
+    message_buffer: MessageBuffer,
+    tcp: Option<TcpTransport>,
+    pending_events: VecDeque<Event>,
+    keyboard_mapping: Option<KeyboardMapping>,
+    state: ClientState,
+}
+
+/// Cached reply of `GetKeyboardMapping`
+#[cfg(feature = "x11rb-client")]
+struct KeyboardMapping {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
 }
 
 #[cfg(feature = "x11rb-client")]
@@ -402,8 +653,52 @@ impl<C: HasConnection> X11rbClient<C> {
         screen_num: usize,
         im_name: Option<&str>,
     ) -> Result<Self, ClientError> {
+        let mut client = Self::new(has_conn, screen_num, im_name)?;
+
+        if client.try_connect()? {
+            Ok(client)
+        } else {
+            Err(ClientError::NoXimServer)
+        }
+    }
+
+    /// Initialize a client which connects to the XIM server whenever it appears.
+    ///
+    /// Unlike [`X11rbClient::init`], this doesn't fail when the server is not running yet. The
+    /// client watches `XIM_SERVERS` of the root window and starts the handshake when a matching
+    /// server is registered, [`ClientHandler::handle_connect`] is called when it's done. The
+    /// client also reconnects when the server is restarted.
+    pub fn init_lazy(
+        has_conn: C,
+        screen_num: usize,
+        im_name: Option<&str>,
+    ) -> Result<Self, ClientError> {
+        let mut client = Self::new(has_conn, screen_num, im_name)?;
+        client.wait_server = true;
+
+        // keep events selected by the application
+        let conn = client.conn();
+        let event_mask = conn
+            .get_window_attributes(client.root)?
+            .reply()?
+            .your_event_mask;
+        conn.change_window_attributes(
+            client.root,
+            &ChangeWindowAttributesAux::new().event_mask(event_mask | EventMask::PROPERTY_CHANGE),
+        )?;
+
+        if !client.try_connect()? {
+            log::info!("Wait XIM server {}", client.im_name);
+            client.conn().flush()?;
+        }
+
+        Ok(client)
+    }
+
+    fn new(has_conn: C, screen_num: usize, im_name: Option<&str>) -> Result<Self, ClientError> {
         let conn = has_conn.conn();
         let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
         let client_window = conn.generate_id()?;
 
         conn.create_window(
@@ -429,67 +724,115 @@ impl<C: HasConnection> X11rbClient<C> {
         let atoms = Atoms::new::<ClientError, _>(|name| {
             Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
         })?;
+
+        let mut state = ClientState::new();
+        let quirks = Quirks::detect(im_name);
+        if quirks != Quirks::none() {
+            log::info!("Apply {:?} for {}", quirks, im_name);
+        }
+        state.set_quirks(quirks);
+
+        Ok(Self {
+            has_conn,
+            root,
+            im_name: im_name.into(),
+            wait_server: false,
+            atoms,
+            server_atom: x11rb::NONE,
+            server_owner_window: x11rb::NONE,
+            im_attributes: AHashMap::with_hasher(Default::default()),
+            ic_attributes: AHashMap::with_hasher(Default::default()),
+            im_window: x11rb::NONE,
+            transport_max: 20,
+            client_window,
+            sequence: 0,
+            buf: Vec::with_capacity(1024),
+            message_buffer: MessageBuffer::new(),
+            tcp: None,
+            pending_events: VecDeque::new(),
+            keyboard_mapping: None,
+            state,
+        })
+    }
+
+    /// Find the server in `XIM_SERVERS` and start the handshake, return `false` when it's not
+    /// registered
+    fn try_connect(&mut self) -> Result<bool, ClientError> {
+        let conn = self.has_conn.conn();
         let server_reply = conn
             .get_property(
                 false,
-                screen.root,
-                atoms.XIM_SERVERS,
+                self.root,
+                self.atoms.XIM_SERVERS,
                 AtomEnum::ATOM,
                 0,
                 u32::MAX,
             )?
             .reply()?;
 
+        // no server is registered yet
+        if server_reply.type_ == x11rb::NONE {
+            return Ok(false);
+        }
+
         if server_reply.type_ != u32::from(AtomEnum::ATOM) || server_reply.format != 32 {
-            Err(ClientError::InvalidReply)
-        } else {
-            for server_atom in server_reply.value32().ok_or(ClientError::InvalidReply)? {
-                let server_owner = conn.get_selection_owner(server_atom)?.reply()?.owner;
-                let name = conn.get_atom_name(server_atom)?.reply()?.name;
-
-                let name = match String::from_utf8(name) {
-                    Ok(name) => name,
-                    _ => continue,
-                };
-
-                if let Some(name) = name.strip_prefix("@server=") {
-                    if name == im_name {
-                        conn.convert_selection(
-                            client_window,
-                            server_atom,
-                            atoms.TRANSPORT,
-                            atoms.TRANSPORT,
-                            CURRENT_TIME,
-                        )?;
+            return Err(ClientError::InvalidReply);
+        }
 
-                        conn.flush()?;
-
-                        return Ok(Self {
-                            has_conn,
-                            atoms,
-                            server_atom,
-                            server_owner_window: server_owner,
-                            im_attributes: AHashMap::with_hasher(Default::default()),
-                            ic_attributes: AHashMap::with_hasher(Default::default()),
-                            im_window: x11rb::NONE,
-                            transport_max: 20,
-                            client_window,
-                            sequence: 0,
-                            buf: Vec::with_capacity(1024),
-                        });
-                    }
-                }
+        for server_atom in server_reply.value32().ok_or(ClientError::InvalidReply)? {
+            let server_owner = conn.get_selection_owner(server_atom)?.reply()?.owner;
+
+            // stale entry of an exited server
+            if server_owner == x11rb::NONE {
+                continue;
             }
 
-            Err(ClientError::NoXimServer)
+            let name = conn.get_atom_name(server_atom)?.reply()?.name;
+
+            let name = match String::from_utf8(name) {
+                Ok(name) => name,
+                _ => continue,
+            };
+
+            if name.strip_prefix("@server=") == Some(self.im_name.as_str()) {
+                conn.convert_selection(
+                    self.client_window,
+                    server_atom,
+                    self.atoms.TRANSPORT,
+                    self.atoms.TRANSPORT,
+                    CURRENT_TIME,
+                )?;
+
+                // notified by DestroyNotify when the server exits
+                conn.change_window_attributes(
+                    server_owner,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+                )?;
+
+                conn.flush()?;
+
+                self.server_atom = server_atom;
+                self.server_owner_window = server_owner;
+
+                return Ok(true);
+            }
         }
+
+        Ok(false)
     }
 
+    /// Filter an event and call the handler if it is relevant.
     pub fn filter_event(
         &mut self,
         e: &Event,
         handler: &mut impl ClientHandler<Self>,
     ) -> Result<bool, ClientError> {
+        let filtered = self.process_event(e)?;
+        dispatch_filtered(self, handler, filtered)
+    }
+
+    /// Handle the transport part of `e`, handlers are not called here
+    pub(crate) fn process_event(&mut self, e: &Event) -> Result<Filtered, ClientError> {
         match e {
             Event::SelectionNotify(e) if e.requestor == self.client_window => {
                 if e.property == self.atoms.LOCALES {
@@ -506,9 +849,13 @@ impl<C: HasConnection> X11rbClient<C> {
                         )?
                         .reply()?;
 
-                    self.xconnect()?;
+                    if self.tcp.is_some() {
+                        self.tcp_connect()?;
+                    } else {
+                        self.xconnect()?;
+                    }
 
-                    Ok(true)
+                    Ok(Filtered::Consumed)
                 } else if e.property == self.atoms.TRANSPORT {
                     let transport = self
                         .conn()
@@ -522,8 +869,13 @@ impl<C: HasConnection> X11rbClient<C> {
                         )?
                         .reply()?;
 
-                    if !transport.value.starts_with(b"@transport=X/") {
-                        return Err(ClientError::UnsupportedTransport);
+                    match select_transport(&transport.value) {
+                        Some(Transport::X) => {}
+                        Some(Transport::Tcp(address)) => {
+                            log::info!("Connect XIM server on tcp/{}", address);
+                            self.tcp = Some(TcpTransport::connect(address)?);
+                        }
+                        None => return Err(ClientError::UnsupportedTransport),
                     }
 
                     self.conn().convert_selection(
@@ -536,9 +888,9 @@ impl<C: HasConnection> X11rbClient<C> {
 
                     self.conn().flush()?;
 
-                    Ok(true)
+                    Ok(Filtered::Consumed)
                 } else {
-                    Ok(false)
+                    Ok(Filtered::Ignored)
                 }
             }
             Event::ClientMessage(msg) if msg.window == self.client_window => {
@@ -553,47 +905,220 @@ impl<C: HasConnection> X11rbClient<C> {
                     );
                     self.im_window = im_window;
                     self.transport_max = max as usize;
-                    self.send_req(Request::Connect {
-                        client_major_protocol_version: 1,
-                        client_minor_protocol_version: 0,
-                        endian: xim_parser::Endian::Native,
-                        client_auth_protocol_names: Vec::new(),
-                    })?;
-                    Ok(true)
+                    self.state.set_transport(TransportInfo::new(
+                        self.im_name.clone(),
+                        major,
+                        minor,
+                        self.transport_max,
+                    ));
+                    if im_window != self.server_owner_window {
+                        self.conn().change_window_attributes(
+                            im_window,
+                            &ChangeWindowAttributesAux::new()
+                                .event_mask(EventMask::STRUCTURE_NOTIFY),
+                        )?;
+                    }
+                    send_connect(self)?;
+                    Ok(Filtered::Consumed)
+                } else if msg.type_ == self.atoms.XIM_MOREDATA {
+                    self.message_buffer.push(&msg.data.as_data8())?;
+                    Ok(Filtered::Consumed)
                 } else if msg.type_ == self.atoms.XIM_PROTOCOL {
-                    self.handle_xim_protocol(msg, handler)?;
-                    Ok(true)
+                    self.handle_xim_protocol(msg)
                 } else {
-                    Ok(false)
+                    Ok(Filtered::Ignored)
                 }
             }
-            _ => Ok(false),
+            Event::DestroyNotify(e)
+                if e.window == self.server_owner_window || e.window == self.im_window =>
+            {
+                self.server_disconnected()?;
+                Ok(Filtered::ServerDisconnected)
+            }
+            Event::PropertyNotify(e)
+                if self.wait_server
+                    && e.window == self.root
+                    && e.atom == self.atoms.XIM_SERVERS =>
+            {
+                if self.server_owner_window == x11rb::NONE {
+                    self.try_connect()?;
+                }
+                Ok(Filtered::Consumed)
+            }
+            Event::MappingNotify(_) => {
+                self.keyboard_mapping = None;
+                Ok(Filtered::Ignored)
+            }
+            _ => Ok(Filtered::Ignored),
         }
     }
 
-    fn handle_xim_protocol(
-        &mut self,
-        msg: &ClientMessageEvent,
-        handler: &mut impl ClientHandler<Self>,
-    ) -> Result<(), ClientError> {
+    fn handle_xim_protocol(&mut self, msg: &ClientMessageEvent) -> Result<Filtered, ClientError> {
         if msg.format == 32 {
             let [length, atom, ..] = msg.data.as_data32();
-            let data = self
-                .conn()
-                .get_property(true, msg.window, atom, AtomEnum::ANY, 0, length)?
-                .reply()?
-                .value;
-            let req = xim_parser::read(&data)?;
-            client_handle_request(self, handler, req)?;
+            let quirks = self.state.quirks();
+            let conn = self.has_conn.conn();
+            let req = read_property_request(length, quirks, |offset, length| {
+                let reply = conn
+                    .get_property(true, msg.window, atom, AtomEnum::ANY, offset, length)?
+                    .reply()?;
+                Ok(PropertyChunk {
+                    format: reply.format,
+                    data: reply.value,
+                    bytes_after: reply.bytes_after,
+                })
+            })?;
+            Ok(req.map_or(Filtered::Consumed, Filtered::Request))
         } else if msg.format == 8 {
-            let data = msg.data.as_data8();
-            let req: xim_parser::Request = xim_parser::read(&data)?;
-            client_handle_request(self, handler, req)?;
+            Ok(Filtered::Request(
+                self.message_buffer
+                    .read(&msg.data.as_data8(), Endian::Native)?,
+            ))
+        } else {
+            Ok(Filtered::Consumed)
+        }
+    }
+
+    fn server_disconnected(&mut self) -> Result<(), ClientError> {
+        log::warn!("XIM server is disconnected");
+
+        self.server_owner_window = x11rb::NONE;
+        self.im_window = x11rb::NONE;
+        self.im_attributes.clear();
+        self.ic_attributes.clear();
+        self.message_buffer = MessageBuffer::new();
+        self.tcp = None;
+        self.state.reset();
+
+        // the server may be registered again before its old window is destroyed
+        if self.wait_server {
+            self.try_connect()?;
+        }
+
+        Ok(())
+    }
+
+    /// File descriptor of the X connection, poll it with [`X11rbClient::tcp_stream`] and call
+    /// [`X11rbClient::poll_filter_pending`] when either is readable.
+    pub fn connection_fd(&self) -> RawFd
+    where
+        C::Connection: ConnectionFd,
+    {
+        self.conn().connection_fd().as_raw_fd()
+    }
+
+    /// Filter events which can be read without blocking and return the first one which is not
+    /// related to XIM. Requests of the TCP transport are also read when no event is left.
+    ///
+    /// Return `None` when nothing is left, wait for the fds to be readable again.
+    pub fn poll_filter_pending(
+        &mut self,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<Option<Event>, ClientError> {
+        // these are already filtered while waiting a reply
+        if let Some(e) = self.pending_events.pop_front() {
+            return Ok(Some(e));
+        }
+
+        while let Some(e) = self.conn().poll_for_event()? {
+            if !self.filter_event(&e, handler)? {
+                return Ok(Some(e));
+            }
+        }
+
+        self.filter_tcp(handler)?;
+        self.conn().flush()?;
+
+        Ok(None)
+    }
+
+    fn destroy_client_window(&mut self) -> Result<(), ClientError> {
+        self.tcp = None;
+
+        if self.client_window != x11rb::NONE {
+            self.conn().destroy_window(self.client_window)?;
+            self.conn().flush()?;
+            self.client_window = x11rb::NONE;
         }
 
         Ok(())
     }
 
+    /// Sleep until the X connection or the TCP transport is readable or `timeout` is elapsed
+    fn poll_readable(&self, timeout: Duration) -> Result<(), ClientError>
+    where
+        C::Connection: ConnectionFd,
+    {
+        let conn = self.conn().connection_fd();
+        let tcp = self.tcp_stream().map(AsFd::as_fd);
+        let mut fds = [
+            PollFd::new(&conn, PollFlags::IN),
+            PollFd::new(tcp.as_ref().unwrap_or(&conn), PollFlags::IN),
+        ];
+        let len = if tcp.is_some() { 2 } else { 1 };
+        let timeout = Timespec::try_from(timeout).ok();
+
+        match rustix::event::poll(&mut fds[..len], timeout.as_ref()) {
+            Ok(_) | Err(rustix::io::Errno::INTR) => Ok(()),
+            Err(err) => Err(std::io::Error::from(err).into()),
+        }
+    }
+
+    /// Socket of the TCP transport, poll it with the X connection and call
+    /// [`X11rbClient::filter_tcp`] when it's readable. `None` when the server uses the X transport.
+    pub fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.tcp.as_ref().map(TcpTransport::stream)
+    }
+
+    /// Read requests arrived on the TCP transport and call the handler.
+    ///
+    /// Return `false` when nothing is read.
+    pub fn filter_tcp(
+        &mut self,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<bool, ClientError> {
+        let mut consumed = false;
+
+        loop {
+            match self.process_tcp()? {
+                Filtered::Ignored => return Ok(consumed),
+                filtered => consumed |= dispatch_filtered(self, handler, filtered)?,
+            }
+        }
+    }
+
+    /// Read the next request of the TCP transport, handlers are not called here
+    pub(crate) fn process_tcp(&mut self) -> Result<Filtered, ClientError> {
+        let tcp = match self.tcp.as_mut() {
+            Some(tcp) => tcp,
+            None => return Ok(Filtered::Ignored),
+        };
+
+        tcp.fill()?;
+
+        if let Some(req) = tcp.read()? {
+            Ok(Filtered::Request(req))
+        } else if tcp.is_closed() {
+            self.server_disconnected()?;
+            Ok(Filtered::ServerDisconnected)
+        } else {
+            Ok(Filtered::Ignored)
+        }
+    }
+
+    /// The TCP transport has no `XIM_XCONNECT`, start the handshake on the socket
+    fn tcp_connect(&mut self) -> Result<(), ClientError> {
+        if let Some(tcp) = self.tcp.as_ref() {
+            log::info!("TCP connected server on {}", tcp.address());
+            self.state.set_transport(TransportInfo::tcp(
+                self.im_name.clone(),
+                tcp.address().into(),
+            ));
+        }
+
+        send_connect(self)
+    }
+
     fn xconnect(&mut self) -> Result<(), ClientError> {
         self.conn().send_event(
             false,
@@ -640,21 +1165,7 @@ impl<C: HasConnection> ClientCore for X11rbClient<C> {
 
     #[inline]
     fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
-        xim_parser::XEvent {
-            response_type: xev.response_type,
-            detail: xev.detail,
-            sequence: xev.sequence,
-            time: xev.time,
-            root: xev.root,
-            event: xev.event,
-            child: xev.child,
-            root_x: xev.root_x,
-            root_y: xev.root_y,
-            event_x: xev.event_x,
-            event_y: xev.event_y,
-            state: xev.state.into(),
-            same_screen: xev.same_screen,
-        }
+        serialize_event_impl(xev)
     }
 
     #[inline]
@@ -662,8 +1173,56 @@ impl<C: HasConnection> ClientCore for X11rbClient<C> {
         deserialize_event_impl(xev)
     }
 
+    fn lookup_keysym(&mut self, xev: &Self::XEvent) -> Result<u32, ClientError> {
+        if self.keyboard_mapping.is_none() {
+            let setup = self.conn().setup();
+            let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+            let reply = self
+                .conn()
+                .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+                .reply()?;
+            self.keyboard_mapping = Some(KeyboardMapping {
+                min_keycode,
+                keysyms_per_keycode: reply.keysyms_per_keycode,
+                keysyms: reply.keysyms,
+            });
+        }
+
+        let mapping = self.keyboard_mapping.as_ref().unwrap();
+        let per_keycode = usize::from(mapping.keysyms_per_keycode);
+        let start = usize::from(xev.detail.wrapping_sub(mapping.min_keycode)) * per_keycode;
+        let syms = mapping
+            .keysyms
+            .get(start..start + per_keycode)
+            .unwrap_or_default();
+
+        // only shift level of the first group is considered like XLookupKeysym
+        let shifted = u16::from(xev.state) & u16::from(KeyButMask::SHIFT) != 0;
+        let keysym = match syms {
+            [_, upper, ..] if shifted && *upper != 0 => *upper,
+            [lower, ..] => *lower,
+            [] => 0,
+        };
+
+        Ok(keysym)
+    }
+
+    #[inline]
+    fn state(&self) -> &ClientState {
+        &self.state
+    }
+
+    #[inline]
+    fn state_mut(&mut self) -> &mut ClientState {
+        &mut self.state
+    }
+
     #[inline]
     fn send_req(&mut self, req: Request) -> Result<(), ClientError> {
+        if let Some(tcp) = self.tcp.as_mut() {
+            return Ok(tcp.send(&req)?);
+        }
+
         send_req_impl(
             &self.has_conn,
             &self.atoms,
@@ -671,11 +1230,87 @@ impl<C: HasConnection> ClientCore for X11rbClient<C> {
             &mut self.buf,
             &mut self.sequence,
             self.transport_max,
+            !self.state.is_batching(),
+            Endian::Native,
             &req,
         )
     }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), ClientError> {
+        self.conn().flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "x11rb-client")]
+impl<C: HasConnection> BlockingClient for X11rbClient<C>
+where
+    C::Connection: ConnectionFd,
+{
+    type Event = Event;
+
+    fn wait_event(&mut self, deadline: Instant) -> Result<Option<Event>, ClientError> {
+        loop {
+            if let Some(e) = self.conn().poll_for_event()? {
+                return Ok(Some(e));
+            }
+
+            if let Some(tcp) = self.tcp.as_mut() {
+                if tcp.fill()? {
+                    return Ok(None);
+                }
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            self.conn().flush()?;
+            self.poll_readable(deadline - now)?;
+        }
+    }
+
+    #[inline]
+    fn dispatch_event<H: ClientHandler<Self>>(
+        &mut self,
+        e: &Event,
+        handler: &mut H,
+    ) -> Result<bool, ClientError> {
+        self.filter_event(e, handler)
+    }
+
+    #[inline]
+    fn dispatch_transport<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<bool, ClientError> {
+        self.filter_tcp(handler)
+    }
+
+    #[inline]
+    fn pending_events(&mut self) -> &mut VecDeque<Event> {
+        &mut self.pending_events
+    }
+
+    #[inline]
+    fn destroy_window(&mut self) -> Result<(), ClientError> {
+        self.destroy_client_window()
+    }
 }
 
+#[cfg(feature = "x11rb-client")]
+impl<C: HasConnection> Drop for X11rbClient<C> {
+    fn drop(&mut self) {
+        // the connection may be already broken, nothing can be done with the error
+        let _ = self.destroy_client_window();
+    }
+}
+
+/// Send `req` to `target`, `buf` is left with the serialized request which is padded to
+/// ClientMessages when it's short
 fn send_req_impl<C: HasConnection, E: From<ConnectionError> + From<ReplyError>>(
     c: &C,
     atoms: &Atoms<Atom>,
@@ -683,35 +1318,36 @@ fn send_req_impl<C: HasConnection, E: From<ConnectionError> + From<ReplyError>>(
     buf: &mut Vec<u8>,
     sequence: &mut u16,
     transport_max: usize,
+    flush: bool,
+    endian: Endian,
     req: &Request,
 ) -> Result<(), E> {
-    if log::log_enabled!(log::Level::Trace) {
-        log::trace!("->: {:?}", req);
-    } else {
-        log::debug!("->: {}", req.name());
-    }
+    crate::trace::request_sent(req);
+    buf.clear();
     buf.resize(req.size(), 0);
-    xim_parser::write(req, buf);
+    xim_parser::write_with_endian(req, buf, endian);
 
     if buf.len() < transport_max {
-        if buf.len() > 20 {
-            todo!("multi-CM");
+        // divide into multiple ClientMessages
+        for (data, last) in split_client_messages(buf) {
+            c.conn().send_event(
+                false,
+                target,
+                EventMask::NO_EVENT,
+                ClientMessageEvent {
+                    response_type: CLIENT_MESSAGE_EVENT,
+                    data: data.into(),
+                    format: 8,
+                    sequence: 0,
+                    type_: if last {
+                        atoms.XIM_PROTOCOL
+                    } else {
+                        atoms.XIM_MOREDATA
+                    },
+                    window: target,
+                },
+            )?;
         }
-        buf.resize(20, 0);
-        let buf: [u8; 20] = buf.as_slice().try_into().unwrap();
-        c.conn().send_event(
-            false,
-            target,
-            EventMask::NO_EVENT,
-            ClientMessageEvent {
-                response_type: CLIENT_MESSAGE_EVENT,
-                data: buf.into(),
-                format: 8,
-                sequence: 0,
-                type_: atoms.XIM_PROTOCOL,
-                window: target,
-            },
-        )?;
     } else {
         let prop = c
             .conn()
@@ -742,13 +1378,75 @@ fn send_req_impl<C: HasConnection, E: From<ConnectionError> + From<ReplyError>>(
             },
         )?;
     }
-    buf.clear();
-    c.conn().flush()?;
+    if flush {
+        c.conn().flush()?;
+    }
     Ok(())
 }
 
 #[inline]
-fn deserialize_event_impl(xev: &xim_parser::XEvent) -> KeyPressEvent {
+pub(crate) fn serialize_event_impl(xev: &KeyPressEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.response_type,
+        detail: xev.detail,
+        sequence: xev.sequence,
+        time: xev.time,
+        root: xev.root,
+        event: xev.event,
+        child: xev.child,
+        root_x: xev.root_x,
+        root_y: xev.root_y,
+        event_x: xev.event_x,
+        event_y: xev.event_y,
+        state: xev.state.into(),
+        same_screen: xev.same_screen,
+    }
+}
+
+/// Serialize `ButtonPress` or `ButtonRelease` to forward it with
+/// [`Client::forward_core_event`](crate::Client::forward_core_event)
+#[cfg(feature = "x11rb-client")]
+pub fn serialize_button_event(xev: &ButtonPressEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.response_type,
+        detail: xev.detail,
+        sequence: xev.sequence,
+        time: xev.time,
+        root: xev.root,
+        event: xev.event,
+        child: xev.child,
+        root_x: xev.root_x,
+        root_y: xev.root_y,
+        event_x: xev.event_x,
+        event_y: xev.event_y,
+        state: xev.state.into(),
+        same_screen: xev.same_screen,
+    }
+}
+
+/// Serialize `MotionNotify` to forward it with
+/// [`Client::forward_core_event`](crate::Client::forward_core_event)
+#[cfg(feature = "x11rb-client")]
+pub fn serialize_motion_event(xev: &MotionNotifyEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.response_type,
+        detail: xev.detail.into(),
+        sequence: xev.sequence,
+        time: xev.time,
+        root: xev.root,
+        event: xev.event,
+        child: xev.child,
+        root_x: xev.root_x,
+        root_y: xev.root_y,
+        event_x: xev.event_x,
+        event_y: xev.event_y,
+        state: xev.state.into(),
+        same_screen: xev.same_screen,
+    }
+}
+
+#[inline]
+pub(crate) fn deserialize_event_impl(xev: &xim_parser::XEvent) -> KeyPressEvent {
     KeyPressEvent {
         response_type: xev.response_type,
         detail: xev.detail,
@@ -0,0 +1,228 @@
+//! A client which hides the X backend behind an enum.
+//!
+//! Toolkits supporting several X libraries can hold a [`XimClient`] and implement
+//! [`ClientHandler<XimClient>`] once, instead of being generic over every backend client.
+
+use alloc::vec::Vec;
+use std::net::TcpStream;
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+
+use crate::client::{
+    dispatch_filtered, ClientCore, ClientError, ClientHandler, ClientState, Filtered,
+};
+use crate::AHashMap;
+use xim_parser::{Attr, AttributeName, Request};
+
+#[cfg(feature = "x11rb-client")]
+use crate::x11rb::X11rbClient;
+#[cfg(feature = "xlib-client")]
+use crate::xlib::XlibClient;
+#[cfg(feature = "xlib-client")]
+use x11_dl::xlib;
+#[cfg(feature = "x11rb-client")]
+use x11rb::protocol::xproto::KeyPressEvent;
+#[cfg(feature = "x11rb-client")]
+use x11rb::rust_connection::RustConnection;
+#[cfg(all(feature = "x11rb-client", feature = "x11rb-xcb"))]
+use x11rb::xcb_ffi::XCBConnection;
+
+/// Client of any enabled backend.
+///
+/// Key events are represented as [`xim_parser::XEvent`] regardless of the backend, use
+/// `XimClient::from_*_key_event` and `XimClient::to_*_key_event` to convert them.
+#[non_exhaustive]
+pub enum XimClient {
+    #[cfg(feature = "x11rb-client")]
+    X11rb(X11rbClient<Arc<RustConnection>>),
+    #[cfg(all(feature = "x11rb-client", feature = "x11rb-xcb"))]
+    X11rbXcb(X11rbClient<Arc<XCBConnection>>),
+    #[cfg(feature = "xlib-client")]
+    Xlib(XlibClient<Arc<xlib::Xlib>>),
+}
+
+macro_rules! forward {
+    ($self:expr, $client:ident => $e:expr) => {
+        match $self {
+            #[cfg(feature = "x11rb-client")]
+            XimClient::X11rb($client) => $e,
+            #[cfg(all(feature = "x11rb-client", feature = "x11rb-xcb"))]
+            XimClient::X11rbXcb($client) => $e,
+            #[cfg(feature = "xlib-client")]
+            XimClient::Xlib($client) => $e,
+        }
+    };
+}
+
+impl XimClient {
+    /// Convert an x11rb key event to pass it to the client
+    #[cfg(feature = "x11rb-client")]
+    pub fn from_x11rb_key_event(xev: &KeyPressEvent) -> xim_parser::XEvent {
+        crate::x11rb::serialize_event_impl(xev)
+    }
+
+    /// Convert a key event forwarded by the server back to x11rb
+    #[cfg(feature = "x11rb-client")]
+    pub fn to_x11rb_key_event(xev: &xim_parser::XEvent) -> KeyPressEvent {
+        crate::x11rb::deserialize_event_impl(xev)
+    }
+
+    /// Convert an Xlib key event to pass it to the client
+    #[cfg(feature = "xlib-client")]
+    pub fn from_xlib_key_event(xev: &xlib::XKeyEvent) -> xim_parser::XEvent {
+        crate::xlib::serialize_event_impl(xev)
+    }
+
+    /// Convert a key event forwarded by the server back to Xlib
+    #[cfg(feature = "xlib-client")]
+    pub fn to_xlib_key_event(
+        xev: &xim_parser::XEvent,
+        display: *mut xlib::Display,
+    ) -> xlib::XKeyEvent {
+        crate::xlib::deserialize_event_impl(xev, display)
+    }
+
+    /// Filter an x11rb event and call the handler if it is relevant.
+    ///
+    /// Return `false` when the event is not related to XIM or the client uses another backend.
+    #[cfg(feature = "x11rb-client")]
+    pub fn filter_x11rb_event(
+        &mut self,
+        e: &x11rb::protocol::Event,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<bool, ClientError> {
+        let filtered = match self {
+            XimClient::X11rb(client) => client.process_event(e)?,
+            #[cfg(feature = "x11rb-xcb")]
+            XimClient::X11rbXcb(client) => client.process_event(e)?,
+            #[allow(unreachable_patterns)]
+            _ => Filtered::Ignored,
+        };
+        dispatch_filtered(self, handler, filtered)
+    }
+
+    /// Filter an Xlib event and call the handler if it is relevant.
+    ///
+    /// Return `false` when the event is not related to XIM or the client uses another backend.
+    ///
+    /// # Safety
+    ///
+    /// The event `e` must be a valid Xlib event.
+    #[cfg(feature = "xlib-client")]
+    pub unsafe fn filter_xlib_event(
+        &mut self,
+        e: &xlib::XEvent,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<bool, ClientError> {
+        let filtered = match self {
+            XimClient::Xlib(client) => client.process_event(e)?,
+            #[allow(unreachable_patterns)]
+            _ => Filtered::Ignored,
+        };
+        dispatch_filtered(self, handler, filtered)
+    }
+
+    /// File descriptor of the X connection
+    pub fn connection_fd(&self) -> RawFd {
+        forward!(self, client => client.connection_fd())
+    }
+
+    /// Socket of the TCP transport, `None` when the server uses the X transport
+    pub fn tcp_stream(&self) -> Option<&TcpStream> {
+        forward!(self, client => client.tcp_stream())
+    }
+
+    /// Read requests arrived on the TCP transport and call the handler.
+    ///
+    /// Return `false` when nothing is read.
+    pub fn filter_tcp(
+        &mut self,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<bool, ClientError> {
+        let mut consumed = false;
+
+        loop {
+            match forward!(self, client => client.process_tcp()?) {
+                Filtered::Ignored => return Ok(consumed),
+                filtered => consumed |= dispatch_filtered(self, handler, filtered)?,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "x11rb-client")]
+impl From<X11rbClient<Arc<RustConnection>>> for XimClient {
+    fn from(client: X11rbClient<Arc<RustConnection>>) -> Self {
+        Self::X11rb(client)
+    }
+}
+
+#[cfg(all(feature = "x11rb-client", feature = "x11rb-xcb"))]
+impl From<X11rbClient<Arc<XCBConnection>>> for XimClient {
+    fn from(client: X11rbClient<Arc<XCBConnection>>) -> Self {
+        Self::X11rbXcb(client)
+    }
+}
+
+#[cfg(feature = "xlib-client")]
+impl From<XlibClient<Arc<xlib::Xlib>>> for XimClient {
+    fn from(client: XlibClient<Arc<xlib::Xlib>>) -> Self {
+        Self::Xlib(client)
+    }
+}
+
+impl ClientCore for XimClient {
+    type XEvent = xim_parser::XEvent;
+
+    #[inline]
+    fn set_attrs(&mut self, ic_attrs: Vec<Attr>, im_attrs: Vec<Attr>) {
+        forward!(self, client => client.set_attrs(ic_attrs, im_attrs))
+    }
+
+    #[inline]
+    fn ic_attributes(&self) -> &AHashMap<AttributeName, u16> {
+        forward!(self, client => client.ic_attributes())
+    }
+
+    #[inline]
+    fn im_attributes(&self) -> &AHashMap<AttributeName, u16> {
+        forward!(self, client => client.im_attributes())
+    }
+
+    #[inline]
+    fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
+        xev.clone()
+    }
+
+    fn lookup_keysym(&mut self, xev: &Self::XEvent) -> Result<u32, ClientError> {
+        forward!(self, client => {
+            let xev = client.deserialize_event(xev);
+            client.lookup_keysym(&xev)
+        })
+    }
+
+    #[inline]
+    fn deserialize_event(&self, xev: &xim_parser::XEvent) -> Self::XEvent {
+        xev.clone()
+    }
+
+    #[inline]
+    fn send_req(&mut self, req: Request) -> Result<(), ClientError> {
+        forward!(self, client => client.send_req(req))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), ClientError> {
+        forward!(self, client => client.flush())
+    }
+
+    #[inline]
+    fn state(&self) -> &ClientState {
+        forward!(self, client => client.state())
+    }
+
+    #[inline]
+    fn state_mut(&mut self) -> &mut ClientState {
+        forward!(self, client => client.state_mut())
+    }
+}
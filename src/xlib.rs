@@ -1,23 +1,80 @@
 //! Provides a wrapper around Xlib (through the [`x11-dl`] crate) that allows to use Xlib as a
-//! client for XIM.
+//! client or server for XIM.
 //!
 //! Note that it is generally discouraged to use Xlib in the modern era.
 
 use crate::AHashMap;
+#[cfg(feature = "xlib-server")]
+use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::vec::Vec;
-use std::ffi::CStr;
 use std::mem::MaybeUninit;
+use std::os::raw::c_long;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::{convert::TryInto, os::raw::c_long};
 
-use crate::{
-    client::{handle_request, ClientCore, ClientError, ClientHandler},
-    Atoms,
+#[cfg(feature = "xlib-client")]
+use alloc::collections::VecDeque;
+#[cfg(feature = "xlib-client")]
+use std::convert::TryInto;
+#[cfg(feature = "xlib-client")]
+use std::ffi::CStr;
+#[cfg(feature = "xlib-client")]
+use std::net::TcpStream;
+#[cfg(feature = "xlib-client")]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(feature = "xlib-client")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "xlib-client")]
+use crate::client::{
+    dispatch_filtered, send_connect, BlockingClient, ClientCore, ClientError, ClientHandler,
+    ClientState, Filtered, Quirks, TransportInfo,
+};
+#[cfg(all(feature = "xlib-server", feature = "metrics"))]
+use crate::server::ServerStats;
+#[cfg(feature = "xlib-server")]
+use crate::server::{
+    ServerCore, ServerError, ServerHandler, WireTap, XimConnection, XimConnections,
 };
+#[cfg(feature = "xlib-client")]
+use crate::transport::{
+    read_property_request, select_transport, MessageBuffer, PropertyChunk, TcpTransport, Transport,
+};
+use crate::{transport::split_client_messages, Atoms};
 use x11_dl::xlib;
-use xim_parser::{AttributeName, Request, XimWrite};
+#[cfg(feature = "xlib-client")]
+use xim_parser::AttributeName;
+use xim_parser::{Endian, Request, XimWrite};
+
+/// Event of the display of an [`XlibClient`] for the safe [`BlockingClient`] API.
+///
+/// [`XlibClient::filter_event`] reads the event as the variant of its type, so only events
+/// returned by [`BlockingClient::wait_event`] or built by the unsafe [`XlibEvent::new`] can be
+/// dispatched without an `unsafe` block.
+#[cfg(feature = "xlib-client")]
+#[derive(Clone, Copy, Debug)]
+pub struct XlibEvent(xlib::XEvent);
+
+#[cfg(feature = "xlib-client")]
+impl XlibEvent {
+    /// # Safety
+    ///
+    /// The event `e` must be a valid Xlib event, e.g. one returned by `XNextEvent`.
+    pub unsafe fn new(e: xlib::XEvent) -> Self {
+        Self(e)
+    }
+
+    pub fn get(&self) -> &xlib::XEvent {
+        &self.0
+    }
 
+    pub fn into_inner(self) -> xlib::XEvent {
+        self.0
+    }
+}
+
+#[cfg(feature = "xlib-client")]
 impl<X: XlibRef> ClientCore for XlibClient<X> {
     type XEvent = xlib::XKeyEvent;
 
@@ -33,50 +90,53 @@ impl<X: XlibRef> ClientCore for XlibClient<X> {
 
     #[inline]
     fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
-        xim_parser::XEvent {
-            response_type: xev.type_ as u8,
-            detail: xev.keycode as u8,
-            sequence: xev.serial as _,
-            time: xev.time as u32,
-            root: xev.root as u32,
-            event: xev.window as u32,
-            child: xev.subwindow as u32,
-            root_x: xev.x_root as i16,
-            root_y: xev.y_root as i16,
-            event_x: xev.x as i16,
-            event_y: xev.y as i16,
-            state: xev.state as u16,
-            same_screen: xev.same_screen != 0,
-        }
+        serialize_event_impl(xev)
     }
 
     #[inline]
     fn deserialize_event(&self, xev: &xim_parser::XEvent) -> Self::XEvent {
-        xlib::XKeyEvent {
-            type_: xev.response_type as _,
-            keycode: xev.detail as _,
-            serial: xev.sequence as _,
-            time: xev.time as _,
-            root: xev.root as _,
-            window: xev.event as _,
-            subwindow: xev.child as _,
-            x_root: xev.root_x as _,
-            y_root: xev.root_y as _,
-            x: xev.event_x as _,
-            y: xev.event_y as _,
-            state: xev.state as _,
-            same_screen: xev.same_screen as i32,
-            display: self.display,
-            send_event: 0,
-        }
+        deserialize_event_impl(xev, self.display)
+    }
+
+    fn lookup_keysym(&mut self, xev: &Self::XEvent) -> Result<u32, ClientError> {
+        let mut xev = *xev;
+        let index = if xev.state & xlib::ShiftMask != 0 {
+            1
+        } else {
+            0
+        };
+        let keysym = unsafe { (self.x.xlib().XLookupKeysym)(&mut xev, index) };
+        Ok(keysym as u32)
+    }
+
+    #[inline]
+    fn state(&self) -> &ClientState {
+        &self.state
+    }
+
+    #[inline]
+    fn state_mut(&mut self) -> &mut ClientState {
+        &mut self.state
     }
 
     #[inline]
     fn send_req(&mut self, req: xim_parser::Request) -> Result<(), ClientError> {
+        if let Some(tcp) = self.tcp.as_mut() {
+            return Ok(tcp.send(&req)?);
+        }
+
         self.send_req_impl(req);
         Ok(())
     }
 
+    #[inline]
+    fn flush(&mut self) -> Result<(), ClientError> {
+        unsafe {
+            (self.x.xlib().XFlush)(self.display);
+        }
+        Ok(())
+    }
+
     fn set_attrs(&mut self, ic_attrs: Vec<xim_parser::Attr>, im_attrs: Vec<xim_parser::Attr>) {
         for im_attr in im_attrs {
             self.im_attributes.insert(im_attr.name, im_attr.id);
@@ -88,7 +148,75 @@ impl<X: XlibRef> ClientCore for XlibClient<X> {
     }
 }
 
-impl<'a> XlibRef for &'a xlib::Xlib {
+#[cfg(feature = "xlib-client")]
+impl<X: XlibRef> BlockingClient for XlibClient<X> {
+    type Event = XlibEvent;
+
+    fn wait_event(&mut self, deadline: Instant) -> Result<Option<XlibEvent>, ClientError> {
+        loop {
+            unsafe {
+                if (self.x.xlib().XPending)(self.display) > 0 {
+                    let mut e = MaybeUninit::uninit();
+                    (self.x.xlib().XNextEvent)(self.display, e.as_mut_ptr());
+                    return Ok(Some(XlibEvent(e.assume_init())));
+                }
+            }
+
+            if let Some(tcp) = self.tcp.as_mut() {
+                if tcp.fill()? {
+                    return Ok(None);
+                }
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            self.poll_readable(deadline - now)?;
+        }
+    }
+
+    #[inline]
+    fn dispatch_event<H: ClientHandler<Self>>(
+        &mut self,
+        e: &XlibEvent,
+        handler: &mut H,
+    ) -> Result<bool, ClientError> {
+        // SAFETY: `XlibEvent` is only made of valid events
+        unsafe { self.filter_event(&e.0, handler) }
+    }
+
+    #[inline]
+    fn dispatch_transport<H: ClientHandler<Self>>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<bool, ClientError> {
+        self.filter_tcp(handler)
+    }
+
+    #[inline]
+    fn pending_events(&mut self) -> &mut VecDeque<XlibEvent> {
+        &mut self.pending_events
+    }
+
+    fn destroy_window(&mut self) -> Result<(), ClientError> {
+        self.tcp = None;
+
+        if self.client_window != 0 {
+            unsafe {
+                (self.x.xlib().XDestroyWindow)(self.display, self.client_window);
+                (self.x.xlib().XFlush)(self.display);
+            }
+            self.client_window = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl XlibRef for &xlib::Xlib {
     fn xlib(&self) -> &xlib::Xlib {
         self
     }
@@ -122,9 +250,102 @@ pub trait XlibRef {
     fn xlib(&self) -> &xlib::Xlib;
 }
 
+#[inline]
+pub(crate) fn serialize_event_impl(xev: &xlib::XKeyEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.type_ as u8 | if xev.send_event != 0 { 0x80 } else { 0 },
+        detail: xev.keycode as u8,
+        sequence: xev.serial as _,
+        time: xev.time as u32,
+        root: xev.root as u32,
+        event: xev.window as u32,
+        child: xev.subwindow as u32,
+        root_x: xev.x_root as i16,
+        root_y: xev.y_root as i16,
+        event_x: xev.x as i16,
+        event_y: xev.y as i16,
+        state: xev.state as u16,
+        same_screen: xev.same_screen != 0,
+    }
+}
+
+/// Serialize `ButtonPress` or `ButtonRelease` to forward it with
+/// [`Client::forward_core_event`](crate::Client::forward_core_event)
+#[cfg(feature = "xlib-client")]
+pub fn serialize_button_event(xev: &xlib::XButtonEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.type_ as u8,
+        detail: xev.button as u8,
+        sequence: xev.serial as _,
+        time: xev.time as u32,
+        root: xev.root as u32,
+        event: xev.window as u32,
+        child: xev.subwindow as u32,
+        root_x: xev.x_root as i16,
+        root_y: xev.y_root as i16,
+        event_x: xev.x as i16,
+        event_y: xev.y as i16,
+        state: xev.state as u16,
+        same_screen: xev.same_screen != 0,
+    }
+}
+
+/// Serialize `MotionNotify` to forward it with
+/// [`Client::forward_core_event`](crate::Client::forward_core_event)
+#[cfg(feature = "xlib-client")]
+pub fn serialize_motion_event(xev: &xlib::XMotionEvent) -> xim_parser::XEvent {
+    xim_parser::XEvent {
+        response_type: xev.type_ as u8,
+        detail: xev.is_hint as u8,
+        sequence: xev.serial as _,
+        time: xev.time as u32,
+        root: xev.root as u32,
+        event: xev.window as u32,
+        child: xev.subwindow as u32,
+        root_x: xev.x_root as i16,
+        root_y: xev.y_root as i16,
+        event_x: xev.x as i16,
+        event_y: xev.y as i16,
+        state: xev.state as u16,
+        same_screen: xev.same_screen != 0,
+    }
+}
+
+#[inline]
+pub(crate) fn deserialize_event_impl(
+    xev: &xim_parser::XEvent,
+    display: *mut xlib::Display,
+) -> xlib::XKeyEvent {
+    xlib::XKeyEvent {
+        type_: (xev.response_type & 0x7f) as _,
+        keycode: xev.detail as _,
+        serial: xev.sequence as _,
+        time: xev.time as _,
+        root: xev.root as _,
+        window: xev.event as _,
+        subwindow: xev.child as _,
+        x_root: xev.root_x as _,
+        y_root: xev.root_y as _,
+        x: xev.event_x as _,
+        y: xev.event_y as _,
+        state: xev.state as _,
+        same_screen: xev.same_screen as i32,
+        display,
+        send_event: (xev.response_type & 0x80 != 0) as _,
+    }
+}
+
+/// XIM client over an Xlib display.
+///
+/// The client window isn't destroyed on drop since the display may be closed before, call
+/// [`BlockingClient::shutdown_blocking`] or [`BlockingClient::destroy_window`] while it's open.
+#[cfg(feature = "xlib-client")]
 pub struct XlibClient<X: XlibRef> {
     x: X,
     display: *mut xlib::Display,
+    root: xlib::Window,
+    im_name: String,
+    wait_server: bool,
     im_window: xlib::Window,
     server_owner_window: xlib::Window,
     server_atom: xlib::Atom,
@@ -135,8 +356,13 @@ pub struct XlibClient<X: XlibRef> {
     ic_attributes: AHashMap<AttributeName, u16>,
     buf: Vec<u8>,
     sequence: u16,
+    message_buffer: MessageBuffer,
+    tcp: Option<TcpTransport>,
+    pending_events: VecDeque<XlibEvent>,
+    state: ClientState,
 }
 
+#[cfg(feature = "xlib-client")]
 impl<X: XlibRef> XlibClient<X> {
     /// Initialize a new `XlibClient` from an Xlib connection.
     ///
@@ -147,6 +373,54 @@ impl<X: XlibRef> XlibClient<X> {
         x: X,
         display: *mut xlib::Display,
         im_name: Option<&str>,
+    ) -> Result<Self, ClientError> {
+        let mut client = Self::new(x, display, im_name)?;
+
+        if client.try_connect()? {
+            Ok(client)
+        } else {
+            Err(ClientError::NoXimServer)
+        }
+    }
+
+    /// Initialize a new `XlibClient` which connects to the XIM server whenever it appears.
+    ///
+    /// The client watches `XIM_SERVERS` of the root window and starts the handshake when a
+    /// matching server is registered, it also reconnects when the server is restarted.
+    ///
+    /// # Safety
+    ///
+    /// The `display` pointer must be a valid Xlib display.
+    pub unsafe fn init_lazy(
+        x: X,
+        display: *mut xlib::Display,
+        im_name: Option<&str>,
+    ) -> Result<Self, ClientError> {
+        let mut client = Self::new(x, display, im_name)?;
+        client.wait_server = true;
+
+        // keep events selected by the application
+        let mut attributes = MaybeUninit::uninit();
+        (client.x.xlib().XGetWindowAttributes)(display, client.root, attributes.as_mut_ptr());
+        let attributes = attributes.assume_init();
+        (client.x.xlib().XSelectInput)(
+            display,
+            client.root,
+            attributes.your_event_mask | xlib::PropertyChangeMask,
+        );
+
+        if !client.try_connect()? {
+            log::info!("Wait XIM server {}", client.im_name);
+            (client.x.xlib().XFlush)(display);
+        }
+
+        Ok(client)
+    }
+
+    unsafe fn new(
+        x: X,
+        display: *mut xlib::Display,
+        im_name: Option<&str>,
     ) -> Result<Self, ClientError> {
         let xlib = x.xlib();
         let root = (xlib.XDefaultRootWindow)(display);
@@ -165,6 +439,42 @@ impl<X: XlibRef> XlibClient<X> {
             }
         })?;
 
+        let mut state = ClientState::new();
+        let quirks = Quirks::detect(im_name);
+        if quirks != Quirks::none() {
+            log::info!("Apply {:?} for {}", quirks, im_name);
+        }
+        state.set_quirks(quirks);
+
+        Ok(Self {
+            atoms,
+            root,
+            im_name: im_name.into(),
+            wait_server: false,
+            client_window,
+            server_atom: 0,
+            server_owner_window: 0,
+            im_window: 0,
+            transport_max: 0,
+            display,
+            x,
+            ic_attributes: AHashMap::with_hasher(Default::default()),
+            im_attributes: AHashMap::with_hasher(Default::default()),
+            buf: Vec::with_capacity(1024),
+            sequence: 0,
+            message_buffer: MessageBuffer::new(),
+            tcp: None,
+            pending_events: VecDeque::new(),
+            state,
+        })
+    }
+
+    /// Find the server in `XIM_SERVERS` and start the handshake, return `false` when it's not
+    /// registered
+    unsafe fn try_connect(&mut self) -> Result<bool, ClientError> {
+        let xlib = self.x.xlib();
+        let display = self.display;
+
         let mut ty = MaybeUninit::uninit();
         let mut format = MaybeUninit::uninit();
         let mut items = MaybeUninit::uninit();
@@ -173,8 +483,8 @@ impl<X: XlibRef> XlibClient<X> {
 
         let code = (xlib.XGetWindowProperty)(
             display,
-            root,
-            atoms.XIM_SERVERS,
+            self.root,
+            self.atoms.XIM_SERVERS,
             0,
             i64::MAX,
             xlib::False,
@@ -196,57 +506,56 @@ impl<X: XlibRef> XlibClient<X> {
         let _bytes = bytes.assume_init();
         let prop = prop.assume_init() as *mut xlib::Atom;
 
+        // no server is registered yet
+        if ty == 0 {
+            return Ok(false);
+        }
+
         if ty != xlib::XA_ATOM || format != 32 {
-            Err(ClientError::InvalidReply)
-        } else {
-            for i in 0..items {
-                let server_atom = prop.add(i as usize).read();
-                let server_owner = (xlib.XGetSelectionOwner)(display, server_atom);
-                let name_ptr = (xlib.XGetAtomName)(display, server_atom);
-                let name = CStr::from_ptr(name_ptr);
-                let name = match name.to_str() {
-                    Ok(s) => s,
-                    _ => continue,
-                };
+            (xlib.XFree)(prop as _);
+            return Err(ClientError::InvalidReply);
+        }
 
-                if let Some(name) = name.strip_prefix("@server=") {
-                    if name == im_name {
-                        (xlib.XConvertSelection)(
-                            display,
-                            server_atom,
-                            atoms.TRANSPORT,
-                            atoms.TRANSPORT,
-                            client_window,
-                            xlib::CurrentTime,
-                        );
-                        (xlib.XFlush)(display);
-                        (xlib.XFree)(name_ptr as _);
-                        (xlib.XFree)(prop as _);
+        for i in 0..items {
+            let server_atom = prop.add(i as usize).read();
+            let server_owner = (xlib.XGetSelectionOwner)(display, server_atom);
 
-                        return Ok(Self {
-                            atoms,
-                            client_window,
-                            server_atom,
-                            server_owner_window: server_owner,
-                            im_window: 0,
-                            transport_max: 0,
-                            display,
-                            x,
-                            ic_attributes: AHashMap::with_hasher(Default::default()),
-                            im_attributes: AHashMap::with_hasher(Default::default()),
-                            buf: Vec::with_capacity(1024),
-                            sequence: 0,
-                        });
-                    }
-                } else {
-                    (xlib.XFree)(name_ptr as _);
-                }
+            // stale entry of an exited server
+            if server_owner == 0 {
+                continue;
             }
 
-            (xlib.XFree)(prop as _);
+            let name_ptr = (xlib.XGetAtomName)(display, server_atom);
+            let matched = match CStr::from_ptr(name_ptr).to_str() {
+                Ok(name) => name.strip_prefix("@server=") == Some(self.im_name.as_str()),
+                _ => false,
+            };
+            (xlib.XFree)(name_ptr as _);
 
-            Err(ClientError::NoXimServer)
+            if matched {
+                (xlib.XConvertSelection)(
+                    display,
+                    server_atom,
+                    self.atoms.TRANSPORT,
+                    self.atoms.TRANSPORT,
+                    self.client_window,
+                    xlib::CurrentTime,
+                );
+                // notified by DestroyNotify when the server exits
+                (xlib.XSelectInput)(display, server_owner, xlib::StructureNotifyMask);
+                (xlib.XFlush)(display);
+                (xlib.XFree)(prop as _);
+
+                self.server_atom = server_atom;
+                self.server_owner_window = server_owner;
+
+                return Ok(true);
+            }
         }
+
+        (xlib.XFree)(prop as _);
+
+        Ok(false)
     }
 
     /// Filter an event and call the handler if it is relevant.
@@ -259,6 +568,19 @@ impl<X: XlibRef> XlibClient<X> {
         e: &xlib::XEvent,
         handler: &mut impl ClientHandler<Self>,
     ) -> Result<bool, ClientError> {
+        let filtered = self.process_event(e)?;
+        dispatch_filtered(self, handler, filtered)
+    }
+
+    /// Handle the transport part of `e`, handlers are not called here
+    ///
+    /// # Safety
+    ///
+    /// The event `e` must be a valid Xlib event.
+    pub(crate) unsafe fn process_event(
+        &mut self,
+        e: &xlib::XEvent,
+    ) -> Result<Filtered, ClientError> {
         match e.get_type() {
             xlib::SelectionNotify if e.selection.requestor == self.client_window => {
                 let mut ty = MaybeUninit::uninit();
@@ -287,29 +609,25 @@ impl<X: XlibRef> XlibClient<X> {
                 let _bytes = bytes.assume_init();
                 let prop = prop.assume_init();
 
-                if e.selection.property == self.atoms.LOCALES {
+                let ret = if e.selection.property == self.atoms.LOCALES {
                     // TODO: set locale
-                    self.xconnect();
+                    if self.tcp.is_some() {
+                        self.tcp_connect()
+                    } else {
+                        self.xconnect();
+                        Ok(())
+                    }
                 } else if e.selection.property == self.atoms.TRANSPORT {
                     let transport = std::slice::from_raw_parts(prop, items as usize);
 
-                    if !transport.starts_with(b"@transport=X/") {
-                        return Err(ClientError::UnsupportedTransport);
-                    }
-
-                    (self.x.xlib().XConvertSelection)(
-                        self.display,
-                        self.server_atom,
-                        self.atoms.LOCALES,
-                        self.atoms.LOCALES,
-                        self.client_window,
-                        xlib::CurrentTime,
-                    );
-                }
+                    self.select_transport(transport)
+                } else {
+                    Ok(())
+                };
 
                 (self.x.xlib().XFree)(prop as _);
 
-                Ok(true)
+                ret.map(|_| Filtered::Consumed)
             }
             xlib::ClientMessage if e.client_message.window == self.client_window => {
                 if e.client_message.message_type == self.atoms.XIM_XCONNECT {
@@ -326,85 +644,295 @@ impl<X: XlibRef> XlibClient<X> {
 
                     self.im_window = im_window as xlib::Window;
                     self.transport_max = max as usize;
-                    self.send_req(Request::Connect {
-                        client_major_protocol_version: 1,
-                        client_minor_protocol_version: 0,
-                        endian: xim_parser::Endian::Native,
-                        client_auth_protocol_names: Vec::new(),
-                    })?;
-
-                    Ok(true)
+                    self.state.set_transport(TransportInfo::new(
+                        self.im_name.clone(),
+                        major as u32,
+                        minor as u32,
+                        self.transport_max,
+                    ));
+                    if self.im_window != self.server_owner_window {
+                        (self.x.xlib().XSelectInput)(
+                            self.display,
+                            self.im_window,
+                            xlib::StructureNotifyMask,
+                        );
+                    }
+                    send_connect(self)?;
+
+                    Ok(Filtered::Consumed)
+                } else if e.client_message.message_type == self.atoms.XIM_MOREDATA {
+                    let bytes = e.client_message.data.as_bytes();
+                    self.message_buffer
+                        .push(std::slice::from_raw_parts(bytes.as_ptr() as _, bytes.len()))?;
+                    Ok(Filtered::Consumed)
                 } else if e.client_message.message_type == self.atoms.XIM_PROTOCOL {
-                    self.handle_xim_protocol(&e.client_message, handler)?;
-                    Ok(true)
+                    self.handle_xim_protocol(&e.client_message)
                 } else {
-                    Ok(false)
+                    Ok(Filtered::Ignored)
                 }
             }
-            _ => Ok(false),
+            xlib::DestroyNotify
+                if e.destroy_window.window == self.server_owner_window
+                    || e.destroy_window.window == self.im_window =>
+            {
+                self.server_disconnected()?;
+                Ok(Filtered::ServerDisconnected)
+            }
+            xlib::PropertyNotify
+                if self.wait_server
+                    && e.property.window == self.root
+                    && e.property.atom == self.atoms.XIM_SERVERS =>
+            {
+                if self.server_owner_window == 0 {
+                    self.try_connect()?;
+                }
+                Ok(Filtered::Consumed)
+            }
+            _ => Ok(Filtered::Ignored),
         }
     }
 
+    fn server_disconnected(&mut self) -> Result<(), ClientError> {
+        log::warn!("XIM server is disconnected");
+
+        self.server_owner_window = 0;
+        self.im_window = 0;
+        self.im_attributes.clear();
+        self.ic_attributes.clear();
+        self.message_buffer = MessageBuffer::new();
+        self.tcp = None;
+        self.state.reset();
+
+        // the server may be registered again before its old window is destroyed
+        if self.wait_server {
+            unsafe {
+                self.try_connect()?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn handle_xim_protocol(
         &mut self,
         msg: &xlib::XClientMessageEvent,
-        handler: &mut impl ClientHandler<Self>,
-    ) -> Result<(), ClientError> {
+    ) -> Result<Filtered, ClientError> {
         if msg.format == 32 {
             let length = msg.data.get_long(0);
             let atom = msg.data.get_long(1);
+            let quirks = self.state.quirks();
+            let xlib = self.x.xlib();
+            let display = self.display;
 
-            let mut ty = MaybeUninit::uninit();
-            let mut format = MaybeUninit::uninit();
-            let mut items = MaybeUninit::uninit();
-            let mut bytes = MaybeUninit::uninit();
-            let mut prop = MaybeUninit::uninit();
-
-            unsafe {
-                let code = (self.x.xlib().XGetWindowProperty)(
-                    self.display,
-                    msg.window,
-                    atom as _,
-                    0,
-                    length,
-                    xlib::True,
-                    0,
-                    ty.as_mut_ptr(),
-                    format.as_mut_ptr(),
-                    items.as_mut_ptr(),
-                    bytes.as_mut_ptr(),
-                    prop.as_mut_ptr(),
-                );
+            let req = read_property_request(length as u32, quirks, |offset, length| {
+                let mut ty = MaybeUninit::uninit();
+                let mut format = MaybeUninit::uninit();
+                let mut items = MaybeUninit::uninit();
+                let mut bytes = MaybeUninit::uninit();
+                let mut prop = MaybeUninit::uninit();
 
-                if code != 0 {
-                    return Err(ClientError::InvalidReply);
-                }
+                unsafe {
+                    let code = (xlib.XGetWindowProperty)(
+                        display,
+                        msg.window,
+                        atom as _,
+                        offset as _,
+                        length as _,
+                        xlib::True,
+                        0,
+                        ty.as_mut_ptr(),
+                        format.as_mut_ptr(),
+                        items.as_mut_ptr(),
+                        bytes.as_mut_ptr(),
+                        prop.as_mut_ptr(),
+                    );
 
-                let _ty = ty.assume_init();
-                let _format = format.assume_init();
-                let items = items.assume_init();
-                let _bytes = bytes.assume_init();
-                let prop = prop.assume_init();
+                    if code != 0 {
+                        return Err(ClientError::InvalidReply);
+                    }
 
-                let data = std::slice::from_raw_parts(prop, items as usize);
+                    let _ty = ty.assume_init();
+                    let format = format.assume_init();
+                    let items = items.assume_init();
+                    let bytes = bytes.assume_init();
+                    let prop = prop.assume_init();
 
-                let req = xim_parser::read(data)?;
+                    let data = if prop.is_null() {
+                        Vec::new()
+                    } else {
+                        let data = std::slice::from_raw_parts(prop, items as usize).to_vec();
+                        (xlib.XFree)(prop as _);
+                        data
+                    };
 
-                handle_request(self, handler, req)?;
+                    Ok(PropertyChunk {
+                        format: format as u8,
+                        data,
+                        bytes_after: bytes as u32,
+                    })
+                }
+            })?;
 
-                (self.x.xlib().XFree)(prop as _);
-            }
+            Ok(req.map_or(Filtered::Consumed, Filtered::Request))
         } else if msg.format == 8 {
             let bytes = msg.data.as_bytes();
             let data: &[u8] =
                 unsafe { std::slice::from_raw_parts(bytes.as_ptr() as _, bytes.len()) };
-            let req = xim_parser::read(data)?;
-            handle_request(self, handler, req)?;
+            Ok(Filtered::Request(
+                self.message_buffer.read(data, Endian::Native)?,
+            ))
+        } else {
+            Ok(Filtered::Consumed)
+        }
+    }
+
+    /// Pick the transport advertised by the server then ask its locales
+    fn select_transport(&mut self, transport: &[u8]) -> Result<(), ClientError> {
+        match select_transport(transport) {
+            Some(Transport::X) => {}
+            Some(Transport::Tcp(address)) => {
+                log::info!("Connect XIM server on tcp/{}", address);
+                self.tcp = Some(TcpTransport::connect(address)?);
+            }
+            None => return Err(ClientError::UnsupportedTransport),
+        }
+
+        unsafe {
+            (self.x.xlib().XConvertSelection)(
+                self.display,
+                self.server_atom,
+                self.atoms.LOCALES,
+                self.atoms.LOCALES,
+                self.client_window,
+                xlib::CurrentTime,
+            );
         }
 
         Ok(())
     }
 
+    /// File descriptor of the display connection, poll it with [`XlibClient::tcp_stream`] and
+    /// call [`XlibClient::poll_filter_pending`] when either is readable.
+    pub fn connection_fd(&self) -> RawFd {
+        unsafe { (self.x.xlib().XConnectionNumber)(self.display) }
+    }
+
+    /// Filter events which can be read without blocking and return the first one which is not
+    /// related to XIM. Requests of the TCP transport are also read when no event is left.
+    ///
+    /// Return `None` when nothing is left, wait for the fds to be readable again.
+    pub fn poll_filter_pending(
+        &mut self,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<Option<xlib::XEvent>, ClientError> {
+        // these are already filtered while waiting a reply
+        if let Some(e) = self.pending_events.pop_front() {
+            return Ok(Some(e.into_inner()));
+        }
+
+        unsafe {
+            while (self.x.xlib().XPending)(self.display) > 0 {
+                let mut e = MaybeUninit::uninit();
+                (self.x.xlib().XNextEvent)(self.display, e.as_mut_ptr());
+                let e = e.assume_init();
+
+                if !self.filter_event(&e, handler)? {
+                    return Ok(Some(e));
+                }
+            }
+        }
+
+        self.filter_tcp(handler)?;
+
+        Ok(None)
+    }
+
+    /// Sleep until the display or the TCP transport is readable or `timeout` is elapsed
+    fn poll_readable(&self, timeout: Duration) -> Result<(), ClientError> {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.connection_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            // negative fds are ignored
+            libc::pollfd {
+                fd: self.tcp_stream().map_or(-1, AsRawFd::as_raw_fd),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        // round up not to spin in the last millisecond
+        let timeout = ((timeout.as_micros() + 999) / 1000).min(libc::c_int::MAX as u128);
+
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout as _) };
+
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Socket of the TCP transport, poll it with the display and call
+    /// [`XlibClient::filter_tcp`] when it's readable. `None` when the server uses the X transport.
+    pub fn tcp_stream(&self) -> Option<&TcpStream> {
+        self.tcp.as_ref().map(TcpTransport::stream)
+    }
+
+    /// Read requests arrived on the TCP transport and call the handler.
+    ///
+    /// Return `false` when nothing is read.
+    pub fn filter_tcp(
+        &mut self,
+        handler: &mut impl ClientHandler<Self>,
+    ) -> Result<bool, ClientError> {
+        let mut consumed = false;
+
+        loop {
+            match self.process_tcp()? {
+                Filtered::Ignored => return Ok(consumed),
+                filtered => consumed |= dispatch_filtered(self, handler, filtered)?,
+            }
+        }
+    }
+
+    /// Read the next request of the TCP transport, handlers are not called here
+    pub(crate) fn process_tcp(&mut self) -> Result<Filtered, ClientError> {
+        let tcp = match self.tcp.as_mut() {
+            Some(tcp) => tcp,
+            None => return Ok(Filtered::Ignored),
+        };
+
+        tcp.fill()?;
+
+        if let Some(req) = tcp.read()? {
+            Ok(Filtered::Request(req))
+        } else if tcp.is_closed() {
+            self.server_disconnected()?;
+            Ok(Filtered::ServerDisconnected)
+        } else {
+            Ok(Filtered::Ignored)
+        }
+    }
+
+    /// The TCP transport has no `XIM_XCONNECT`, start the handshake on the socket
+    fn tcp_connect(&mut self) -> Result<(), ClientError> {
+        if let Some(tcp) = self.tcp.as_ref() {
+            log::info!("TCP connected server on {}", tcp.address());
+            self.state.set_transport(TransportInfo::tcp(
+                self.im_name.clone(),
+                tcp.address().into(),
+            ));
+        }
+
+        send_connect(self)
+    }
+
     fn xconnect(&mut self) {
         let mut ev = xlib::XClientMessageEvent {
             display: self.display,
@@ -430,40 +958,38 @@ impl<X: XlibRef> XlibClient<X> {
     }
 
     fn send_req_impl(&mut self, req: Request) {
-        if log::log_enabled!(log::Level::Trace) {
-            log::trace!("->: {:?}", req);
-        } else {
-            log::debug!("->: {}", req.name());
-        }
+        crate::trace::request_sent(&req);
 
         self.buf.resize(req.size(), 0);
         xim_parser::write(&req, &mut self.buf);
 
         if self.buf.len() < self.transport_max {
-            if self.buf.len() > 20 {
-                todo!("multi-CM");
-            }
-            self.buf.resize(20, 0);
-            let buf: [u8; 20] = self.buf.as_slice().try_into().unwrap();
-            let mut ev = xlib::XClientMessageEvent {
-                type_: xlib::ClientMessage,
-                display: self.display,
-                message_type: self.atoms.XIM_PROTOCOL,
-                data: buf.into(),
-                format: 8,
-                serial: 0,
-                send_event: xlib::True,
-                window: self.im_window,
-            }
-            .into();
-            unsafe {
-                (self.x.xlib().XSendEvent)(
-                    self.display,
-                    self.im_window,
-                    xlib::False,
-                    xlib::NoEventMask,
-                    &mut ev,
-                );
+            // divide into multiple ClientMessages
+            for (data, last) in split_client_messages(&mut self.buf) {
+                let mut ev = xlib::XClientMessageEvent {
+                    type_: xlib::ClientMessage,
+                    display: self.display,
+                    message_type: if last {
+                        self.atoms.XIM_PROTOCOL
+                    } else {
+                        self.atoms.XIM_MOREDATA
+                    },
+                    data: data.into(),
+                    format: 8,
+                    serial: 0,
+                    send_event: xlib::True,
+                    window: self.im_window,
+                }
+                .into();
+                unsafe {
+                    (self.x.xlib().XSendEvent)(
+                        self.display,
+                        self.im_window,
+                        xlib::False,
+                        xlib::NoEventMask,
+                        &mut ev,
+                    );
+                }
             }
         } else {
             let name = alloc::format!("_XIM_DATA_{}\0", self.sequence);
@@ -507,3 +1033,592 @@ impl<X: XlibRef> XlibClient<X> {
         self.buf.clear();
     }
 }
+
+/// XIM server over an Xlib display.
+///
+/// Windows are not destroyed and the server is not unregistered on drop since the display may
+/// be closed before, call [`XlibServer::unregister`] while it's open.
+#[cfg(feature = "xlib-server")]
+pub struct XlibServer<X: XlibRef> {
+    x: X,
+    display: *mut xlib::Display,
+    locale_data: String,
+    im_win: xlib::Window,
+    root: xlib::Window,
+    server_names: Vec<xlib::Atom>,
+    /// Whether `server_names` are still in `XIM_SERVERS` by us
+    registered: bool,
+    atoms: Atoms<xlib::Atom>,
+    buf: Vec<u8>,
+    sequence: u16,
+    /// Byte order of clients which differ from ours, keyed by their windows
+    client_endians: AHashMap<u32, Endian>,
+    #[cfg(feature = "metrics")]
+    stats: ServerStats,
+    wire_tap: Option<Box<dyn WireTap>>,
+}
+
+#[cfg(feature = "xlib-server")]
+impl<X: XlibRef> XlibServer<X> {
+    /// Initialize a new `XlibServer` registered as `im_name`.
+    ///
+    /// # Safety
+    ///
+    /// The `display` pointer must be a valid Xlib display.
+    pub unsafe fn init(
+        x: X,
+        display: *mut xlib::Display,
+        im_name: &str,
+        locales: &str,
+    ) -> Result<Self, ServerError> {
+        Self::init_with_names(x, display, &[im_name], locales)
+    }
+
+    /// Register the server under every name of `im_names`, see
+    /// [`X11rbServer::init_with_names`](crate::x11rb::X11rbServer::init_with_names).
+    ///
+    /// # Safety
+    ///
+    /// The `display` pointer must be a valid Xlib display.
+    pub unsafe fn init_with_names(
+        x: X,
+        display: *mut xlib::Display,
+        im_names: &[&str],
+        locales: &str,
+    ) -> Result<Self, ServerError> {
+        let xlib = x.xlib();
+        let root = (xlib.XDefaultRootWindow)(display);
+        let im_win = (xlib.XCreateSimpleWindow)(display, root, 0, 0, 1, 1, 0, 0, 0);
+
+        let atoms = Atoms::new_null::<ServerError, _>(|name| intern_atom(xlib, display, name))?;
+        let prev_servers = get_atoms(xlib, display, root, atoms.XIM_SERVERS)?;
+
+        let mut server_names = Vec::with_capacity(im_names.len());
+        let mut new_servers: Vec<c_long> = Vec::new();
+
+        for im_name in im_names {
+            let server_name = intern_atom(xlib, display, &alloc::format!("@server={}\0", im_name))?;
+
+            if server_names.contains(&server_name) {
+                continue;
+            }
+
+            if prev_servers.contains(&server_name) {
+                log::info!("Found previous XIM_SERVER it will overrided");
+            } else {
+                new_servers.push(server_name as c_long);
+            }
+
+            // override owner
+            (xlib.XSetSelectionOwner)(display, server_name, im_win, xlib::CurrentTime);
+            server_names.push(server_name);
+        }
+
+        if !new_servers.is_empty() {
+            (xlib.XChangeProperty)(
+                display,
+                root,
+                atoms.XIM_SERVERS,
+                xlib::XA_ATOM,
+                32,
+                xlib::PropModePrepend,
+                new_servers.as_ptr().cast(),
+                new_servers.len() as _,
+            );
+        }
+
+        (xlib.XFlush)(display);
+
+        log::info!("Start server win: {}", im_win);
+
+        Ok(Self {
+            x,
+            display,
+            locale_data: alloc::format!("@locale={}", locales),
+            im_win,
+            root,
+            server_names,
+            registered: true,
+            atoms,
+            buf: Vec::with_capacity(1024),
+            sequence: 0,
+            client_endians: AHashMap::with_hasher(Default::default()),
+            #[cfg(feature = "metrics")]
+            stats: ServerStats::new(),
+            wire_tap: None,
+        })
+    }
+
+    /// Pass every raw message exchanged with clients to `tap`, `None` removes it
+    pub fn set_wire_tap(&mut self, tap: Option<Box<dyn WireTap>>) {
+        self.wire_tap = tap;
+    }
+
+    /// Clients of `connections` and requests exchanged with them
+    #[cfg(feature = "metrics")]
+    pub fn stats<T>(&self, connections: &XimConnections<T>) -> ServerStats {
+        self.stats.snapshot(connections)
+    }
+
+    /// Remove the server from `XIM_SERVERS` of the root window and give up the selection, so
+    /// clients don't try to connect to it anymore.
+    ///
+    /// Names of which another server took the selection are not removed.
+    pub fn unregister(&mut self) -> Result<(), ServerError> {
+        if !self.registered {
+            return Ok(());
+        }
+
+        self.registered = false;
+
+        let xlib = self.x.xlib();
+        let mut owned = Vec::with_capacity(self.server_names.len());
+
+        unsafe {
+            for &server_name in self.server_names.iter() {
+                if (xlib.XGetSelectionOwner)(self.display, server_name) != self.im_win {
+                    log::info!("XIM_SERVER is owned by another server");
+                    continue;
+                }
+
+                (xlib.XSetSelectionOwner)(self.display, server_name, 0, xlib::CurrentTime);
+                owned.push(server_name);
+            }
+
+            if owned.is_empty() {
+                return Ok(());
+            }
+
+            let servers: Vec<c_long> =
+                get_atoms(xlib, self.display, self.root, self.atoms.XIM_SERVERS)?
+                    .into_iter()
+                    .filter(|s| !owned.contains(s))
+                    .map(|s| s as c_long)
+                    .collect();
+
+            (xlib.XChangeProperty)(
+                self.display,
+                self.root,
+                self.atoms.XIM_SERVERS,
+                xlib::XA_ATOM,
+                32,
+                xlib::PropModeReplace,
+                servers.as_ptr().cast(),
+                servers.len() as _,
+            );
+            (xlib.XFlush)(self.display);
+        }
+
+        Ok(())
+    }
+
+    /// Handle `e` if it's for the server, return `false` when it's not.
+    ///
+    /// # Safety
+    ///
+    /// `e` must be an event of the display of the server.
+    pub unsafe fn filter_event<T>(
+        &mut self,
+        e: &xlib::XEvent,
+        connections: &mut XimConnections<T>,
+        handler: &mut impl ServerHandler<Self, InputContextData = T>,
+    ) -> Result<bool, ServerError> {
+        match e.get_type() {
+            xlib::SelectionRequest if e.selection_request.owner == self.im_win => {
+                let req = &e.selection_request;
+
+                if req.property == self.atoms.LOCALES {
+                    log::trace!("Selection notify locale");
+                    self.send_selection_notify(req, &self.locale_data);
+                } else if req.property == self.atoms.TRANSPORT {
+                    log::trace!("Selection notify transport");
+                    self.send_selection_notify(req, "@transport=X/");
+                }
+                Ok(true)
+            }
+            xlib::ClientMessage => {
+                let msg = &e.client_message;
+                let xlib = self.x.xlib();
+
+                if msg.message_type == self.atoms.XIM_XCONNECT {
+                    let com_win =
+                        (xlib.XCreateSimpleWindow)(self.display, self.im_win, 0, 0, 1, 1, 0, 0, 0);
+                    let client_win = msg.data.get_long(0) as xlib::Window;
+                    log::info!("XConnected with {}", client_win);
+                    // clean up the connection when the client dies without `Disconnect`
+                    (xlib.XSelectInput)(self.display, client_win, xlib::StructureNotifyMask);
+
+                    let mut ev = xlib::XClientMessageEvent {
+                        type_: xlib::ClientMessage,
+                        display: self.display,
+                        message_type: self.atoms.XIM_XCONNECT,
+                        data: [com_win as c_long, 0, 0, 0, 0].into(),
+                        format: 32,
+                        serial: 0,
+                        send_event: xlib::True,
+                        window: client_win,
+                    }
+                    .into();
+                    (xlib.XSendEvent)(
+                        self.display,
+                        client_win,
+                        xlib::False,
+                        xlib::NoEventMask,
+                        &mut ev,
+                    );
+                    (xlib.XFlush)(self.display);
+                    connections.new_connection(com_win as u32, client_win as u32);
+                } else if msg.message_type == self.atoms.XIM_MOREDATA {
+                    if let Some(connection) = connections.get_connection(msg.window as u32) {
+                        let bytes = msg.data.as_bytes();
+                        connection
+                            .message_buffer
+                            .push(std::slice::from_raw_parts(bytes.as_ptr() as _, bytes.len()))?;
+                    } else {
+                        log::warn!("Unknown connection");
+                    }
+                } else if msg.message_type == self.atoms.XIM_PROTOCOL {
+                    let com_win = msg.window as u32;
+
+                    if let Some(connection) = connections.get_connection(com_win) {
+                        self.handle_xim_protocol(msg, connection, handler)?;
+                        if connection.disconnected {
+                            self.client_endians.remove(&connection.client_win);
+                            connections.remove_connection(com_win);
+                        } else {
+                            connections.update_focus(com_win);
+                        }
+                    } else {
+                        log::warn!("Unknown connection");
+                    }
+                }
+
+                Ok(true)
+            }
+            xlib::DestroyNotify => {
+                let window = e.destroy_window.window as u32;
+                let com_win = connections
+                    .connections
+                    .iter()
+                    .find(|(_, connection)| connection.client_win == window)
+                    .map(|(com_win, _)| *com_win);
+
+                match com_win {
+                    Some(com_win) => {
+                        log::info!("Client window {} is destroyed", window);
+                        self.client_endians.remove(&window);
+
+                        if let Some(mut connection) = connections.remove_connection(com_win) {
+                            connection.disconnect(self, handler)?;
+                        }
+
+                        let xlib = self.x.xlib();
+                        (xlib.XDestroyWindow)(self.display, com_win as xlib::Window);
+                        (xlib.XFlush)(self.display);
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn handle_xim_protocol<T>(
+        &mut self,
+        msg: &xlib::XClientMessageEvent,
+        connection: &mut XimConnection<T>,
+        handler: &mut impl ServerHandler<Self, InputContextData = T>,
+    ) -> Result<(), ServerError> {
+        let req = if msg.format == 32 {
+            let length = msg.data.get_long(0);
+            let atom = msg.data.get_long(1) as xlib::Atom;
+            let data = unsafe {
+                get_property(self.x.xlib(), self.display, msg.window, atom, length as _)?
+            };
+            connection.read_request(&data, self.wire_tap.as_deref_mut())?
+        } else {
+            let bytes = msg.data.as_bytes();
+            let data: &[u8] =
+                unsafe { std::slice::from_raw_parts(bytes.as_ptr() as _, bytes.len()) };
+            connection.read_request(data, self.wire_tap.as_deref_mut())?
+        };
+
+        #[cfg(feature = "metrics")]
+        self.stats.request_received(&req);
+        self.update_client_endian(connection);
+        connection.handle_request(self, req, handler)
+    }
+
+    /// Replies are written in the byte order `Connect` declared
+    fn update_client_endian<T>(&mut self, connection: &XimConnection<T>) {
+        if connection.client_endian() == Endian::Native {
+            self.client_endians.remove(&connection.client_win);
+        } else {
+            self.client_endians
+                .insert(connection.client_win, connection.client_endian());
+        }
+    }
+
+    unsafe fn send_selection_notify(&self, req: &xlib::XSelectionRequestEvent, data: &str) {
+        let xlib = self.x.xlib();
+        let mut ev = xlib::XSelectionEvent {
+            type_: xlib::SelectionNotify,
+            serial: 0,
+            send_event: xlib::True,
+            display: self.display,
+            requestor: req.requestor,
+            selection: req.selection,
+            target: req.target,
+            property: req.property,
+            time: req.time,
+        }
+        .into();
+
+        (xlib.XChangeProperty)(
+            self.display,
+            req.requestor,
+            req.property,
+            req.target,
+            8,
+            xlib::PropModeReplace,
+            data.as_ptr(),
+            data.len() as _,
+        );
+        (xlib.XSendEvent)(
+            self.display,
+            req.requestor,
+            xlib::False,
+            xlib::NoEventMask,
+            &mut ev,
+        );
+        (xlib.XFlush)(self.display);
+    }
+}
+
+#[cfg(feature = "xlib-server")]
+impl<X: XlibRef> ServerCore for XlibServer<X> {
+    type XEvent = xlib::XKeyEvent;
+
+    fn send_req(&mut self, client_win: u32, req: Request) -> Result<(), ServerError> {
+        crate::trace::request_sent(&req);
+        #[cfg(feature = "metrics")]
+        self.stats.request_sent(&req);
+
+        let endian = self
+            .client_endians
+            .get(&client_win)
+            .copied()
+            .unwrap_or(Endian::Native);
+        let target = client_win as xlib::Window;
+        let xlib = self.x.xlib();
+
+        self.buf.resize(req.size(), 0);
+        xim_parser::write_with_endian(&req, &mut self.buf, endian);
+
+        if let Some(tap) = self.wire_tap.as_mut() {
+            tap.on_send(client_win, &self.buf);
+        }
+
+        if self.buf.len() < 20 {
+            // divide into multiple ClientMessages
+            for (data, last) in split_client_messages(&mut self.buf) {
+                let mut ev = xlib::XClientMessageEvent {
+                    type_: xlib::ClientMessage,
+                    display: self.display,
+                    message_type: if last {
+                        self.atoms.XIM_PROTOCOL
+                    } else {
+                        self.atoms.XIM_MOREDATA
+                    },
+                    data: data.into(),
+                    format: 8,
+                    serial: 0,
+                    send_event: xlib::True,
+                    window: target,
+                }
+                .into();
+                unsafe {
+                    (xlib.XSendEvent)(
+                        self.display,
+                        target,
+                        xlib::False,
+                        xlib::NoEventMask,
+                        &mut ev,
+                    );
+                }
+            }
+        } else {
+            let name = alloc::format!("_XIM_DATA_{}\0", self.sequence);
+            self.sequence = self.sequence.wrapping_add(1);
+
+            unsafe {
+                let prop = intern_atom(xlib, self.display, &name)?;
+                (xlib.XChangeProperty)(
+                    self.display,
+                    target,
+                    prop,
+                    xlib::XA_STRING,
+                    8,
+                    xlib::PropModeAppend,
+                    self.buf.as_ptr(),
+                    self.buf.len() as _,
+                );
+                let mut ev = xlib::XClientMessageEvent {
+                    type_: xlib::ClientMessage,
+                    display: self.display,
+                    message_type: self.atoms.XIM_PROTOCOL,
+                    data: [self.buf.len() as c_long, prop as c_long, 0, 0, 0].into(),
+                    format: 32,
+                    serial: 0,
+                    send_event: xlib::True,
+                    window: target,
+                }
+                .into();
+                (xlib.XSendEvent)(
+                    self.display,
+                    target,
+                    xlib::False,
+                    xlib::NoEventMask,
+                    &mut ev,
+                );
+            }
+        }
+
+        self.buf.clear();
+
+        unsafe {
+            (xlib.XFlush)(self.display);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn serialize_event(&self, xev: &Self::XEvent) -> xim_parser::XEvent {
+        serialize_event_impl(xev)
+    }
+
+    #[inline]
+    fn deserialize_event(&self, ev: &xim_parser::XEvent) -> Self::XEvent {
+        deserialize_event_impl(ev, self.display)
+    }
+}
+
+/// Intern `name` which ends with a nul
+#[cfg(feature = "xlib-server")]
+unsafe fn intern_atom(
+    xlib: &xlib::Xlib,
+    display: *mut xlib::Display,
+    name: &str,
+) -> Result<xlib::Atom, ServerError> {
+    let atom = (xlib.XInternAtom)(display, name.as_ptr().cast(), xlib::False);
+
+    if atom == 0 {
+        Err(ServerError::InvalidReply)
+    } else {
+        Ok(atom)
+    }
+}
+
+/// Read and delete the property of `length` bytes
+#[cfg(feature = "xlib-server")]
+unsafe fn get_property(
+    xlib: &xlib::Xlib,
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    property: xlib::Atom,
+    length: c_long,
+) -> Result<Vec<u8>, ServerError> {
+    let mut ty = MaybeUninit::uninit();
+    let mut format = MaybeUninit::uninit();
+    let mut items = MaybeUninit::uninit();
+    let mut bytes = MaybeUninit::uninit();
+    let mut prop = MaybeUninit::uninit();
+
+    let code = (xlib.XGetWindowProperty)(
+        display,
+        window,
+        property,
+        0,
+        // in 32 bit units
+        (length + 3) / 4,
+        xlib::True,
+        xlib::AnyPropertyType as _,
+        ty.as_mut_ptr(),
+        format.as_mut_ptr(),
+        items.as_mut_ptr(),
+        bytes.as_mut_ptr(),
+        prop.as_mut_ptr(),
+    );
+
+    if code != 0 {
+        return Err(ServerError::InvalidReply);
+    }
+
+    let items = items.assume_init();
+    let prop: *mut u8 = prop.assume_init();
+
+    if prop.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let data = std::slice::from_raw_parts(prop, items as usize).to_vec();
+    (xlib.XFree)(prop.cast());
+
+    Ok(data)
+}
+
+/// Atoms in the property of `ATOM` type, Xlib returns them as `long`s
+#[cfg(feature = "xlib-server")]
+unsafe fn get_atoms(
+    xlib: &xlib::Xlib,
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    property: xlib::Atom,
+) -> Result<Vec<xlib::Atom>, ServerError> {
+    let mut ty = MaybeUninit::uninit();
+    let mut format = MaybeUninit::uninit();
+    let mut items = MaybeUninit::uninit();
+    let mut bytes = MaybeUninit::uninit();
+    let mut prop = MaybeUninit::uninit();
+
+    let code = (xlib.XGetWindowProperty)(
+        display,
+        window,
+        property,
+        0,
+        c_long::MAX,
+        xlib::False,
+        xlib::XA_ATOM,
+        ty.as_mut_ptr(),
+        format.as_mut_ptr(),
+        items.as_mut_ptr(),
+        bytes.as_mut_ptr(),
+        prop.as_mut_ptr(),
+    );
+
+    if code != 0 {
+        return Err(ServerError::InvalidReply);
+    }
+
+    let ty = ty.assume_init();
+    let items = items.assume_init();
+    let prop: *mut u8 = prop.assume_init();
+
+    if ty != 0 && ty != xlib::XA_ATOM {
+        if !prop.is_null() {
+            (xlib.XFree)(prop.cast());
+        }
+        return Err(ServerError::InvalidReply);
+    }
+
+    if prop.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let atoms = std::slice::from_raw_parts(prop as *const xlib::Atom, items as usize).to_vec();
+    (xlib.XFree)(prop.cast());
+
+    Ok(atoms)
+}
@@ -1,8 +1,6 @@
 // DO NOT EDIT YOURSELF
 // This source is auto generated by xim-gen
 
-#![allow(clippy::identity_op)]
-
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -23,15 +21,52 @@ where
     val.write(&mut Writer::new(out));
 }
 
+/// Same as [`read`] but with the byte order of the peer
+pub fn read_with_endian<T>(b: &[u8], endian: Endian) -> Result<T, ReadError>
+where
+    T: XimRead,
+{
+    T::read(&mut Reader::with_endian(b, endian))
+}
+
+/// Same as [`write`] but with the byte order of the peer
+pub fn write_with_endian<T>(val: T, out: &mut [u8], endian: Endian)
+where
+    T: XimWrite,
+{
+    val.write(&mut Writer::with_endian(out, endian));
+}
+
+/// Byte order of the messages declared by `Connect`
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum Endian {
+    Big = 0x42,
+    Little = 0x6c,
+}
+
+impl Endian {
     #[cfg(target_endian = "little")]
-    Native = 0x6c,
+    #[allow(non_upper_case_globals)]
+    pub const Native: Self = Self::Little;
     #[cfg(target_endian = "big")]
-    Native = 0x42,
-    // Big = 0x42,
-    // Little = 0x6c,
+    #[allow(non_upper_case_globals)]
+    pub const Native: Self = Self::Big;
+
+    /// Endian of the byte `n` of `Connect`
+    pub fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            0x42 => Some(Self::Big),
+            0x6c => Some(Self::Little),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Self::Native
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -56,37 +91,6 @@ pub enum CommitData {
         syncronous: bool,
     },
 }
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct InputStyleList {
-    pub styles: Vec<InputStyle>,
-}
-impl XimRead for InputStyleList {
-    fn read(reader: &mut Reader) -> Result<Self, ReadError> {
-        Ok(Self {
-            styles: {
-                let len = u16::read(reader)? as usize;
-                reader.consume(2)?;
-                let mut out = Vec::with_capacity(len);
-                for _ in 0..len {
-                    out.push(InputStyle::read(reader)?);
-                }
-                out
-            },
-        })
-    }
-}
-impl XimWrite for InputStyleList {
-    fn write(&self, writer: &mut Writer) {
-        (self.styles.len() as u16).write(writer);
-        0u16.write(writer);
-        for elem in self.styles.iter() {
-            elem.write(writer);
-        }
-    }
-    fn size(&self) -> usize {
-        self.styles.len() * 4 + 4
-    }
-}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct HotKeyTriggers {
@@ -132,19 +136,38 @@ fn with_pad4(len: usize) -> usize {
     len + pad4(len)
 }
 
+macro_rules! from_bytes {
+    ($ty:ident, $endian:expr, $bytes:expr) => {
+        match $endian {
+            Endian::Big => $ty::from_be_bytes($bytes),
+            Endian::Little => $ty::from_le_bytes($bytes),
+        }
+    };
+}
+
 pub struct Reader<'b> {
     bytes: &'b [u8],
     start: usize,
+    endian: Endian,
 }
 
 impl<'b> Reader<'b> {
     pub fn new(bytes: &'b [u8]) -> Self {
+        Self::with_endian(bytes, Endian::Native)
+    }
+
+    pub fn with_endian(bytes: &'b [u8], endian: Endian) -> Self {
         Self {
             bytes,
             start: bytes.as_ptr() as usize,
+            endian,
         }
     }
 
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
     fn ptr_offset(&self) -> usize {
         self.bytes.as_ptr() as usize - self.start
     }
@@ -175,22 +198,22 @@ impl<'b> Reader<'b> {
 
     pub fn i16(&mut self) -> Result<i16, ReadError> {
         let bytes = self.consume(2)?.try_into().unwrap();
-        Ok(i16::from_ne_bytes(bytes))
+        Ok(from_bytes!(i16, self.endian, bytes))
     }
 
     pub fn u16(&mut self) -> Result<u16, ReadError> {
         let bytes = self.consume(2)?.try_into().unwrap();
-        Ok(u16::from_ne_bytes(bytes))
+        Ok(from_bytes!(u16, self.endian, bytes))
     }
 
     pub fn u32(&mut self) -> Result<u32, ReadError> {
         let bytes = self.consume(4)?.try_into().unwrap();
-        Ok(u32::from_ne_bytes(bytes))
+        Ok(from_bytes!(u32, self.endian, bytes))
     }
 
     pub fn i32(&mut self) -> Result<i32, ReadError> {
         let bytes = self.consume(4)?.try_into().unwrap();
-        Ok(i32::from_ne_bytes(bytes))
+        Ok(from_bytes!(i32, self.endian, bytes))
     }
 
     pub fn consume(&mut self, len: usize) -> Result<&'b [u8], ReadError> {
@@ -207,11 +230,24 @@ impl<'b> Reader<'b> {
 pub struct Writer<'b> {
     out: &'b mut [u8],
     idx: usize,
+    endian: Endian,
 }
 
 impl<'b> Writer<'b> {
     pub fn new(out: &'b mut [u8]) -> Self {
-        Self { out, idx: 0 }
+        Self::with_endian(out, Endian::Native)
+    }
+
+    pub fn with_endian(out: &'b mut [u8], endian: Endian) -> Self {
+        Self {
+            out,
+            idx: 0,
+            endian,
+        }
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
     }
 
     pub fn write_u8(&mut self, b: u8) {
@@ -241,7 +277,7 @@ pub trait XimWrite {
     fn size(&self) -> usize;
 }
 
-impl<'a, T> XimWrite for &'a T
+impl<T> XimWrite for &T
 where
     T: XimWrite,
 {
@@ -258,12 +294,7 @@ where
 impl XimRead for Endian {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let n = u8::read(reader)?;
-
-        if n == Endian::Native as u8 {
-            Ok(Self::Native)
-        } else {
-            Err(ReadError::NotNativeEndian)
-        }
+        Self::from_u8(n).ok_or_else(|| reader.invalid_data("Endian", n))
     }
 }
 
@@ -324,7 +355,7 @@ impl XimRead for CommitData {
                 reader.pad4()?;
                 Ok(Self::Chars {
                     commited: bytes.to_vec(),
-                    syncronous: ty == 5,
+                    syncronous: ty == 3,
                 })
             }
             4 | 5 => {
@@ -332,7 +363,7 @@ impl XimRead for CommitData {
                 let keysym = reader.u32()?;
                 Ok(Self::Keysym {
                     keysym,
-                    syncronous: ty == 3,
+                    syncronous: ty == 5,
                 })
             }
             6 | 7 => {
@@ -473,7 +504,10 @@ macro_rules! impl_int {
 
         impl XimWrite for $ty {
             fn write(&self, writer: &mut Writer) {
-                writer.write(&self.to_ne_bytes())
+                match writer.endian {
+                    Endian::Big => writer.write(&self.to_be_bytes()),
+                    Endian::Little => writer.write(&self.to_le_bytes()),
+                }
             }
 
             fn size(&self) -> usize {
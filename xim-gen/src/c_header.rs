@@ -0,0 +1,126 @@
+use crate::format_type::{Field, FormatType};
+use crate::XimFormat;
+use convert_case::{Case, Casing};
+use std::io::{self, Write};
+
+fn primitive_c_type(name: &str) -> Option<(&'static str, usize)> {
+    let ty = match name {
+        "u8" | "bool" | "Endian" => ("uint8_t", 1),
+        "u16" => ("uint16_t", 2),
+        "i16" => ("int16_t", 2),
+        "u32" => ("uint32_t", 4),
+        "i32" => ("int32_t", 4),
+        _ => return None,
+    };
+
+    Some(ty)
+}
+
+impl XimFormat {
+    /// C type and wire size of `ty` if it has fixed size
+    fn c_type(&self, ty: &FormatType) -> Option<(String, usize)> {
+        match ty {
+            FormatType::Normal(name) => {
+                if let Some((c_ty, size)) = primitive_c_type(name) {
+                    Some((c_ty.into(), size))
+                } else if let Some(em) = self.enums.get(name) {
+                    primitive_c_type(&em.repr).map(|(c_ty, size)| (c_ty.into(), size))
+                } else if let Some(st) = self.structs.get(name) {
+                    self.fields_size(&st.body)
+                        .map(|size| (format!("Xim{}", name), size))
+                } else {
+                    None
+                }
+            }
+            FormatType::Append(inner, size) => self
+                .c_type(inner)
+                .map(|(c_ty, inner_size)| (c_ty, inner_size + size)),
+            _ => None,
+        }
+    }
+
+    fn fields_size(&self, fields: &[Field]) -> Option<usize> {
+        fields
+            .iter()
+            .map(|field| self.c_type(&field.ty).map(|(_, size)| size))
+            .sum()
+    }
+
+    fn write_c_fields(&self, fields: &[Field], out: &mut impl Write) -> io::Result<()> {
+        for field in fields.iter() {
+            let (c_ty, _) = self.c_type(&field.ty).unwrap();
+            writeln!(out, "    {} {};", c_ty, field.name)?;
+            if let FormatType::Append(_, size) = field.ty {
+                writeln!(out, "    uint8_t {}_unused[{}];", field.name, size)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn write_c_header(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "/* DO NOT EDIT YOURSELF */")?;
+        writeln!(out, "/* This header is auto generated by xim-gen */")?;
+        writeln!(out)?;
+        writeln!(out, "#ifndef XIM_PROTOCOL_H")?;
+        writeln!(out, "#define XIM_PROTOCOL_H")?;
+        writeln!(out)?;
+        writeln!(out, "#include <stdint.h>")?;
+        writeln!(out)?;
+
+        for (name, em) in self.enums.iter() {
+            let prefix = format!("XIM_{}", name.to_case(Case::UpperSnake));
+            let mut variants = em.variants.iter().collect::<Vec<_>>();
+            variants.sort_unstable_by(|l, r| l.1.cmp(r.1));
+
+            writeln!(out, "/* {} */", name)?;
+            for (variant, value) in variants {
+                writeln!(
+                    out,
+                    "#define {}_{} {:#x}",
+                    prefix,
+                    variant.to_case(Case::UpperSnake),
+                    value
+                )?;
+            }
+            writeln!(out)?;
+        }
+
+        for (name, st) in self.structs.iter() {
+            match self.fields_size(&st.body) {
+                Some(size) => {
+                    writeln!(out, "typedef struct {{")?;
+                    self.write_c_fields(&st.body, out)?;
+                    writeln!(out, "}} __attribute__((packed)) Xim{};", name)?;
+                    writeln!(
+                        out,
+                        "#define XIM_{}_SIZE {}",
+                        name.to_case(Case::UpperSnake),
+                        size
+                    )?;
+                }
+                None => {
+                    writeln!(out, "/* Xim{} has variable length */", name)?;
+                }
+            }
+            writeln!(out)?;
+        }
+
+        for (name, req) in self.sorted_requests() {
+            let name = name.to_case(Case::UpperSnake);
+            writeln!(out, "#define XIM_{} {}", name, req.major_opcode)?;
+            if let Some(minor) = req.minor_opcode {
+                writeln!(out, "#define XIM_{}_MINOR {}", name, minor)?;
+            }
+            // size including the 4 byte request header
+            if let Some(size) = self.fields_size(&req.body) {
+                writeln!(out, "#define XIM_{}_SIZE {}", name, size + 4)?;
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "#endif /* XIM_PROTOCOL_H */")?;
+
+        Ok(())
+    }
+}
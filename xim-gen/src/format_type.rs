@@ -14,6 +14,7 @@ pub enum FormatType {
     Append(Box<Self>, usize),
     Pad(Box<Self>, usize),
     List(Box<Self>, usize, usize),
+    CountedList(Box<Self>, usize, usize),
     String { between_unused: usize, len: usize },
     XString,
     Normal(String),
@@ -33,10 +34,11 @@ impl FormatType {
                 write!(out, "; reader.pad4()?; inner }}")?;
             }
             FormatType::List(inner, prefix, len) => {
-                writeln!(out, "{{ let mut out = Vec::new(); let len = u{}::read(reader)? as usize; let end = reader.cursor() - len;", len * 8)?;
+                writeln!(out, "{{ let mut out = alloc::vec::Vec::new(); let len = u{}::read(reader)? as usize;", len * 8)?;
                 if *prefix > 0 {
                     writeln!(out, "u{}::read(reader)?;", prefix * 8)?;
                 }
+                writeln!(out, "let end = reader.cursor() - len;")?;
                 writeln!(out, "while reader.cursor() > end {{")?;
                 write!(out, "out.push(")?;
                 inner.read(out)?;
@@ -44,6 +46,19 @@ impl FormatType {
                 write!(out, "}}")?;
                 write!(out, "out }}")?;
             }
+            FormatType::CountedList(inner, prefix, len) => {
+                writeln!(out, "{{ let len = u{}::read(reader)? as usize;", len * 8)?;
+                if *prefix > 0 {
+                    writeln!(out, "reader.consume({})?;", prefix)?;
+                }
+                writeln!(out, "let mut out = alloc::vec::Vec::with_capacity(len);")?;
+                writeln!(out, "for _ in 0..len {{")?;
+                write!(out, "out.push(")?;
+                inner.read(out)?;
+                write!(out, ");")?;
+                write!(out, "}}")?;
+                write!(out, "out }}")?;
+            }
             FormatType::XString => {
                 writeln!(
                     out,
@@ -60,7 +75,7 @@ impl FormatType {
                 }
                 writeln!(
                     out,
-                    "String::from_utf8(reader.consume(len as usize)?.to_vec())?"
+                    "alloc::string::String::from_utf8(reader.consume(len as usize)?.to_vec())?"
                 )?;
                 writeln!(out, "}}")?
             }
@@ -95,6 +110,17 @@ impl FormatType {
                 inner.write("elem", out)?;
                 writeln!(out, "}}")?;
             }
+            FormatType::CountedList(inner, prefix, len) => {
+                writeln!(out, "({}.len() as u{}).write(writer);", this, len * 8)?;
+
+                if *prefix > 0 {
+                    writeln!(out, "writer.write(&[0u8; {}]);", prefix)?;
+                }
+
+                writeln!(out, "for elem in {}.iter() {{", this)?;
+                inner.write("elem", out)?;
+                writeln!(out, "}}")?;
+            }
             FormatType::Pad(inner, _size_add) => {
                 inner.write(this, out)?;
                 writeln!(out, "writer.write_pad4();")?;
@@ -132,7 +158,7 @@ impl FormatType {
             } => {
                 write!(out, "{}.len() + {} + {}", this, len, between_unused)
             }
-            FormatType::List(inner, prefix, len) => {
+            FormatType::List(inner, prefix, len) | FormatType::CountedList(inner, prefix, len) => {
                 write!(out, "{}.iter().map(|e| ", this)?;
                 inner.size("e", out)?;
                 write!(out, ").sum::<usize>() + {} + {}", prefix, len)
@@ -157,9 +183,12 @@ impl fmt::Display for FormatType {
         match self {
             FormatType::Append(inner, _len) => inner.fmt(f),
             FormatType::Pad(inner, ..) => inner.fmt(f),
-            FormatType::List(inner, _prefix, _len) => write!(f, "Vec<{}>", inner),
-            FormatType::XString => f.write_str("Vec<u8>"),
-            FormatType::String { .. } => f.write_str("String"),
+            FormatType::List(inner, _prefix, _len)
+            | FormatType::CountedList(inner, _prefix, _len) => {
+                write!(f, "alloc::vec::Vec<{}>", inner)
+            }
+            FormatType::XString => f.write_str("alloc::vec::Vec<u8>"),
+            FormatType::String { .. } => f.write_str("alloc::string::String"),
             FormatType::Normal(name) => f.write_str(name),
         }
     }
@@ -192,19 +221,12 @@ impl std::str::FromStr for FormatType {
             Ok(Self::Pad(Box::new(left.parse()?), 2))
         } else if let Some(left) = s.strip_prefix("@pad") {
             Ok(Self::Pad(Box::new(left.parse()?), 0))
-        } else if let Some(mut left) = s.strip_prefix("@list") {
-            let mut prefix = 0;
-            let mut len = 2;
-
-            if left.chars().next().unwrap().is_numeric() {
-                let (num, new_left) = left.split_at(2);
-                left = new_left;
-                let num = num.parse::<usize>().map_err(|_| "Invalid number")?;
-                prefix = num / 10;
-                len = num % 10;
-            }
-
+        } else if let Some(left) = s.strip_prefix("@list") {
+            let (prefix, len, left) = parse_list_args(left)?;
             Ok(Self::List(Box::new(left.parse()?), prefix, len))
+        } else if let Some(left) = s.strip_prefix("@countedlist") {
+            let (prefix, len, left) = parse_list_args(left)?;
+            Ok(Self::CountedList(Box::new(left.parse()?), prefix, len))
         } else if let Some(left) = s.strip_prefix("@append") {
             let (n, left) = left.split_at(1);
             Ok(Self::Append(
@@ -235,3 +257,27 @@ impl std::str::FromStr for FormatType {
         }
     }
 }
+
+/// parse optional `<prefix><len>` digits after list commands
+fn parse_list_args(mut left: &str) -> Result<(usize, usize, &str), &'static str> {
+    let mut prefix = 0;
+    let mut len = 2;
+
+    if left.trim().is_empty() {
+        return Err("Missing list element type");
+    }
+
+    if left.as_bytes()[0].is_ascii_digit() {
+        if !matches!(left.as_bytes().get(1), Some(b) if b.is_ascii_digit()) {
+            return Err("List need 2 digits of prefix and length");
+        }
+
+        let (num, new_left) = left.split_at(2);
+        left = new_left;
+        let num = num.parse::<usize>().map_err(|_| "Invalid number")?;
+        prefix = num / 10;
+        len = num % 10;
+    }
+
+    Ok((prefix, len, left))
+}
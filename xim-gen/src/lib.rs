@@ -3,16 +3,20 @@
 #![allow(clippy::uninlined_format_args)]
 
 use crate::format_type::Field;
+pub use crate::merge::MergeError;
 use convert_case::{Case, Casing};
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+mod c_header;
 mod format_type;
+mod merge;
+pub mod xml;
 
-#[derive(Deserialize)]
-#[cfg_attr(debug_assertions, derive(Debug, Eq, PartialEq))]
+#[derive(Deserialize, Eq, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
 struct EnumFormat {
     repr: String,
     #[serde(default)]
@@ -109,16 +113,16 @@ impl EnumFormat {
     }
 }
 
-#[derive(Deserialize)]
-#[cfg_attr(debug_assertions, derive(Debug, Eq, PartialEq))]
+#[derive(Deserialize, Eq, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
 struct RequestFormat {
     major_opcode: u8,
     minor_opcode: Option<u8>,
     body: Vec<Field>,
 }
 
-#[derive(Deserialize)]
-#[cfg_attr(debug_assertions, derive(Debug, Eq, PartialEq))]
+#[derive(Deserialize, Eq, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
 #[serde(transparent)]
 struct StructFormat {
     body: Vec<Field>,
@@ -185,9 +189,10 @@ impl StructFormat {
     }
 }
 
-#[derive(Deserialize)]
-#[cfg_attr(debug_assertions, derive(Debug, Eq, PartialEq))]
-struct XimFormat {
+/// Parsed protocol definition
+#[derive(Deserialize, Eq, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct XimFormat {
     #[serde(rename = "Enums")]
     enums: BTreeMap<String, EnumFormat>,
     #[serde(rename = "AttributeNames")]
@@ -199,6 +204,13 @@ struct XimFormat {
 }
 
 impl XimFormat {
+    /// Requests ordered by opcode so generated output doesn't depend on naming
+    fn sorted_requests(&self) -> Vec<(&String, &RequestFormat)> {
+        let mut requests = self.requests.iter().collect::<Vec<_>>();
+        requests.sort_by_key(|(name, req)| (req.major_opcode, req.minor_opcode, *name));
+        requests
+    }
+
     pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
         for (name, em) in self.enums.iter() {
             em.write(name, out)?;
@@ -269,7 +281,7 @@ impl XimFormat {
         writeln!(out, "#[derive(Debug, Clone, Eq, PartialEq)]")?;
         writeln!(out, "pub enum Request {{")?;
 
-        for (name, req) in self.requests.iter() {
+        for (name, req) in self.sorted_requests() {
             writeln!(out, "{} {{", name)?;
             for field in req.body.iter() {
                 writeln!(out, "{}: {},", field.name, field.ty)?;
@@ -277,18 +289,47 @@ impl XimFormat {
             writeln!(out, "}},")?;
         }
 
+        writeln!(
+            out,
+            "/// Extension message which has no format, `data` is padded to 4 bytes"
+        )?;
+        writeln!(
+            out,
+            "ExtensionMessage {{ major_opcode: u8, minor_opcode: u8, data: alloc::vec::Vec<u8>, }},"
+        )?;
+
         writeln!(out, "}}")?;
 
         writeln!(out, "impl Request {{")?;
         writeln!(out, "pub fn name(&self) -> &'static str {{")?;
         writeln!(out, "match self {{")?;
-        for (name, _req) in self.requests.iter() {
+        for (name, _req) in self.sorted_requests() {
             writeln!(out, "Request::{} {{ .. }} => \"{}\",", name, name)?;
         }
+        writeln!(
+            out,
+            "Request::ExtensionMessage {{ .. }} => \"ExtensionMessage\","
+        )?;
         // match
         writeln!(out, "}}")?;
         // fn name
         writeln!(out, "}}")?;
+
+        for id in ["input_method_id", "input_context_id"] {
+            writeln!(out, "pub fn {}(&self) -> Option<u16> {{", id)?;
+            writeln!(out, "match self {{")?;
+            for (name, req) in self.sorted_requests() {
+                if req.body.iter().any(|field| field.name == id) {
+                    writeln!(out, "Request::{} {{ {}, .. }} => Some(*{}),", name, id, id)?;
+                }
+            }
+            writeln!(out, "_ => None,")?;
+            // match
+            writeln!(out, "}}")?;
+            // fn
+            writeln!(out, "}}")?;
+        }
+
         // impl Request
         writeln!(out, "}}")?;
 
@@ -301,12 +342,12 @@ impl XimFormat {
 
         writeln!(
             out,
-            "let major_opcode = reader.u8()?; let minor_opcode = reader.u8()?; let _length = reader.u16()?;"
+            "let major_opcode = reader.u8()?; let minor_opcode = reader.u8()?; let length = reader.u16()?;"
         )?;
 
         writeln!(out, "match (major_opcode, minor_opcode) {{")?;
 
-        for (name, req) in self.requests.iter() {
+        for (name, req) in self.sorted_requests() {
             write!(out, "({}, ", req.major_opcode)?;
 
             if let Some(minor) = req.minor_opcode {
@@ -324,6 +365,7 @@ impl XimFormat {
             writeln!(out, "}}),")?;
         }
 
+        writeln!(out, "(major_opcode, minor_opcode) if major_opcode >= 128 => Ok(Request::ExtensionMessage {{ major_opcode, minor_opcode, data: reader.consume(length as usize * 4)?.to_vec() }}),")?;
         writeln!(out, "_ => Err(reader.invalid_data(\"Opcode\", alloc::format!(\"({{}}, {{}})\", major_opcode, minor_opcode))),")?;
 
         // match
@@ -341,7 +383,7 @@ impl XimFormat {
 
         writeln!(out, "match self {{")?;
 
-        for (name, req) in self.requests.iter() {
+        for (name, req) in self.sorted_requests() {
             writeln!(out, "Request::{} {{", name)?;
             for field in req.body.iter() {
                 write!(out, "{}, ", field.name)?;
@@ -359,6 +401,17 @@ impl XimFormat {
             writeln!(out, "}}")?;
         }
 
+        writeln!(
+            out,
+            "Request::ExtensionMessage {{ major_opcode, minor_opcode, data }} => {{"
+        )?;
+        writeln!(out, "major_opcode.write(writer);")?;
+        writeln!(out, "minor_opcode.write(writer);")?;
+        writeln!(out, "(((self.size() - 4) / 4) as u16).write(writer);")?;
+        writeln!(out, "writer.write(data);")?;
+        writeln!(out, "writer.write_pad4();")?;
+        writeln!(out, "}}")?;
+
         // match
         writeln!(out, "}}")?;
 
@@ -370,7 +423,7 @@ impl XimFormat {
 
         writeln!(out, "match self {{")?;
 
-        for (name, req) in self.requests.iter() {
+        for (name, req) in self.sorted_requests() {
             writeln!(out, "Request::{} {{", name)?;
             for field in req.body.iter() {
                 write!(out, "{}, ", field.name)?;
@@ -386,6 +439,11 @@ impl XimFormat {
             writeln!(out, "}}")?;
         }
 
+        writeln!(
+            out,
+            "Request::ExtensionMessage {{ data, .. }} => {{ content_size += with_pad4(data.len()); }}"
+        )?;
+
         // match
         writeln!(out, "}}")?;
         writeln!(out, "content_size + 4")?;
@@ -400,26 +458,396 @@ impl XimFormat {
     }
 }
 
+impl XimFormat {
+    /// Parse YAML definition like `xim-parser/xim-format.yaml`
+    pub fn from_yaml(format_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_yaml::from_str(format_str)?)
+    }
+
+    /// Parse xcbproto style XML definition, see [`xml`] module for supported elements
+    pub fn from_xml(format_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        xml::read_format(format_str)
+    }
+
+    fn render_rust(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut generated = Vec::new();
+        self.write(&mut generated)?;
+        let generated = syn::parse_file(std::str::from_utf8(&generated)?)?;
+
+        // snippet is written as-is to keep its comments, only generated part need formatting
+        let mut out = include_bytes!("../res/snippet.rs").to_vec();
+        out.extend_from_slice(prettyplease::unparse(&generated).as_bytes());
+
+        Ok(out)
+    }
+
+    /// Generate the parser module into `out_path`.
+    ///
+    /// The generated module only refers to `core` and `alloc` paths so it can be included from a
+    /// `no_std` crate with `extern crate alloc`, the `std::error::Error` impl is gated behind the
+    /// `std` feature of the including crate.
+    pub fn generate_rust(
+        &self,
+        out_path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        write_if_changed(out_path.as_ref(), &self.render_rust()?)
+    }
+
+    /// Generate a C header with the enum values, fixed size structs, opcodes and size macros
+    /// into `out_path`.
+    pub fn generate_c_header(
+        &self,
+        out_path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut out = Vec::new();
+        self.write_c_header(&mut out)?;
+        write_if_changed(out_path.as_ref(), &out)
+    }
+}
+
+/// Don't touch the file when content is same so cargo doesn't consider it changed
+fn write_if_changed(out_path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    if std::fs::read(out_path).ok().as_deref() == Some(content) {
+        return Ok(());
+    }
+
+    std::fs::write(out_path, content)?;
+
+    Ok(())
+}
+
+/// Generate the parser module from definition files for usage in `build.rs`.
+///
+/// Files ending with `.xml` are read with [`XimFormat::from_xml`], others as YAML. All files are
+/// merged in order then written to `out_dir/xim_protocol.rs` and the path of it is returned.
+/// `cargo:rerun-if-changed` is printed for every file, and the output is only rewritten when its
+/// content changed, so the build stays reproducible and cache friendly.
+///
+/// ```no_run
+/// // build.rs
+/// let out_dir = std::env::var("OUT_DIR").unwrap();
+/// xim_gen::generate_into(&["xim-format.yaml", "my-extension.yaml"], out_dir).unwrap();
+/// ```
+///
+/// ```ignore
+/// // lib.rs, the generated module uses `alloc`
+/// extern crate alloc;
+///
+/// #[allow(clippy::identity_op)]
+/// mod protocol {
+///     include!(concat!(env!("OUT_DIR"), "/xim_protocol.rs"));
+/// }
+/// ```
+pub fn generate_into(
+    format_paths: &[impl AsRef<Path>],
+    out_dir: impl AsRef<Path>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut format: Option<XimFormat> = None;
+
+    for path in format_paths.iter() {
+        let path = path.as_ref();
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let text = std::fs::read_to_string(path)?;
+        let new = if path.extension() == Some("xml".as_ref()) {
+            XimFormat::from_xml(&text)?
+        } else {
+            XimFormat::from_yaml(&text)?
+        };
+
+        match format.as_mut() {
+            Some(format) => format.merge(new)?,
+            None => format = Some(new),
+        }
+    }
+
+    let out_path = out_dir.as_ref().join("xim_protocol.rs");
+    format.ok_or("No format given")?.generate_rust(&out_path)?;
+
+    Ok(out_path)
+}
+
+/// Generate the parser module from YAML `format_str` into `out_path`.
 pub fn write_format(
     format_str: &str,
     out_path: impl AsRef<Path>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let format: XimFormat = serde_yaml::from_str(format_str)?;
+    XimFormat::from_yaml(format_str)?.generate_rust(out_path)
+}
 
-    let mut file = std::io::BufWriter::new(std::fs::File::create(out_path.as_ref())?);
+/// Merge several YAML definitions then generate the parser module into `out_path`.
+///
+/// This allows defining private extension requests in a separate file without forking the
+/// core definition, see [`XimFormat::merge`] for conflict rules.
+pub fn write_formats(
+    format_strs: &[&str],
+    out_path: impl AsRef<Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut iter = format_strs.iter();
+    let mut format = XimFormat::from_yaml(iter.next().ok_or("No format given")?)?;
+
+    for format_str in iter {
+        format.merge(XimFormat::from_yaml(format_str)?)?;
+    }
 
-    file.write_all(include_bytes!("../res/snippet.rs"))?;
-    format.write(&mut file)?;
-    file.flush()?;
+    format.generate_rust(out_path)
+}
+
+/// Generate a C header from YAML `format_str` into `out_path`.
+pub fn write_c_header(
+    format_str: &str,
+    out_path: impl AsRef<Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    XimFormat::from_yaml(format_str)?.generate_c_header(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format_type::FormatType;
+    use crate::{MergeError, XimFormat};
+
+    const FORMAT: &str = include_str!("../../xim-parser/xim-format.yaml");
+
+    #[test]
+    fn list_args() {
+        assert!(matches!(
+            "@list22 u32".parse(),
+            Ok(FormatType::List(_, 2, 2))
+        ));
+        assert_eq!(
+            "@list".parse::<FormatType>().err(),
+            Some("Missing list element type")
+        );
+        assert_eq!(
+            "@countedlist ".parse::<FormatType>().err(),
+            Some("Missing list element type")
+        );
+        assert_eq!(
+            "@countedlist2".parse::<FormatType>().err(),
+            Some("List need 2 digits of prefix and length")
+        );
+        assert_eq!(
+            "@list2\u{d55c} u8".parse::<FormatType>().err(),
+            Some("List need 2 digits of prefix and length")
+        );
+    }
 
-    let rustfmt = std::process::Command::new("rustfmt")
-        .arg(std::fs::canonicalize(out_path.as_ref())?)
-        .spawn()
-        .expect("call rustfmt")
-        .wait()
+    #[test]
+    fn c_header() {
+        let format = XimFormat::from_yaml(FORMAT).unwrap();
+        let mut out = Vec::new();
+        format.write_c_header(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("#define XIM_SET_EVENT_MASK 37\n"));
+        assert!(out.contains("#define XIM_SET_EVENT_MASK_SIZE 16\n"));
+        assert!(out.contains("#define XIM_INPUT_STYLE_PREEDIT_POSITION 0x4\n"));
+        assert!(out.contains("#define XIM_X_EVENT_SIZE 32\n"));
+        assert!(out.contains("/* XimAttr has variable length */"));
+    }
+
+    #[test]
+    fn xml_same_as_yaml() {
+        let yaml = XimFormat::from_yaml(
+            r#"
+Enums:
+  ErrorFlag:
+    repr: u16
+    bitflag: true
+    variants:
+      InputMethodIdValid: 1
+      InputContextIdValid: 2
+AttributeNames:
+  QueryInputStyle: queryInputStyle
+Structs:
+  Attr:
+    - "id u16"
+    - "ty AttrType"
+    - "name @pad AttributeName"
+Requests:
+  Close:
+    major_opcode: 32
+    minor_opcode: ~
+    body:
+      - "input_method_id @append2 u16"
+  GetImValues:
+    major_opcode: 44
+    minor_opcode: ~
+    body:
+      - "input_method_id u16"
+      - "im_attributes @padadd2 @list u16"
+"#,
+        )
+        .unwrap();
+        let xml = XimFormat::from_xml(
+            r#"
+<xcb header="xim">
+  <enum name="ErrorFlag" repr="u16" bitflag="true">
+    <item name="InputMethodIdValid"><bit>0</bit></item>
+    <item name="InputContextIdValid"><value>2</value></item>
+  </enum>
+  <attributename name="QueryInputStyle" value="queryInputStyle" />
+  <struct name="Attr">
+    <field type="CARD16" name="id" />
+    <field type="AttrType" name="ty" />
+    <field type="AttributeName" name="name" />
+    <pad align="4" />
+  </struct>
+  <request name="Close" opcode="32">
+    <field type="CARD16" name="input_method_id" />
+    <pad bytes="2" />
+  </request>
+  <request name="GetImValues" opcode="44">
+    <field type="CARD16" name="input_method_id" />
+    <field name="im_attributes" format="@padadd2 @list u16" />
+  </request>
+</xcb>
+"#,
+        )
         .unwrap();
 
-    assert!(rustfmt.success());
+        assert_eq!(yaml, xml);
+    }
 
-    Ok(())
+    #[test]
+    fn merge_extension() {
+        let mut format = XimFormat::from_yaml(FORMAT).unwrap();
+        let ext = XimFormat::from_yaml(
+            r#"
+Enums:
+  ErrorCode:
+    repr: u16
+    variants:
+      BadAlloc: 1
+      PrivateError: 1000
+AttributeNames: {}
+Structs: {}
+Requests:
+  PrivateRequest:
+    major_opcode: 128
+    minor_opcode: 1
+    body:
+      - "input_method_id @append2 u16"
+"#,
+        )
+        .unwrap();
+
+        format.merge(ext).unwrap();
+        assert_eq!(format.enums["ErrorCode"].variants["PrivateError"], 1000);
+        assert!(format.requests.contains_key("PrivateRequest"));
+    }
+
+    #[test]
+    fn merge_conflict() {
+        let mut format = XimFormat::from_yaml(FORMAT).unwrap();
+        let ext = XimFormat::from_yaml(
+            r#"
+Enums: {}
+AttributeNames: {}
+Structs: {}
+Requests:
+  MyOpen:
+    major_opcode: 30
+    minor_opcode: ~
+    body: []
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            format.merge(ext),
+            Err(MergeError::Opcode {
+                major_opcode: 30,
+                minor_opcode: None,
+                first: "Open".into(),
+                second: "MyOpen".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn merge_duplicate_enum_value() {
+        let mut format = XimFormat::from_yaml(FORMAT).unwrap();
+        let ext = XimFormat::from_yaml(
+            r#"
+Enums:
+  ErrorCode:
+    repr: u16
+    variants:
+      PrivateError: 16
+AttributeNames: {}
+Structs: {}
+Requests: {}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            format.merge(ext),
+            Err(MergeError::EnumValue {
+                name: "ErrorCode".into(),
+                value: 16,
+                first: "LocaleNotSupported".into(),
+                second: "PrivateError".into(),
+            })
+        );
+
+        let mut format = XimFormat::from_yaml(FORMAT).unwrap();
+        let ext = XimFormat::from_yaml(
+            r#"
+Enums:
+  PrivateKind:
+    repr: u8
+    variants:
+      First: 1
+      Second: 1
+AttributeNames: {}
+Structs: {}
+Requests: {}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            format.merge(ext),
+            Err(MergeError::EnumValue {
+                name: "PrivateKind".into(),
+                value: 1,
+                first: "First".into(),
+                second: "Second".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn merge_conflict_in_other() {
+        let mut format = XimFormat::from_yaml(FORMAT).unwrap();
+        let ext = XimFormat::from_yaml(
+            r#"
+Enums: {}
+AttributeNames: {}
+Structs: {}
+Requests:
+  PrivateFirst:
+    major_opcode: 128
+    minor_opcode: 1
+    body: []
+  PrivateSecond:
+    major_opcode: 128
+    minor_opcode: 1
+    body: []
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            format.merge(ext),
+            Err(MergeError::Opcode {
+                major_opcode: 128,
+                minor_opcode: Some(1),
+                first: "PrivateFirst".into(),
+                second: "PrivateSecond".into(),
+            })
+        );
+    }
 }
@@ -0,0 +1,185 @@
+use crate::{EnumFormat, RequestFormat, XimFormat};
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fmt;
+
+/// Conflict found while merging definitions
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MergeError {
+    /// Same enum variant defined with different value, or enum repr/bitflag mismatch
+    Enum(String),
+    /// Same attribute name mapped to different strings
+    AttributeName(String),
+    /// Same struct defined with different body
+    Struct(String),
+    /// Same request defined with different body or opcode
+    Request(String),
+    /// Two variants of an enum which is not a bitflag share the same value
+    EnumValue {
+        name: String,
+        value: usize,
+        first: String,
+        second: String,
+    },
+    /// Two requests share the same opcode
+    Opcode {
+        major_opcode: u8,
+        minor_opcode: Option<u8>,
+        first: String,
+        second: String,
+    },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::Enum(name) => write!(f, "Conflicting definition of enum {}", name),
+            MergeError::AttributeName(name) => {
+                write!(f, "Conflicting definition of attribute name {}", name)
+            }
+            MergeError::EnumValue {
+                name,
+                value,
+                first,
+                second,
+            } => write!(
+                f,
+                "Variant {} and {} of enum {} use same value {}",
+                first, second, name, value
+            ),
+            MergeError::Struct(name) => write!(f, "Conflicting definition of struct {}", name),
+            MergeError::Request(name) => write!(f, "Conflicting definition of request {}", name),
+            MergeError::Opcode {
+                major_opcode,
+                minor_opcode,
+                first,
+                second,
+            } => write!(
+                f,
+                "Request {} and {} use same opcode ({}, {:?})",
+                first, second, major_opcode, minor_opcode
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+fn merge_same<V: PartialEq>(
+    map: &mut BTreeMap<String, V>,
+    other: BTreeMap<String, V>,
+    err: impl Fn(String) -> MergeError,
+) -> Result<(), MergeError> {
+    for (name, value) in other {
+        match map.entry(name) {
+            Entry::Vacant(v) => {
+                v.insert(value);
+            }
+            Entry::Occupied(o) => {
+                if *o.get() != value {
+                    return Err(err(o.key().clone()));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_enum(name: &str, em: &mut EnumFormat, other: EnumFormat) -> Result<(), MergeError> {
+    if em.repr != other.repr || em.bitflag != other.bitflag {
+        return Err(MergeError::Enum(name.into()));
+    }
+
+    merge_same(&mut em.variants, other.variants, |_| {
+        MergeError::Enum(name.into())
+    })?;
+
+    check_values(name, em)
+}
+
+/// Variants of an enum which is not a bitflag must have distinct values to be a `#[repr]` enum
+fn check_values(name: &str, em: &EnumFormat) -> Result<(), MergeError> {
+    if em.bitflag {
+        return Ok(());
+    }
+
+    let mut values: BTreeMap<usize, &String> = BTreeMap::new();
+
+    for (variant, value) in em.variants.iter() {
+        if let Some(first) = values.insert(*value, variant) {
+            return Err(MergeError::EnumValue {
+                name: name.into(),
+                value: *value,
+                first: first.clone(),
+                second: variant.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn check_opcode<'a>(
+    requests: impl Iterator<Item = (&'a String, &'a RequestFormat)>,
+    name: &str,
+    req: &RequestFormat,
+) -> Result<(), MergeError> {
+    for (prev_name, prev) in requests {
+        let minor_conflict = match (prev.minor_opcode, req.minor_opcode) {
+            (Some(l), Some(r)) => l == r,
+            // `None` match any minor opcode
+            _ => true,
+        };
+
+        if prev_name != name && prev.major_opcode == req.major_opcode && minor_conflict {
+            return Err(MergeError::Opcode {
+                major_opcode: req.major_opcode,
+                minor_opcode: req.minor_opcode,
+                first: prev_name.clone(),
+                second: name.into(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+impl XimFormat {
+    /// Merge `other` definitions into `self`.
+    ///
+    /// Items defined in both must be identical, except enums whose variants are merged.
+    /// Variants of an enum which is not a bitflag must not share a value, and requests must not
+    /// share an opcode with a request of a different name.
+    pub fn merge(&mut self, other: XimFormat) -> Result<(), MergeError> {
+        for (name, em) in other.enums {
+            match self.enums.entry(name) {
+                Entry::Vacant(v) => {
+                    check_values(v.key(), &em)?;
+                    v.insert(em);
+                }
+                Entry::Occupied(mut o) => {
+                    let name = o.key().clone();
+                    merge_enum(&name, o.get_mut(), em)?;
+                }
+            }
+        }
+
+        merge_same(
+            &mut self.attribute_names,
+            other.attribute_names,
+            MergeError::AttributeName,
+        )?;
+        merge_same(&mut self.structs, other.structs, MergeError::Struct)?;
+
+        // against the current requests and the previous ones of `other`
+        for (i, (name, req)) in other.requests.iter().enumerate() {
+            check_opcode(
+                self.requests.iter().chain(other.requests.iter().take(i)),
+                name,
+                req,
+            )?;
+        }
+
+        merge_same(&mut self.requests, other.requests, MergeError::Request)
+    }
+}
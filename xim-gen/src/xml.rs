@@ -0,0 +1,192 @@
+//! Reader for xcbproto style XML definitions.
+//!
+//! ```xml
+//! <xcb header="xim">
+//!   <enum name="ErrorFlag" repr="u16" bitflag="true">
+//!     <item name="InputMethodIdValid"><bit>0</bit></item>
+//!   </enum>
+//!   <attributename name="QueryInputStyle" value="queryInputStyle" />
+//!   <struct name="Point">
+//!     <field type="INT16" name="x" />
+//!     <field type="INT16" name="y" />
+//!   </struct>
+//!   <request name="Close" opcode="32">
+//!     <field type="CARD16" name="input_method_id" />
+//!     <pad bytes="2" />
+//!   </request>
+//! </xcb>
+//! ```
+//!
+//! Core xcb types (`CARD8`, `INT16`, `BOOL`, ...) are mapped to rust types, `<pad bytes>` and
+//! `<pad align="4">` apply to the preceding field and `<list>` maps to `@list` (or
+//! `@countedlist` with `counted="true"`). XIM specific encodings can be written with the
+//! `format` attribute which takes the same syntax as the YAML definition.
+
+use crate::format_type::{Field, FormatType};
+use crate::{EnumFormat, RequestFormat, StructFormat, XimFormat};
+use roxmltree::Node;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::error::Error;
+
+fn xcb_type(name: &str) -> &str {
+    match name {
+        "CARD8" | "BYTE" => "u8",
+        "CARD16" => "u16",
+        "CARD32" => "u32",
+        "INT16" => "i16",
+        "INT32" => "i32",
+        "BOOL" => "bool",
+        _ => name,
+    }
+}
+
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, Box<dyn Error>> {
+    node.attribute(name).ok_or_else(|| {
+        format!(
+            "<{}> at {} need `{}` attribute",
+            node.tag_name().name(),
+            node.document().text_pos_at(node.range().start),
+            name
+        )
+        .into()
+    })
+}
+
+fn number(text: &str) -> Result<usize, Box<dyn Error>> {
+    let text = text.trim();
+    let num = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16)?,
+        None => text.parse()?,
+    };
+    Ok(num)
+}
+
+fn read_enum(node: Node) -> Result<EnumFormat, Box<dyn Error>> {
+    let mut variants = BTreeMap::new();
+
+    for item in node.children().filter(|n| n.has_tag_name("item")) {
+        let name = attr(item, "name")?;
+        let value = item
+            .children()
+            .find(Node::is_element)
+            .ok_or_else(|| format!("enum item {} has no value", name))?;
+        let text = value.text().unwrap_or_default();
+        let value = match value.tag_name().name() {
+            "value" => number(text)?,
+            "bit" => 1 << number(text)?,
+            other => return Err(format!("Unknown enum item value {}", other).into()),
+        };
+        variants.insert(name.into(), value);
+    }
+
+    Ok(EnumFormat {
+        repr: node.attribute("repr").unwrap_or("u32").into(),
+        bitflag: node.attribute("bitflag") == Some("true"),
+        variants,
+    })
+}
+
+fn read_body(node: Node) -> Result<Vec<Field>, Box<dyn Error>> {
+    let mut body: Vec<Field> = Vec::new();
+
+    for child in node.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "field" => {
+                let ty = match child.attribute("format") {
+                    Some(format) => format.parse()?,
+                    None => FormatType::Normal(xcb_type(attr(child, "type")?).into()),
+                };
+                body.push(Field {
+                    name: attr(child, "name")?.into(),
+                    ty,
+                });
+            }
+            "list" => {
+                let inner = Box::new(FormatType::Normal(xcb_type(attr(child, "type")?).into()));
+                let prefix = child.attribute("prefix").map_or(Ok(0), number)?;
+                let len = child.attribute("len").map_or(Ok(2), number)?;
+                let ty = if child.attribute("counted") == Some("true") {
+                    FormatType::CountedList(inner, prefix, len)
+                } else {
+                    FormatType::List(inner, prefix, len)
+                };
+                body.push(Field {
+                    name: attr(child, "name")?.into(),
+                    ty,
+                });
+            }
+            "pad" => {
+                let last = body
+                    .pop()
+                    .ok_or("<pad> must be placed after a field or list")?;
+                let ty = if let Some(bytes) = child.attribute("bytes") {
+                    FormatType::Append(Box::new(last.ty), number(bytes)?)
+                } else if child.attribute("align") == Some("4") {
+                    FormatType::Pad(Box::new(last.ty), 0)
+                } else {
+                    return Err("<pad> need `bytes` or align=\"4\"".into());
+                };
+                body.push(Field {
+                    name: last.name,
+                    ty,
+                });
+            }
+            "doc" => {}
+            other => return Err(format!("Unknown body element <{}>", other).into()),
+        }
+    }
+
+    Ok(body)
+}
+
+pub fn read_format(text: &str) -> Result<XimFormat, Box<dyn Error>> {
+    let doc = roxmltree::Document::parse(text)?;
+
+    let mut format = XimFormat {
+        enums: BTreeMap::new(),
+        attribute_names: BTreeMap::new(),
+        structs: BTreeMap::new(),
+        requests: BTreeMap::new(),
+    };
+
+    for node in doc.root_element().children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "enum" => {
+                format
+                    .enums
+                    .insert(attr(node, "name")?.into(), read_enum(node)?);
+            }
+            "attributename" => {
+                format
+                    .attribute_names
+                    .insert(attr(node, "name")?.into(), attr(node, "value")?.into());
+            }
+            "struct" => {
+                format.structs.insert(
+                    attr(node, "name")?.into(),
+                    StructFormat {
+                        body: read_body(node)?,
+                    },
+                );
+            }
+            "request" => {
+                format.requests.insert(
+                    attr(node, "name")?.into(),
+                    RequestFormat {
+                        major_opcode: number(attr(node, "opcode")?)?.try_into()?,
+                        minor_opcode: node
+                            .attribute("minor-opcode")
+                            .map(|n| number(n).and_then(|n| Ok(n.try_into()?)))
+                            .transpose()?,
+                        body: read_body(node)?,
+                    },
+                );
+            }
+            "import" | "doc" => {}
+            other => return Err(format!("Unknown top level element <{}>", other).into()),
+        }
+    }
+
+    Ok(format)
+}
@@ -44,5 +44,6 @@ define_attrs! {
     (BACKGROUND_PIXMAP, AttributeName::BackgroundPixmap, AttrType::Long),
     (SPOT_LOCATION, AttributeName::SpotLocation, AttrType::XPoint),
     (LINE_SPACE, AttributeName::LineSpace, AttrType::Long),
+    (PREEDIT_STATE, AttributeName::PreeditState, AttrType::PreeditState),
     (SEPARATOR_OF_NESTED_LIST, AttributeName::SeparatorofNestedList, AttrType::Separator),
 }
@@ -17,6 +17,8 @@ extern crate std;
 use alloc::vec::Vec;
 
 pub mod attrs;
+#[rustfmt::skip]
+#[allow(clippy::identity_op)]
 mod parser;
 
 pub use parser::*;
@@ -33,6 +35,20 @@ pub fn write_to_vec(f: impl XimWrite) -> Vec<u8> {
     out
 }
 
+/// Same as [`write_extend_vec`] but with the byte order of the peer
+pub fn write_extend_vec_with_endian(f: impl XimWrite, out: &mut Vec<u8>, endian: Endian) {
+    let from = out.len();
+    out.extend(core::iter::repeat(0).take(f.size()));
+    f.write(&mut Writer::with_endian(&mut out[from..], endian));
+}
+
+/// Same as [`write_to_vec`] but with the byte order of the peer
+pub fn write_to_vec_with_endian(f: impl XimWrite, endian: Endian) -> Vec<u8> {
+    let mut out: Vec<u8> = core::iter::repeat(0).take(f.size()).collect();
+    f.write(&mut Writer::with_endian(&mut out, endian));
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{parser::*, write_to_vec};
@@ -56,6 +72,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_big_endian_connect_req() {
+        let bytes = b"\x01\x00\x00\x02\x42\x00\x00\x01\x00\x00\x00\x00";
+        let req: Request = read_with_endian(bytes, Endian::Big).unwrap();
+
+        assert_eq!(
+            req,
+            Request::Connect {
+                endian: Endian::Big,
+                client_auth_protocol_names: vec![],
+                client_minor_protocol_version: 0,
+                client_major_protocol_version: 1,
+            }
+        );
+
+        let mut out = vec![0; req.size()];
+        write_with_endian(&req, &mut out, Endian::Big);
+        assert_eq!(out, bytes);
+    }
+
     #[test]
     fn read_open() {
         let req = read::<Request>(&[
@@ -97,6 +133,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn write_input_styles() {
+        let styles = InputStyleList {
+            styles: vec![
+                InputStyle::PREEDIT_POSITION | InputStyle::STATUS_AREA,
+                InputStyle::PREEDIT_CALLBACKS | InputStyle::STATUS_NOTHING,
+            ],
+        };
+
+        let out = write_to_vec(&styles);
+        assert_eq!(out, [2, 0, 0, 0, 4, 1, 0, 0, 2, 4, 0, 0]);
+        assert_eq!(read::<InputStyleList>(&out).unwrap(), styles);
+    }
+
     #[test]
     fn commit() {
         let req = Request::Commit {
@@ -373,4 +423,166 @@ mod tests {
         let new: Request = read(&out).unwrap();
         assert_eq!(value, new);
     }
+
+    #[test]
+    fn commit_syncronous() {
+        for data in [
+            CommitData::Chars {
+                commited: vec![b'a'],
+                syncronous: true,
+            },
+            CommitData::Keysym {
+                keysym: 0x61,
+                syncronous: true,
+            },
+        ] {
+            let out = write_to_vec(&data);
+            assert_eq!(read::<CommitData>(&out).unwrap(), data);
+        }
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn read_commit_flags() {
+        // XimSYNCHRONUS | XimLookupChars and XimSYNCHRONUS | XimLookupKeySym
+        let chars: CommitData = read(&[3, 0, 1, 0, b'a', 0, 0, 0]).unwrap();
+        assert_eq!(
+            chars,
+            CommitData::Chars {
+                commited: vec![b'a'],
+                syncronous: true,
+            }
+        );
+        let keysym: CommitData = read(&[5, 0, 0, 0, 0x61, 0, 0, 0]).unwrap();
+        assert_eq!(
+            keysym,
+            CommitData::Keysym {
+                keysym: 0x61,
+                syncronous: true,
+            }
+        );
+    }
+
+    #[test]
+    fn request_ids() {
+        let req = Request::SetIcFocus {
+            input_method_id: 1,
+            input_context_id: 2,
+        };
+        assert_eq!(req.input_method_id(), Some(1));
+        assert_eq!(req.input_context_id(), Some(2));
+
+        let req = Request::Close { input_method_id: 3 };
+        assert_eq!(req.input_method_id(), Some(3));
+        assert_eq!(req.input_context_id(), None);
+
+        assert_eq!(Request::Disconnect {}.input_method_id(), None);
+    }
+
+    #[test]
+    fn commit_both() {
+        let data = CommitData::Both {
+            keysym: 0xfe51,
+            commited: vec![b'a', b'b', b'c'],
+            syncronous: false,
+        };
+        let out = write_to_vec(&data);
+        assert_eq!(data.size(), out.len());
+        assert_eq!(read::<CommitData>(&out).unwrap(), data);
+    }
+
+    #[test]
+    fn status_draw_compound_text() {
+        let value = Request::StatusDraw {
+            input_method_id: 1,
+            input_context_id: 2,
+            content: StatusContent::Text(StatusTextContent {
+                status: PreeditDrawStatus::NO_FEEDBACK,
+                status_string: xim_ctext::utf8_to_compound_text("한"),
+                feedbacks: vec![],
+            }),
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(value.size(), out.len());
+        assert_eq!(out.len() % 4, 0);
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+    }
+
+    #[test]
+    fn preedit_state() {
+        let value = Request::PreeditState {
+            input_method_id: 1,
+            input_context_id: 2,
+            state: PreeditStateFlag::DISABLE,
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(value.size(), out.len());
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+        assert_eq!(
+            crate::attrs::get_name(crate::attrs::PREEDIT_STATE.id),
+            Some(AttributeName::PreeditState)
+        );
+    }
+
+    #[test]
+    fn str_conversion_reply() {
+        let value = Request::StrConversionReply {
+            input_method_id: 1,
+            input_context_id: 2,
+            feedback: 0,
+            text: StrConvText {
+                string: b"abc".to_vec(),
+                feedbacks: vec![0; 3],
+            },
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(value.size(), out.len());
+        assert_eq!(out.len() % 4, 0);
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+    }
+
+    #[test]
+    fn ext_set_event_mask() {
+        let value = Request::ExtSetEventMask {
+            input_method_id: 1,
+            input_context_id: 2,
+            filter_event_mask: 3,
+            intercept_event_mask: 0,
+            select_event_mask: 0,
+            forward_event_mask: 3,
+            synchronous_event_mask: 1,
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(&out[..2], &[128, 0x30]);
+        assert_eq!(value.size(), out.len());
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+    }
+
+    #[test]
+    fn auth_required() {
+        let value = Request::AuthRequired {
+            auth_protocol_index: 1,
+            auth_data: b"cookie".to_vec(),
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(
+            out,
+            [10, 0, 4, 0, 1, 0, 0, 0, 6, 0, 0, 0, 99, 111, 111, 107, 105, 101, 0, 0]
+        );
+        assert_eq!(value.size(), out.len());
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+    }
+
+    #[test]
+    fn extension_message() {
+        let value = Request::ExtensionMessage {
+            major_opcode: 129,
+            minor_opcode: 2,
+            data: vec![1, 0, 2, 0, 3, 0, 0, 0],
+        };
+        let out = write_to_vec(&value);
+        assert_eq!(out, [129, 2, 2, 0, 1, 0, 2, 0, 3, 0, 0, 0]);
+        assert_eq!(value.size(), out.len());
+        assert_eq!(read::<Request>(&out).unwrap(), value);
+    }
 }
@@ -1,8 +1,6 @@
 // DO NOT EDIT YOURSELF
 // This source is auto generated by xim-gen
 
-#![allow(clippy::identity_op)]
-
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -23,15 +21,52 @@ where
     val.write(&mut Writer::new(out));
 }
 
+/// Same as [`read`] but with the byte order of the peer
+pub fn read_with_endian<T>(b: &[u8], endian: Endian) -> Result<T, ReadError>
+where
+    T: XimRead,
+{
+    T::read(&mut Reader::with_endian(b, endian))
+}
+
+/// Same as [`write`] but with the byte order of the peer
+pub fn write_with_endian<T>(val: T, out: &mut [u8], endian: Endian)
+where
+    T: XimWrite,
+{
+    val.write(&mut Writer::with_endian(out, endian));
+}
+
+/// Byte order of the messages declared by `Connect`
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum Endian {
+    Big = 0x42,
+    Little = 0x6c,
+}
+
+impl Endian {
     #[cfg(target_endian = "little")]
-    Native = 0x6c,
+    #[allow(non_upper_case_globals)]
+    pub const Native: Self = Self::Little;
     #[cfg(target_endian = "big")]
-    Native = 0x42,
-    // Big = 0x42,
-    // Little = 0x6c,
+    #[allow(non_upper_case_globals)]
+    pub const Native: Self = Self::Big;
+
+    /// Endian of the byte `n` of `Connect`
+    pub fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            0x42 => Some(Self::Big),
+            0x6c => Some(Self::Little),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Self::Native
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -56,37 +91,6 @@ pub enum CommitData {
         syncronous: bool,
     },
 }
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct InputStyleList {
-    pub styles: Vec<InputStyle>,
-}
-impl XimRead for InputStyleList {
-    fn read(reader: &mut Reader) -> Result<Self, ReadError> {
-        Ok(Self {
-            styles: {
-                let len = u16::read(reader)? as usize;
-                reader.consume(2)?;
-                let mut out = Vec::with_capacity(len);
-                for _ in 0..len {
-                    out.push(InputStyle::read(reader)?);
-                }
-                out
-            },
-        })
-    }
-}
-impl XimWrite for InputStyleList {
-    fn write(&self, writer: &mut Writer) {
-        (self.styles.len() as u16).write(writer);
-        0u16.write(writer);
-        for elem in self.styles.iter() {
-            elem.write(writer);
-        }
-    }
-    fn size(&self) -> usize {
-        self.styles.len() * 4 + 4
-    }
-}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct HotKeyTriggers {
@@ -132,19 +136,38 @@ fn with_pad4(len: usize) -> usize {
     len + pad4(len)
 }
 
+macro_rules! from_bytes {
+    ($ty:ident, $endian:expr, $bytes:expr) => {
+        match $endian {
+            Endian::Big => $ty::from_be_bytes($bytes),
+            Endian::Little => $ty::from_le_bytes($bytes),
+        }
+    };
+}
+
 pub struct Reader<'b> {
     bytes: &'b [u8],
     start: usize,
+    endian: Endian,
 }
 
 impl<'b> Reader<'b> {
     pub fn new(bytes: &'b [u8]) -> Self {
+        Self::with_endian(bytes, Endian::Native)
+    }
+
+    pub fn with_endian(bytes: &'b [u8], endian: Endian) -> Self {
         Self {
             bytes,
             start: bytes.as_ptr() as usize,
+            endian,
         }
     }
 
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
     fn ptr_offset(&self) -> usize {
         self.bytes.as_ptr() as usize - self.start
     }
@@ -175,22 +198,22 @@ impl<'b> Reader<'b> {
 
     pub fn i16(&mut self) -> Result<i16, ReadError> {
         let bytes = self.consume(2)?.try_into().unwrap();
-        Ok(i16::from_ne_bytes(bytes))
+        Ok(from_bytes!(i16, self.endian, bytes))
     }
 
     pub fn u16(&mut self) -> Result<u16, ReadError> {
         let bytes = self.consume(2)?.try_into().unwrap();
-        Ok(u16::from_ne_bytes(bytes))
+        Ok(from_bytes!(u16, self.endian, bytes))
     }
 
     pub fn u32(&mut self) -> Result<u32, ReadError> {
         let bytes = self.consume(4)?.try_into().unwrap();
-        Ok(u32::from_ne_bytes(bytes))
+        Ok(from_bytes!(u32, self.endian, bytes))
     }
 
     pub fn i32(&mut self) -> Result<i32, ReadError> {
         let bytes = self.consume(4)?.try_into().unwrap();
-        Ok(i32::from_ne_bytes(bytes))
+        Ok(from_bytes!(i32, self.endian, bytes))
     }
 
     pub fn consume(&mut self, len: usize) -> Result<&'b [u8], ReadError> {
@@ -207,11 +230,24 @@ impl<'b> Reader<'b> {
 pub struct Writer<'b> {
     out: &'b mut [u8],
     idx: usize,
+    endian: Endian,
 }
 
 impl<'b> Writer<'b> {
     pub fn new(out: &'b mut [u8]) -> Self {
-        Self { out, idx: 0 }
+        Self::with_endian(out, Endian::Native)
+    }
+
+    pub fn with_endian(out: &'b mut [u8], endian: Endian) -> Self {
+        Self {
+            out,
+            idx: 0,
+            endian,
+        }
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
     }
 
     pub fn write_u8(&mut self, b: u8) {
@@ -241,7 +277,7 @@ pub trait XimWrite {
     fn size(&self) -> usize;
 }
 
-impl<'a, T> XimWrite for &'a T
+impl<T> XimWrite for &T
 where
     T: XimWrite,
 {
@@ -258,12 +294,7 @@ where
 impl XimRead for Endian {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let n = u8::read(reader)?;
-
-        if n == Endian::Native as u8 {
-            Ok(Self::Native)
-        } else {
-            Err(ReadError::NotNativeEndian)
-        }
+        Self::from_u8(n).ok_or_else(|| reader.invalid_data("Endian", n))
     }
 }
 
@@ -324,7 +355,7 @@ impl XimRead for CommitData {
                 reader.pad4()?;
                 Ok(Self::Chars {
                     commited: bytes.to_vec(),
-                    syncronous: ty == 5,
+                    syncronous: ty == 3,
                 })
             }
             4 | 5 => {
@@ -332,7 +363,7 @@ impl XimRead for CommitData {
                 let keysym = reader.u32()?;
                 Ok(Self::Keysym {
                     keysym,
-                    syncronous: ty == 3,
+                    syncronous: ty == 5,
                 })
             }
             6 | 7 => {
@@ -473,7 +504,10 @@ macro_rules! impl_int {
 
         impl XimWrite for $ty {
             fn write(&self, writer: &mut Writer) {
-                writer.write(&self.to_ne_bytes())
+                match writer.endian {
+                    Endian::Big => writer.write(&self.to_be_bytes()),
+                    Endian::Little => writer.write(&self.to_le_bytes()),
+                }
             }
 
             fn size(&self) -> usize {
@@ -662,11 +696,8 @@ impl XimWrite for ErrorCode {
     }
 }
 bitflags::bitflags! {
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct ErrorFlag: u16 {
-const INPUT_METHOD_ID_VALID = 1;
-const INPUT_CONTEXT_ID_VALID = 2;
-}
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)] pub struct ErrorFlag : u16 { const
+    INPUT_METHOD_ID_VALID = 1; const INPUT_CONTEXT_ID_VALID = 2; }
 }
 impl XimRead for ErrorFlag {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -721,17 +752,15 @@ impl XimWrite for Feedback {
     }
 }
 bitflags::bitflags! {
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct ForwardEventFlag: u16 {
-const SYNCHRONOUS = 1;
-const REQUEST_FILTERING = 2;
-const REQUEST_LOOP_UPSTRING = 4;
-}
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)] pub struct ForwardEventFlag : u16 {
+    const SYNCHRONOUS = 1; const REQUEST_FILTERING = 2; const REQUEST_LOOP_UPSTRING = 4;
+    }
 }
 impl XimRead for ForwardEventFlag {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let repr = u16::read(reader)?;
-        Self::from_bits(repr).ok_or_else(|| reader.invalid_data("ForwardEventFlag", repr))
+        Self::from_bits(repr)
+            .ok_or_else(|| reader.invalid_data("ForwardEventFlag", repr))
     }
 }
 impl XimWrite for ForwardEventFlag {
@@ -767,18 +796,10 @@ impl XimWrite for HotKeyState {
     }
 }
 bitflags::bitflags! {
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct InputStyle: u32 {
-const PREEDIT_AREA = 1;
-const PREEDIT_CALLBACKS = 2;
-const PREEDIT_POSITION = 4;
-const PREEDIT_NOTHING = 8;
-const PREEDIT_NONE = 16;
-const STATUS_AREA = 256;
-const STATUS_CALLBACKS = 512;
-const STATUS_NOTHING = 1024;
-const STATUS_NONE = 2048;
-}
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)] pub struct InputStyle : u32 { const
+    PREEDIT_AREA = 1; const PREEDIT_CALLBACKS = 2; const PREEDIT_POSITION = 4; const
+    PREEDIT_NOTHING = 8; const PREEDIT_NONE = 16; const STATUS_AREA = 256; const
+    STATUS_CALLBACKS = 512; const STATUS_NOTHING = 1024; const STATUS_NONE = 2048; }
 }
 impl XimRead for InputStyle {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -795,16 +816,14 @@ impl XimWrite for InputStyle {
     }
 }
 bitflags::bitflags! {
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct PreeditDrawStatus: u32 {
-const NO_STRING = 1;
-const NO_FEEDBACK = 2;
-}
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)] pub struct PreeditDrawStatus : u32 {
+    const NO_STRING = 1; const NO_FEEDBACK = 2; }
 }
 impl XimRead for PreeditDrawStatus {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let repr = u32::read(reader)?;
-        Self::from_bits(repr).ok_or_else(|| reader.invalid_data("PreeditDrawStatus", repr))
+        Self::from_bits(repr)
+            .ok_or_else(|| reader.invalid_data("PreeditDrawStatus", repr))
     }
 }
 impl XimWrite for PreeditDrawStatus {
@@ -816,17 +835,14 @@ impl XimWrite for PreeditDrawStatus {
     }
 }
 bitflags::bitflags! {
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct PreeditStateFlag: u32 {
-const UNKNOWN = 0;
-const ENABLE = 1;
-const DISABLE = 2;
-}
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)] pub struct PreeditStateFlag : u32 {
+    const UNKNOWN = 0; const ENABLE = 1; const DISABLE = 2; }
 }
 impl XimRead for PreeditStateFlag {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let repr = u32::read(reader)?;
-        Self::from_bits(repr).ok_or_else(|| reader.invalid_data("PreeditStateFlag", repr))
+        Self::from_bits(repr)
+            .ok_or_else(|| reader.invalid_data("PreeditStateFlag", repr))
     }
 }
 impl XimWrite for PreeditStateFlag {
@@ -838,6 +854,30 @@ impl XimWrite for PreeditStateFlag {
     }
 }
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u16)]
+pub enum StrConversionOperation {
+    Substitution = 1,
+    Retrieval = 2,
+}
+impl XimRead for StrConversionOperation {
+    fn read(reader: &mut Reader) -> Result<Self, ReadError> {
+        let repr = u16::read(reader)?;
+        match repr {
+            1 => Ok(Self::Substitution),
+            2 => Ok(Self::Retrieval),
+            _ => Err(reader.invalid_data("StrConversionOperation", repr)),
+        }
+    }
+}
+impl XimWrite for StrConversionOperation {
+    fn write(&self, writer: &mut Writer) {
+        (*self as u16).write(writer);
+    }
+    fn size(&self) -> usize {
+        core::mem::size_of::<u16>()
+    }
+}
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum TriggerNotifyFlag {
     OnKeyList = 0,
@@ -898,7 +938,7 @@ impl XimWrite for Attr {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Attribute {
     pub id: u16,
-    pub value: Vec<u8>,
+    pub value: alloc::vec::Vec<u8>,
 }
 impl XimRead for Attribute {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -933,7 +973,7 @@ impl XimWrite for Attribute {
 pub struct Extension {
     pub major_opcode: u8,
     pub minor_opcode: u8,
-    pub name: String,
+    pub name: alloc::string::String,
 }
 impl XimRead for Extension {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -943,7 +983,9 @@ impl XimRead for Extension {
             name: {
                 let inner = {
                     let len = u16::read(reader)?;
-                    String::from_utf8(reader.consume(len as usize)?.to_vec())?
+                    alloc::string::String::from_utf8(
+                        reader.consume(len as usize)?.to_vec(),
+                    )?
                 };
                 reader.pad4()?;
                 inner
@@ -969,7 +1011,7 @@ impl XimWrite for Extension {
 }
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FontSet {
-    pub name: String,
+    pub name: alloc::string::String,
 }
 impl XimRead for FontSet {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -977,7 +1019,9 @@ impl XimRead for FontSet {
             name: {
                 let inner = {
                     let len = u16::read(reader)?;
-                    String::from_utf8(reader.consume(len as usize)?.to_vec())?
+                    alloc::string::String::from_utf8(
+                        reader.consume(len as usize)?.to_vec(),
+                    )?
                 };
                 reader.pad4()?;
                 inner
@@ -998,6 +1042,39 @@ impl XimWrite for FontSet {
     }
 }
 #[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InputStyleList {
+    pub styles: alloc::vec::Vec<InputStyle>,
+}
+impl XimRead for InputStyleList {
+    fn read(reader: &mut Reader) -> Result<Self, ReadError> {
+        Ok(Self {
+            styles: {
+                let len = u16::read(reader)? as usize;
+                reader.consume(2)?;
+                let mut out = alloc::vec::Vec::with_capacity(len);
+                for _ in 0..len {
+                    out.push(InputStyle::read(reader)?);
+                }
+                out
+            },
+        })
+    }
+}
+impl XimWrite for InputStyleList {
+    fn write(&self, writer: &mut Writer) {
+        (self.styles.len() as u16).write(writer);
+        writer.write(&[0u8; 2]);
+        for elem in self.styles.iter() {
+            elem.write(writer);
+        }
+    }
+    fn size(&self) -> usize {
+        let mut content_size = 0;
+        content_size += self.styles.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+        content_size
+    }
+}
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Point {
     pub x: i16,
     pub y: i16,
@@ -1058,8 +1135,8 @@ impl XimWrite for Rectangle {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StatusTextContent {
     pub status: PreeditDrawStatus,
-    pub status_string: String,
-    pub feedbacks: Vec<Feedback>,
+    pub status_string: alloc::vec::Vec<u8>,
+    pub feedbacks: alloc::vec::Vec<Feedback>,
 }
 impl XimRead for StatusTextContent {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
@@ -1068,16 +1145,16 @@ impl XimRead for StatusTextContent {
             status_string: {
                 let inner = {
                     let len = u16::read(reader)?;
-                    String::from_utf8(reader.consume(len as usize)?.to_vec())?
+                    reader.consume(len as usize)?.to_vec()
                 };
                 reader.pad4()?;
                 inner
             },
             feedbacks: {
-                let mut out = Vec::new();
+                let mut out = alloc::vec::Vec::new();
                 let len = u16::read(reader)? as usize;
-                let end = reader.cursor() - len;
                 u16::read(reader)?;
+                let end = reader.cursor() - len;
                 while reader.cursor() > end {
                     out.push(Feedback::read(reader)?);
                 }
@@ -1090,7 +1167,7 @@ impl XimWrite for StatusTextContent {
     fn write(&self, writer: &mut Writer) {
         self.status.write(writer);
         (self.status_string.len() as u16).write(writer);
-        writer.write(self.status_string.as_bytes());
+        writer.write(&self.status_string);
         writer.write_pad4();
         ((self.feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
             .write(writer);
@@ -1102,7 +1179,55 @@ impl XimWrite for StatusTextContent {
     fn size(&self) -> usize {
         let mut content_size = 0;
         content_size += self.status.size();
-        content_size += with_pad4(self.status_string.len() + 2 + 0 - 0);
+        content_size += with_pad4(self.status_string.len() + 2 - 0);
+        content_size += self.feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+        content_size
+    }
+}
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StrConvText {
+    pub string: alloc::vec::Vec<u8>,
+    pub feedbacks: alloc::vec::Vec<u32>,
+}
+impl XimRead for StrConvText {
+    fn read(reader: &mut Reader) -> Result<Self, ReadError> {
+        Ok(Self {
+            string: {
+                let inner = {
+                    let len = u16::read(reader)?;
+                    reader.consume(len as usize)?.to_vec()
+                };
+                reader.pad4()?;
+                inner
+            },
+            feedbacks: {
+                let mut out = alloc::vec::Vec::new();
+                let len = u16::read(reader)? as usize;
+                u16::read(reader)?;
+                let end = reader.cursor() - len;
+                while reader.cursor() > end {
+                    out.push(u32::read(reader)?);
+                }
+                out
+            },
+        })
+    }
+}
+impl XimWrite for StrConvText {
+    fn write(&self, writer: &mut Writer) {
+        (self.string.len() as u16).write(writer);
+        writer.write(&self.string);
+        writer.write_pad4();
+        ((self.feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
+            .write(writer);
+        0u16.write(writer);
+        for elem in self.feedbacks.iter() {
+            elem.write(writer);
+        }
+    }
+    fn size(&self) -> usize {
+        let mut content_size = 0;
+        content_size += with_pad4(self.string.len() + 2 - 0);
         content_size += self.feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
         content_size
     }
@@ -1349,10 +1474,15 @@ impl XimRead for AttributeName {
             b"stringConversion" => Ok(Self::StringConversion),
             b"stringConversionCallback" => Ok(Self::StringConversionCallback),
             b"visiblePosition" => Ok(Self::VisiblePosition),
-            bytes => Err(reader.invalid_data(
-                "AttributeName",
-                core::str::from_utf8(bytes).unwrap_or("NOT_UTF8"),
-            )),
+            bytes => {
+                Err(
+                    reader
+                        .invalid_data(
+                            "AttributeName",
+                            core::str::from_utf8(bytes).unwrap_or("NOT_UTF8"),
+                        ),
+                )
+            }
         }
     }
 }
@@ -1368,120 +1498,128 @@ impl XimWrite for AttributeName {
 }
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Request {
-    AuthNext {},
-    AuthNg {},
-    AuthReply {},
-    AuthRequired {},
-    AuthSetup {},
-    Close {
-        input_method_id: u16,
-    },
-    CloseReply {
-        input_method_id: u16,
-    },
-    Commit {
-        input_method_id: u16,
-        input_context_id: u16,
-        data: CommitData,
-    },
     Connect {
         endian: Endian,
         client_major_protocol_version: u16,
         client_minor_protocol_version: u16,
-        client_auth_protocol_names: Vec<String>,
+        client_auth_protocol_names: alloc::vec::Vec<alloc::string::String>,
     },
     ConnectReply {
         server_major_protocol_version: u16,
         server_minor_protocol_version: u16,
     },
-    CreateIc {
-        input_method_id: u16,
-        ic_attributes: Vec<Attribute>,
-    },
-    CreateIcReply {
+    Disconnect {},
+    DisconnectReply {},
+    AuthRequired { auth_protocol_index: u8, auth_data: alloc::vec::Vec<u8> },
+    AuthReply { auth_data: alloc::vec::Vec<u8> },
+    AuthNext { auth_data: alloc::vec::Vec<u8> },
+    AuthSetup { auth_protocol_names: alloc::vec::Vec<alloc::string::String> },
+    AuthNg {},
+    Error {
         input_method_id: u16,
         input_context_id: u16,
+        flag: ErrorFlag,
+        code: ErrorCode,
+        detail: alloc::string::String,
     },
-    DestroyIc {
+    Open { locale: alloc::string::String },
+    OpenReply {
         input_method_id: u16,
-        input_context_id: u16,
+        im_attrs: alloc::vec::Vec<Attr>,
+        ic_attrs: alloc::vec::Vec<Attr>,
     },
-    DestroyIcReply {
+    Close { input_method_id: u16 },
+    CloseReply { input_method_id: u16 },
+    RegisterTriggerKeys {
         input_method_id: u16,
-        input_context_id: u16,
+        on_keys: alloc::vec::Vec<TriggerKey>,
+        off_keys: alloc::vec::Vec<TriggerKey>,
     },
-    Disconnect {},
-    DisconnectReply {},
-    EncodingNegotiation {
+    TriggerNotify {
         input_method_id: u16,
-        encodings: Vec<String>,
-        encoding_infos: Vec<String>,
+        input_context_id: u16,
+        flag: TriggerNotifyFlag,
+        index: u32,
+        event_mask: u32,
     },
-    EncodingNegotiationReply {
+    TriggerNotifyReply { input_method_id: u16, input_context_id: u16 },
+    SetEventMask {
         input_method_id: u16,
-        category: u16,
-        index: i16,
+        input_context_id: u16,
+        forward_event_mask: u32,
+        synchronous_event_mask: u32,
     },
-    Error {
+    EncodingNegotiation {
         input_method_id: u16,
-        input_context_id: u16,
-        flag: ErrorFlag,
-        code: ErrorCode,
-        detail: String,
+        encodings: alloc::vec::Vec<alloc::string::String>,
+        encoding_infos: alloc::vec::Vec<alloc::string::String>,
     },
-    ForwardEvent {
+    EncodingNegotiationReply { input_method_id: u16, category: u16, index: i16 },
+    QueryExtension {
         input_method_id: u16,
-        input_context_id: u16,
-        flag: ForwardEventFlag,
-        serial_number: u16,
-        xev: XEvent,
+        extensions: alloc::vec::Vec<alloc::string::String>,
     },
-    Geometry {
+    QueryExtensionReply { input_method_id: u16, extensions: alloc::vec::Vec<Extension> },
+    SetImValues { input_method_id: u16, attributes: alloc::vec::Vec<Attribute> },
+    SetImValuesReply { input_method_id: u16 },
+    GetImValues { input_method_id: u16, im_attributes: alloc::vec::Vec<u16> },
+    GetImValuesReply { input_method_id: u16, im_attributes: alloc::vec::Vec<Attribute> },
+    CreateIc { input_method_id: u16, ic_attributes: alloc::vec::Vec<Attribute> },
+    CreateIcReply { input_method_id: u16, input_context_id: u16 },
+    DestroyIc { input_method_id: u16, input_context_id: u16 },
+    DestroyIcReply { input_method_id: u16, input_context_id: u16 },
+    SetIcValues {
         input_method_id: u16,
         input_context_id: u16,
+        ic_attributes: alloc::vec::Vec<Attribute>,
     },
+    SetIcValuesReply { input_method_id: u16, input_context_id: u16 },
     GetIcValues {
         input_method_id: u16,
         input_context_id: u16,
-        ic_attributes: Vec<u16>,
+        ic_attributes: alloc::vec::Vec<u16>,
     },
     GetIcValuesReply {
         input_method_id: u16,
         input_context_id: u16,
-        ic_attributes: Vec<Attribute>,
-    },
-    GetImValues {
-        input_method_id: u16,
-        im_attributes: Vec<u16>,
-    },
-    GetImValuesReply {
-        input_method_id: u16,
-        im_attributes: Vec<Attribute>,
-    },
-    Open {
-        locale: String,
+        ic_attributes: alloc::vec::Vec<Attribute>,
     },
-    OpenReply {
+    SetIcFocus { input_method_id: u16, input_context_id: u16 },
+    UnsetIcFocus { input_method_id: u16, input_context_id: u16 },
+    ForwardEvent {
         input_method_id: u16,
-        im_attrs: Vec<Attr>,
-        ic_attrs: Vec<Attr>,
+        input_context_id: u16,
+        flag: ForwardEventFlag,
+        serial_number: u16,
+        xev: XEvent,
     },
-    PreeditCaret {
+    Sync { input_method_id: u16, input_context_id: u16 },
+    SyncReply { input_method_id: u16, input_context_id: u16 },
+    Commit { input_method_id: u16, input_context_id: u16, data: CommitData },
+    ResetIc { input_method_id: u16, input_context_id: u16 },
+    ResetIcReply {
         input_method_id: u16,
         input_context_id: u16,
-        position: i32,
-        direction: CaretDirection,
-        style: CaretStyle,
+        preedit_string: alloc::vec::Vec<u8>,
     },
-    PreeditCaretReply {
+    Geometry { input_method_id: u16, input_context_id: u16 },
+    StrConversion {
         input_method_id: u16,
         input_context_id: u16,
-        position: i32,
+        position: u16,
+        direction: CaretDirection,
+        operation: StrConversionOperation,
+        factor: u16,
+        conversion_type: u32,
     },
-    PreeditDone {
+    StrConversionReply {
         input_method_id: u16,
         input_context_id: u16,
+        feedback: u32,
+        text: StrConvText,
     },
+    PreeditStart { input_method_id: u16, input_context_id: u16 },
+    PreeditStartReply { input_method_id: u16, input_context_id: u16, return_value: i32 },
     PreeditDraw {
         input_method_id: u16,
         input_context_id: u16,
@@ -1489,168 +1627,223 @@ pub enum Request {
         chg_first: i32,
         chg_length: i32,
         status: PreeditDrawStatus,
-        preedit_string: Vec<u8>,
-        feedbacks: Vec<Feedback>,
-    },
-    PreeditStart {
-        input_method_id: u16,
-        input_context_id: u16,
+        preedit_string: alloc::vec::Vec<u8>,
+        feedbacks: alloc::vec::Vec<Feedback>,
     },
-    PreeditStartReply {
+    PreeditCaret {
         input_method_id: u16,
         input_context_id: u16,
-        return_value: i32,
+        position: i32,
+        direction: CaretDirection,
+        style: CaretStyle,
     },
+    PreeditCaretReply { input_method_id: u16, input_context_id: u16, position: i32 },
+    PreeditDone { input_method_id: u16, input_context_id: u16 },
+    StatusStart { input_method_id: u16, input_context_id: u16 },
+    StatusDraw { input_method_id: u16, input_context_id: u16, content: StatusContent },
+    StatusDone { input_method_id: u16, input_context_id: u16 },
     PreeditState {
         input_method_id: u16,
         input_context_id: u16,
         state: PreeditStateFlag,
     },
-    QueryExtension {
-        input_method_id: u16,
-        extensions: Vec<String>,
-    },
-    QueryExtensionReply {
-        input_method_id: u16,
-        extensions: Vec<Extension>,
-    },
-    RegisterTriggerKeys {
-        input_method_id: u16,
-        on_keys: Vec<TriggerKey>,
-        off_keys: Vec<TriggerKey>,
-    },
-    ResetIc {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    ResetIcReply {
-        input_method_id: u16,
-        input_context_id: u16,
-        preedit_string: Vec<u8>,
-    },
-    SetEventMask {
+    ExtSetEventMask {
         input_method_id: u16,
         input_context_id: u16,
+        filter_event_mask: u32,
+        intercept_event_mask: u32,
+        select_event_mask: u32,
         forward_event_mask: u32,
         synchronous_event_mask: u32,
     },
-    SetIcFocus {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    SetIcValues {
-        input_method_id: u16,
-        input_context_id: u16,
-        ic_attributes: Vec<Attribute>,
-    },
-    SetIcValuesReply {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    SetImValues {
-        input_method_id: u16,
-        attributes: Vec<Attribute>,
-    },
-    SetImValuesReply {
-        input_method_id: u16,
-    },
-    StatusDone {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    StatusDraw {
-        input_method_id: u16,
-        input_context_id: u16,
-        content: StatusContent,
-    },
-    StatusStart {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    StrConversion {},
-    StrConversionReply {},
-    Sync {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    SyncReply {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    TriggerNotify {
-        input_method_id: u16,
-        input_context_id: u16,
-        flag: TriggerNotifyFlag,
-        index: u32,
-        event_mask: u32,
-    },
-    TriggerNotifyReply {
-        input_method_id: u16,
-        input_context_id: u16,
-    },
-    UnsetIcFocus {
+    ExtForwardKeyEvent {
         input_method_id: u16,
         input_context_id: u16,
+        flag: ForwardEventFlag,
+        sequence_number: u16,
+        xev_type: u8,
+        keycode: u8,
+        state: u16,
+        time: u32,
+        window: u32,
     },
+    ExtMove { input_method_id: u16, input_context_id: u16, x: i16, y: i16 },
+    /// Extension message which has no format, `data` is padded to 4 bytes
+    ExtensionMessage { major_opcode: u8, minor_opcode: u8, data: alloc::vec::Vec<u8> },
 }
 impl Request {
     pub fn name(&self) -> &'static str {
         match self {
-            Request::AuthNext { .. } => "AuthNext",
-            Request::AuthNg { .. } => "AuthNg",
-            Request::AuthReply { .. } => "AuthReply",
+            Request::Connect { .. } => "Connect",
+            Request::ConnectReply { .. } => "ConnectReply",
+            Request::Disconnect { .. } => "Disconnect",
+            Request::DisconnectReply { .. } => "DisconnectReply",
             Request::AuthRequired { .. } => "AuthRequired",
+            Request::AuthReply { .. } => "AuthReply",
+            Request::AuthNext { .. } => "AuthNext",
             Request::AuthSetup { .. } => "AuthSetup",
+            Request::AuthNg { .. } => "AuthNg",
+            Request::Error { .. } => "Error",
+            Request::Open { .. } => "Open",
+            Request::OpenReply { .. } => "OpenReply",
             Request::Close { .. } => "Close",
             Request::CloseReply { .. } => "CloseReply",
-            Request::Commit { .. } => "Commit",
-            Request::Connect { .. } => "Connect",
-            Request::ConnectReply { .. } => "ConnectReply",
+            Request::RegisterTriggerKeys { .. } => "RegisterTriggerKeys",
+            Request::TriggerNotify { .. } => "TriggerNotify",
+            Request::TriggerNotifyReply { .. } => "TriggerNotifyReply",
+            Request::SetEventMask { .. } => "SetEventMask",
+            Request::EncodingNegotiation { .. } => "EncodingNegotiation",
+            Request::EncodingNegotiationReply { .. } => "EncodingNegotiationReply",
+            Request::QueryExtension { .. } => "QueryExtension",
+            Request::QueryExtensionReply { .. } => "QueryExtensionReply",
+            Request::SetImValues { .. } => "SetImValues",
+            Request::SetImValuesReply { .. } => "SetImValuesReply",
+            Request::GetImValues { .. } => "GetImValues",
+            Request::GetImValuesReply { .. } => "GetImValuesReply",
             Request::CreateIc { .. } => "CreateIc",
             Request::CreateIcReply { .. } => "CreateIcReply",
             Request::DestroyIc { .. } => "DestroyIc",
             Request::DestroyIcReply { .. } => "DestroyIcReply",
-            Request::Disconnect { .. } => "Disconnect",
-            Request::DisconnectReply { .. } => "DisconnectReply",
-            Request::EncodingNegotiation { .. } => "EncodingNegotiation",
-            Request::EncodingNegotiationReply { .. } => "EncodingNegotiationReply",
-            Request::Error { .. } => "Error",
-            Request::ForwardEvent { .. } => "ForwardEvent",
-            Request::Geometry { .. } => "Geometry",
+            Request::SetIcValues { .. } => "SetIcValues",
+            Request::SetIcValuesReply { .. } => "SetIcValuesReply",
             Request::GetIcValues { .. } => "GetIcValues",
             Request::GetIcValuesReply { .. } => "GetIcValuesReply",
-            Request::GetImValues { .. } => "GetImValues",
-            Request::GetImValuesReply { .. } => "GetImValuesReply",
-            Request::Open { .. } => "Open",
-            Request::OpenReply { .. } => "OpenReply",
-            Request::PreeditCaret { .. } => "PreeditCaret",
-            Request::PreeditCaretReply { .. } => "PreeditCaretReply",
-            Request::PreeditDone { .. } => "PreeditDone",
-            Request::PreeditDraw { .. } => "PreeditDraw",
-            Request::PreeditStart { .. } => "PreeditStart",
-            Request::PreeditStartReply { .. } => "PreeditStartReply",
-            Request::PreeditState { .. } => "PreeditState",
-            Request::QueryExtension { .. } => "QueryExtension",
-            Request::QueryExtensionReply { .. } => "QueryExtensionReply",
-            Request::RegisterTriggerKeys { .. } => "RegisterTriggerKeys",
+            Request::SetIcFocus { .. } => "SetIcFocus",
+            Request::UnsetIcFocus { .. } => "UnsetIcFocus",
+            Request::ForwardEvent { .. } => "ForwardEvent",
+            Request::Sync { .. } => "Sync",
+            Request::SyncReply { .. } => "SyncReply",
+            Request::Commit { .. } => "Commit",
             Request::ResetIc { .. } => "ResetIc",
             Request::ResetIcReply { .. } => "ResetIcReply",
-            Request::SetEventMask { .. } => "SetEventMask",
-            Request::SetIcFocus { .. } => "SetIcFocus",
-            Request::SetIcValues { .. } => "SetIcValues",
-            Request::SetIcValuesReply { .. } => "SetIcValuesReply",
-            Request::SetImValues { .. } => "SetImValues",
-            Request::SetImValuesReply { .. } => "SetImValuesReply",
-            Request::StatusDone { .. } => "StatusDone",
-            Request::StatusDraw { .. } => "StatusDraw",
-            Request::StatusStart { .. } => "StatusStart",
+            Request::Geometry { .. } => "Geometry",
             Request::StrConversion { .. } => "StrConversion",
             Request::StrConversionReply { .. } => "StrConversionReply",
-            Request::Sync { .. } => "Sync",
-            Request::SyncReply { .. } => "SyncReply",
-            Request::TriggerNotify { .. } => "TriggerNotify",
-            Request::TriggerNotifyReply { .. } => "TriggerNotifyReply",
-            Request::UnsetIcFocus { .. } => "UnsetIcFocus",
+            Request::PreeditStart { .. } => "PreeditStart",
+            Request::PreeditStartReply { .. } => "PreeditStartReply",
+            Request::PreeditDraw { .. } => "PreeditDraw",
+            Request::PreeditCaret { .. } => "PreeditCaret",
+            Request::PreeditCaretReply { .. } => "PreeditCaretReply",
+            Request::PreeditDone { .. } => "PreeditDone",
+            Request::StatusStart { .. } => "StatusStart",
+            Request::StatusDraw { .. } => "StatusDraw",
+            Request::StatusDone { .. } => "StatusDone",
+            Request::PreeditState { .. } => "PreeditState",
+            Request::ExtSetEventMask { .. } => "ExtSetEventMask",
+            Request::ExtForwardKeyEvent { .. } => "ExtForwardKeyEvent",
+            Request::ExtMove { .. } => "ExtMove",
+            Request::ExtensionMessage { .. } => "ExtensionMessage",
+        }
+    }
+    pub fn input_method_id(&self) -> Option<u16> {
+        match self {
+            Request::Error { input_method_id, .. } => Some(*input_method_id),
+            Request::OpenReply { input_method_id, .. } => Some(*input_method_id),
+            Request::Close { input_method_id, .. } => Some(*input_method_id),
+            Request::CloseReply { input_method_id, .. } => Some(*input_method_id),
+            Request::RegisterTriggerKeys { input_method_id, .. } => {
+                Some(*input_method_id)
+            }
+            Request::TriggerNotify { input_method_id, .. } => Some(*input_method_id),
+            Request::TriggerNotifyReply { input_method_id, .. } => Some(*input_method_id),
+            Request::SetEventMask { input_method_id, .. } => Some(*input_method_id),
+            Request::EncodingNegotiation { input_method_id, .. } => {
+                Some(*input_method_id)
+            }
+            Request::EncodingNegotiationReply { input_method_id, .. } => {
+                Some(*input_method_id)
+            }
+            Request::QueryExtension { input_method_id, .. } => Some(*input_method_id),
+            Request::QueryExtensionReply { input_method_id, .. } => {
+                Some(*input_method_id)
+            }
+            Request::SetImValues { input_method_id, .. } => Some(*input_method_id),
+            Request::SetImValuesReply { input_method_id, .. } => Some(*input_method_id),
+            Request::GetImValues { input_method_id, .. } => Some(*input_method_id),
+            Request::GetImValuesReply { input_method_id, .. } => Some(*input_method_id),
+            Request::CreateIc { input_method_id, .. } => Some(*input_method_id),
+            Request::CreateIcReply { input_method_id, .. } => Some(*input_method_id),
+            Request::DestroyIc { input_method_id, .. } => Some(*input_method_id),
+            Request::DestroyIcReply { input_method_id, .. } => Some(*input_method_id),
+            Request::SetIcValues { input_method_id, .. } => Some(*input_method_id),
+            Request::SetIcValuesReply { input_method_id, .. } => Some(*input_method_id),
+            Request::GetIcValues { input_method_id, .. } => Some(*input_method_id),
+            Request::GetIcValuesReply { input_method_id, .. } => Some(*input_method_id),
+            Request::SetIcFocus { input_method_id, .. } => Some(*input_method_id),
+            Request::UnsetIcFocus { input_method_id, .. } => Some(*input_method_id),
+            Request::ForwardEvent { input_method_id, .. } => Some(*input_method_id),
+            Request::Sync { input_method_id, .. } => Some(*input_method_id),
+            Request::SyncReply { input_method_id, .. } => Some(*input_method_id),
+            Request::Commit { input_method_id, .. } => Some(*input_method_id),
+            Request::ResetIc { input_method_id, .. } => Some(*input_method_id),
+            Request::ResetIcReply { input_method_id, .. } => Some(*input_method_id),
+            Request::Geometry { input_method_id, .. } => Some(*input_method_id),
+            Request::StrConversion { input_method_id, .. } => Some(*input_method_id),
+            Request::StrConversionReply { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditStart { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditStartReply { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditDraw { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditCaret { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditCaretReply { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditDone { input_method_id, .. } => Some(*input_method_id),
+            Request::StatusStart { input_method_id, .. } => Some(*input_method_id),
+            Request::StatusDraw { input_method_id, .. } => Some(*input_method_id),
+            Request::StatusDone { input_method_id, .. } => Some(*input_method_id),
+            Request::PreeditState { input_method_id, .. } => Some(*input_method_id),
+            Request::ExtSetEventMask { input_method_id, .. } => Some(*input_method_id),
+            Request::ExtForwardKeyEvent { input_method_id, .. } => Some(*input_method_id),
+            Request::ExtMove { input_method_id, .. } => Some(*input_method_id),
+            _ => None,
+        }
+    }
+    pub fn input_context_id(&self) -> Option<u16> {
+        match self {
+            Request::Error { input_context_id, .. } => Some(*input_context_id),
+            Request::TriggerNotify { input_context_id, .. } => Some(*input_context_id),
+            Request::TriggerNotifyReply { input_context_id, .. } => {
+                Some(*input_context_id)
+            }
+            Request::SetEventMask { input_context_id, .. } => Some(*input_context_id),
+            Request::CreateIcReply { input_context_id, .. } => Some(*input_context_id),
+            Request::DestroyIc { input_context_id, .. } => Some(*input_context_id),
+            Request::DestroyIcReply { input_context_id, .. } => Some(*input_context_id),
+            Request::SetIcValues { input_context_id, .. } => Some(*input_context_id),
+            Request::SetIcValuesReply { input_context_id, .. } => Some(*input_context_id),
+            Request::GetIcValues { input_context_id, .. } => Some(*input_context_id),
+            Request::GetIcValuesReply { input_context_id, .. } => Some(*input_context_id),
+            Request::SetIcFocus { input_context_id, .. } => Some(*input_context_id),
+            Request::UnsetIcFocus { input_context_id, .. } => Some(*input_context_id),
+            Request::ForwardEvent { input_context_id, .. } => Some(*input_context_id),
+            Request::Sync { input_context_id, .. } => Some(*input_context_id),
+            Request::SyncReply { input_context_id, .. } => Some(*input_context_id),
+            Request::Commit { input_context_id, .. } => Some(*input_context_id),
+            Request::ResetIc { input_context_id, .. } => Some(*input_context_id),
+            Request::ResetIcReply { input_context_id, .. } => Some(*input_context_id),
+            Request::Geometry { input_context_id, .. } => Some(*input_context_id),
+            Request::StrConversion { input_context_id, .. } => Some(*input_context_id),
+            Request::StrConversionReply { input_context_id, .. } => {
+                Some(*input_context_id)
+            }
+            Request::PreeditStart { input_context_id, .. } => Some(*input_context_id),
+            Request::PreeditStartReply { input_context_id, .. } => {
+                Some(*input_context_id)
+            }
+            Request::PreeditDraw { input_context_id, .. } => Some(*input_context_id),
+            Request::PreeditCaret { input_context_id, .. } => Some(*input_context_id),
+            Request::PreeditCaretReply { input_context_id, .. } => {
+                Some(*input_context_id)
+            }
+            Request::PreeditDone { input_context_id, .. } => Some(*input_context_id),
+            Request::StatusStart { input_context_id, .. } => Some(*input_context_id),
+            Request::StatusDraw { input_context_id, .. } => Some(*input_context_id),
+            Request::StatusDone { input_context_id, .. } => Some(*input_context_id),
+            Request::PreeditState { input_context_id, .. } => Some(*input_context_id),
+            Request::ExtSetEventMask { input_context_id, .. } => Some(*input_context_id),
+            Request::ExtForwardKeyEvent { input_context_id, .. } => {
+                Some(*input_context_id)
+            }
+            Request::ExtMove { input_context_id, .. } => Some(*input_context_id),
+            _ => None,
         }
     }
 }
@@ -1658,496 +1851,864 @@ impl XimRead for Request {
     fn read(reader: &mut Reader) -> Result<Self, ReadError> {
         let major_opcode = reader.u8()?;
         let minor_opcode = reader.u8()?;
-        let _length = reader.u16()?;
+        let length = reader.u16()?;
         match (major_opcode, minor_opcode) {
-            (12, _) => Ok(Request::AuthNext {}),
-            (14, _) => Ok(Request::AuthNg {}),
-            (11, _) => Ok(Request::AuthReply {}),
-            (10, _) => Ok(Request::AuthRequired {}),
-            (13, _) => Ok(Request::AuthSetup {}),
-            (32, _) => Ok(Request::Close {
-                input_method_id: {
-                    let inner = u16::read(reader)?;
-                    reader.consume(2)?;
-                    inner
-                },
-            }),
-            (33, _) => Ok(Request::CloseReply {
-                input_method_id: {
-                    let inner = u16::read(reader)?;
-                    reader.consume(2)?;
-                    inner
-                },
-            }),
-            (63, _) => Ok(Request::Commit {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                data: CommitData::read(reader)?,
-            }),
-            (1, _) => Ok(Request::Connect {
-                endian: {
-                    let inner = Endian::read(reader)?;
-                    reader.consume(1)?;
-                    inner
-                },
-                client_major_protocol_version: u16::read(reader)?,
-                client_minor_protocol_version: u16::read(reader)?,
-                client_auth_protocol_names: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push({
-                            let inner = {
-                                let len = u16::read(reader)?;
-                                String::from_utf8(reader.consume(len as usize)?.to_vec())?
-                            };
-                            reader.pad4()?;
-                            inner
-                        });
-                    }
-                    out
-                },
-            }),
-            (2, _) => Ok(Request::ConnectReply {
-                server_major_protocol_version: u16::read(reader)?,
-                server_minor_protocol_version: u16::read(reader)?,
-            }),
-            (50, _) => Ok(Request::CreateIc {
-                input_method_id: u16::read(reader)?,
-                ic_attributes: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(Attribute::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (51, _) => Ok(Request::CreateIcReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (52, _) => Ok(Request::DestroyIc {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (53, _) => Ok(Request::DestroyIcReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
+            (1, _) => {
+                Ok(Request::Connect {
+                    endian: {
+                        let inner = Endian::read(reader)?;
+                        reader.consume(1)?;
+                        inner
+                    },
+                    client_major_protocol_version: u16::read(reader)?,
+                    client_minor_protocol_version: u16::read(reader)?,
+                    client_auth_protocol_names: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push({
+                                let inner = {
+                                    let len = u16::read(reader)?;
+                                    alloc::string::String::from_utf8(
+                                        reader.consume(len as usize)?.to_vec(),
+                                    )?
+                                };
+                                reader.pad4()?;
+                                inner
+                            });
+                        }
+                        out
+                    },
+                })
+            }
+            (2, _) => {
+                Ok(Request::ConnectReply {
+                    server_major_protocol_version: u16::read(reader)?,
+                    server_minor_protocol_version: u16::read(reader)?,
+                })
+            }
             (3, _) => Ok(Request::Disconnect {}),
             (4, _) => Ok(Request::DisconnectReply {}),
-            (38, _) => Ok(Request::EncodingNegotiation {
-                input_method_id: u16::read(reader)?,
-                encodings: {
-                    let inner = {
-                        let mut out = Vec::new();
+            (10, _) => {
+                Ok(Request::AuthRequired {
+                    auth_protocol_index: {
+                        let inner = u8::read(reader)?;
+                        reader.consume(3)?;
+                        inner
+                    },
+                    auth_data: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            u16::read(reader)?;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push(u8::read(reader)?);
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (11, _) => {
+                Ok(Request::AuthReply {
+                    auth_data: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            u16::read(reader)?;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push(u8::read(reader)?);
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (12, _) => {
+                Ok(Request::AuthNext {
+                    auth_data: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            u16::read(reader)?;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push(u8::read(reader)?);
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (13, _) => {
+                Ok(Request::AuthSetup {
+                    auth_protocol_names: {
+                        let mut out = alloc::vec::Vec::new();
                         let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
                         let end = reader.cursor() - len;
                         while reader.cursor() > end {
                             out.push({
-                                let len = u8::read(reader)?;
-                                String::from_utf8(reader.consume(len as usize)?.to_vec())?
+                                let inner = {
+                                    let len = u16::read(reader)?;
+                                    alloc::string::String::from_utf8(
+                                        reader.consume(len as usize)?.to_vec(),
+                                    )?
+                                };
+                                reader.pad4()?;
+                                inner
                             });
                         }
                         out
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-                encoding_infos: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    u16::read(reader)?;
-                    while reader.cursor() > end {
-                        out.push({
-                            let inner = {
-                                let len = u16::read(reader)?;
-                                String::from_utf8(reader.consume(len as usize)?.to_vec())?
-                            };
-                            reader.pad4()?;
-                            inner
-                        });
-                    }
-                    out
-                },
-            }),
-            (39, _) => Ok(Request::EncodingNegotiationReply {
-                input_method_id: u16::read(reader)?,
-                category: u16::read(reader)?,
-                index: {
-                    let inner = i16::read(reader)?;
-                    reader.consume(2)?;
-                    inner
-                },
-            }),
-            (20, _) => Ok(Request::Error {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                flag: ErrorFlag::read(reader)?,
-                code: ErrorCode::read(reader)?,
-                detail: {
-                    let inner = {
-                        let len = u16::read(reader)?;
-                        reader.consume(2)?;
-                        String::from_utf8(reader.consume(len as usize)?.to_vec())?
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (60, _) => Ok(Request::ForwardEvent {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                flag: ForwardEventFlag::read(reader)?,
-                serial_number: u16::read(reader)?,
-                xev: XEvent::read(reader)?,
-            }),
-            (70, _) => Ok(Request::Geometry {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (56, _) => Ok(Request::GetIcValues {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                ic_attributes: {
-                    let inner = {
-                        let mut out = Vec::new();
+                    },
+                })
+            }
+            (14, _) => Ok(Request::AuthNg {}),
+            (20, _) => {
+                Ok(Request::Error {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    flag: ErrorFlag::read(reader)?,
+                    code: ErrorCode::read(reader)?,
+                    detail: {
+                        let inner = {
+                            let len = u16::read(reader)?;
+                            reader.consume(2)?;
+                            alloc::string::String::from_utf8(
+                                reader.consume(len as usize)?.to_vec(),
+                            )?
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (30, _) => {
+                Ok(Request::Open {
+                    locale: {
+                        let inner = {
+                            let len = u8::read(reader)?;
+                            alloc::string::String::from_utf8(
+                                reader.consume(len as usize)?.to_vec(),
+                            )?
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (31, _) => {
+                Ok(Request::OpenReply {
+                    input_method_id: u16::read(reader)?,
+                    im_attrs: {
+                        let mut out = alloc::vec::Vec::new();
                         let len = u16::read(reader)? as usize;
                         let end = reader.cursor() - len;
                         while reader.cursor() > end {
-                            out.push(u16::read(reader)?);
+                            out.push(Attr::read(reader)?);
                         }
                         out
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (57, _) => Ok(Request::GetIcValuesReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                ic_attributes: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    u16::read(reader)?;
-                    while reader.cursor() > end {
-                        out.push(Attribute::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (44, _) => Ok(Request::GetImValues {
-                input_method_id: u16::read(reader)?,
-                im_attributes: {
-                    let inner = {
-                        let mut out = Vec::new();
+                    },
+                    ic_attrs: {
+                        let mut out = alloc::vec::Vec::new();
                         let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attr::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (32, _) => {
+                Ok(Request::Close {
+                    input_method_id: {
+                        let inner = u16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                })
+            }
+            (33, _) => {
+                Ok(Request::CloseReply {
+                    input_method_id: {
+                        let inner = u16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                })
+            }
+            (34, _) => {
+                Ok(Request::RegisterTriggerKeys {
+                    input_method_id: {
+                        let inner = u16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                    on_keys: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u32::read(reader)? as usize;
                         let end = reader.cursor() - len;
                         while reader.cursor() > end {
-                            out.push(u16::read(reader)?);
+                            out.push(TriggerKey::read(reader)?);
                         }
                         out
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (45, _) => Ok(Request::GetImValuesReply {
-                input_method_id: u16::read(reader)?,
-                im_attributes: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(Attribute::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (30, _) => Ok(Request::Open {
-                locale: {
-                    let inner = {
-                        let len = u8::read(reader)?;
-                        String::from_utf8(reader.consume(len as usize)?.to_vec())?
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (31, _) => Ok(Request::OpenReply {
-                input_method_id: u16::read(reader)?,
-                im_attrs: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(Attr::read(reader)?);
-                    }
-                    out
-                },
-                ic_attrs: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    u16::read(reader)?;
-                    while reader.cursor() > end {
-                        out.push(Attr::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (76, _) => Ok(Request::PreeditCaret {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                position: i32::read(reader)?,
-                direction: CaretDirection::read(reader)?,
-                style: CaretStyle::read(reader)?,
-            }),
-            (77, _) => Ok(Request::PreeditCaretReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                position: i32::read(reader)?,
-            }),
-            (78, _) => Ok(Request::PreeditDone {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (75, _) => Ok(Request::PreeditDraw {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                caret: i32::read(reader)?,
-                chg_first: i32::read(reader)?,
-                chg_length: i32::read(reader)?,
-                status: PreeditDrawStatus::read(reader)?,
-                preedit_string: {
-                    let inner = {
-                        let len = u16::read(reader)?;
-                        reader.consume(len as usize)?.to_vec()
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-                feedbacks: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    u16::read(reader)?;
-                    while reader.cursor() > end {
-                        out.push(Feedback::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (73, _) => Ok(Request::PreeditStart {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (74, _) => Ok(Request::PreeditStartReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                return_value: i32::read(reader)?,
-            }),
-            (82, _) => Ok(Request::PreeditState {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                state: PreeditStateFlag::read(reader)?,
-            }),
-            (40, _) => Ok(Request::QueryExtension {
-                input_method_id: u16::read(reader)?,
-                extensions: {
-                    let inner = {
-                        let mut out = Vec::new();
+                    },
+                    off_keys: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u32::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(TriggerKey::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (35, _) => {
+                Ok(Request::TriggerNotify {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    flag: TriggerNotifyFlag::read(reader)?,
+                    index: u32::read(reader)?,
+                    event_mask: u32::read(reader)?,
+                })
+            }
+            (36, _) => {
+                Ok(Request::TriggerNotifyReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (37, _) => {
+                Ok(Request::SetEventMask {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    forward_event_mask: u32::read(reader)?,
+                    synchronous_event_mask: u32::read(reader)?,
+                })
+            }
+            (38, _) => {
+                Ok(Request::EncodingNegotiation {
+                    input_method_id: u16::read(reader)?,
+                    encodings: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push({
+                                    let len = u8::read(reader)?;
+                                    alloc::string::String::from_utf8(
+                                        reader.consume(len as usize)?.to_vec(),
+                                    )?
+                                });
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                    encoding_infos: {
+                        let mut out = alloc::vec::Vec::new();
                         let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
                         let end = reader.cursor() - len;
                         while reader.cursor() > end {
                             out.push({
-                                let len = u8::read(reader)?;
-                                String::from_utf8(reader.consume(len as usize)?.to_vec())?
+                                let inner = {
+                                    let len = u16::read(reader)?;
+                                    alloc::string::String::from_utf8(
+                                        reader.consume(len as usize)?.to_vec(),
+                                    )?
+                                };
+                                reader.pad4()?;
+                                inner
                             });
                         }
                         out
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (41, _) => Ok(Request::QueryExtensionReply {
-                input_method_id: u16::read(reader)?,
-                extensions: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(Extension::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (34, _) => Ok(Request::RegisterTriggerKeys {
-                input_method_id: {
-                    let inner = u16::read(reader)?;
-                    reader.consume(2)?;
-                    inner
-                },
-                on_keys: {
-                    let mut out = Vec::new();
-                    let len = u32::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(TriggerKey::read(reader)?);
-                    }
-                    out
-                },
-                off_keys: {
-                    let mut out = Vec::new();
-                    let len = u32::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(TriggerKey::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (64, _) => Ok(Request::ResetIc {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (65, _) => Ok(Request::ResetIcReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                preedit_string: {
-                    let inner = {
-                        let len = u16::read(reader)?;
-                        reader.consume(len as usize)?.to_vec()
-                    };
-                    reader.pad4()?;
-                    inner
-                },
-            }),
-            (37, _) => Ok(Request::SetEventMask {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                forward_event_mask: u32::read(reader)?,
-                synchronous_event_mask: u32::read(reader)?,
-            }),
-            (58, _) => Ok(Request::SetIcFocus {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (54, _) => Ok(Request::SetIcValues {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                ic_attributes: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    u16::read(reader)?;
-                    while reader.cursor() > end {
-                        out.push(Attribute::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (55, _) => Ok(Request::SetIcValuesReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (42, _) => Ok(Request::SetImValues {
-                input_method_id: u16::read(reader)?,
-                attributes: {
-                    let mut out = Vec::new();
-                    let len = u16::read(reader)? as usize;
-                    let end = reader.cursor() - len;
-                    while reader.cursor() > end {
-                        out.push(Attribute::read(reader)?);
-                    }
-                    out
-                },
-            }),
-            (43, _) => Ok(Request::SetImValuesReply {
-                input_method_id: {
-                    let inner = u16::read(reader)?;
-                    reader.consume(2)?;
-                    inner
-                },
-            }),
-            (81, _) => Ok(Request::StatusDone {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (80, _) => Ok(Request::StatusDraw {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                content: StatusContent::read(reader)?,
-            }),
-            (79, _) => Ok(Request::StatusStart {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (71, _) => Ok(Request::StrConversion {}),
-            (72, _) => Ok(Request::StrConversionReply {}),
-            (61, _) => Ok(Request::Sync {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (62, _) => Ok(Request::SyncReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (35, _) => Ok(Request::TriggerNotify {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-                flag: TriggerNotifyFlag::read(reader)?,
-                index: u32::read(reader)?,
-                event_mask: u32::read(reader)?,
-            }),
-            (36, _) => Ok(Request::TriggerNotifyReply {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            (59, _) => Ok(Request::UnsetIcFocus {
-                input_method_id: u16::read(reader)?,
-                input_context_id: u16::read(reader)?,
-            }),
-            _ => Err(reader.invalid_data(
-                "Opcode",
-                alloc::format!("({}, {})", major_opcode, minor_opcode),
-            )),
+                    },
+                })
+            }
+            (39, _) => {
+                Ok(Request::EncodingNegotiationReply {
+                    input_method_id: u16::read(reader)?,
+                    category: u16::read(reader)?,
+                    index: {
+                        let inner = i16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                })
+            }
+            (40, _) => {
+                Ok(Request::QueryExtension {
+                    input_method_id: u16::read(reader)?,
+                    extensions: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push({
+                                    let len = u8::read(reader)?;
+                                    alloc::string::String::from_utf8(
+                                        reader.consume(len as usize)?.to_vec(),
+                                    )?
+                                });
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (41, _) => {
+                Ok(Request::QueryExtensionReply {
+                    input_method_id: u16::read(reader)?,
+                    extensions: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Extension::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (42, _) => {
+                Ok(Request::SetImValues {
+                    input_method_id: u16::read(reader)?,
+                    attributes: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attribute::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (43, _) => {
+                Ok(Request::SetImValuesReply {
+                    input_method_id: {
+                        let inner = u16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                })
+            }
+            (44, _) => {
+                Ok(Request::GetImValues {
+                    input_method_id: u16::read(reader)?,
+                    im_attributes: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push(u16::read(reader)?);
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (45, _) => {
+                Ok(Request::GetImValuesReply {
+                    input_method_id: u16::read(reader)?,
+                    im_attributes: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attribute::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (50, _) => {
+                Ok(Request::CreateIc {
+                    input_method_id: u16::read(reader)?,
+                    ic_attributes: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attribute::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (51, _) => {
+                Ok(Request::CreateIcReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (52, _) => {
+                Ok(Request::DestroyIc {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (53, _) => {
+                Ok(Request::DestroyIcReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (54, _) => {
+                Ok(Request::SetIcValues {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    ic_attributes: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attribute::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (55, _) => {
+                Ok(Request::SetIcValuesReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (56, _) => {
+                Ok(Request::GetIcValues {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    ic_attributes: {
+                        let inner = {
+                            let mut out = alloc::vec::Vec::new();
+                            let len = u16::read(reader)? as usize;
+                            let end = reader.cursor() - len;
+                            while reader.cursor() > end {
+                                out.push(u16::read(reader)?);
+                            }
+                            out
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (57, _) => {
+                Ok(Request::GetIcValuesReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    ic_attributes: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Attribute::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (58, _) => {
+                Ok(Request::SetIcFocus {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (59, _) => {
+                Ok(Request::UnsetIcFocus {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (60, _) => {
+                Ok(Request::ForwardEvent {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    flag: ForwardEventFlag::read(reader)?,
+                    serial_number: u16::read(reader)?,
+                    xev: XEvent::read(reader)?,
+                })
+            }
+            (61, _) => {
+                Ok(Request::Sync {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (62, _) => {
+                Ok(Request::SyncReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (63, _) => {
+                Ok(Request::Commit {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    data: CommitData::read(reader)?,
+                })
+            }
+            (64, _) => {
+                Ok(Request::ResetIc {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (65, _) => {
+                Ok(Request::ResetIcReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    preedit_string: {
+                        let inner = {
+                            let len = u16::read(reader)?;
+                            reader.consume(len as usize)?.to_vec()
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                })
+            }
+            (70, _) => {
+                Ok(Request::Geometry {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (71, _) => {
+                Ok(Request::StrConversion {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    position: {
+                        let inner = u16::read(reader)?;
+                        reader.consume(2)?;
+                        inner
+                    },
+                    direction: CaretDirection::read(reader)?,
+                    operation: StrConversionOperation::read(reader)?,
+                    factor: u16::read(reader)?,
+                    conversion_type: u32::read(reader)?,
+                })
+            }
+            (72, _) => {
+                Ok(Request::StrConversionReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    feedback: u32::read(reader)?,
+                    text: StrConvText::read(reader)?,
+                })
+            }
+            (73, _) => {
+                Ok(Request::PreeditStart {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (74, _) => {
+                Ok(Request::PreeditStartReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    return_value: i32::read(reader)?,
+                })
+            }
+            (75, _) => {
+                Ok(Request::PreeditDraw {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    caret: i32::read(reader)?,
+                    chg_first: i32::read(reader)?,
+                    chg_length: i32::read(reader)?,
+                    status: PreeditDrawStatus::read(reader)?,
+                    preedit_string: {
+                        let inner = {
+                            let len = u16::read(reader)?;
+                            reader.consume(len as usize)?.to_vec()
+                        };
+                        reader.pad4()?;
+                        inner
+                    },
+                    feedbacks: {
+                        let mut out = alloc::vec::Vec::new();
+                        let len = u16::read(reader)? as usize;
+                        u16::read(reader)?;
+                        let end = reader.cursor() - len;
+                        while reader.cursor() > end {
+                            out.push(Feedback::read(reader)?);
+                        }
+                        out
+                    },
+                })
+            }
+            (76, _) => {
+                Ok(Request::PreeditCaret {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    position: i32::read(reader)?,
+                    direction: CaretDirection::read(reader)?,
+                    style: CaretStyle::read(reader)?,
+                })
+            }
+            (77, _) => {
+                Ok(Request::PreeditCaretReply {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    position: i32::read(reader)?,
+                })
+            }
+            (78, _) => {
+                Ok(Request::PreeditDone {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (79, _) => {
+                Ok(Request::StatusStart {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (80, _) => {
+                Ok(Request::StatusDraw {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    content: StatusContent::read(reader)?,
+                })
+            }
+            (81, _) => {
+                Ok(Request::StatusDone {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                })
+            }
+            (82, _) => {
+                Ok(Request::PreeditState {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    state: PreeditStateFlag::read(reader)?,
+                })
+            }
+            (128, 48) => {
+                Ok(Request::ExtSetEventMask {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    filter_event_mask: u32::read(reader)?,
+                    intercept_event_mask: u32::read(reader)?,
+                    select_event_mask: u32::read(reader)?,
+                    forward_event_mask: u32::read(reader)?,
+                    synchronous_event_mask: u32::read(reader)?,
+                })
+            }
+            (128, 50) => {
+                Ok(Request::ExtForwardKeyEvent {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    flag: ForwardEventFlag::read(reader)?,
+                    sequence_number: u16::read(reader)?,
+                    xev_type: u8::read(reader)?,
+                    keycode: u8::read(reader)?,
+                    state: u16::read(reader)?,
+                    time: u32::read(reader)?,
+                    window: u32::read(reader)?,
+                })
+            }
+            (128, 51) => {
+                Ok(Request::ExtMove {
+                    input_method_id: u16::read(reader)?,
+                    input_context_id: u16::read(reader)?,
+                    x: i16::read(reader)?,
+                    y: i16::read(reader)?,
+                })
+            }
+            (major_opcode, minor_opcode) if major_opcode >= 128 => {
+                Ok(Request::ExtensionMessage {
+                    major_opcode,
+                    minor_opcode,
+                    data: reader.consume(length as usize * 4)?.to_vec(),
+                })
+            }
+            _ => {
+                Err(
+                    reader
+                        .invalid_data(
+                            "Opcode",
+                            alloc::format!("({}, {})", major_opcode, minor_opcode),
+                        ),
+                )
+            }
         }
     }
 }
 impl XimWrite for Request {
     fn write(&self, writer: &mut Writer) {
         match self {
-            Request::AuthNext {} => {
+            Request::Connect {
+                endian,
+                client_major_protocol_version,
+                client_minor_protocol_version,
+                client_auth_protocol_names,
+            } => {
+                1u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                endian.write(writer);
+                writer.write(&[0u8; 1]);
+                client_major_protocol_version.write(writer);
+                client_minor_protocol_version.write(writer);
+                ((client_auth_protocol_names
+                    .iter()
+                    .map(|e| with_pad4(e.len() + 2 + 0 - 0))
+                    .sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                    .write(writer);
+                for elem in client_auth_protocol_names.iter() {
+                    (elem.len() as u16).write(writer);
+                    writer.write(elem.as_bytes());
+                    writer.write_pad4();
+                }
+            }
+            Request::ConnectReply {
+                server_major_protocol_version,
+                server_minor_protocol_version,
+            } => {
+                2u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                server_major_protocol_version.write(writer);
+                server_minor_protocol_version.write(writer);
+            }
+            Request::Disconnect {} => {
+                3u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+            }
+            Request::DisconnectReply {} => {
+                4u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+            }
+            Request::AuthRequired { auth_protocol_index, auth_data } => {
+                10u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                auth_protocol_index.write(writer);
+                writer.write(&[0u8; 3]);
+                ((auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in auth_data.iter() {
+                    elem.write(writer);
+                }
+                writer.write_pad4();
+            }
+            Request::AuthReply { auth_data } => {
+                11u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                ((auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in auth_data.iter() {
+                    elem.write(writer);
+                }
+                writer.write_pad4();
+            }
+            Request::AuthNext { auth_data } => {
                 12u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                ((auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in auth_data.iter() {
+                    elem.write(writer);
+                }
+                writer.write_pad4();
+            }
+            Request::AuthSetup { auth_protocol_names } => {
+                13u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                ((auth_protocol_names
+                    .iter()
+                    .map(|e| with_pad4(e.len() + 2 + 0 - 0))
+                    .sum::<usize>() + 2 + 2 - 2 - 2) as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in auth_protocol_names.iter() {
+                    (elem.len() as u16).write(writer);
+                    writer.write(elem.as_bytes());
+                    writer.write_pad4();
+                }
             }
             Request::AuthNg {} => {
                 14u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
             }
-            Request::AuthReply {} => {
-                11u8.write(writer);
+            Request::Error { input_method_id, input_context_id, flag, code, detail } => {
+                20u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+                flag.write(writer);
+                code.write(writer);
+                (detail.len() as u16).write(writer);
+                writer.write(&[0u8; 2]);
+                writer.write(detail.as_bytes());
+                writer.write_pad4();
             }
-            Request::AuthRequired {} => {
-                10u8.write(writer);
+            Request::Open { locale } => {
+                30u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                (locale.len() as u8).write(writer);
+                writer.write(locale.as_bytes());
+                writer.write_pad4();
             }
-            Request::AuthSetup {} => {
-                13u8.write(writer);
+            Request::OpenReply { input_method_id, im_attrs, ic_attrs } => {
+                31u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                ((im_attrs.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
+                    .write(writer);
+                for elem in im_attrs.iter() {
+                    elem.write(writer);
+                }
+                ((ic_attrs.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in ic_attrs.iter() {
+                    elem.write(writer);
+                }
             }
             Request::Close { input_method_id } => {
                 32u8.write(writer);
@@ -2163,109 +2724,61 @@ impl XimWrite for Request {
                 input_method_id.write(writer);
                 writer.write(&[0u8; 2]);
             }
-            Request::Commit {
-                input_method_id,
-                input_context_id,
-                data,
-            } => {
-                63u8.write(writer);
+            Request::RegisterTriggerKeys { input_method_id, on_keys, off_keys } => {
+                34u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                input_context_id.write(writer);
-                data.write(writer);
-            }
-            Request::Connect {
-                endian,
-                client_major_protocol_version,
-                client_minor_protocol_version,
-                client_auth_protocol_names,
-            } => {
-                1u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                endian.write(writer);
-                writer.write(&[0u8; 1]);
-                client_major_protocol_version.write(writer);
-                client_minor_protocol_version.write(writer);
-                ((client_auth_protocol_names
-                    .iter()
-                    .map(|e| with_pad4(e.len() + 2 + 0 - 0))
-                    .sum::<usize>()
-                    + 0
-                    + 2
-                    - 2
-                    - 0) as u16)
+                writer.write(&[0u8; 2]);
+                ((on_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4 - 4 - 0)
+                    as u32)
                     .write(writer);
-                for elem in client_auth_protocol_names.iter() {
-                    (elem.len() as u16).write(writer);
-                    writer.write(elem.as_bytes());
-                    writer.write_pad4();
+                for elem in on_keys.iter() {
+                    elem.write(writer);
                 }
-            }
-            Request::ConnectReply {
-                server_major_protocol_version,
-                server_minor_protocol_version,
-            } => {
-                2u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                server_major_protocol_version.write(writer);
-                server_minor_protocol_version.write(writer);
-            }
-            Request::CreateIc {
-                input_method_id,
-                ic_attributes,
-            } => {
-                50u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                input_method_id.write(writer);
-                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                ((off_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4 - 4 - 0)
+                    as u32)
                     .write(writer);
-                for elem in ic_attributes.iter() {
+                for elem in off_keys.iter() {
                     elem.write(writer);
                 }
             }
-            Request::CreateIcReply {
+            Request::TriggerNotify {
                 input_method_id,
                 input_context_id,
+                flag,
+                index,
+                event_mask,
             } => {
-                51u8.write(writer);
+                35u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                flag.write(writer);
+                index.write(writer);
+                event_mask.write(writer);
             }
-            Request::DestroyIc {
-                input_method_id,
-                input_context_id,
-            } => {
-                52u8.write(writer);
+            Request::TriggerNotifyReply { input_method_id, input_context_id } => {
+                36u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
             }
-            Request::DestroyIcReply {
+            Request::SetEventMask {
                 input_method_id,
                 input_context_id,
+                forward_event_mask,
+                synchronous_event_mask,
             } => {
-                53u8.write(writer);
+                37u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-            }
-            Request::Disconnect {} => {
-                3u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-            }
-            Request::DisconnectReply {} => {
-                4u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
+                forward_event_mask.write(writer);
+                synchronous_event_mask.write(writer);
             }
             Request::EncodingNegotiation {
                 input_method_id,
@@ -2276,7 +2789,8 @@ impl XimWrite for Request {
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((encodings.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                ((encodings.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 2
+                    - 0) as u16)
                     .write(writer);
                 for elem in encodings.iter() {
                     (elem.len() as u8).write(writer);
@@ -2286,11 +2800,7 @@ impl XimWrite for Request {
                 ((encoding_infos
                     .iter()
                     .map(|e| with_pad4(e.len() + 2 + 0 - 0))
-                    .sum::<usize>()
-                    + 2
-                    + 2
-                    - 2
-                    - 2) as u16)
+                    .sum::<usize>() + 2 + 2 - 2 - 2) as u16)
                     .write(writer);
                 0u16.write(writer);
                 for elem in encoding_infos.iter() {
@@ -2299,11 +2809,7 @@ impl XimWrite for Request {
                     writer.write_pad4();
                 }
             }
-            Request::EncodingNegotiationReply {
-                input_method_id,
-                category,
-                index,
-            } => {
+            Request::EncodingNegotiationReply { input_method_id, category, index } => {
                 39u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
@@ -2312,300 +2818,223 @@ impl XimWrite for Request {
                 index.write(writer);
                 writer.write(&[0u8; 2]);
             }
-            Request::Error {
-                input_method_id,
-                input_context_id,
-                flag,
-                code,
-                detail,
-            } => {
-                20u8.write(writer);
+            Request::QueryExtension { input_method_id, extensions } => {
+                40u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                input_context_id.write(writer);
-                flag.write(writer);
-                code.write(writer);
-                (detail.len() as u16).write(writer);
-                writer.write(&[0u8; 2]);
-                writer.write(detail.as_bytes());
+                ((extensions.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 2
+                    - 0) as u16)
+                    .write(writer);
+                for elem in extensions.iter() {
+                    (elem.len() as u8).write(writer);
+                    writer.write(elem.as_bytes());
+                }
                 writer.write_pad4();
             }
-            Request::ForwardEvent {
-                input_method_id,
-                input_context_id,
-                flag,
-                serial_number,
-                xev,
-            } => {
-                60u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                input_method_id.write(writer);
-                input_context_id.write(writer);
-                flag.write(writer);
-                serial_number.write(writer);
-                xev.write(writer);
-            }
-            Request::Geometry {
-                input_method_id,
-                input_context_id,
-            } => {
-                70u8.write(writer);
+            Request::QueryExtensionReply { input_method_id, extensions } => {
+                41u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                input_context_id.write(writer);
+                ((extensions.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
+                    .write(writer);
+                for elem in extensions.iter() {
+                    elem.write(writer);
+                }
             }
-            Request::GetIcValues {
-                input_method_id,
-                input_context_id,
-                ic_attributes,
-            } => {
-                56u8.write(writer);
+            Request::SetImValues { input_method_id, attributes } => {
+                42u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                input_context_id.write(writer);
-                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                ((attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
                     .write(writer);
-                for elem in ic_attributes.iter() {
+                for elem in attributes.iter() {
                     elem.write(writer);
                 }
-                writer.write_pad4();
             }
-            Request::GetIcValuesReply {
-                input_method_id,
-                input_context_id,
-                ic_attributes,
-            } => {
-                57u8.write(writer);
+            Request::SetImValuesReply { input_method_id } => {
+                43u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                input_context_id.write(writer);
-                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
-                    .write(writer);
-                0u16.write(writer);
-                for elem in ic_attributes.iter() {
-                    elem.write(writer);
-                }
+                writer.write(&[0u8; 2]);
             }
-            Request::GetImValues {
-                input_method_id,
-                im_attributes,
-            } => {
+            Request::GetImValues { input_method_id, im_attributes } => {
                 44u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                ((im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
                     .write(writer);
                 for elem in im_attributes.iter() {
                     elem.write(writer);
                 }
                 writer.write_pad4();
             }
-            Request::GetImValuesReply {
-                input_method_id,
-                im_attributes,
-            } => {
+            Request::GetImValuesReply { input_method_id, im_attributes } => {
                 45u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                ((im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
                     .write(writer);
                 for elem in im_attributes.iter() {
                     elem.write(writer);
                 }
             }
-            Request::Open { locale } => {
-                30u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                (locale.len() as u8).write(writer);
-                writer.write(locale.as_bytes());
-                writer.write_pad4();
-            }
-            Request::OpenReply {
-                input_method_id,
-                im_attrs,
-                ic_attrs,
-            } => {
-                31u8.write(writer);
+            Request::CreateIc { input_method_id, ic_attributes } => {
+                50u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((im_attrs.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
-                    .write(writer);
-                for elem in im_attrs.iter() {
-                    elem.write(writer);
-                }
-                ((ic_attrs.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
+                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
                     .write(writer);
-                0u16.write(writer);
-                for elem in ic_attrs.iter() {
+                for elem in ic_attributes.iter() {
                     elem.write(writer);
                 }
             }
-            Request::PreeditCaret {
+            Request::CreateIcReply { input_method_id, input_context_id } => {
+                51u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+            }
+            Request::DestroyIc { input_method_id, input_context_id } => {
+                52u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+            }
+            Request::DestroyIcReply { input_method_id, input_context_id } => {
+                53u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+            }
+            Request::SetIcValues {
                 input_method_id,
                 input_context_id,
-                position,
-                direction,
-                style,
+                ic_attributes,
             } => {
-                76u8.write(writer);
+                54u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                position.write(writer);
-                direction.write(writer);
-                style.write(writer);
+                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
+                    .write(writer);
+                0u16.write(writer);
+                for elem in ic_attributes.iter() {
+                    elem.write(writer);
+                }
             }
-            Request::PreeditCaretReply {
-                input_method_id,
-                input_context_id,
-                position,
-            } => {
-                77u8.write(writer);
+            Request::SetIcValuesReply { input_method_id, input_context_id } => {
+                55u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                position.write(writer);
             }
-            Request::PreeditDone {
+            Request::GetIcValues {
                 input_method_id,
                 input_context_id,
+                ic_attributes,
             } => {
-                78u8.write(writer);
+                56u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0)
+                    as u16)
+                    .write(writer);
+                for elem in ic_attributes.iter() {
+                    elem.write(writer);
+                }
+                writer.write_pad4();
             }
-            Request::PreeditDraw {
+            Request::GetIcValuesReply {
                 input_method_id,
                 input_context_id,
-                caret,
-                chg_first,
-                chg_length,
-                status,
-                preedit_string,
-                feedbacks,
+                ic_attributes,
             } => {
-                75u8.write(writer);
+                57u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                caret.write(writer);
-                chg_first.write(writer);
-                chg_length.write(writer);
-                status.write(writer);
-                (preedit_string.len() as u16).write(writer);
-                writer.write(&preedit_string);
-                writer.write_pad4();
-                ((feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
+                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
                     .write(writer);
                 0u16.write(writer);
-                for elem in feedbacks.iter() {
+                for elem in ic_attributes.iter() {
                     elem.write(writer);
                 }
             }
-            Request::PreeditStart {
-                input_method_id,
-                input_context_id,
-            } => {
-                73u8.write(writer);
+            Request::SetIcFocus { input_method_id, input_context_id } => {
+                58u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
             }
-            Request::PreeditStartReply {
-                input_method_id,
-                input_context_id,
-                return_value,
-            } => {
-                74u8.write(writer);
+            Request::UnsetIcFocus { input_method_id, input_context_id } => {
+                59u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                return_value.write(writer);
             }
-            Request::PreeditState {
+            Request::ForwardEvent {
                 input_method_id,
                 input_context_id,
-                state,
+                flag,
+                serial_number,
+                xev,
             } => {
-                82u8.write(writer);
+                60u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                state.write(writer);
+                flag.write(writer);
+                serial_number.write(writer);
+                xev.write(writer);
             }
-            Request::QueryExtension {
-                input_method_id,
-                extensions,
-            } => {
-                40u8.write(writer);
+            Request::Sync { input_method_id, input_context_id } => {
+                61u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((extensions.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 2 - 0)
-                    as u16)
-                    .write(writer);
-                for elem in extensions.iter() {
-                    (elem.len() as u8).write(writer);
-                    writer.write(elem.as_bytes());
-                }
-                writer.write_pad4();
+                input_context_id.write(writer);
             }
-            Request::QueryExtensionReply {
-                input_method_id,
-                extensions,
-            } => {
-                41u8.write(writer);
+            Request::SyncReply { input_method_id, input_context_id } => {
+                62u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((extensions.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
-                    .write(writer);
-                for elem in extensions.iter() {
-                    elem.write(writer);
-                }
+                input_context_id.write(writer);
             }
-            Request::RegisterTriggerKeys {
-                input_method_id,
-                on_keys,
-                off_keys,
-            } => {
-                34u8.write(writer);
+            Request::Commit { input_method_id, input_context_id, data } => {
+                63u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                writer.write(&[0u8; 2]);
-                ((on_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4 - 4 - 0) as u32)
-                    .write(writer);
-                for elem in on_keys.iter() {
-                    elem.write(writer);
-                }
-                ((off_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4 - 4 - 0) as u32)
-                    .write(writer);
-                for elem in off_keys.iter() {
-                    elem.write(writer);
-                }
+                input_context_id.write(writer);
+                data.write(writer);
             }
-            Request::ResetIc {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::ResetIc { input_method_id, input_context_id } => {
                 64u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
@@ -2626,201 +3055,227 @@ impl XimWrite for Request {
                 writer.write(&preedit_string);
                 writer.write_pad4();
             }
-            Request::SetEventMask {
-                input_method_id,
-                input_context_id,
-                forward_event_mask,
-                synchronous_event_mask,
-            } => {
-                37u8.write(writer);
+            Request::Geometry { input_method_id, input_context_id } => {
+                70u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                forward_event_mask.write(writer);
-                synchronous_event_mask.write(writer);
             }
-            Request::SetIcFocus {
+            Request::StrConversion {
                 input_method_id,
                 input_context_id,
+                position,
+                direction,
+                operation,
+                factor,
+                conversion_type,
             } => {
-                58u8.write(writer);
+                71u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                position.write(writer);
+                writer.write(&[0u8; 2]);
+                direction.write(writer);
+                operation.write(writer);
+                factor.write(writer);
+                conversion_type.write(writer);
             }
-            Request::SetIcValues {
+            Request::StrConversionReply {
                 input_method_id,
                 input_context_id,
-                ic_attributes,
+                feedback,
+                text,
             } => {
-                54u8.write(writer);
+                72u8.write(writer);
+                0u8.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+                feedback.write(writer);
+                text.write(writer);
+            }
+            Request::PreeditStart { input_method_id, input_context_id } => {
+                73u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                ((ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2) as u16)
-                    .write(writer);
-                0u16.write(writer);
-                for elem in ic_attributes.iter() {
-                    elem.write(writer);
-                }
             }
-            Request::SetIcValuesReply {
+            Request::PreeditStartReply {
                 input_method_id,
                 input_context_id,
+                return_value,
             } => {
-                55u8.write(writer);
+                74u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                return_value.write(writer);
             }
-            Request::SetImValues {
+            Request::PreeditDraw {
                 input_method_id,
-                attributes,
+                input_context_id,
+                caret,
+                chg_first,
+                chg_length,
+                status,
+                preedit_string,
+                feedbacks,
             } => {
-                42u8.write(writer);
+                75u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
-                ((attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2 - 0) as u16)
+                input_context_id.write(writer);
+                caret.write(writer);
+                chg_first.write(writer);
+                chg_length.write(writer);
+                status.write(writer);
+                (preedit_string.len() as u16).write(writer);
+                writer.write(&preedit_string);
+                writer.write_pad4();
+                ((feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 2 - 2)
+                    as u16)
                     .write(writer);
-                for elem in attributes.iter() {
+                0u16.write(writer);
+                for elem in feedbacks.iter() {
                     elem.write(writer);
                 }
             }
-            Request::SetImValuesReply { input_method_id } => {
-                43u8.write(writer);
-                0u8.write(writer);
-                (((self.size() - 4) / 4) as u16).write(writer);
-                input_method_id.write(writer);
-                writer.write(&[0u8; 2]);
-            }
-            Request::StatusDone {
+            Request::PreeditCaret {
                 input_method_id,
                 input_context_id,
+                position,
+                direction,
+                style,
             } => {
-                81u8.write(writer);
+                76u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                position.write(writer);
+                direction.write(writer);
+                style.write(writer);
             }
-            Request::StatusDraw {
+            Request::PreeditCaretReply {
                 input_method_id,
                 input_context_id,
-                content,
+                position,
             } => {
-                80u8.write(writer);
+                77u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                content.write(writer);
+                position.write(writer);
             }
-            Request::StatusStart {
-                input_method_id,
-                input_context_id,
-            } => {
-                79u8.write(writer);
+            Request::PreeditDone { input_method_id, input_context_id } => {
+                78u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
             }
-            Request::StrConversion {} => {
-                71u8.write(writer);
+            Request::StatusStart { input_method_id, input_context_id } => {
+                79u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
             }
-            Request::StrConversionReply {} => {
-                72u8.write(writer);
+            Request::StatusDraw { input_method_id, input_context_id, content } => {
+                80u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
+                input_method_id.write(writer);
+                input_context_id.write(writer);
+                content.write(writer);
             }
-            Request::Sync {
-                input_method_id,
-                input_context_id,
-            } => {
-                61u8.write(writer);
+            Request::StatusDone { input_method_id, input_context_id } => {
+                81u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
             }
-            Request::SyncReply {
-                input_method_id,
-                input_context_id,
-            } => {
-                62u8.write(writer);
+            Request::PreeditState { input_method_id, input_context_id, state } => {
+                82u8.write(writer);
                 0u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                state.write(writer);
             }
-            Request::TriggerNotify {
+            Request::ExtSetEventMask {
                 input_method_id,
                 input_context_id,
-                flag,
-                index,
-                event_mask,
+                filter_event_mask,
+                intercept_event_mask,
+                select_event_mask,
+                forward_event_mask,
+                synchronous_event_mask,
             } => {
-                35u8.write(writer);
-                0u8.write(writer);
+                128u8.write(writer);
+                48u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
-                flag.write(writer);
-                index.write(writer);
-                event_mask.write(writer);
+                filter_event_mask.write(writer);
+                intercept_event_mask.write(writer);
+                select_event_mask.write(writer);
+                forward_event_mask.write(writer);
+                synchronous_event_mask.write(writer);
             }
-            Request::TriggerNotifyReply {
+            Request::ExtForwardKeyEvent {
                 input_method_id,
                 input_context_id,
+                flag,
+                sequence_number,
+                xev_type,
+                keycode,
+                state,
+                time,
+                window,
             } => {
-                36u8.write(writer);
-                0u8.write(writer);
+                128u8.write(writer);
+                50u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                flag.write(writer);
+                sequence_number.write(writer);
+                xev_type.write(writer);
+                keycode.write(writer);
+                state.write(writer);
+                time.write(writer);
+                window.write(writer);
             }
-            Request::UnsetIcFocus {
-                input_method_id,
-                input_context_id,
-            } => {
-                59u8.write(writer);
-                0u8.write(writer);
+            Request::ExtMove { input_method_id, input_context_id, x, y } => {
+                128u8.write(writer);
+                51u8.write(writer);
                 (((self.size() - 4) / 4) as u16).write(writer);
                 input_method_id.write(writer);
                 input_context_id.write(writer);
+                x.write(writer);
+                y.write(writer);
             }
-        }
-    }
-    fn size(&self) -> usize {
-        let mut content_size = 0;
-        match self {
-            Request::AuthNext {} => {}
-            Request::AuthNg {} => {}
-            Request::AuthReply {} => {}
-            Request::AuthRequired {} => {}
-            Request::AuthSetup {} => {}
-            Request::Close { input_method_id } => {
-                content_size += input_method_id.size() + 2;
-            }
-            Request::CloseReply { input_method_id } => {
-                content_size += input_method_id.size() + 2;
-            }
-            Request::Commit {
-                input_method_id,
-                input_context_id,
-                data,
-            } => {
-                content_size += input_method_id.size();
-                content_size += input_context_id.size();
-                content_size += data.size();
+            Request::ExtensionMessage { major_opcode, minor_opcode, data } => {
+                major_opcode.write(writer);
+                minor_opcode.write(writer);
+                (((self.size() - 4) / 4) as u16).write(writer);
+                writer.write(data);
+                writer.write_pad4();
             }
+        }
+    }
+    fn size(&self) -> usize {
+        let mut content_size = 0;
+        match self {
             Request::Connect {
                 endian,
                 client_major_protocol_version,
@@ -2830,12 +3285,11 @@ impl XimWrite for Request {
                 content_size += endian.size() + 1;
                 content_size += client_major_protocol_version.size();
                 content_size += client_minor_protocol_version.size();
-                content_size += client_auth_protocol_names
-                    .iter()
-                    .map(|e| with_pad4(e.len() + 2 + 0 - 0))
-                    .sum::<usize>()
-                    + 0
-                    + 2;
+                content_size
+                    += client_auth_protocol_names
+                        .iter()
+                        .map(|e| with_pad4(e.len() + 2 + 0 - 0))
+                        .sum::<usize>() + 0 + 2;
             }
             Request::ConnectReply {
                 server_major_protocol_version,
@@ -2844,93 +3298,174 @@ impl XimWrite for Request {
                 content_size += server_major_protocol_version.size();
                 content_size += server_minor_protocol_version.size();
             }
-            Request::CreateIc {
-                input_method_id,
-                ic_attributes,
-            } => {
+            Request::Disconnect {} => {}
+            Request::DisconnectReply {} => {}
+            Request::AuthRequired { auth_protocol_index, auth_data } => {
+                content_size += auth_protocol_index.size() + 3;
+                content_size
+                    += with_pad4(
+                        auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 0,
+                    );
+            }
+            Request::AuthReply { auth_data } => {
+                content_size
+                    += with_pad4(
+                        auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 0,
+                    );
+            }
+            Request::AuthNext { auth_data } => {
+                content_size
+                    += with_pad4(
+                        auth_data.iter().map(|e| e.size()).sum::<usize>() + 2 + 2 - 0,
+                    );
+            }
+            Request::AuthSetup { auth_protocol_names } => {
+                content_size
+                    += auth_protocol_names
+                        .iter()
+                        .map(|e| with_pad4(e.len() + 2 + 0 - 0))
+                        .sum::<usize>() + 2 + 2;
+            }
+            Request::AuthNg {} => {}
+            Request::Error { input_method_id, input_context_id, flag, code, detail } => {
+                content_size += input_method_id.size();
+                content_size += input_context_id.size();
+                content_size += flag.size();
+                content_size += code.size();
+                content_size += with_pad4(detail.len() + 2 + 2 - 0);
+            }
+            Request::Open { locale } => {
+                content_size += with_pad4(locale.len() + 1 + 0 - 0);
+            }
+            Request::OpenReply { input_method_id, im_attrs, ic_attrs } => {
                 content_size += input_method_id.size();
-                content_size += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+                content_size += im_attrs.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+                content_size += ic_attrs.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+            }
+            Request::Close { input_method_id } => {
+                content_size += input_method_id.size() + 2;
+            }
+            Request::CloseReply { input_method_id } => {
+                content_size += input_method_id.size() + 2;
             }
-            Request::CreateIcReply {
+            Request::RegisterTriggerKeys { input_method_id, on_keys, off_keys } => {
+                content_size += input_method_id.size() + 2;
+                content_size += on_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4;
+                content_size += off_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4;
+            }
+            Request::TriggerNotify {
                 input_method_id,
                 input_context_id,
+                flag,
+                index,
+                event_mask,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += flag.size();
+                content_size += index.size();
+                content_size += event_mask.size();
             }
-            Request::DestroyIc {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::TriggerNotifyReply { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
             }
-            Request::DestroyIcReply {
+            Request::SetEventMask {
                 input_method_id,
                 input_context_id,
+                forward_event_mask,
+                synchronous_event_mask,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += forward_event_mask.size();
+                content_size += synchronous_event_mask.size();
             }
-            Request::Disconnect {} => {}
-            Request::DisconnectReply {} => {}
             Request::EncodingNegotiation {
                 input_method_id,
                 encodings,
                 encoding_infos,
             } => {
                 content_size += input_method_id.size();
-                content_size +=
-                    with_pad4(encodings.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 2)
-                        + 2;
-                content_size += encoding_infos
-                    .iter()
-                    .map(|e| with_pad4(e.len() + 2 + 0 - 0))
-                    .sum::<usize>()
-                    + 2
-                    + 2;
-            }
-            Request::EncodingNegotiationReply {
-                input_method_id,
-                category,
-                index,
-            } => {
+                content_size
+                    += with_pad4(
+                        encodings.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2
+                            - 2,
+                    ) + 2;
+                content_size
+                    += encoding_infos
+                        .iter()
+                        .map(|e| with_pad4(e.len() + 2 + 0 - 0))
+                        .sum::<usize>() + 2 + 2;
+            }
+            Request::EncodingNegotiationReply { input_method_id, category, index } => {
                 content_size += input_method_id.size();
                 content_size += category.size();
                 content_size += index.size() + 2;
             }
-            Request::Error {
-                input_method_id,
-                input_context_id,
-                flag,
-                code,
-                detail,
-            } => {
+            Request::QueryExtension { input_method_id, extensions } => {
+                content_size += input_method_id.size();
+                content_size
+                    += with_pad4(
+                        extensions.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2
+                            - 0,
+                    );
+            }
+            Request::QueryExtensionReply { input_method_id, extensions } => {
+                content_size += input_method_id.size();
+                content_size
+                    += extensions.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+            }
+            Request::SetImValues { input_method_id, attributes } => {
+                content_size += input_method_id.size();
+                content_size
+                    += attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+            }
+            Request::SetImValuesReply { input_method_id } => {
+                content_size += input_method_id.size() + 2;
+            }
+            Request::GetImValues { input_method_id, im_attributes } => {
+                content_size += input_method_id.size();
+                content_size
+                    += with_pad4(
+                        im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2,
+                    ) + 2;
+            }
+            Request::GetImValuesReply { input_method_id, im_attributes } => {
+                content_size += input_method_id.size();
+                content_size
+                    += im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+            }
+            Request::CreateIc { input_method_id, ic_attributes } => {
+                content_size += input_method_id.size();
+                content_size
+                    += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
+            }
+            Request::CreateIcReply { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += flag.size();
-                content_size += code.size();
-                content_size += with_pad4(detail.len() + 2 + 2 - 0);
             }
-            Request::ForwardEvent {
-                input_method_id,
-                input_context_id,
-                flag,
-                serial_number,
-                xev,
-            } => {
+            Request::DestroyIc { input_method_id, input_context_id } => {
+                content_size += input_method_id.size();
+                content_size += input_context_id.size();
+            }
+            Request::DestroyIcReply { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += flag.size();
-                content_size += serial_number.size();
-                content_size += xev.size();
             }
-            Request::Geometry {
+            Request::SetIcValues {
                 input_method_id,
                 input_context_id,
+                ic_attributes,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size
+                    += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+            }
+            Request::SetIcValuesReply { input_method_id, input_context_id } => {
+                content_size += input_method_id.size();
+                content_size += input_context_id.size();
             }
             Request::GetIcValues {
                 input_method_id,
@@ -2939,8 +3474,10 @@ impl XimWrite for Request {
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size +=
-                    with_pad4(ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 0);
+                content_size
+                    += with_pad4(
+                        ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 0,
+                    );
             }
             Request::GetIcValuesReply {
                 input_method_id,
@@ -2949,259 +3486,211 @@ impl XimWrite for Request {
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+                content_size
+                    += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
             }
-            Request::GetImValues {
-                input_method_id,
-                im_attributes,
-            } => {
+            Request::SetIcFocus { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
-                content_size +=
-                    with_pad4(im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2 - 2)
-                        + 2;
+                content_size += input_context_id.size();
             }
-            Request::GetImValuesReply {
-                input_method_id,
-                im_attributes,
-            } => {
+            Request::UnsetIcFocus { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
-                content_size += im_attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
-            }
-            Request::Open { locale } => {
-                content_size += with_pad4(locale.len() + 1 + 0 - 0);
+                content_size += input_context_id.size();
             }
-            Request::OpenReply {
+            Request::ForwardEvent {
                 input_method_id,
-                im_attrs,
-                ic_attrs,
+                input_context_id,
+                flag,
+                serial_number,
+                xev,
             } => {
                 content_size += input_method_id.size();
-                content_size += im_attrs.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
-                content_size += ic_attrs.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+                content_size += input_context_id.size();
+                content_size += flag.size();
+                content_size += serial_number.size();
+                content_size += xev.size();
             }
-            Request::PreeditCaret {
-                input_method_id,
-                input_context_id,
-                position,
-                direction,
-                style,
-            } => {
+            Request::Sync { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += position.size();
-                content_size += direction.size();
-                content_size += style.size();
             }
-            Request::PreeditCaretReply {
-                input_method_id,
-                input_context_id,
-                position,
-            } => {
+            Request::SyncReply { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += position.size();
             }
-            Request::PreeditDone {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::Commit { input_method_id, input_context_id, data } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += data.size();
             }
-            Request::PreeditDraw {
+            Request::ResetIc { input_method_id, input_context_id } => {
+                content_size += input_method_id.size();
+                content_size += input_context_id.size();
+            }
+            Request::ResetIcReply {
                 input_method_id,
                 input_context_id,
-                caret,
-                chg_first,
-                chg_length,
-                status,
                 preedit_string,
-                feedbacks,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += caret.size();
-                content_size += chg_first.size();
-                content_size += chg_length.size();
-                content_size += status.size();
                 content_size += with_pad4(preedit_string.len() + 2 - 0);
-                content_size += feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
             }
-            Request::PreeditStart {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::Geometry { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
             }
-            Request::PreeditStartReply {
+            Request::StrConversion {
                 input_method_id,
                 input_context_id,
-                return_value,
+                position,
+                direction,
+                operation,
+                factor,
+                conversion_type,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += return_value.size();
+                content_size += position.size() + 2;
+                content_size += direction.size();
+                content_size += operation.size();
+                content_size += factor.size();
+                content_size += conversion_type.size();
             }
-            Request::PreeditState {
+            Request::StrConversionReply {
                 input_method_id,
                 input_context_id,
-                state,
+                feedback,
+                text,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += state.size();
-            }
-            Request::QueryExtension {
-                input_method_id,
-                extensions,
-            } => {
-                content_size += input_method_id.size();
-                content_size += with_pad4(
-                    extensions.iter().map(|e| e.len() + 1 + 0).sum::<usize>() + 0 + 2 - 0,
-                );
+                content_size += feedback.size();
+                content_size += text.size();
             }
-            Request::QueryExtensionReply {
-                input_method_id,
-                extensions,
-            } => {
+            Request::PreeditStart { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
-                content_size += extensions.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
-            }
-            Request::RegisterTriggerKeys {
-                input_method_id,
-                on_keys,
-                off_keys,
-            } => {
-                content_size += input_method_id.size() + 2;
-                content_size += on_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4;
-                content_size += off_keys.iter().map(|e| e.size()).sum::<usize>() + 0 + 4;
+                content_size += input_context_id.size();
             }
-            Request::ResetIc {
+            Request::PreeditStartReply {
                 input_method_id,
                 input_context_id,
+                return_value,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += return_value.size();
             }
-            Request::ResetIcReply {
+            Request::PreeditDraw {
                 input_method_id,
                 input_context_id,
+                caret,
+                chg_first,
+                chg_length,
+                status,
                 preedit_string,
+                feedbacks,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += caret.size();
+                content_size += chg_first.size();
+                content_size += chg_length.size();
+                content_size += status.size();
                 content_size += with_pad4(preedit_string.len() + 2 - 0);
+                content_size
+                    += feedbacks.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
             }
-            Request::SetEventMask {
-                input_method_id,
-                input_context_id,
-                forward_event_mask,
-                synchronous_event_mask,
-            } => {
-                content_size += input_method_id.size();
-                content_size += input_context_id.size();
-                content_size += forward_event_mask.size();
-                content_size += synchronous_event_mask.size();
-            }
-            Request::SetIcFocus {
+            Request::PreeditCaret {
                 input_method_id,
                 input_context_id,
+                position,
+                direction,
+                style,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += position.size();
+                content_size += direction.size();
+                content_size += style.size();
             }
-            Request::SetIcValues {
+            Request::PreeditCaretReply {
                 input_method_id,
                 input_context_id,
-                ic_attributes,
+                position,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
-                content_size += ic_attributes.iter().map(|e| e.size()).sum::<usize>() + 2 + 2;
+                content_size += position.size();
             }
-            Request::SetIcValuesReply {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::PreeditDone { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
             }
-            Request::SetImValues {
-                input_method_id,
-                attributes,
-            } => {
-                content_size += input_method_id.size();
-                content_size += attributes.iter().map(|e| e.size()).sum::<usize>() + 0 + 2;
-            }
-            Request::SetImValuesReply { input_method_id } => {
-                content_size += input_method_id.size() + 2;
-            }
-            Request::StatusDone {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::StatusStart { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
             }
-            Request::StatusDraw {
-                input_method_id,
-                input_context_id,
-                content,
-            } => {
+            Request::StatusDraw { input_method_id, input_context_id, content } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
                 content_size += content.size();
             }
-            Request::StatusStart {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::StatusDone { input_method_id, input_context_id } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
             }
-            Request::StrConversion {} => {}
-            Request::StrConversionReply {} => {}
-            Request::Sync {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::PreeditState { input_method_id, input_context_id, state } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += state.size();
             }
-            Request::SyncReply {
+            Request::ExtSetEventMask {
                 input_method_id,
                 input_context_id,
+                filter_event_mask,
+                intercept_event_mask,
+                select_event_mask,
+                forward_event_mask,
+                synchronous_event_mask,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += filter_event_mask.size();
+                content_size += intercept_event_mask.size();
+                content_size += select_event_mask.size();
+                content_size += forward_event_mask.size();
+                content_size += synchronous_event_mask.size();
             }
-            Request::TriggerNotify {
+            Request::ExtForwardKeyEvent {
                 input_method_id,
                 input_context_id,
                 flag,
-                index,
-                event_mask,
+                sequence_number,
+                xev_type,
+                keycode,
+                state,
+                time,
+                window,
             } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
                 content_size += flag.size();
-                content_size += index.size();
-                content_size += event_mask.size();
+                content_size += sequence_number.size();
+                content_size += xev_type.size();
+                content_size += keycode.size();
+                content_size += state.size();
+                content_size += time.size();
+                content_size += window.size();
             }
-            Request::TriggerNotifyReply {
-                input_method_id,
-                input_context_id,
-            } => {
+            Request::ExtMove { input_method_id, input_context_id, x, y } => {
                 content_size += input_method_id.size();
                 content_size += input_context_id.size();
+                content_size += x.size();
+                content_size += y.size();
             }
-            Request::UnsetIcFocus {
-                input_method_id,
-                input_context_id,
-            } => {
-                content_size += input_method_id.size();
-                content_size += input_context_id.size();
+            Request::ExtensionMessage { data, .. } => {
+                content_size += with_pad4(data.len());
             }
         }
         content_size + 4